@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+
+use widgetry::{EventCtx, GfxCtx, Key, Line, Panel, SimpleState, State, Text, Widget};
+
+use crate::{App, Transition};
+
+/// Goals beyond the score, unlocked once and remembered forever
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Achievement {
+    MeetGoal,
+    Deliver100Houses,
+    CompleteNeighborhood,
+    Combo10,
+    FinishWithNoEnergy,
+}
+
+impl Achievement {
+    pub const ALL: [Achievement; 5] = [
+        Achievement::MeetGoal,
+        Achievement::Deliver100Houses,
+        Achievement::CompleteNeighborhood,
+        Achievement::Combo10,
+        Achievement::FinishWithNoEnergy,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Achievement::MeetGoal => "Ho ho ho",
+            Achievement::Deliver100Houses => "Century",
+            Achievement::CompleteNeighborhood => "Good neighbor",
+            Achievement::Combo10 => "On a roll",
+            Achievement::FinishWithNoEnergy => "Running on fumes",
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            Achievement::MeetGoal => "Meet the goal on any level",
+            Achievement::Deliver100Houses => "Deliver to 100 houses in one run",
+            Achievement::CompleteNeighborhood => "Deliver to every house on a street",
+            Achievement::Combo10 => "Make 10 quick deliveries in a row",
+            Achievement::FinishWithNoEnergy => "Run out of time with no blood sugar left",
+        }
+    }
+}
+
+/// Lists every achievement and which ones are unlocked.
+pub struct Achievements;
+
+impl Achievements {
+    pub fn new_state(ctx: &mut EventCtx, app: &App) -> Box<dyn State<App>> {
+        let mut txt = Text::new();
+        for a in Achievement::ALL {
+            if app.session.achievements.contains(&a) {
+                txt.add_line(Line(format!("{} (unlocked)", a.name())).small_heading());
+            } else {
+                txt.add_line(Line(a.name()).small_heading().secondary());
+            }
+            txt.add_line(a.describe());
+        }
+
+        <dyn SimpleState<_>>::new_state(
+            Panel::new_builder(Widget::col(vec![
+                Widget::row(vec![
+                    Line("Achievements").big_heading_plain().into_widget(ctx),
+                    ctx.style().btn_close_widget(ctx),
+                ]),
+                txt.into_widget(ctx),
+                ctx.style()
+                    .btn_outline
+                    .text("Back")
+                    .hotkey(Key::Enter)
+                    .build_def(ctx)
+                    .centered_horiz(),
+            ]))
+            .build(ctx),
+            Box::new(Achievements),
+        )
+    }
+}
+
+impl SimpleState<App> for Achievements {
+    fn on_click(&mut self, _: &mut EventCtx, _: &mut App, x: &str, _: &mut Panel) -> Transition {
+        match x {
+            "close" | "Back" => Transition::Pop,
+            _ => unreachable!(),
+        }
+    }
+
+    fn other_event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        app.session.update_music(ctx);
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        app.session.music.draw(g);
+    }
+}
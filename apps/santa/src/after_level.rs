@@ -1,21 +1,38 @@
 use abstutil::prettyprint_usize;
-use geom::{Distance, PolyLine, Pt2D, Tessellation};
+use geom::{Distance, Duration, PolyLine, Pt2D, Tessellation};
+use map_gui::tools::{FileSaver, FileSaverContents};
 use widgetry::tools::{ColorLegend, PopupMsg};
 use widgetry::{
     Color, Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Key, Line, Panel,
     SimpleState, State, Text, VerticalAlignment, Widget,
 };
 
-use crate::buildings::{BldgState, Buildings};
-use crate::levels::Level;
+use crate::buildings::{hatching, BldgState, Buildings};
+use crate::coverage::{Coverage, CoverageMap};
+use crate::difficulty::DifficultyPreset;
+use crate::export;
+use crate::kiosk::{IdleTimer, KioskRestart, KIOSK_RESULTS_TIME};
+use crate::leaderboard;
+use crate::levels::{Level, WinCondition};
+use crate::mode::Mode;
+use crate::replay::{Replay, ReplayViewer};
+use crate::share;
+use crate::shop::Shop;
+use crate::stats::{DeliveryLog, RunCharts, RunStats};
 use crate::title::TitleScreen;
 use crate::{App, Transition};
 
 const ZOOM: f64 = 2.0;
 
 pub struct Strategize {
+    level: String,
     unlock_messages: Option<Vec<String>>,
     draw_all: Drawable,
+    stats: RunStats,
+    coverage: Coverage,
+    // Only in kiosk mode
+    restart: Option<KioskRestart>,
+    idle: IdleTimer,
 }
 
 impl Strategize {
@@ -26,6 +43,10 @@ impl Strategize {
         level: &Level,
         bldgs: &Buildings,
         path: RecordPath,
+        stats: RunStats,
+        coverage: Coverage,
+        difficulty: DifficultyPreset,
+        restart: Option<KioskRestart>,
     ) -> Box<dyn State<App>> {
         ctx.canvas.cam_zoom = ZOOM;
 
@@ -37,17 +58,26 @@ impl Strategize {
         let start = app.map.get_i(intersection_id).polygon.center();
         ctx.canvas.center_on_map_pt(start);
 
-        let unlock_messages = app.session.record_score(level.title.clone(), score);
+        let unlock_messages =
+            app.session
+                .record_score(level.title.clone(), level.goal, score, difficulty);
 
         let mut txt = Text::new();
-        txt.add_line(Line(format!("Results for {}", level.title)).small_heading());
-        txt.add_line(format!(
-            "You delivered {} presents",
-            prettyprint_usize(score)
-        ));
+        let locale = app.session.locale;
+        txt.add_line(
+            Line(locale.tr_args("Results for {}", &[level.title.clone()])).small_heading(),
+        );
+        txt.add_line(locale.tr_args("You delivered {} presents", &[prettyprint_usize(score)]));
         txt.add_line("");
-        txt.add_line("High scores:");
-        for (idx, score) in app.session.high_scores[&level.title].iter().enumerate() {
+        txt.add_line(locale.tr_args(
+            "Difficulty: {}",
+            &[locale.tr(difficulty.describe()).to_string()],
+        ));
+        txt.add_line(locale.tr("High scores:"));
+        for (idx, score) in app.session.high_scores[&difficulty.score_key(&level.title)]
+            .iter()
+            .enumerate()
+        {
             txt.add_line(format!("{}) {}", idx + 1, prettyprint_usize(*score)));
         }
 
@@ -56,7 +86,7 @@ impl Strategize {
         let mut batch = GeomBatch::new();
         for b in app.map.all_buildings() {
             match bldgs.buildings[&b.id] {
-                BldgState::Undelivered(num_housing_units) => {
+                BldgState::Undelivered(num_housing_units, _) => {
                     batch.push(
                         if num_housing_units > 5 {
                             app.session.colors.apartment
@@ -74,7 +104,7 @@ impl Strategize {
                         );
                     }
                 }
-                BldgState::Store => {
+                BldgState::Store(_) | BldgState::Snacked(_) => {
                     batch.push(
                         if bldgs.upzones.contains(&b.id) {
                             Color::PINK
@@ -85,7 +115,10 @@ impl Strategize {
                     );
                 }
                 BldgState::Done => {
-                    batch.push(Color::RED, b.polygon.clone());
+                    batch.push(app.session.colors.delivered, b.polygon.clone());
+                    if app.session.palette.hatch_done() {
+                        batch.extend(app.session.colors.visited, hatching(&b.polygon));
+                    }
                 }
                 BldgState::Ignore => {
                     batch.push(app.session.colors.visited, b.polygon.clone());
@@ -99,17 +132,63 @@ impl Strategize {
             txt.into_widget(ctx),
             ctx.style()
                 .btn_outline
-                .text("Back to title screen")
+                .text(locale.tr("Back to title screen"))
                 .hotkey(Key::Enter)
-                .build_def(ctx),
+                .build_widget(ctx, "Back to title screen"),
+            // Nothing gets saved in kiosk mode
+            if app.session.kiosk {
+                Widget::nothing()
+            } else {
+                ctx.style()
+                    .btn_outline
+                    .text(locale.tr("Watch replay"))
+                    .hotkey(Key::R)
+                    .build_widget(ctx, "Watch replay")
+            },
+            ctx.style()
+                .btn_outline
+                .text(locale.tr("Upgrade shop"))
+                .hotkey(Key::U)
+                .build_widget(ctx, "Upgrade shop"),
             Widget::row(vec![
-                ColorLegend::row(ctx, app.session.colors.house, "house"),
-                ColorLegend::row(ctx, app.session.colors.apartment, "apartment"),
-                ColorLegend::row(ctx, app.session.colors.store, "store"),
+                ctx.style()
+                    .btn_outline
+                    .text(locale.tr("See how the run went"))
+                    .hotkey(Key::S)
+                    .build_widget(ctx, "See how the run went"),
+                ctx.style()
+                    .btn_outline
+                    .text(locale.tr("Every delivery"))
+                    .hotkey(Key::D)
+                    .build_widget(ctx, "Every delivery"),
+                ctx.style()
+                    .btn_outline
+                    .text(locale.tr("Coverage map"))
+                    .hotkey(Key::C)
+                    .build_widget(ctx, "Coverage map"),
             ]),
+            if app.session.kiosk {
+                Widget::nothing()
+            } else {
+                Widget::row(vec![
+                    ctx.style()
+                        .btn_outline
+                        .text(locale.tr("Export summary (JSON)"))
+                        .build_widget(ctx, "Export summary (JSON)"),
+                    ctx.style()
+                        .btn_outline
+                        .text(locale.tr("Export path (GeoJSON)"))
+                        .build_widget(ctx, "Export path (GeoJSON)"),
+                ])
+            },
             Widget::row(vec![
-                ColorLegend::row(ctx, Color::PINK, "upzoned store"),
-                ColorLegend::row(ctx, Color::RED, "delivered!"),
+                ColorLegend::row(ctx, app.session.colors.house, locale.tr("house")),
+                ColorLegend::row(ctx, app.session.colors.apartment, locale.tr("apartment")),
+                ColorLegend::row(ctx, app.session.colors.store, locale.tr("store")),
+            ]),
+            Widget::row(vec![
+                ColorLegend::row(ctx, Color::PINK, locale.tr("upzoned store")),
+                ColorLegend::row(ctx, app.session.colors.delivered, locale.tr("delivered!")),
             ])
             .evenly_spaced(),
         ]))
@@ -118,8 +197,13 @@ impl Strategize {
         <dyn SimpleState<_>>::new_state(
             panel,
             Box::new(Strategize {
+                level: level.title.clone(),
                 unlock_messages,
                 draw_all: ctx.upload(batch),
+                stats,
+                coverage,
+                restart,
+                idle: IdleTimer::new(),
             }),
         )
     }
@@ -142,12 +226,58 @@ impl SimpleState<App> for Strategize {
                 if let Some(msgs) = self.unlock_messages.take() {
                     transitions.push(Transition::Push(PopupMsg::new_state(
                         ctx,
-                        "Level complete!",
+                        app.session.locale.tr("Level complete!"),
                         msgs,
                     )));
                 }
                 Transition::Multi(transitions)
             }
+            "Watch replay" => {
+                if let Some(replay) = Replay::load(&self.level) {
+                    Transition::Push(ReplayViewer::new_state(ctx, app, replay))
+                } else {
+                    Transition::Push(PopupMsg::new_state(
+                        ctx,
+                        app.session.locale.tr("Error"),
+                        vec![app.session.locale.tr("This run couldn't be saved")],
+                    ))
+                }
+            }
+            "Upgrade shop" => Transition::Push(Shop::new_state(ctx, app)),
+            "See how the run went" => Transition::Push(RunCharts::new_state(ctx, app, &self.stats)),
+            "Every delivery" => Transition::Push(DeliveryLog::new_state(ctx, app, &self.stats)),
+            "Coverage map" => {
+                Transition::Push(CoverageMap::new_state(ctx, app, self.coverage.clone()))
+            }
+            "Export summary (JSON)" | "Export path (GeoJSON)" => {
+                let geojson = x == "Export path (GeoJSON)";
+                let result = Replay::load(&self.level)
+                    .ok_or_else(|| anyhow!("This run couldn't be saved"))
+                    .and_then(|replay| {
+                        if geojson {
+                            export::path_geojson(app, &replay)
+                        } else {
+                            export::summary_json(app, &replay)
+                        }
+                    });
+                Transition::Push(match result {
+                    Ok(contents) => FileSaver::with_default_messages(
+                        ctx,
+                        format!(
+                            "santa_{}.{}",
+                            app.map.get_name().as_filename(),
+                            if geojson { "geojson" } else { "json" }
+                        ),
+                        None,
+                        FileSaverContents::String(contents),
+                    ),
+                    Err(err) => PopupMsg::new_state(
+                        ctx,
+                        app.session.locale.tr("Export failed"),
+                        vec![err.to_string()],
+                    ),
+                })
+            }
             _ => unreachable!(),
         }
     }
@@ -155,6 +285,17 @@ impl SimpleState<App> for Strategize {
     fn other_event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
         ctx.canvas_movement();
         app.session.update_music(ctx);
+
+        // Nobody has to walk up and start the next run
+        if app.session.kiosk && self.idle.event(ctx, KIOSK_RESULTS_TIME) {
+            return match self.restart.take() {
+                Some(restart) => Transition::Replace(restart.start(ctx, app)),
+                None => Transition::Multi(vec![
+                    Transition::Pop,
+                    Transition::Replace(TitleScreen::new_state(ctx, app)),
+                ]),
+            };
+        }
         Transition::Keep
     }
 
@@ -164,69 +305,177 @@ impl SimpleState<App> for Strategize {
     }
 }
 
-pub struct Results;
+pub struct Results {
+    level: String,
+    mode: Mode,
+    score: usize,
+    url: String,
+    // Only in kiosk mode
+    idle: IdleTimer,
+}
 
 impl Results {
     pub fn new_state(
         ctx: &mut EventCtx,
         app: &mut App,
         score: usize,
+        met_goal: bool,
         level: &Level,
+        mode: Mode,
+        seed: u64,
+        difficulty: DifficultyPreset,
     ) -> Box<dyn State<App>> {
+        let locale = app.session.locale;
         let mut txt = Text::new();
-        if score < level.goal {
-            txt.add_line(Line("Not quite...").small_heading());
-            txt.add_line(format!(
-                "You only delivered {} / {} presents",
-                prettyprint_usize(score),
-                prettyprint_usize(level.goal)
-            ));
-            txt.add_line("Review your route and try again.");
+        if !met_goal {
+            txt.add_line(Line(locale.tr("Not quite...")).small_heading());
+            txt.add_line(match level.script.win {
+                WinCondition::Score => locale.tr_args(
+                    "You only delivered {} / {} presents",
+                    &[prettyprint_usize(score), prettyprint_usize(level.goal)],
+                ),
+                WinCondition::Houses(n) => locale.tr_args(
+                    "You didn't deliver to {} buildings in time",
+                    &[n.to_string()],
+                ),
+                WinCondition::Zones(n) => {
+                    locale.tr_args("You didn't finish {} streets in time", &[n.to_string()])
+                }
+                WinCondition::EveryHouse => locale
+                    .tr("You didn't reach every house in time")
+                    .to_string(),
+            });
+            txt.add_line(locale.tr("Review your route and try again."));
             txt.add_line("");
-            txt.add_line("Hint: look for any apartments you missed!");
+            txt.add_line(locale.tr("Hint: look for any apartments you missed!"));
         } else {
-            txt.add_line(Line("Thank you, Santa!").small_heading());
-            txt.add_line(format!(
-                "You delivered {} presents, more than the goal of {}!",
-                prettyprint_usize(score),
-                prettyprint_usize(level.goal)
-            ));
-            let high_score = app.session.high_scores[&level.title][0];
+            txt.add_line(Line(locale.tr("Thank you, Santa!")).small_heading());
+            txt.add_line(match level.script.win {
+                WinCondition::Score => locale.tr_args(
+                    "You delivered {} presents, more than the goal of {}!",
+                    &[prettyprint_usize(score), prettyprint_usize(level.goal)],
+                ),
+                WinCondition::Houses(_) | WinCondition::Zones(_) | WinCondition::EveryHouse => {
+                    locale.tr_args(
+                        "You delivered {} presents and met the goal!",
+                        &[prettyprint_usize(score)],
+                    )
+                }
+            });
+            let high_score = app.session.high_scores[&difficulty.score_key(&level.title)][0];
             if high_score == score {
-                txt.add_line("Wow, a new high score!");
+                txt.add_line(locale.tr("Wow, a new high score!"));
             } else {
-                txt.add_line(format!(
+                txt.add_line(locale.tr_args(
                     "But can you beat the high score of {}?",
-                    prettyprint_usize(high_score)
+                    &[prettyprint_usize(high_score)],
                 ));
             }
         }
+        if let Some(challenge) = app.session.challenge {
+            txt.add_line(if score > challenge {
+                locale.tr_args(
+                    "You beat your friend's challenge of {}!",
+                    &[prettyprint_usize(challenge)],
+                )
+            } else {
+                locale.tr_args(
+                    "Your friend's challenge of {} still stands",
+                    &[prettyprint_usize(challenge)],
+                )
+            });
+        }
+        txt.add_line("");
+        txt.add_line(
+            Line(locale.tr_args(
+                "Difficulty: {}",
+                &[locale.tr(difficulty.describe()).to_string()],
+            ))
+            .secondary(),
+        );
+        txt.add_line(
+            Line(locale.tr_args(
+                "Seed {} -- start with --seed={} to play the same setup again",
+                &[seed.to_string(), seed.to_string()],
+            ))
+            .secondary(),
+        );
+        let url = share::challenge_url(level, mode, seed, score);
+        if cfg!(target_arch = "wasm32") {
+            txt.add_line(
+                Line(locale.tr("Share this page's URL to challenge a friend to the same setup"))
+                    .secondary(),
+            );
+        }
 
         <dyn SimpleState<_>>::new_state(
             Panel::new_builder(Widget::col(vec![
                 txt.into_widget(ctx),
                 ctx.style()
                     .btn_solid_primary
-                    .text("OK")
+                    .text(locale.tr("OK"))
                     .hotkey(Key::Enter)
-                    .build_def(ctx),
+                    .build_widget(ctx, "OK"),
+                if cfg!(target_arch = "wasm32") {
+                    Widget::nothing()
+                } else {
+                    ctx.style()
+                        .btn_outline
+                        .text(locale.tr("Copy a link to challenge a friend"))
+                        .build_widget(ctx, "Copy a link to challenge a friend")
+                },
+                // The leaderboard only compares runs on the usual difficulty
+                if app.session.leaderboard.is_active() && difficulty == DifficultyPreset::Normal {
+                    ctx.style()
+                        .btn_outline
+                        .text(locale.tr("Submit to the leaderboard"))
+                        .build_widget(ctx, "Submit to the leaderboard")
+                } else {
+                    Widget::nothing()
+                },
             ]))
             .build(ctx),
-            Box::new(Results),
+            Box::new(Results {
+                level: level.title.clone(),
+                mode,
+                score,
+                url,
+                idle: IdleTimer::new(),
+            }),
         )
     }
 }
 
 impl SimpleState<App> for Results {
-    fn on_click(&mut self, _: &mut EventCtx, _: &mut App, x: &str, _: &mut Panel) -> Transition {
+    fn on_click(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &mut App,
+        x: &str,
+        _: &mut Panel,
+    ) -> Transition {
         match x {
             "OK" => Transition::Pop,
+            "Copy a link to challenge a friend" => {
+                widgetry::tools::set_clipboard(self.url.clone());
+                Transition::Keep
+            }
+            "Submit to the leaderboard" => Transition::Replace(leaderboard::submit(
+                ctx,
+                app,
+                self.level.clone(),
+                self.mode,
+                self.score,
+            )),
             _ => unreachable!(),
         }
     }
 
     fn other_event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
         app.session.update_music(ctx);
+        if app.session.kiosk && self.idle.event(ctx, KIOSK_RESULTS_TIME) {
+            return Transition::Pop;
+        }
         Transition::Keep
     }
 
@@ -264,3 +513,90 @@ impl RecordPath {
         PolyLine::unchecked_new(self.pts).thicken_tessellation(thickness)
     }
 }
+
+/// Levels that need every house delivered are all or nothing, so instead of the score, this
+/// shows how close the run came
+pub struct AllOrNothingResults {
+    // Only in kiosk mode
+    idle: IdleTimer,
+}
+
+impl AllOrNothingResults {
+    pub fn new_state(
+        ctx: &mut EventCtx,
+        app: &App,
+        level: &Level,
+        houses_left: usize,
+        elapsed: Duration,
+    ) -> Box<dyn State<App>> {
+        let locale = app.session.locale;
+        let mut txt = Text::new();
+        if houses_left == 0 {
+            txt.add_line(
+                Line(locale.tr_args("{} is covered!", &[level.title.clone()]))
+                    .big_heading_plain()
+                    .fg(app.session.colors.good),
+            );
+            txt.add_line(locale.tr_args(
+                "Every house got its present, with {} to spare.",
+                &[(level.time_limit - elapsed).to_string()],
+            ));
+        } else {
+            txt.add_line(
+                Line(locale.tr_args("{} holds out", &[level.title.clone()]))
+                    .big_heading_plain()
+                    .fg(app.session.colors.bad),
+            );
+            txt.add_line(if houses_left == 1 {
+                locale.tr("One house never got its present.").to_string()
+            } else {
+                locale.tr_args(
+                    "{} houses never got their presents.",
+                    &[prettyprint_usize(houses_left)],
+                )
+            });
+            txt.add_line("");
+            txt.add_line(
+                locale.tr("There's nowhere to restock, so every present and every bite counts."),
+            );
+            txt.add_line(locale.tr("Plan a route that doesn't double back."));
+        }
+
+        <dyn SimpleState<_>>::new_state(
+            Panel::new_builder(Widget::col(vec![
+                txt.into_widget(ctx),
+                ctx.style()
+                    .btn_solid_primary
+                    .text(locale.tr("OK"))
+                    .hotkey(Key::Enter)
+                    .build_widget(ctx, "OK")
+                    .centered_horiz(),
+            ]))
+            .build(ctx),
+            Box::new(AllOrNothingResults {
+                idle: IdleTimer::new(),
+            }),
+        )
+    }
+}
+
+impl SimpleState<App> for AllOrNothingResults {
+    fn on_click(&mut self, _: &mut EventCtx, _: &mut App, x: &str, _: &mut Panel) -> Transition {
+        match x {
+            "OK" => Transition::Pop,
+            _ => unreachable!(),
+        }
+    }
+
+    fn other_event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        app.session.update_music(ctx);
+        if app.session.kiosk && self.idle.event(ctx, KIOSK_RESULTS_TIME) {
+            return Transition::Pop;
+        }
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        app.session.music.draw(g);
+    }
+}
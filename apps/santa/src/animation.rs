@@ -1,7 +1,7 @@
 use rand::{Rng, SeedableRng};
 use rand_xorshift::XorShiftRng;
 
-use geom::{Distance, Duration, PolyLine, Pt2D, Time};
+use geom::{Angle, Circle, Distance, Duration, PolyLine, Polygon, Pt2D, Time};
 use widgetry::{Color, Drawable, EventCtx, GeomBatch, GfxCtx, RewriteColor};
 
 pub struct Animator {
@@ -33,6 +33,25 @@ pub enum Effect {
         alpha_scale: (f32, f32),
         cycles: usize,
     },
+    /// Drift upwards while fading out
+    Float {
+        orig: GeomBatch,
+        start: Pt2D,
+        rise: Distance,
+    },
+    /// Throw something in an arc, spinning it along the way
+    Toss {
+        orig: GeomBatch,
+        from: Pt2D,
+        to: Pt2D,
+        height: Distance,
+    },
+    /// Sparks flying out in every direction, fading as they go
+    Burst {
+        center: Pt2D,
+        radius: Distance,
+        colors: Vec<Color>,
+    },
 }
 
 impl Animator {
@@ -140,10 +159,81 @@ impl Effect {
 
                 batch.append(orig.clone().color(RewriteColor::ChangeAlpha(alpha)));
             }
+            Effect::Float {
+                ref orig,
+                start,
+                rise,
+            } => {
+                // Stay solid for a bit, so there's time to read it
+                let alpha = (2.0 * (1.0 - pct)).min(1.0) as f32;
+                let pt = Pt2D::new(start.x(), start.y() - pct * rise.inner_meters());
+                batch.append(
+                    orig.clone()
+                        .centered_on(pt)
+                        .color(RewriteColor::ChangeAlpha(alpha)),
+                );
+            }
+            Effect::Toss {
+                ref orig,
+                from,
+                to,
+                height,
+            } => {
+                // A parabola peaking halfway. Negative y is up on the screen.
+                let lift = height.inner_meters() * 4.0 * pct * (1.0 - pct);
+                let pt = Pt2D::new(
+                    from.x() + pct * (to.x() - from.x()),
+                    from.y() + pct * (to.y() - from.y()) - lift,
+                );
+                batch.append(
+                    orig.clone()
+                        .centered_on(pt)
+                        .rotate_around_batch_center(Angle::degrees(360.0 * pct)),
+                );
+            }
+            Effect::Burst {
+                center,
+                radius,
+                ref colors,
+            } => {
+                let num_sparks = 24;
+                // Fast at first, then slowing down
+                let dist = *radius * (1.0 - (1.0 - pct).powi(2));
+                let alpha = (1.0 - pct) as f32;
+                for i in 0..num_sparks {
+                    // Alternate sparks go a little less far, so it's not a perfect ring
+                    let dist = if i % 2 == 0 { dist } else { dist * 0.7 };
+                    let pt = center.project_away(
+                        dist,
+                        Angle::degrees(360.0 * (i as f64) / (num_sparks as f64)),
+                    );
+                    batch.push(
+                        colors[i % colors.len()].alpha(alpha),
+                        Circle::new(pt, Distance::meters(1.0)).to_polygon(),
+                    );
+                }
+            }
         }
     }
 }
 
+/// A little wrapped box, for tossing to houses
+pub fn present(color: Color) -> GeomBatch {
+    let size = 4.0;
+    let ribbon = 0.8;
+    let mut batch = GeomBatch::new();
+    batch.push(color, Polygon::rectangle(size, size));
+    batch.push(
+        Color::WHITE,
+        Polygon::rectangle(ribbon, size).translate((size - ribbon) / 2.0, 0.0),
+    );
+    batch.push(
+        Color::WHITE,
+        Polygon::rectangle(size, ribbon).translate(0.0, (size - ribbon) / 2.0),
+    );
+    batch
+}
+
 pub struct SnowEffect {
     rng: XorShiftRng,
     flakes: Vec<Snowflake>,
@@ -170,7 +260,7 @@ impl Snowflake {
 }
 
 impl SnowEffect {
-    pub fn new(ctx: &mut EventCtx) -> SnowEffect {
+    pub fn new(ctx: &mut EventCtx, num_flakes: usize) -> SnowEffect {
         let mut snow = SnowEffect {
             rng: XorShiftRng::seed_from_u64(42),
             flakes: Vec::new(),
@@ -178,8 +268,7 @@ impl SnowEffect {
         };
 
         let now = Time::START_OF_DAY;
-        // TODO Amp back up after fixing slow performance in debug mode
-        for _ in 0..20 {
+        for _ in 0..num_flakes {
             let initial_pos = Pt2D::new(
                 snow.rng.gen_range(0.0..ctx.canvas.window_width),
                 snow.rng.gen_range(0.0..ctx.canvas.window_height),
@@ -206,6 +295,9 @@ impl SnowEffect {
     }
 
     pub fn event(&mut self, ctx: &mut EventCtx, now: Time) {
+        if self.flakes.is_empty() {
+            return;
+        }
         let shape = GeomBatch::load_svg(ctx, "system/assets/map/snowflake.svg").scale(0.1);
 
         let mut batch = GeomBatch::new();
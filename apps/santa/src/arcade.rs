@@ -0,0 +1,272 @@
+use std::collections::{BTreeMap, HashSet};
+
+use geom::{Angle, Duration, Speed, Time};
+use map_model::BuildingID;
+use widgetry::tools::PopupMsg;
+use widgetry::{
+    Color, EventCtx, GfxCtx, HorizontalAlignment, Line, Panel, State, Text, UpdateType,
+    VerticalAlignment, Widget,
+};
+
+use crate::buildings::{Buildings, PresentType};
+use crate::camera::Camera;
+use crate::controls::SleighState;
+use crate::levels::Level;
+use crate::meters::{custom_bar, hud_text};
+use crate::mode::Mode;
+use crate::pause::{Pausable, PauseMenu};
+use crate::player::Player;
+use crate::title::TitleScreen;
+use crate::tuning::Tuning;
+use crate::vehicles::Vehicle;
+use crate::{App, Transition};
+
+/// What riding around a level's map is for. Everything else a game here needs -- riding the
+/// roads, the camera, the clock, and the HUD -- comes from `Arcade`, so a new game on the same
+/// maps only has to fill this in.
+pub trait Objective {
+    /// Like "Snow plow"
+    fn name(&self) -> &'static str;
+    /// Shown under the clock, and only redrawn when it changes
+    fn describe_score(&self, app: &App) -> String;
+    /// Called every tick, after the player moves
+    fn update(&mut self, ctx: &mut EventCtx, app: &App, player: &Player);
+    /// Drawn over the map, under the player
+    fn draw(&self, g: &mut GfxCtx);
+    /// Once time runs out
+    fn results(&self, app: &App) -> Vec<String>;
+}
+
+/// The riding that the main game and every `Objective` share: the clock, the player moving over
+/// the map, and the camera following them.
+pub struct Ride {
+    pub player: Player,
+    /// With two players in the main game, it frames both of them instead of following one
+    pub camera: Camera,
+}
+
+impl Ride {
+    pub fn new(player: Player) -> Ride {
+        Ride {
+            player,
+            camera: Camera::new(),
+        }
+    }
+
+    /// Moves the clock forward and reads the gamepad. Call first thing every tick.
+    pub fn tick(app: &mut App, dt: Duration) {
+        app.time += dt;
+        let gamepad_enabled = !app.session.disable_gamepad;
+        app.session.gamepad.poll(gamepad_enabled);
+    }
+
+    /// Returns the buildings passed along the way
+    pub fn steer(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &App,
+        sleigh: &SleighState,
+        analog: Option<(Angle, f64)>,
+        speed: Speed,
+        wind: (f64, f64),
+    ) -> Vec<BuildingID> {
+        self.player
+            .update_with_speed(ctx, app, sleigh, analog, speed, wind)
+    }
+
+    /// Keeps the camera on the player. When `hovering` over a building, it zooms in to read it.
+    pub fn follow(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &App,
+        dt: Duration,
+        vehicle: &Vehicle,
+        hovering: bool,
+    ) {
+        self.camera
+            .update(ctx, app, dt, self.player.get_pos(), vehicle.speed, hovering);
+    }
+}
+
+/// Whole seconds left before the time limit, so the clock only has to be redrawn once a second,
+/// and how much of it has passed, from 0 to 1
+pub fn time_left(app: &App, time_limit: Duration) -> (Duration, f64) {
+    let elapsed = app.time - Time::START_OF_DAY;
+    let left = (time_limit - elapsed).inner_seconds().max(0.0);
+    (
+        Duration::seconds(left.ceil()),
+        (elapsed / time_limit).min(1.0),
+    )
+}
+
+/// Plays any `Objective` on a level: ride around until the time limit, then see how it went. It
+/// leaves out everything about presents -- stores, blood sugar, boosting, and so on -- which
+/// belongs to the main game.
+pub struct Arcade {
+    objective: Box<dyn Objective>,
+    // To start the objective over
+    new_objective: fn(&mut EventCtx, &App) -> Box<dyn Objective>,
+    level: Level,
+    vehicle: Vehicle,
+    tuning: Tuning,
+    ride: Ride,
+    // Just for drawing, and because the controls expect them
+    bldgs: Buildings,
+    presents: BTreeMap<PresentType, usize>,
+
+    panel: Panel,
+    // What's shown in the panel, to only redraw it when something changes
+    shown: Option<(Duration, String)>,
+}
+
+impl Arcade {
+    pub fn new_state(
+        ctx: &mut EventCtx,
+        app: &mut App,
+        level: Level,
+        vehicle: Vehicle,
+        new_objective: fn(&mut EventCtx, &App) -> Box<dyn Objective>,
+    ) -> Box<dyn State<App>> {
+        app.time = Time::START_OF_DAY;
+        app.session.music.specify_volume(crate::music::IN_GAME);
+
+        let start = app
+            .map
+            .find_i_by_pt2d(app.map.localise_lon_lat_to_map(level.start))
+            .expect("Failed to get level start point");
+        let bldgs = Buildings::new(ctx, app, HashSet::new(), app.session.seed(Mode::Normal));
+
+        let mut arcade = Arcade {
+            objective: new_objective(ctx, app),
+            new_objective,
+            level,
+            vehicle,
+            tuning: Tuning::default(),
+            ride: Ride::new(Player::new(ctx, app, start)),
+            bldgs,
+            presents: BTreeMap::new(),
+
+            panel: Panel::empty(ctx),
+            shown: None,
+        };
+        arcade.update_panel(ctx, app);
+        Box::new(arcade)
+    }
+
+    fn elapsed(&self, app: &App) -> Duration {
+        app.time - Time::START_OF_DAY
+    }
+
+    fn update_panel(&mut self, ctx: &mut EventCtx, app: &App) {
+        let (left, pct) = time_left(app, self.level.time_limit);
+        let score = self.objective.describe_score(app);
+        let shown = Some((left, score.clone()));
+        if self.shown == shown {
+            return;
+        }
+        self.shown = shown;
+
+        self.panel = Panel::new_builder(Widget::col(vec![
+            hud_text(ctx, app, Line(self.objective.name()).small_heading()),
+            custom_bar(
+                ctx,
+                Color::hex("#5D92C2"),
+                1.0 - pct,
+                Text::from(Line(format!("{} left", left)).fg(Color::BLACK)),
+            ),
+            hud_text(ctx, app, score),
+        ]))
+        .aligned(HorizontalAlignment::Center, VerticalAlignment::Top)
+        .build(ctx);
+    }
+
+    fn update(&mut self, ctx: &mut EventCtx, app: &mut App, dt: Duration) {
+        Ride::tick(app, dt);
+
+        let speed = self
+            .vehicle
+            .speed_at(app, &self.ride.player, self.tuning.grade_speed_factor);
+        let sleigh = SleighState {
+            pos: self.ride.player.get_pos(),
+            roads: self.ride.player.current_roads(app),
+            bldgs: &self.bldgs,
+            presents: &self.presents,
+            energy: self.vehicle.max_energy,
+            low_energy_warning: 0,
+            windows: None,
+            pct_elapsed: time_left(app, self.level.time_limit).1,
+        };
+        self.ride.steer(
+            ctx,
+            app,
+            &sleigh,
+            app.session.gamepad.stick,
+            speed,
+            (0.0, 0.0),
+        );
+        self.objective.update(ctx, app, &self.ride.player);
+        self.ride.follow(ctx, app, dt, &self.vehicle, false);
+        self.update_panel(ctx, app);
+    }
+
+    fn finish(&self, ctx: &mut EventCtx, app: &App) -> Transition {
+        Transition::Multi(vec![
+            Transition::Pop,
+            Transition::Replace(TitleScreen::new_state(ctx, app)),
+            Transition::Push(PopupMsg::new_state(
+                ctx,
+                self.objective.name(),
+                self.objective.results(app),
+            )),
+        ])
+    }
+}
+
+impl State<App> for Arcade {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        if let Some(dt) = ctx.input.nonblocking_is_update_event() {
+            self.update(ctx, app, dt);
+            if self.elapsed(app) >= self.level.time_limit {
+                return self.finish(ctx, app);
+            }
+        }
+        if app.session.keys.pressed(ctx, "pause") {
+            return Transition::Push(PauseMenu::new_state::<Arcade>(ctx, app));
+        }
+        ctx.request_update(UpdateType::Game);
+
+        app.session.update_music(ctx);
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        g.redraw(&self.bldgs.draw_all);
+        self.objective.draw(g);
+        self.vehicle.draw_at(g, app.time, &self.ride.player);
+        self.panel.draw(g);
+        app.session.music.draw(g);
+    }
+}
+
+impl Pausable for Arcade {
+    /// Nothing carries over, so it's the same as starting from the level picker
+    fn restart_level() -> Transition {
+        Transition::ConsumeState(Box::new(|state, ctx, app| {
+            let arcade = *state.downcast::<Arcade>().ok().unwrap();
+            vec![Arcade::new_state(
+                ctx,
+                app,
+                arcade.level,
+                arcade.vehicle,
+                arcade.new_objective,
+            )]
+        }))
+    }
+
+    /// Minigames have no runs to resume
+    fn quit_to_title() -> Transition {
+        Transition::ConsumeState(Box::new(|_, ctx, app| {
+            vec![TitleScreen::new_state(ctx, app)]
+        }))
+    }
+}
@@ -0,0 +1,148 @@
+use geom::{Duration, Polygon, Pt2D};
+use widgetry::{
+    Color, Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Line, Panel, State,
+    UpdateType, VerticalAlignment, Widget,
+};
+
+use crate::buildings::Buildings;
+use crate::levels::Level;
+use crate::replay::Replay;
+use crate::trail::{Trail, TRAIL_EVERY, TRAIL_LENGTH};
+use crate::{App, Transition};
+
+/// How long the title screen sits untouched before the demo starts
+pub const IDLE_BEFORE_DEMO: Duration = Duration::const_seconds(30.0);
+const DEMO_ZOOM: f64 = 5.0;
+
+/// Like an arcade cabinet, plays back a recorded run over and over while nobody's at the title
+/// screen. Any key or click goes back.
+pub struct AttractMode {
+    panel: Panel,
+    replay: Replay,
+    bldgs: Buildings,
+
+    // Since the start of the run
+    time: Duration,
+    // How many deliveries have been drawn so far
+    num_deliveries: Option<usize>,
+    draw_deliveries: Drawable,
+    trail: Trail,
+
+    // To put the camera back afterwards
+    orig_center: Pt2D,
+    orig_zoom: f64,
+}
+
+impl AttractMode {
+    /// Only runs on the map that's already loaded count, since switching maps takes a while.
+    /// None if nothing's been played here yet.
+    pub fn find_demo(app: &App) -> Option<Replay> {
+        let boss = Level::boss();
+        app.session
+            .levels
+            .iter()
+            .chain(std::iter::once(&boss))
+            .chain(&app.session.custom_levels)
+            .filter(|level| &level.map == app.map.get_name())
+            .find_map(|level| Replay::load(&level.title))
+    }
+
+    pub fn new_state(ctx: &mut EventCtx, app: &App, replay: Replay) -> Box<dyn State<App>> {
+        let panel = Panel::new_builder(Widget::col(vec![
+            Line("PRESS ANY KEY")
+                .big_heading_plain()
+                .into_widget(ctx)
+                .centered_horiz(),
+            Line(format!("Demo: {}", replay.level))
+                .small_heading()
+                .into_widget(ctx)
+                .centered_horiz(),
+        ]))
+        .aligned(HorizontalAlignment::Center, VerticalAlignment::Bottom)
+        .build(ctx);
+
+        let orig_center = ctx.canvas.center_to_map_pt();
+        let orig_zoom = ctx.canvas.cam_zoom;
+        ctx.canvas.cam_zoom = DEMO_ZOOM;
+
+        Box::new(AttractMode {
+            panel,
+            bldgs: replay.buildings(ctx, app),
+            replay,
+
+            time: Duration::ZERO,
+            num_deliveries: None,
+            draw_deliveries: Drawable::empty(ctx),
+            trail: Trail::new(ctx),
+
+            orig_center,
+            orig_zoom,
+        })
+    }
+
+    fn update_time(&mut self, ctx: &mut EventCtx, app: &App) {
+        if let Some(pt) = self.replay.sleigh_pos(self.time) {
+            ctx.canvas.center_on_map_pt(pt);
+        }
+        self.trail.update(
+            ctx,
+            self.replay
+                .recent_points(self.time, TRAIL_LENGTH, TRAIL_EVERY),
+        );
+
+        let deliveries = self.replay.deliveries();
+        let num_deliveries = deliveries
+            .iter()
+            .take_while(|(t, _, _)| *t <= self.time)
+            .count();
+        if self.num_deliveries == Some(num_deliveries) {
+            return;
+        }
+        self.num_deliveries = Some(num_deliveries);
+        let mut batch = GeomBatch::new();
+        for (_, b, _) in &deliveries[0..num_deliveries] {
+            batch.push(
+                app.session.colors.visited,
+                app.map.get_b(*b).polygon.clone(),
+            );
+        }
+        self.draw_deliveries = ctx.upload(batch);
+    }
+}
+
+impl State<App> for AttractMode {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        if let Some(dt) = ctx.input.nonblocking_is_update_event() {
+            self.time += dt;
+            if self.time >= self.replay.duration() {
+                // Loop forever
+                self.time = Duration::ZERO;
+            }
+            self.update_time(ctx, app);
+        } else if ctx.input.any_pressed().is_some() || ctx.input.left_mouse_button_pressed() {
+            ctx.canvas.cam_zoom = self.orig_zoom;
+            ctx.canvas.center_on_map_pt(self.orig_center);
+            return Transition::Pop;
+        }
+        ctx.request_update(UpdateType::Game);
+
+        app.session.update_music(ctx);
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        g.redraw(&self.bldgs.draw_all);
+        self.bldgs.labels.draw(g);
+        g.redraw(&self.draw_deliveries);
+        self.trail.draw(g);
+        self.replay.draw_sleigh(g, self.time);
+        g.fork_screenspace();
+        g.draw_polygon(
+            Color::BLACK.alpha(0.3),
+            Polygon::rectangle(g.canvas.window_width, g.canvas.window_height),
+        );
+        g.unfork();
+        self.panel.draw(g);
+        app.session.music.draw(g);
+    }
+}
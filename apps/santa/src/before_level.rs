@@ -6,20 +6,28 @@ use rand_xorshift::XorShiftRng;
 
 use abstutil::prettyprint_usize;
 use geom::Time;
-use map_gui::load::MapLoader;
 use map_gui::ID;
-use map_model::BuildingID;
+use map_model::{BuildingID, IntersectionID};
 use widgetry::tools::PopupMsg;
 use widgetry::{
-    ButtonBuilder, Color, ControlState, Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment,
-    Image, Key, Line, Outcome, Panel, RewriteColor, State, Text, TextExt, VerticalAlignment,
-    Widget,
+    ButtonBuilder, Choice, Color, ControlState, Drawable, EventCtx, GeomBatch, GfxCtx,
+    HorizontalAlignment, Image, Key, Line, Outcome, Panel, RewriteColor, State, Text, TextExt,
+    VerticalAlignment, Widget,
 };
 
-use crate::buildings::{BldgState, Buildings};
+use crate::arcade::Arcade;
+use crate::buildings::{closest_intersection, BldgState, Buildings};
+use crate::controls::KeyBindings;
+use crate::difficulty::{Difficulty, DifficultyPreset};
 use crate::game::Game;
-use crate::levels::Level;
+use crate::kiosk::{IdleTimer, KIOSK_IDLE_RESET};
+use crate::levels::{Level, WinCondition};
+use crate::load_map::load_map;
 use crate::meters::{custom_bar, make_bar};
+use crate::mode::Mode;
+use crate::plow::SnowPlow;
+use crate::settings::Settings;
+use crate::title::TitleScreen;
 use crate::vehicles::Vehicle;
 use crate::{App, Transition};
 
@@ -33,16 +41,25 @@ pub struct Picker {
     bldgs: Buildings,
     current_picks: BTreeSet<BuildingID>,
     draw_start: Drawable,
+    // Only used in kiosk mode
+    idle: IdleTimer,
 }
 
 impl Picker {
-    pub fn new_state(ctx: &mut EventCtx, app: &App, level: Level) -> Box<dyn State<App>> {
-        MapLoader::new_state(
+    pub fn new_state(
+        ctx: &mut EventCtx,
+        app: &App,
+        level: Level,
+        mode: Mode,
+    ) -> Box<dyn State<App>> {
+        load_map(
             ctx,
             app,
             level.map.clone(),
             Box::new(move |ctx, app| {
                 app.session.music.change_song(&level.music);
+                let mut level = level;
+                level.resolve_depot(&app.map);
 
                 ctx.canvas.cam_zoom = ZOOM;
 
@@ -54,15 +71,55 @@ impl Picker {
                 let start = app.map.get_i(intersection_id).polygon.center();
                 ctx.canvas.center_on_map_pt(start);
 
-                let bldgs = Buildings::new(ctx, app, HashSet::new());
+                let bldgs = Buildings::new(ctx, app, HashSet::new(), Mode::Normal.seed());
+
+                let difficulty = Difficulty::for_level(&level, &app.map);
+                let (goal, time_limit) = difficulty
+                    .as_ref()
+                    .map(|d| (d.goal, d.time_limit))
+                    .unwrap_or((level.goal, level.time_limit));
 
                 let mut txt = Text::new();
-                txt.add_line(Line(format!("Ready for {}?", level.title)).small_heading());
-                txt.add_line(format!(
-                    "Goal: deliver {} presents",
-                    prettyprint_usize(level.goal)
-                ));
-                txt.add_line(format!("Time limit: {}", level.time_limit));
+                let locale = app.session.locale;
+                txt.add_line(
+                    Line(locale.tr_args("Ready for {}?", &[level.title.clone()])).small_heading(),
+                );
+                txt.add_line(match level.script.win {
+                    WinCondition::Score => {
+                        locale.tr_args("Goal: deliver {} presents", &[prettyprint_usize(goal)])
+                    }
+                    WinCondition::Houses(n) => {
+                        locale.tr_args("Goal: deliver to {} buildings", &[prettyprint_usize(n)])
+                    }
+                    WinCondition::Zones(n) => {
+                        locale.tr_args("Goal: finish {} streets", &[prettyprint_usize(n)])
+                    }
+                    WinCondition::EveryHouse => {
+                        locale.tr("Goal: deliver to every house").to_string()
+                    }
+                });
+                txt.add_line(locale.tr_args("Time limit: {}", &[time_limit.to_string()]));
+                if level.script.no_stores {
+                    txt.add_line(
+                        Line("Every store is closed, and going home won't refill anything")
+                            .fg(app.session.colors.bad),
+                    );
+                }
+                if let Some(n) = level.script.presents {
+                    txt.add_line(format!("You can carry {} of each kind of present", n));
+                }
+                if let Some(difficulty) = difficulty {
+                    txt.add_line(difficulty.describe());
+                }
+                if let Some(challenge) = app.session.challenge {
+                    txt.add_line(
+                        Line(format!(
+                            "A friend challenged you to beat {} presents",
+                            prettyprint_usize(challenge)
+                        ))
+                        .fg(app.session.colors.good),
+                    );
+                }
                 txt.add_appended(vec![
                     Line("Deliver presents to "),
                     Line("single-family homes").fg(app.cs.residential_building),
@@ -84,12 +141,26 @@ impl Picker {
                             ),
                         )
                         .into_widget(ctx),
-                        Text::from_all(vec![
-                            Line("arrow keys").fg(ctx.style().text_hotkey_color),
-                            Line(" to move (or "),
-                            Line("WASD").fg(ctx.style().text_hotkey_color),
-                            Line(")"),
-                        ])
+                        if app.session.keys == KeyBindings::default() {
+                            Text::from_all(vec![
+                                Line("arrow keys").fg(ctx.style().text_hotkey_color),
+                                Line(" to move (or "),
+                                Line("WASD").fg(ctx.style().text_hotkey_color),
+                                Line(")"),
+                            ])
+                        } else {
+                            Text::from_all(vec![
+                                Line(format!(
+                                    "{}, {}, {}, {}",
+                                    app.session.keys.describe("up"),
+                                    app.session.keys.describe("left"),
+                                    app.session.keys.describe("down"),
+                                    app.session.keys.describe("right")
+                                ))
+                                .fg(ctx.style().text_hotkey_color),
+                                Line(" to move"),
+                            ])
+                        }
                         .into_widget(ctx),
                     ]),
                     Widget::row(vec![
@@ -102,10 +173,49 @@ impl Picker {
                         .into_widget(ctx),
                     ]),
                     Text::from_all(vec![
-                        Line("Escape key").fg(ctx.style().text_hotkey_color),
+                        Line(app.session.keys.describe("boost")).fg(ctx.style().text_hotkey_color),
+                        Line(" to boost, "),
+                        Line(app.session.keys.describe("pause")).fg(ctx.style().text_hotkey_color),
                         Line(" to pause"),
                     ])
                     .into_widget(ctx),
+                    ctx.style()
+                        .btn_outline
+                        .text("Change controls and other settings")
+                        .build_widget(ctx, "Settings"),
+                    ctx.style()
+                        .btn_outline
+                        .text("Choose where to start")
+                        .build_def(ctx),
+                    ctx.style()
+                        .btn_outline
+                        .text("Plow the snow instead")
+                        .build_def(ctx),
+                    Widget::row(vec![
+                        "Mode:".text_widget(ctx).centered_vert(),
+                        Widget::dropdown(
+                            ctx,
+                            "mode",
+                            mode,
+                            Mode::ALL
+                                .into_iter()
+                                .map(|mode| Choice::new(mode.describe(), mode))
+                                .collect(),
+                        ),
+                    ]),
+                    // The daily challenge and races ignore this, so everyone starts out even
+                    Widget::row(vec![
+                        "Difficulty:".text_widget(ctx).centered_vert(),
+                        Widget::dropdown(
+                            ctx,
+                            "difficulty",
+                            app.session.difficulty,
+                            DifficultyPreset::ALL
+                                .into_iter()
+                                .map(|d| Choice::new(d.describe(), d))
+                                .collect(),
+                        ),
+                    ]),
                 ]))
                 .aligned(HorizontalAlignment::LeftInset, VerticalAlignment::TopInset)
                 .build(ctx);
@@ -127,21 +237,31 @@ impl Picker {
                     bldgs,
                     current_picks,
                     draw_start: ctx.upload(draw_start),
+                    idle: IdleTimer::new(),
                 }))
             }),
         )
     }
 
+    /// Moves where the level starts, for this attempt only
+    fn set_start(&mut self, ctx: &mut EventCtx, app: &App, i: IntersectionID) {
+        let start = app.map.get_i(i).polygon.center();
+        self.level.start = start.to_gps(app.map.get_gps_bounds());
+        self.draw_start = ctx.upload(map_gui::tools::start_marker(ctx, start, 3.0));
+        ctx.canvas.center_on_map_pt(start);
+    }
+
     fn randomly_pick_upzones(&mut self, app: &App) {
         let mut choices = Vec::new();
         for (b, state) in &self.bldgs.buildings {
-            if let BldgState::Undelivered(_) = state {
+            if let BldgState::Undelivered(..) = state {
                 if !self.current_picks.contains(b) {
                     choices.push(*b);
                 }
             }
         }
-        let mut rng = XorShiftRng::seed_from_u64(42);
+        let mode: Mode = self.instructions_panel.dropdown_value("mode");
+        let mut rng = XorShiftRng::seed_from_u64(app.session.seed(mode));
         choices.shuffle(&mut rng);
         let n = app.session.upzones_unlocked - self.current_picks.len();
         // Maps are definitely large enough for this to be fine
@@ -165,7 +285,7 @@ impl State<App> for Picker {
                     ID::Building(b) => b,
                     _ => panic!("Can't call as_building on {:?}", id),
                 };
-                matches!(self.bldgs.buildings[&b], BldgState::Undelivered(_))
+                matches!(self.bldgs.buildings[&b], BldgState::Undelivered(..))
             });
         }
         if let Some(ID::Building(b)) = app.current_selection {
@@ -186,6 +306,9 @@ impl State<App> for Picker {
                     app.session
                         .upzones_per_level
                         .set(self.level.title.clone(), self.current_picks.clone());
+                    let mode = self.instructions_panel.dropdown_value("mode");
+                    app.session.last_mode = mode;
+                    app.session.difficulty = self.instructions_panel.dropdown_value("difficulty");
                     app.session.save();
 
                     return Transition::Replace(Game::new_state(
@@ -194,6 +317,8 @@ impl State<App> for Picker {
                         self.level.clone(),
                         Vehicle::get(&app.session.current_vehicle),
                         self.current_picks.clone().into_iter().collect(),
+                        mode,
+                        None,
                     ));
                 }
                 "Randomly choose upzones" => {
@@ -211,6 +336,29 @@ impl State<App> for Picker {
             }
         }
 
+        if let Outcome::Clicked(x) = self.instructions_panel.event(ctx) {
+            match x.as_ref() {
+                "Settings" => {
+                    return Transition::Push(Settings::new_state(ctx, app));
+                }
+                "Choose where to start" => {
+                    let mode = self.instructions_panel.dropdown_value("mode");
+                    return Transition::Push(StartPicker::new_state(ctx, app, &self.bldgs, mode));
+                }
+                "Plow the snow instead" => {
+                    app.current_selection = None;
+                    return Transition::Replace(Arcade::new_state(
+                        ctx,
+                        app,
+                        self.level.clone(),
+                        Vehicle::get(&app.session.current_vehicle),
+                        SnowPlow::new_objective,
+                    ));
+                }
+                _ => unreachable!(),
+            }
+        }
+
         if let Outcome::Clicked(x) = self.vehicle_panel.event(ctx) {
             app.session.current_vehicle = x;
             self.vehicle_panel = make_vehicle_panel(ctx, app);
@@ -218,6 +366,10 @@ impl State<App> for Picker {
 
         app.session.update_music(ctx);
 
+        if app.session.kiosk && self.idle.event(ctx, KIOSK_IDLE_RESET) {
+            return Transition::Clear(vec![TitleScreen::new_state(ctx, app)]);
+        }
+
         Transition::Keep
     }
 
@@ -227,6 +379,9 @@ impl State<App> for Picker {
         self.instructions_panel.draw(g);
         app.session.music.draw(g);
         g.redraw(&self.bldgs.draw_all);
+        if app.session.show_score_labels {
+            self.bldgs.labels.draw(g);
+        }
         for b in &self.current_picks {
             g.draw_polygon(Color::PINK, app.map.get_b(*b).polygon.clone());
         }
@@ -238,6 +393,125 @@ impl State<App> for Picker {
     }
 }
 
+/// Highlights every store, so the player can start the level next to one of them. The usual start
+/// isn't always in a good spot, especially on maps from the command line.
+struct StartPicker {
+    panel: Panel,
+    stores: Vec<BuildingID>,
+    draw_stores: Drawable,
+    hovering: Option<BuildingID>,
+    rng: XorShiftRng,
+}
+
+impl StartPicker {
+    fn new_state(
+        ctx: &mut EventCtx,
+        app: &App,
+        bldgs: &Buildings,
+        mode: Mode,
+    ) -> Box<dyn State<App>> {
+        let mut stores = bldgs.all_stores();
+        // all_stores comes from a HashMap, so sort to keep random picks the same for a seed
+        stores.sort();
+        let mut batch = GeomBatch::new();
+        for b in &stores {
+            batch.push(
+                app.session.colors.store.alpha(0.8),
+                app.map.get_b(*b).polygon.clone(),
+            );
+        }
+
+        let locale = app.session.locale;
+        let panel = Panel::new_builder(Widget::col(vec![
+            Line(locale.tr("Choose where to start"))
+                .small_heading()
+                .into_widget(ctx),
+            Widget::row(vec![
+                Image::from_path("system/assets/tools/mouse.svg").into_widget(ctx),
+                Line(locale.tr("Click a store to start next to it"))
+                    .fg(ctx.style().text_hotkey_color)
+                    .into_widget(ctx),
+            ]),
+            Widget::row(vec![
+                ctx.style()
+                    .btn_outline
+                    .text(locale.tr("Random"))
+                    .disabled(stores.is_empty())
+                    .build_widget(ctx, "Random"),
+                ctx.style()
+                    .btn_outline
+                    .text(locale.tr("Cancel"))
+                    .hotkey(Key::Escape)
+                    .build_widget(ctx, "Cancel"),
+            ]),
+        ]))
+        .aligned(HorizontalAlignment::Center, VerticalAlignment::TopInset)
+        .build(ctx);
+
+        Box::new(StartPicker {
+            panel,
+            stores,
+            draw_stores: ctx.upload(batch),
+            hovering: None,
+            rng: XorShiftRng::seed_from_u64(app.session.seed(mode)),
+        })
+    }
+
+    fn pick(&self, app: &App, b: BuildingID) -> Transition {
+        let i = closest_intersection(&app.map, b);
+        Transition::Multi(vec![
+            Transition::Pop,
+            Transition::ModifyState(Box::new(move |state, ctx, app| {
+                let picker = state.downcast_mut::<Picker>().unwrap();
+                picker.set_start(ctx, app, i);
+            })),
+        ])
+    }
+}
+
+impl State<App> for StartPicker {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        ctx.canvas_movement();
+
+        if ctx.redo_mouseover() {
+            self.hovering = match app.mouseover_unzoomed_buildings(ctx) {
+                Some(ID::Building(b)) if self.stores.contains(&b) => Some(b),
+                _ => None,
+            };
+        }
+        if let Some(b) = self.hovering {
+            if ctx.normal_left_click() {
+                return self.pick(app, b);
+            }
+        }
+
+        if let Outcome::Clicked(x) = self.panel.event(ctx) {
+            match x.as_ref() {
+                "Random" => {
+                    let b = *self.stores.choose(&mut self.rng).unwrap();
+                    return self.pick(app, b);
+                }
+                "Cancel" => {
+                    return Transition::Pop;
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        app.session.update_music(ctx);
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        g.redraw(&self.draw_stores);
+        if let Some(b) = self.hovering {
+            g.draw_polygon(app.cs.selected, app.map.get_b(b).polygon.clone());
+        }
+        self.panel.draw(g);
+        app.session.music.draw(g);
+    }
+}
+
 fn make_vehicle_panel(ctx: &mut EventCtx, app: &App) -> Panel {
     let mut buttons = Vec::new();
     for name in &app.session.vehicles_unlocked {
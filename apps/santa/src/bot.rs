@@ -0,0 +1,323 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+
+use geom::{Angle, Distance, Duration, Pt2D, Speed};
+use map_model::{BuildingID, IntersectionID, Road, RoadID};
+use widgetry::EventCtx;
+
+use crate::buildings::{BldgState, PresentType};
+use crate::controls::{Controller, InstantController, KeyBindings, SleighState};
+use crate::player::driveway;
+use crate::App;
+
+// Look for something better this often, since other players and elves deliver too
+const REPLAN_PERIOD: Duration = Duration::const_seconds(1.0);
+// Close enough to a point along the route to head for the next one
+const WAYPOINT_RADIUS: Distance = Distance::const_meters(2.0);
+// Stores only refill when the sleigh rides past, so don't stop right on the driveway
+const OVERSHOOT: Distance = Distance::const_meters(3.0);
+
+/// A baseline automated player, following the same plan as the headless simulation: go for the
+/// house worth the most presents for the distance, and refill at the nearest store when blood
+/// sugar or presents run low. It rides the roads with the same weight as the instant controls,
+/// so it's no faster than a person.
+pub struct GreedyBot {
+    steering: InstantController,
+    // The building it's headed to, and the present it wants if it's a house
+    target: Option<(BuildingID, Option<PresentType>)>,
+    route: VecDeque<Pt2D>,
+    // Since the last plan. None means plan right away.
+    since_plan: Option<Duration>,
+}
+
+impl GreedyBot {
+    pub fn new() -> GreedyBot {
+        GreedyBot {
+            steering: InstantController::new(KeyBindings::default()),
+            target: None,
+            route: VecDeque::new(),
+            since_plan: None,
+        }
+    }
+
+    fn plan(&mut self, app: &App, sleigh: &SleighState) {
+        let costs = Costs::from(app, sleigh);
+        let hungry = sleigh.energy <= sleigh.low_energy_warning
+            || sleigh.presents.values().all(|count| *count == 0);
+        let next = if hungry {
+            None
+        } else {
+            best_house(app, sleigh, &costs)
+        }
+        .or_else(|| closest_store(app, sleigh, &costs));
+
+        self.target = next;
+        self.route = match next {
+            Some((b, wanted)) => costs.route_to(app, sleigh, driveway(app, b), wanted.is_none()),
+            None => VecDeque::new(),
+        };
+    }
+
+    // Someone else may have gotten there first, or the present just dropped
+    fn target_gone(&self, sleigh: &SleighState) -> bool {
+        match self.target {
+            Some((b, Some(_))) => !matches!(sleigh.bldgs.buildings[&b], BldgState::Undelivered(..)),
+            Some((_, None)) => false,
+            None => true,
+        }
+    }
+}
+
+impl Controller for GreedyBot {
+    fn displacement(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &App,
+        sleigh: &SleighState,
+        _: Option<(Angle, f64)>,
+        speed: Speed,
+    ) -> Option<(f64, f64)> {
+        let dt = ctx.input.nonblocking_is_update_event()?;
+
+        let since_plan = self.since_plan.map(|t| t + dt);
+        if since_plan.map(|t| t >= REPLAN_PERIOD).unwrap_or(true)
+            || self.route.is_empty()
+            || self.target_gone(sleigh)
+        {
+            self.plan(app, sleigh);
+            self.since_plan = Some(Duration::ZERO);
+        } else {
+            self.since_plan = since_plan;
+        }
+
+        while let Some(pt) = self.route.front() {
+            if pt.dist_to(sleigh.pos) > WAYPOINT_RADIUS {
+                break;
+            }
+            self.route.pop_front();
+        }
+        let input = self.route.front().map(|pt| (sleigh.pos.angle_to(*pt), 1.0));
+        self.steering.steer(dt, input, speed)
+    }
+
+    fn facing(&self) -> Angle {
+        self.steering.facing
+    }
+
+    fn is_bot(&self) -> bool {
+        true
+    }
+
+    fn choose_present(&self) -> Option<PresentType> {
+        self.target.and_then(|(_, wanted)| wanted)
+    }
+}
+
+fn best_house(
+    app: &App,
+    sleigh: &SleighState,
+    costs: &Costs,
+) -> Option<(BuildingID, Option<PresentType>)> {
+    if sleigh.energy == 0 {
+        return None;
+    }
+    sleigh
+        .bldgs
+        .buildings
+        .iter()
+        .filter_map(|(b, state)| match state {
+            BldgState::Undelivered(num_housing_units, wanted)
+                if sleigh.presents[wanted] > 0 && window_open(sleigh, *b) =>
+            {
+                let (cost, _) = costs.cost_to(app, sleigh, driveway(app, *b))?;
+                // Avoid dividing by zero for houses right here
+                let per_meter = (*num_housing_units as f64) / (cost.inner_meters() + 10.0);
+                Some(((per_meter * 1000.0).round() as usize, *b, *wanted))
+            }
+            _ => None,
+        })
+        .max_by_key(|(value, b, _)| (*value, Reverse(*b)))
+        .map(|(_, b, wanted)| (b, Some(wanted)))
+}
+
+fn closest_store(
+    app: &App,
+    sleigh: &SleighState,
+    costs: &Costs,
+) -> Option<(BuildingID, Option<PresentType>)> {
+    sleigh
+        .bldgs
+        .buildings
+        .iter()
+        .filter_map(|(b, state)| match state {
+            BldgState::Store(_) | BldgState::Snacked(_) => {
+                let (cost, _) = costs.cost_to(app, sleigh, driveway(app, *b))?;
+                Some((cost, *b))
+            }
+            _ => None,
+        })
+        .min()
+        .map(|(_, b)| (b, None))
+}
+
+fn window_open(sleigh: &SleighState, b: BuildingID) -> bool {
+    sleigh
+        .windows
+        .and_then(|windows| windows.get(b))
+        .map(|window| window.is_open(sleigh.pct_elapsed))
+        .unwrap_or(true)
+}
+
+// How far it is to ride to every intersection from the sleigh. The sleigh can go either way down
+// any road, so this doesn't use the map's pathfinding.
+struct Costs {
+    // The distance along the shortest route, and the intersection and road it came from
+    best: HashMap<IntersectionID, (Distance, Option<(IntersectionID, RoadID)>)>,
+}
+
+impl Costs {
+    fn from(app: &App, sleigh: &SleighState) -> Costs {
+        let mut best = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        for r in &sleigh.roads {
+            let road = app.map.get_r(*r);
+            for i in [road.src_i, road.dst_i] {
+                let cost = sleigh.pos.dist_to(app.map.get_i(i).polygon.center());
+                if best.get(&i).map(|(c, _)| cost < *c).unwrap_or(true) {
+                    best.insert(i, (cost, None));
+                    queue.push(Reverse((cost, i)));
+                }
+            }
+        }
+
+        while let Some(Reverse((cost, i))) = queue.pop() {
+            if best[&i].0 < cost {
+                continue;
+            }
+            for r in &app.map.get_i(i).roads {
+                let road = app.map.get_r(*r);
+                if road.is_light_rail() {
+                    continue;
+                }
+                let next = if road.src_i == i {
+                    road.dst_i
+                } else {
+                    road.src_i
+                };
+                let next_cost = cost + road.length();
+                if best.get(&next).map(|(c, _)| next_cost < *c).unwrap_or(true) {
+                    best.insert(next, (next_cost, Some((i, *r))));
+                    queue.push(Reverse((next_cost, next)));
+                }
+            }
+        }
+
+        Costs { best }
+    }
+
+    // Also returns which end of the road to come from, or None to ride straight there along the
+    // road the sleigh is already on
+    fn cost_to(
+        &self,
+        app: &App,
+        sleigh: &SleighState,
+        (r, dist): (RoadID, Distance),
+    ) -> Option<(Distance, Option<IntersectionID>)> {
+        let road = app.map.get_r(r);
+        let mut options = Vec::new();
+        if sleigh.roads.contains(&r) {
+            let here = dist_along(road, sleigh.pos);
+            options.push((abs_diff(here, dist), None));
+        }
+        if let Some((cost, _)) = self.best.get(&road.src_i) {
+            options.push((*cost + dist, Some(road.src_i)));
+        }
+        if let Some((cost, _)) = self.best.get(&road.dst_i) {
+            options.push((*cost + road.length() - dist, Some(road.dst_i)));
+        }
+        options.into_iter().min_by_key(|(cost, _)| *cost)
+    }
+
+    fn route_to(
+        &self,
+        app: &App,
+        sleigh: &SleighState,
+        (r, dist): (RoadID, Distance),
+        overshoot: bool,
+    ) -> VecDeque<Pt2D> {
+        let road = app.map.get_r(r);
+        let mut pts = Vec::new();
+        let from = match self.cost_to(app, sleigh, (r, dist)) {
+            Some((_, None)) => dist_along(road, sleigh.pos),
+            Some((_, Some(i))) => {
+                let mut hops = Vec::new();
+                let mut at = i;
+                while let Some((_, Some((prev, via)))) = self.best.get(&at) {
+                    hops.push((*prev, *via));
+                    at = *prev;
+                }
+                hops.reverse();
+
+                pts.push(app.map.get_i(at).polygon.center());
+                for (from_i, via) in hops {
+                    let via = app.map.get_r(via);
+                    let mut segment = via.center_pts.clone().into_points();
+                    if via.src_i != from_i {
+                        segment.reverse();
+                    }
+                    pts.extend(segment);
+                }
+                if road.src_i == i {
+                    Distance::ZERO
+                } else {
+                    road.length()
+                }
+            }
+            None => {
+                return VecDeque::new();
+            }
+        };
+        let to = match (overshoot, from <= dist) {
+            (false, _) => dist,
+            (true, true) => (dist + OVERSHOOT).min(road.length()),
+            (true, false) => (dist - OVERSHOOT).max(Distance::ZERO),
+        };
+        pts.extend(along(road, from, to));
+        pts.into_iter().collect()
+    }
+}
+
+fn dist_along(road: &Road, pt: Pt2D) -> Distance {
+    road.center_pts
+        .dist_along_of_point(road.center_pts.project_pt(pt))
+        .map(|(dist, _)| dist)
+        .unwrap_or(Distance::ZERO)
+}
+
+fn abs_diff(a: Distance, b: Distance) -> Distance {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+// The points along a road's center line between two distances, in that order
+fn along(road: &Road, from: Distance, to: Distance) -> Vec<Pt2D> {
+    let mut pts = match road
+        .center_pts
+        .maybe_exact_slice(from.min(to), from.max(to))
+    {
+        Ok(pl) => pl.into_points(),
+        // Too short to slice, so just go to the end
+        Err(_) => road
+            .center_pts
+            .dist_along(to)
+            .map(|(pt, _)| vec![pt])
+            .unwrap_or_default(),
+    };
+    if from > to {
+        pts.reverse();
+    }
+    pts
+}
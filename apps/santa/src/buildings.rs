@@ -1,8 +1,16 @@
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 
-use map_model::{AmenityType, BuildingID, BuildingType};
-use widgetry::{Color, Drawable, EventCtx, GeomBatch, Line, Text};
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+use serde::{Deserialize, Serialize};
 
+use abstutil::{prettyprint_usize, Timer};
+use geom::{Angle, Circle, Distance, PolyLine, Polygon, Pt2D, QuadTree};
+use map_model::{AmenityType, Building, BuildingID, BuildingType, IntersectionID, Map};
+use widgetry::{Color, Drawable, EventCtx, GeomBatch, GfxCtx, Line, Text};
+
+use crate::session::ColorScheme;
 use crate::App;
 
 pub struct Buildings {
@@ -11,37 +19,172 @@ pub struct Buildings {
     // This an unchanging base layer that can get covered up by drawing on top of it. Maybe we
     // could even replace the one in DrawMap.
     pub draw_all: Drawable,
+    // Houses and stores are too small to spot on the minimap, so also mark them with dots
+    pub draw_minimap_dots: Drawable,
+    pub labels: BuildingLabels,
+    pub index: BuildingIndex,
     pub total_housing_units: usize,
     pub upzones: HashSet<BuildingID>,
 }
 
 #[derive(Clone)]
 pub enum BldgState {
-    // Score
-    Undelivered(usize),
-    Store,
+    // Score, and what kind of present is wanted
+    Undelivered(usize, PresentType),
+    Store(StoreType),
+    // A restaurant where Santa already had a snack. It still refills blood sugar.
+    Snacked(StoreType),
     Done,
     // Not a relevant building
     Ignore,
 }
 
+/// Every house wants one kind of present, and Santa carries a limited number of each.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub enum PresentType {
+    Toy,
+    Book,
+    Sweater,
+}
+
+impl PresentType {
+    pub const ALL: [PresentType; 3] = [PresentType::Toy, PresentType::Book, PresentType::Sweater];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PresentType::Toy => "toy",
+            PresentType::Book => "book",
+            PresentType::Sweater => "sweater",
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            PresentType::Toy => Color::hex("#E0245E"),
+            PresentType::Book => Color::hex("#1DA1F2"),
+            PresentType::Sweater => Color::hex("#FFFFFF"),
+        }
+    }
+
+    /// Cycles through all of the types
+    pub fn next(self) -> PresentType {
+        match self {
+            PresentType::Toy => PresentType::Book,
+            PresentType::Book => PresentType::Sweater,
+            PresentType::Sweater => PresentType::Toy,
+        }
+    }
+}
+
+/// Different places to refill blood sugar. Some refill more than others.
+#[derive(Clone, Copy, PartialEq)]
+pub enum StoreType {
+    Supermarket,
+    Restaurant,
+    ConvenienceStore,
+    // Placed by the player before the level
+    Upzoned,
+}
+
+impl StoreType {
+    /// Only places with food count
+    pub fn from_amenity(amenity_type: &str) -> Option<StoreType> {
+        match AmenityType::categorize(amenity_type)? {
+            AmenityType::Supermarket => Some(StoreType::Supermarket),
+            AmenityType::Food | AmenityType::FastFood => Some(StoreType::Restaurant),
+            AmenityType::ConvenienceStore => Some(StoreType::ConvenienceStore),
+            _ => None,
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            StoreType::Supermarket => "Supermarket",
+            StoreType::Restaurant => "Restaurant",
+            StoreType::ConvenienceStore => "Convenience store",
+            StoreType::Upzoned => "Upzoned store",
+        }
+    }
+
+    /// A simple shape drawn on top of the building, so the types can be told apart at a glance
+    fn icon(self, center: Pt2D) -> Option<Polygon> {
+        let size = Distance::meters(4.0);
+        match self {
+            StoreType::Supermarket => Some(Polygon::rectangle_centered(center, size, size)),
+            StoreType::Restaurant => Some(Circle::new(center, size / 2.0).to_polygon()),
+            StoreType::ConvenienceStore => {
+                Some(Polygon::rectangle_centered(center, size, size).rotate(Angle::degrees(45.0)))
+            }
+            // These have a label instead
+            StoreType::Upzoned => None,
+        }
+    }
+}
+
 impl Buildings {
-    pub fn new(ctx: &mut EventCtx, app: &App, upzones: HashSet<BuildingID>) -> Buildings {
+    pub fn new(
+        ctx: &mut EventCtx,
+        app: &App,
+        upzones: HashSet<BuildingID>,
+        seed: u64,
+    ) -> Buildings {
+        let buildings = Buildings::initial_states(&app.map, &upzones, seed);
+
+        // Working out how to draw each building doesn't depend on any others, so split the work
+        // up across threads. On the web, this just runs in order.
+        let states = &buildings;
         let colors = &app.session.colors;
+        let language = app.opts.language.as_ref();
+        let mut timer = Timer::new("set up buildings");
+        let chunks = timer.parallelize(
+            "draw buildings",
+            app.map
+                .all_buildings()
+                .chunks(BUILDINGS_PER_THREAD)
+                .collect(),
+            move |chunk| draw_chunk(chunk, states, colors, language),
+        );
 
-        let mut buildings = HashMap::new();
         let mut total_housing_units = 0;
         let mut batch = GeomBatch::new();
-        for b in app.map.all_buildings() {
+        let mut dots = GeomBatch::new();
+        let mut labels = BuildingLabels::new(app.session.text_size.scale());
+        for chunk in chunks {
+            total_housing_units += chunk.housing_units;
+            batch.append(chunk.batch);
+            dots.append(chunk.dots);
+            for (b, center, txt, scale) in chunk.labels {
+                let units = match buildings[&b] {
+                    BldgState::Undelivered(units, _) => units,
+                    _ => 0,
+                };
+                labels.add(b, center, txt, scale * labels.text_scale, units);
+            }
+        }
+
+        Buildings {
+            index: BuildingIndex::new(&app.map, &buildings),
+            buildings,
+            draw_all: ctx.upload(batch),
+            draw_minimap_dots: ctx.upload(dots),
+            labels,
+            total_housing_units,
+            upzones,
+        }
+    }
+
+    /// What every building starts as, without drawing anything. The same seed always picks the
+    /// same presents.
+    pub fn initial_states(
+        map: &Map,
+        upzones: &HashSet<BuildingID>,
+        seed: u64,
+    ) -> HashMap<BuildingID, BldgState> {
+        let mut buildings = HashMap::new();
+        let mut rng = XorShiftRng::seed_from_u64(seed);
+        for b in map.all_buildings() {
             if upzones.contains(&b.id) {
-                buildings.insert(b.id, BldgState::Store);
-                batch.push(colors.store, b.polygon.clone());
-                batch.append(
-                    Text::from("Upzoned")
-                        .render_autocropped(ctx)
-                        .scale(0.1)
-                        .centered_on(b.label_center),
-                );
+                buildings.insert(b.id, BldgState::Store(StoreType::Upzoned));
                 continue;
             }
 
@@ -51,80 +194,568 @@ impl Buildings {
             {
                 // There are some unused commercial buildings around!
                 if num_housing_units > 0 {
-                    buildings.insert(b.id, BldgState::Undelivered(num_housing_units));
-                    total_housing_units += num_housing_units;
-
-                    let color = if num_housing_units > 5 {
-                        colors.apartment
-                    } else {
-                        colors.house
-                    };
-                    batch.push(color, b.polygon.clone());
-                    // Call out non-single family homes
-                    if num_housing_units > 1 {
-                        batch.append(
-                            Text::from(Line(num_housing_units.to_string()).fg(Color::BLACK))
-                                .render_autocropped(ctx)
-                                .scale(0.2)
-                                .centered_on(b.label_center),
-                        );
-                    }
+                    let present = PresentType::ALL[rng.gen_range(0..PresentType::ALL.len())];
+                    buildings.insert(b.id, BldgState::Undelivered(num_housing_units, present));
                     continue;
                 }
-            } else if let Some(amenity) = b.amenities.iter().find(|a| {
-                if let Some(at) = AmenityType::categorize(&a.amenity_type) {
-                    at == AmenityType::Bar
-                        || at == AmenityType::ConvenienceStore
-                        || at == AmenityType::Food
-                        || at == AmenityType::Supermarket
-                } else {
-                    false
-                }
-            }) {
-                buildings.insert(b.id, BldgState::Store);
-                batch.push(colors.store, b.polygon.clone());
-                batch.append(
-                    Text::from(amenity.names.get(app.opts.language.as_ref()))
-                        .render_autocropped(ctx)
-                        .scale(0.1)
-                        .centered_on(b.label_center),
-                );
+            } else if let Some(store_type) = b
+                .amenities
+                .iter()
+                .find_map(|a| StoreType::from_amenity(&a.amenity_type))
+            {
+                buildings.insert(b.id, BldgState::Store(store_type));
                 continue;
             }
 
-            // If it's not a residence or store, just blank it out.
             buildings.insert(b.id, BldgState::Ignore);
-            batch.push(colors.visited, b.polygon.clone());
         }
+        buildings
+    }
 
-        Buildings {
-            buildings,
-            draw_all: ctx.upload(batch),
-            total_housing_units,
-            upzones,
-        }
+    /// Undo every delivery, keeping everything that's already drawn. The seed has to match the
+    /// original one, or the labels would be wrong.
+    pub fn reset(&mut self, map: &Map, seed: u64) {
+        self.buildings = Buildings::initial_states(map, &self.upzones, seed);
+        self.index = BuildingIndex::new(map, &self.buildings);
+        self.labels.show_all();
     }
 
     pub fn all_stores(&self) -> Vec<BuildingID> {
         let mut stores = Vec::new();
         for (b, state) in &self.buildings {
-            if let BldgState::Store = state {
+            if let BldgState::Store(_) | BldgState::Snacked(_) = state {
                 stores.push(*b);
             }
         }
         stores
     }
+}
 
-    pub fn draw_done_houses(&self, ctx: &mut EventCtx, app: &App) -> Drawable {
-        let mut batch = GeomBatch::new();
-        for (b, state) in &self.buildings {
-            if let BldgState::Done = state {
+/// Undelivered houses and stores, to find the one under the mouse without searching through
+/// everything else in the map. Doesn't keep itself in sync; update it after every delivery.
+pub struct BuildingIndex {
+    quadtree: QuadTree<BuildingID>,
+}
+
+impl BuildingIndex {
+    fn new(map: &Map, buildings: &HashMap<BuildingID, BldgState>) -> BuildingIndex {
+        let mut quadtree = QuadTree::builder();
+        for (b, state) in buildings {
+            if !matches!(state, BldgState::Done | BldgState::Ignore) {
+                quadtree.add_with_box(*b, map.get_b(*b).polygon.get_bounds());
+            }
+        }
+        BuildingIndex {
+            quadtree: quadtree.build(),
+        }
+    }
+
+    /// After a house is delivered to
+    pub fn remove(&mut self, b: BuildingID) {
+        self.quadtree.remove(b);
+    }
+
+    /// After a house wants presents again
+    pub fn insert(&mut self, map: &Map, b: BuildingID) {
+        self.quadtree
+            .insert_with_box(b, map.get_b(b).polygon.get_bounds());
+    }
+
+    pub fn building_at(&self, map: &Map, pt: Pt2D) -> Option<BuildingID> {
+        self.quadtree
+            .query_bbox(Circle::new(pt, Distance::meters(3.0)).get_bounds())
+            .find(|b| map.get_b(*b).polygon.contains_pt(pt))
+    }
+}
+
+// Buildings handed to each thread at once
+const BUILDINGS_PER_THREAD: usize = 1000;
+
+struct DrawnChunk {
+    batch: GeomBatch,
+    dots: GeomBatch,
+    labels: Vec<(BuildingID, Pt2D, Text, f64)>,
+    housing_units: usize,
+}
+
+fn draw_chunk(
+    chunk: &[Building],
+    states: &HashMap<BuildingID, BldgState>,
+    colors: &ColorScheme,
+    language: Option<&String>,
+) -> DrawnChunk {
+    let mut drawn = DrawnChunk {
+        batch: GeomBatch::new(),
+        dots: GeomBatch::new(),
+        labels: Vec::new(),
+        housing_units: 0,
+    };
+    for b in chunk {
+        match states[&b.id] {
+            BldgState::Store(StoreType::Upzoned) => {
+                drawn.batch.push(colors.store, b.polygon.clone());
+                drawn.dots.push(
+                    colors.store,
+                    Circle::new(b.label_center, MINIMAP_DOT_RADIUS).to_polygon(),
+                );
+                drawn
+                    .labels
+                    .push((b.id, b.label_center, Text::from("Upzoned"), 0.1));
+            }
+            BldgState::Undelivered(num_housing_units, present) => {
+                drawn.housing_units += num_housing_units;
+
+                let color = if num_housing_units > 5 {
+                    colors.apartment
+                } else {
+                    colors.house
+                };
+                drawn.batch.push(color, b.polygon.clone());
+                drawn.dots.push(
+                    color,
+                    Circle::new(b.label_center, MINIMAP_DOT_RADIUS).to_polygon(),
+                );
+                // Call out non-single family homes
+                let label = if num_housing_units > 1 {
+                    format!("{} {}s", num_housing_units, present.name())
+                } else {
+                    present.name().to_string()
+                };
+                drawn.labels.push((
+                    b.id,
+                    b.label_center,
+                    Text::from(Line(label).fg(present.color()).outlined(Color::BLACK)),
+                    0.2,
+                ));
+            }
+            BldgState::Store(store_type) | BldgState::Snacked(store_type) => {
+                drawn.batch.push(colors.store, b.polygon.clone());
+                if let Some(icon) = store_type.icon(b.polygon.center()) {
+                    drawn.batch.push(Color::WHITE.alpha(0.8), icon);
+                }
+                drawn.dots.push(
+                    colors.store,
+                    Circle::new(b.label_center, MINIMAP_DOT_RADIUS).to_polygon(),
+                );
+                if let Some(amenity) = b
+                    .amenities
+                    .iter()
+                    .find(|a| StoreType::from_amenity(&a.amenity_type).is_some())
+                {
+                    drawn.labels.push((
+                        b.id,
+                        b.label_center,
+                        Text::from(amenity.names.get(language)),
+                        0.1,
+                    ));
+                }
+            }
+            BldgState::Done | BldgState::Ignore => {
+                // If it's not a residence or store, just blank it out.
+                drawn.batch.push(colors.visited, b.polygon.clone());
+            }
+        }
+    }
+    drawn
+}
+
+// Radius of the dots marking buildings on the minimap, where their real shape is too small to see
+const MINIMAP_DOT_RADIUS: Distance = Distance::const_meters(8.0);
+// Delivered houses get strung with lights this far apart
+const LIGHT_SPACING: Distance = Distance::const_meters(3.0);
+const LIGHT_RADIUS: Distance = Distance::const_meters(0.6);
+// Delivered houses are wrapped up like presents. Each neighborhood, a square this wide in meters,
+// gets its own paper.
+const WRAPPING_NEIGHBORHOOD_SIZE: f64 = 300.0;
+const RIBBON_THICKNESS: Distance = Distance::const_meters(1.5);
+const BOW_RADIUS: Distance = Distance::const_meters(1.5);
+
+/// Delivered buildings only ever accumulate during a level, so only the newest ones need to be
+/// uploaded after each delivery. They're lit up with Christmas lights, to stand out as night
+/// falls.
+pub struct DoneHouses {
+    polygons: AppendOnlyBatch,
+    minimap_dots: AppendOnlyBatch,
+}
+
+impl DoneHouses {
+    pub fn new(ctx: &EventCtx) -> DoneHouses {
+        DoneHouses {
+            polygons: AppendOnlyBatch::new(ctx),
+            minimap_dots: AppendOnlyBatch::new(ctx),
+        }
+    }
+
+    pub fn add(&mut self, ctx: &mut EventCtx, app: &App, b: BuildingID) {
+        let bldg = app.map.get_b(b);
+        let mut batch = GeomBatch::from(vec![(
+            wrapping_paper(bldg.polygon.center()),
+            bldg.polygon.clone(),
+        )]);
+        if app.session.palette.hatch_done() {
+            // Otherwise delivered houses look just like buildings that never wanted anything
+            batch.extend(Color::WHITE.alpha(0.5), hatching(&bldg.polygon));
+        }
+        batch.extend(Color::hex("#FFD700"), ribbon(&bldg.polygon));
+        let outline = PolyLine::unchecked_new(bldg.polygon.get_outer_ring().clone().into_points());
+        let colors = [Color::RED, Color::GREEN, Color::hex("#FFD700"), Color::CYAN];
+        let mut dist = Distance::ZERO;
+        let mut idx = 0;
+        while dist < outline.length() {
+            if let Ok((pt, _)) = outline.dist_along(dist) {
                 batch.push(
-                    app.session.colors.visited,
-                    app.map.get_b(*b).polygon.clone(),
+                    colors[idx % colors.len()],
+                    Circle::new(pt, LIGHT_RADIUS).to_polygon(),
                 );
             }
+            dist += LIGHT_SPACING;
+            idx += 1;
+        }
+        self.polygons.add(ctx, batch);
+        self.minimap_dots.add(
+            ctx,
+            GeomBatch::from(vec![(
+                app.session.colors.visited,
+                Circle::new(bldg.label_center, MINIMAP_DOT_RADIUS).to_polygon(),
+            )]),
+        );
+    }
+
+    pub fn minimap_layers(&self) -> Vec<&Drawable> {
+        let mut layers = self.polygons.layers();
+        layers.extend(self.minimap_dots.layers());
+        layers
+    }
+
+    pub fn draw(&self, g: &mut GfxCtx) {
+        for draw in self.polygons.layers() {
+            g.redraw(draw);
+        }
+    }
+}
+
+// Once this many items accumulate, freeze them into their own Drawable
+const DONE_HOUSES_PER_CHUNK: usize = 50;
+
+/// Keeps things in append-only chunks, so adding something only re-uploads the small, most recent
+/// chunk.
+struct AppendOnlyBatch {
+    sealed: Vec<Drawable>,
+    pending: GeomBatch,
+    num_pending: usize,
+    draw_pending: Drawable,
+}
+
+impl AppendOnlyBatch {
+    fn new(ctx: &EventCtx) -> AppendOnlyBatch {
+        AppendOnlyBatch {
+            sealed: Vec::new(),
+            pending: GeomBatch::new(),
+            num_pending: 0,
+            draw_pending: Drawable::empty(ctx),
+        }
+    }
+
+    fn add(&mut self, ctx: &mut EventCtx, batch: GeomBatch) {
+        self.pending.append(batch);
+        self.num_pending += 1;
+        if self.num_pending == DONE_HOUSES_PER_CHUNK {
+            let batch = std::mem::replace(&mut self.pending, GeomBatch::new());
+            self.sealed.push(ctx.upload(batch));
+            self.num_pending = 0;
+            self.draw_pending = Drawable::empty(ctx);
+        } else {
+            self.draw_pending = ctx.upload(self.pending.clone());
+        }
+    }
+
+    fn layers(&self) -> Vec<&Drawable> {
+        let mut layers: Vec<&Drawable> = self.sealed.iter().collect();
+        layers.push(&self.draw_pending);
+        layers
+    }
+}
+
+// Cells are square, in meters
+const LABEL_CELL_SIZE: f64 = 250.0;
+// Below this zoom, labels are too small to read anyway, so each cell gets one big label with its
+// total instead
+const MIN_ZOOM_FOR_LABELS: f64 = 1.5;
+const TOTAL_LABEL_SCALE: f64 = 1.5;
+// Rendering text is slow, so spread out rendering cells that aren't visible yet
+const PRERENDER_CELLS_PER_FRAME: usize = 2;
+
+/// Rendering text for every building up-front is very expensive on large maps, so only labels
+/// near the camera are rendered, lazily, and then cached per grid cell. Hiding one label only
+/// re-renders its cell.
+pub struct BuildingLabels {
+    per_cell: HashMap<(usize, usize), Vec<(BuildingID, Pt2D, Text, f64)>>,
+    draw_per_cell: RefCell<HashMap<(usize, usize), Drawable>>,
+    // When zoomed out, the total still wanted in each cell
+    draw_totals_per_cell: RefCell<HashMap<(usize, usize), Drawable>>,
+    // Delivered houses don't need a label anymore
+    hidden: HashSet<BuildingID>,
+    cell_per_bldg: HashMap<BuildingID, (usize, usize)>,
+    units_per_bldg: HashMap<BuildingID, usize>,
+    // From the player's text size setting
+    text_scale: f64,
+}
+
+impl BuildingLabels {
+    fn new(text_scale: f64) -> BuildingLabels {
+        BuildingLabels {
+            per_cell: HashMap::new(),
+            draw_per_cell: RefCell::new(HashMap::new()),
+            draw_totals_per_cell: RefCell::new(HashMap::new()),
+            hidden: HashSet::new(),
+            cell_per_bldg: HashMap::new(),
+            units_per_bldg: HashMap::new(),
+            text_scale,
+        }
+    }
+
+    fn add(&mut self, b: BuildingID, center: Pt2D, txt: Text, scale: f64, units: usize) {
+        let key = cell(center);
+        self.per_cell
+            .entry(key)
+            .or_insert_with(Vec::new)
+            .push((b, center, txt, scale));
+        self.cell_per_bldg.insert(b, key);
+        if units > 0 {
+            self.units_per_bldg.insert(b, units);
         }
-        ctx.upload(batch)
+    }
+
+    pub fn hide(&mut self, b: BuildingID) {
+        if self.hidden.insert(b) {
+            self.invalidate(b);
+        }
+    }
+
+    /// In endless mode, houses want presents again
+    pub fn show(&mut self, b: BuildingID) {
+        if self.hidden.remove(&b) {
+            self.invalidate(b);
+        }
+    }
+
+    fn show_all(&mut self) {
+        for b in std::mem::take(&mut self.hidden) {
+            self.invalidate(b);
+        }
+    }
+
+    // The cell gets rendered again the next time it's drawn
+    fn invalidate(&mut self, b: BuildingID) {
+        if let Some(key) = self.cell_per_bldg.get(&b) {
+            self.draw_per_cell.get_mut().remove(key);
+            self.draw_totals_per_cell.get_mut().remove(key);
+        }
+    }
+
+    pub fn draw(&self, g: &mut GfxCtx) {
+        let bounds = g.get_screen_bounds();
+        let (x1, y1) = cell(Pt2D::new(bounds.min_x, bounds.min_y));
+        let (x2, y2) = cell(Pt2D::new(bounds.max_x, bounds.max_y));
+
+        if g.canvas.cam_zoom < MIN_ZOOM_FOR_LABELS {
+            let mut cache = self.draw_totals_per_cell.borrow_mut();
+            for x in x1..=x2 {
+                for y in y1..=y2 {
+                    let key = (x, y);
+                    if let Some(list) = self.per_cell.get(&key) {
+                        if !cache.contains_key(&key) {
+                            cache.insert(
+                                key,
+                                render_total(
+                                    g,
+                                    list,
+                                    &self.units_per_bldg,
+                                    &self.hidden,
+                                    self.text_scale,
+                                ),
+                            );
+                        }
+                        g.redraw(&cache[&key]);
+                    }
+                }
+            }
+            return;
+        }
+
+        let mut cache = self.draw_per_cell.borrow_mut();
+        for x in x1..=x2 {
+            for y in y1..=y2 {
+                let key = (x, y);
+                let list = if let Some(list) = self.per_cell.get(&key) {
+                    list
+                } else {
+                    continue;
+                };
+                if !cache.contains_key(&key) {
+                    cache.insert(key, render_cell(g, list, &self.hidden));
+                }
+                g.redraw(&cache[&key]);
+            }
+        }
+
+        // Get ahead of the player by rendering a few cells just off-screen each frame, so moving
+        // into them doesn't stall
+        let mut budget = PRERENDER_CELLS_PER_FRAME;
+        for x in x1.saturating_sub(1)..=x2 + 1 {
+            for y in y1.saturating_sub(1)..=y2 + 1 {
+                if budget == 0 {
+                    return;
+                }
+                let key = (x, y);
+                if cache.contains_key(&key) {
+                    continue;
+                }
+                if let Some(list) = self.per_cell.get(&key) {
+                    cache.insert(key, render_cell(g, list, &self.hidden));
+                    budget -= 1;
+                }
+            }
+        }
+    }
+}
+
+// Centered on the houses in the cell still wanting presents
+fn render_total(
+    g: &mut GfxCtx,
+    list: &[(BuildingID, Pt2D, Text, f64)],
+    units_per_bldg: &HashMap<BuildingID, usize>,
+    hidden: &HashSet<BuildingID>,
+    text_scale: f64,
+) -> Drawable {
+    let mut total = 0;
+    let mut centers = Vec::new();
+    for (b, center, _, _) in list.iter().filter(|(b, _, _, _)| !hidden.contains(b)) {
+        if let Some(units) = units_per_bldg.get(b) {
+            total += units;
+            centers.push(*center);
+        }
+    }
+    let mut batch = GeomBatch::new();
+    if total > 0 {
+        batch.append(
+            Text::from(
+                Line(prettyprint_usize(total))
+                    .fg(Color::WHITE)
+                    .outlined(Color::BLACK),
+            )
+            .render_autocropped(g)
+            .scale(TOTAL_LABEL_SCALE * text_scale)
+            .centered_on(Pt2D::center(&centers)),
+        );
+    }
+    g.upload(batch)
+}
+
+fn render_cell(
+    g: &mut GfxCtx,
+    list: &[(BuildingID, Pt2D, Text, f64)],
+    hidden: &HashSet<BuildingID>,
+) -> Drawable {
+    let mut batch = GeomBatch::new();
+    for (_, center, txt, scale) in list.iter().filter(|(b, _, _, _)| !hidden.contains(b)) {
+        batch.append(
+            txt.clone()
+                .render_autocropped(g)
+                .scale(*scale)
+                .centered_on(*center),
+        );
+    }
+    g.upload(batch)
+}
+
+fn cell(pt: Pt2D) -> (usize, usize) {
+    (
+        (pt.x().max(0.0) / LABEL_CELL_SIZE) as usize,
+        (pt.y().max(0.0) / LABEL_CELL_SIZE) as usize,
+    )
+}
+
+/// Festive colors that stay dark enough for the lights and ribbon to stand out, picked by
+/// neighborhood so nearby houses match
+fn wrapping_paper(pt: Pt2D) -> Color {
+    let papers = [
+        Color::hex("#8B0000"),
+        Color::hex("#0B6623"),
+        Color::hex("#1C2E6B"),
+        Color::hex("#5B2C6F"),
+    ];
+    let x = (pt.x().max(0.0) / WRAPPING_NEIGHBORHOOD_SIZE) as usize;
+    let y = (pt.y().max(0.0) / WRAPPING_NEIGHBORHOOD_SIZE) as usize;
+    papers[(x + 3 * y) % papers.len()]
+}
+
+/// A ribbon crossing over a building, tied in a bow at the center
+fn ribbon(polygon: &Polygon) -> Vec<Polygon> {
+    let bounds = polygon.get_bounds();
+    let center = polygon.center();
+    let mut pieces = Vec::new();
+    for (pt1, pt2) in [
+        (
+            Pt2D::new(bounds.min_x, center.y()),
+            Pt2D::new(bounds.max_x, center.y()),
+        ),
+        (
+            Pt2D::new(center.x(), bounds.min_y),
+            Pt2D::new(center.x(), bounds.max_y),
+        ),
+    ] {
+        if let Ok(line) = geom::Line::new(pt1, pt2) {
+            if let Ok(list) = line.make_polygons(RIBBON_THICKNESS).intersection(polygon) {
+                pieces.extend(list);
+            }
+        }
+    }
+    for offset in [-1.0, 1.0] {
+        pieces.push(
+            Circle::new(
+                center.offset(offset * BOW_RADIUS.inner_meters(), 0.0),
+                BOW_RADIUS,
+            )
+            .to_polygon(),
+        );
+    }
+    pieces
+}
+
+const HATCH_SPACING: Distance = Distance::const_meters(3.0);
+const HATCH_THICKNESS: Distance = Distance::const_meters(0.8);
+
+/// Diagonal stripes covering a polygon, to mark it without relying on color
+pub fn hatching(polygon: &Polygon) -> Vec<Polygon> {
+    let bounds = polygon.get_bounds();
+    let height = bounds.height();
+    let mut stripes = Vec::new();
+    let mut x = bounds.min_x - height;
+    while x < bounds.max_x {
+        let stripe = geom::Line::must_new(
+            Pt2D::new(x, bounds.min_y),
+            Pt2D::new(x + height, bounds.max_y),
+        )
+        .make_polygons(HATCH_THICKNESS);
+        if let Ok(list) = stripe.intersection(polygon) {
+            stripes.extend(list);
+        }
+        x += HATCH_SPACING.inner_meters();
+    }
+    stripes
+}
+
+/// Whichever end of the driveway's road is closer, to start or teleport next to a building
+pub fn closest_intersection(map: &Map, b: BuildingID) -> IntersectionID {
+    let bldg = map.get_b(b);
+    let road = map.get_r(bldg.sidewalk_pos.lane().road);
+    let pt = bldg.driveway_geom.last_pt();
+    if map.get_i(road.src_i).polygon.center().dist_to(pt)
+        < map.get_i(road.dst_i).polygon.center().dist_to(pt)
+    {
+        road.src_i
+    } else {
+        road.dst_i
     }
 }
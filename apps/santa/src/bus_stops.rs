@@ -0,0 +1,65 @@
+use geom::{Circle, Distance, Duration, Pt2D};
+use widgetry::{Color, Drawable, EventCtx, GeomBatch, GfxCtx, Line, Text};
+
+use crate::App;
+
+const RADIUS: Distance = Distance::const_meters(2.5);
+// How close the player has to stay to a stop to recharge
+const RECHARGE_DIST: Distance = Distance::const_meters(10.0);
+
+/// Bus stops slowly refill blood sugar while the player waits nearby. They're much weaker than
+/// stores, but help when stranded far away from any.
+pub struct BusStops {
+    stops: Vec<Pt2D>,
+    // Energy accumulates fractionally, but is only handed out in whole units
+    charge: f64,
+    draw: Drawable,
+}
+
+impl BusStops {
+    pub fn new(ctx: &EventCtx, app: &App) -> BusStops {
+        let mut stops = Vec::new();
+        let mut batch = GeomBatch::new();
+        let color = Color::hex("#1D6FD1");
+        for ts in app.map.all_transit_stops().values() {
+            if ts.is_train_stop {
+                continue;
+            }
+            let pt = ts.driving_pos.pt(&app.map);
+            stops.push(pt);
+            batch.push(Color::WHITE, Circle::new(pt, RADIUS).to_polygon());
+            batch.push(color, Circle::new(pt, RADIUS * 0.8).to_polygon());
+            batch.append(
+                Text::from(Line("B").fg(Color::WHITE))
+                    .render_autocropped(ctx)
+                    .scale(0.1)
+                    .centered_on(pt),
+            );
+        }
+        BusStops {
+            stops,
+            charge: 0.0,
+            draw: ctx.upload(batch),
+        }
+    }
+
+    /// Returns how much energy to add, if the player is near a stop. `rate` is per second.
+    pub fn recharge(&mut self, player: Pt2D, dt: Duration, rate: f64) -> usize {
+        if !self
+            .stops
+            .iter()
+            .any(|pt| pt.dist_to(player) <= RECHARGE_DIST)
+        {
+            self.charge = 0.0;
+            return 0;
+        }
+        self.charge += rate * dt.inner_seconds();
+        let whole = self.charge.floor();
+        self.charge -= whole;
+        whole as usize
+    }
+
+    pub fn draw(&self, g: &mut GfxCtx) {
+        g.redraw(&self.draw);
+    }
+}
@@ -0,0 +1,208 @@
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+use geom::{Duration, Pt2D, Speed, Time};
+use widgetry::EventCtx;
+
+use crate::session::CameraMode;
+use crate::App;
+
+// With the loose camera, the player can move freely in the middle of the screen, up to this
+// fraction of the window from any edge
+const LOOSE_CAMERA_MARGIN: f64 = 0.3;
+// The most the smooth camera trails behind. It covers about two thirds of the gap to the player
+// in this time.
+const MAX_LAG: Duration = Duration::const_seconds(0.5);
+// The most the smooth camera looks ahead: wherever the player will be in this much time
+const MAX_LOOKAHEAD: Duration = Duration::const_seconds(1.0);
+// At full speed, zoom out this much from whatever the player picked
+const MAX_ZOOM_OUT: f64 = 0.3;
+// And zoom in this much when the mouse is over a building, to read its label
+const HOVER_ZOOM_IN: f64 = 1.3;
+// Changing zoom covers about two thirds of the gap in this time
+const ZOOM_LAG: Duration = Duration::const_seconds(0.8);
+const SHAKE_DURATION: Duration = Duration::const_seconds(0.3);
+// In pixels
+const SHAKE_MAGNITUDE: f64 = 15.0;
+
+/// Keeps the player on screen. Depending on the settings, the camera stays locked on, only pans
+/// near the edge of the screen, or trails behind smoothly while looking ahead in the direction the
+/// player is moving. Collisions can also shake the screen, and the zoom can follow the player's
+/// speed.
+pub struct Camera {
+    // What the camera is centered on, not counting any shaking
+    center: Option<Pt2D>,
+    // Where the player was last update, to figure out how fast they're going
+    last_player: Option<Pt2D>,
+    // What the player chose by scrolling. The actual zoom changes around this.
+    base_zoom: Option<f64>,
+    zoom: Option<f64>,
+    shake_until: Option<Time>,
+    // In pixels. Undone before moving the camera again.
+    shake_offset: (f64, f64),
+    // Shaking doesn't affect gameplay, so it doesn't need to be seeded with the level
+    rng: XorShiftRng,
+}
+
+impl Camera {
+    pub fn new() -> Camera {
+        Camera {
+            center: None,
+            last_player: None,
+            base_zoom: None,
+            zoom: None,
+            shake_until: None,
+            shake_offset: (0.0, 0.0),
+            rng: XorShiftRng::seed_from_u64(42),
+        }
+    }
+
+    /// After the player teleports, cut straight there instead of sweeping across the map
+    pub fn jump(&mut self) {
+        self.center = None;
+        self.last_player = None;
+    }
+
+    /// Only if the player wants it
+    pub fn shake(&mut self, app: &App) {
+        if app.session.screen_shake && !app.session.reduce_motion {
+            self.shake_until = Some(app.time + SHAKE_DURATION);
+        }
+    }
+
+    /// Scrolling zooms in or out, as a starting point for any automatic zoom
+    pub fn scroll_zoom(&mut self, ctx: &mut EventCtx, app: &App, dy: f64) {
+        let zoom = 1.1_f64
+            .powf(self.base_zoom.unwrap_or(ctx.canvas.cam_zoom).log(1.1) + dy)
+            .max(ctx.canvas.settings.min_zoom_for_detail)
+            .min(50.0);
+        self.base_zoom = Some(zoom);
+        if !app.session.auto_zoom {
+            self.zoom = Some(zoom);
+            ctx.canvas.cam_zoom = zoom;
+        }
+        self.apply(ctx);
+    }
+
+    /// Call every tick. `top_speed` is how fast the player can normally go, to decide how far to
+    /// zoom out. When `hovering` over a building, zoom in a bit to read it.
+    pub fn update(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &App,
+        dt: Duration,
+        player: Pt2D,
+        top_speed: Speed,
+        hovering: bool,
+    ) {
+        let secs = dt.inner_seconds();
+        let velocity = match self.last_player {
+            Some(last) if secs > 0.0 => (
+                (player.x() - last.x()) / secs,
+                (player.y() - last.y()) / secs,
+            ),
+            _ => (0.0, 0.0),
+        };
+        self.last_player = Some(player);
+        let speed = velocity.0.hypot(velocity.1);
+        let speed_pct = if top_speed.inner_meters_per_second() > 0.0 {
+            (speed / top_speed.inner_meters_per_second()).min(1.0)
+        } else {
+            0.0
+        };
+        self.update_zoom(ctx, app, dt, speed_pct, hovering);
+
+        let current = self.center.unwrap_or(player);
+        let center = match app.session.camera {
+            CameraMode::Centered => player,
+            CameraMode::Loose => {
+                // Work in pixels, so the margin stays the same while zooming
+                let zoom = ctx.canvas.cam_zoom;
+                let (width, height) = (ctx.canvas.window_width, ctx.canvas.window_height);
+                let dx = past_margin((player.x() - current.x()) * zoom + width / 2.0, width);
+                let dy = past_margin((player.y() - current.y()) * zoom + height / 2.0, height);
+                current.offset(dx / zoom, dy / zoom)
+            }
+            CameraMode::Smooth => {
+                let lookahead = MAX_LOOKAHEAD.inner_seconds() * app.session.camera_lookahead;
+                let target = player.offset(velocity.0 * lookahead, velocity.1 * lookahead);
+                let lag = MAX_LAG.inner_seconds() * app.session.camera_lag;
+                if lag == 0.0 {
+                    target
+                } else {
+                    // Exponential smoothing, so this doesn't depend on the frame rate
+                    let pct = 1.0 - (-dt.inner_seconds() / lag).exp();
+                    current.offset(
+                        (target.x() - current.x()) * pct,
+                        (target.y() - current.y()) * pct,
+                    )
+                }
+            }
+        };
+        self.center = Some(center);
+
+        self.shake_offset = match self.shake_until {
+            Some(until) if app.time < until => {
+                let magnitude = SHAKE_MAGNITUDE * (until - app.time) / SHAKE_DURATION;
+                (
+                    self.rng.gen_range(-1.0..1.0) * magnitude,
+                    self.rng.gen_range(-1.0..1.0) * magnitude,
+                )
+            }
+            _ => {
+                self.shake_until = None;
+                (0.0, 0.0)
+            }
+        };
+        self.apply(ctx);
+    }
+
+    fn update_zoom(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &App,
+        dt: Duration,
+        speed_pct: f64,
+        hovering: bool,
+    ) {
+        let base = *self.base_zoom.get_or_insert(ctx.canvas.cam_zoom);
+        let target = if app.session.auto_zoom {
+            let zoom = base * (1.0 - MAX_ZOOM_OUT * speed_pct);
+            if hovering {
+                zoom * HOVER_ZOOM_IN
+            } else {
+                zoom
+            }
+        } else {
+            base
+        };
+        let current = self.zoom.unwrap_or(target);
+        let pct = 1.0 - (-dt.inner_seconds() / ZOOM_LAG.inner_seconds()).exp();
+        let zoom = (current + (target - current) * pct)
+            .max(ctx.canvas.settings.min_zoom_for_detail)
+            .min(50.0);
+        self.zoom = Some(zoom);
+        ctx.canvas.cam_zoom = zoom;
+    }
+
+    /// Moves the canvas to match, like after zooming
+    pub fn apply(&self, ctx: &mut EventCtx) {
+        if let Some(center) = self.center {
+            ctx.canvas.center_on_map_pt(center);
+            ctx.canvas.cam_x += self.shake_offset.0;
+            ctx.canvas.cam_y += self.shake_offset.1;
+        }
+    }
+}
+
+// How far past the loose camera's margin a screen coordinate is, with the sign showing which side
+fn past_margin(x: f64, window_size: f64) -> f64 {
+    let margin = LOOSE_CAMERA_MARGIN * window_size;
+    if x < margin {
+        x - margin
+    } else if x > window_size - margin {
+        x - (window_size - margin)
+    } else {
+        0.0
+    }
+}
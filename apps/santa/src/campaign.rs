@@ -0,0 +1,250 @@
+use abstutil::prettyprint_usize;
+use geom::{Duration, Polygon};
+use widgetry::{
+    Color, DrawBaselayer, EventCtx, GfxCtx, HorizontalAlignment, Line, Panel, State, Text,
+    UpdateType, VerticalAlignment, Widget,
+};
+
+use crate::game::Game;
+use crate::levels::Level;
+use crate::load_map::load_map;
+use crate::vehicles::Vehicle;
+use crate::{App, Transition};
+
+// How long the screen takes to fade out between maps
+const FADE_DURATION: Duration = Duration::const_seconds(1.5);
+
+/// Some neighborhoods border each other, but are split into different maps. A campaign strings
+/// their levels together: after meeting the goal on one map, riding off the edge of it continues
+/// on the next, with the score and vehicle carried over.
+#[derive(Clone)]
+pub struct Campaign {
+    pub title: String,
+    pub description: String,
+    pub legs: Vec<Level>,
+}
+
+impl Campaign {
+    pub fn all() -> Vec<Campaign> {
+        let level = |title: &str| {
+            Level::all()
+                .into_iter()
+                .find(|level| level.title == title)
+                .unwrap()
+        };
+        vec![
+            Campaign {
+                title: "North of the Ship Canal".to_string(),
+                description: "Phinney Ridge, Wallingfjord, and the University District in one \
+                              night"
+                    .to_string(),
+                legs: vec![
+                    level("Phinney Ridge"),
+                    level("Wallingfjord"),
+                    level("University District"),
+                ],
+            },
+            Campaign {
+                title: "Around the lake".to_string(),
+                description: "From Queen Anne down to the South Pole Union".to_string(),
+                legs: vec![level("Queen Anne"), level("South Pole Union")],
+            },
+        ]
+    }
+
+    /// Every leg has to be unlocked already
+    pub fn is_unlocked(&self, app: &App) -> bool {
+        self.legs.iter().all(|leg| {
+            app.session
+                .levels
+                .iter()
+                .position(|level| level.title == leg.title)
+                .map(|idx| idx < app.session.levels_unlocked)
+                .unwrap_or(false)
+        })
+    }
+
+    /// Loads the first map and starts playing
+    pub fn start(self, ctx: &mut EventCtx, app: &App) -> Transition {
+        // Make sure every map is there first, so the campaign isn't interrupted partway
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(leg) = self
+            .legs
+            .iter()
+            .find(|leg| !abstio::file_exists(leg.map.path()))
+        {
+            return map_gui::tools::prompt_to_download_missing_data(
+                ctx,
+                leg.map.clone(),
+                Box::new(move |ctx, app| {
+                    // There might be more to download
+                    Transition::Multi(vec![Transition::Pop, self.start(ctx, app)])
+                }),
+            );
+        }
+
+        let progress = CampaignProgress {
+            vehicle: app.session.current_vehicle.clone(),
+            campaign: self,
+            leg: 0,
+            score_so_far: 0,
+            goal_so_far: 0,
+            energy: None,
+        };
+        Transition::Push(progress.load_leg(ctx, app, false))
+    }
+}
+
+/// Everything carried from one leg of a campaign to the next
+#[derive(Clone)]
+pub struct CampaignProgress {
+    pub campaign: Campaign,
+    // Index into the legs
+    pub leg: usize,
+    // From the legs before this one
+    pub score_so_far: usize,
+    goal_so_far: usize,
+    vehicle: String,
+    // None on the first leg, which starts full
+    pub energy: Option<usize>,
+}
+
+impl CampaignProgress {
+    pub fn has_next_leg(&self) -> bool {
+        self.leg + 1 < self.campaign.legs.len()
+    }
+
+    pub fn total_score(&self, leg_score: usize) -> usize {
+        self.score_so_far + leg_score
+    }
+
+    /// For recording the whole campaign's score at the end, as if it were one long level
+    pub fn summary_level(&self, leg: &Level) -> Level {
+        let mut level = leg.clone();
+        level.title = self.campaign.title.clone();
+        level.goal = self.goal_so_far + leg.goal;
+        level
+    }
+
+    /// Called after meeting the goal on the current leg and leaving the map
+    pub fn finish_leg(&mut self, leg: &Level, score: usize, energy: usize) {
+        self.score_so_far += score;
+        self.goal_so_far += leg.goal;
+        self.energy = Some(energy);
+        self.leg += 1;
+    }
+
+    /// If the loading is cancelled, it goes back to the state underneath. When that's the game on
+    /// the previous map, it's replaced once the next one is ready.
+    fn load_leg(self, ctx: &mut EventCtx, app: &App, from_game: bool) -> Box<dyn State<App>> {
+        let level = self.campaign.legs[self.leg].clone();
+        load_map(
+            ctx,
+            app,
+            level.map.clone(),
+            Box::new(move |ctx, app| {
+                app.session.music.change_song(&level.music);
+                let upzones = app
+                    .session
+                    .upzones_per_level
+                    .get(level.title.clone())
+                    .iter()
+                    .cloned()
+                    .collect();
+                let vehicle = Vehicle::get(&self.vehicle);
+                let game = Game::new_campaign_leg(ctx, app, level, vehicle, upzones, self);
+                if from_game {
+                    Transition::Multi(vec![Transition::Pop, Transition::Replace(game)])
+                } else {
+                    Transition::Replace(game)
+                }
+            }),
+        )
+    }
+}
+
+/// Pushed on top of the game after riding off the edge of one map in a campaign. Fades out, then
+/// loads the next map.
+pub struct NextLeg {
+    panel: Panel,
+    progress: Option<CampaignProgress>,
+    fade: Duration,
+}
+
+impl NextLeg {
+    pub fn new_state(
+        ctx: &mut EventCtx,
+        app: &mut App,
+        progress: CampaignProgress,
+    ) -> Box<dyn State<App>> {
+        app.session.music.specify_volume(crate::music::OUT_OF_GAME);
+        let next = &progress.campaign.legs[progress.leg];
+        let mut txt = Text::new();
+        txt.add_line(Line(format!("On to {}", next.title)).small_heading());
+        txt.add_line(format!(
+            "{} presents delivered so far",
+            prettyprint_usize(progress.score_so_far)
+        ));
+        let panel = Panel::new_builder(txt.into_widget(ctx).container())
+            .aligned(HorizontalAlignment::Center, VerticalAlignment::Center)
+            .build(ctx);
+        Box::new(NextLeg {
+            panel,
+            progress: Some(progress),
+            fade: Duration::ZERO,
+        })
+    }
+}
+
+impl State<App> for NextLeg {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        if let Some(dt) = ctx.input.nonblocking_is_update_event() {
+            self.fade += dt;
+        }
+        if self.fade >= FADE_DURATION {
+            let progress = self.progress.take().unwrap();
+            return Transition::Replace(progress.load_leg(ctx, app, true));
+        }
+        ctx.request_update(UpdateType::Game);
+
+        app.session.update_music(ctx);
+        Transition::Keep
+    }
+
+    fn draw_baselayer(&self) -> DrawBaselayer {
+        DrawBaselayer::PreviousState
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        let pct = (self.fade / FADE_DURATION).min(1.0) as f32;
+        g.fork_screenspace();
+        g.draw_polygon(
+            Color::BLACK.alpha(pct),
+            Polygon::rectangle(g.canvas.window_width, g.canvas.window_height),
+        );
+        g.unfork();
+        self.panel.draw(g);
+        app.session.music.draw(g);
+    }
+}
+
+/// The title screen lists campaigns under the levels
+pub fn campaign_btn(ctx: &mut EventCtx, app: &App, campaign: &Campaign) -> Widget {
+    let mut txt = Text::new();
+    txt.add_line(Line(&campaign.title).small_heading());
+    txt.add_line(&campaign.description);
+    txt.add_line(
+        Line(format!("{} maps in a row", campaign.legs.len())).fg(app.session.colors.good),
+    );
+    let unlocked = campaign.is_unlocked(app);
+    ctx.style()
+        .btn_outline
+        .text(if unlocked {
+            campaign.title.clone()
+        } else {
+            format!("{} (locked)", campaign.title)
+        })
+        .tooltip(txt)
+        .disabled(!unlocked)
+        .build_widget(ctx, &campaign.title)
+}
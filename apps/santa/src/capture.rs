@@ -0,0 +1,127 @@
+use geom::Duration;
+use widgetry::{
+    Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Line, Panel, State, Text,
+    UpdateType, VerticalAlignment, Widget,
+};
+
+use crate::buildings::Buildings;
+use crate::replay::Replay;
+use crate::{App, Transition};
+
+// How much of the end of the run to save
+const CAPTURE_DURATION: Duration = Duration::const_seconds(10.0);
+const FRAMES_PER_SECOND: f64 = 15.0;
+
+/// Saves the last few seconds of a run as numbered PNGs, so players can share a highlight without
+/// any other tools. Keeping every frame around while playing would take too much memory, so this
+/// plays the recorded replay back one frame at a time instead, saving each as it goes.
+pub struct Capture {
+    panel: Panel,
+    replay: Replay,
+    bldgs: Buildings,
+    dir: String,
+
+    // Since the start of the run
+    start: Duration,
+    time: Duration,
+    frame: usize,
+    num_frames: usize,
+    // How many deliveries have been drawn so far
+    num_deliveries: Option<usize>,
+    draw_deliveries: Drawable,
+}
+
+impl Capture {
+    pub fn new_state(ctx: &mut EventCtx, app: &App, replay: Replay) -> Box<dyn State<App>> {
+        let start = if replay.duration() > CAPTURE_DURATION {
+            replay.duration() - CAPTURE_DURATION
+        } else {
+            Duration::ZERO
+        };
+        let num_frames =
+            ((replay.duration() - start).inner_seconds() * FRAMES_PER_SECOND).ceil() as usize;
+        let dir = format!(
+            "screenshots/santa/{}_{}",
+            replay.level,
+            chrono::Local::now().format("%Y%m%d_%H%M%S")
+        );
+        let panel = Panel::new_builder(Widget::col(vec![
+            Line("Saving the last few seconds")
+                .small_heading()
+                .into_widget(ctx),
+            Text::new().into_widget(ctx).named("progress"),
+        ]))
+        .aligned(HorizontalAlignment::Center, VerticalAlignment::TopInset)
+        .build(ctx);
+
+        Box::new(Capture {
+            panel,
+            bldgs: replay.buildings(ctx, app),
+            replay,
+            dir,
+
+            start,
+            time: start,
+            frame: 0,
+            num_frames,
+            num_deliveries: None,
+            draw_deliveries: Drawable::empty(ctx),
+        })
+    }
+}
+
+impl State<App> for Capture {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        if self.frame == self.num_frames {
+            info!("Saved {} frames to {}", self.num_frames, self.dir);
+            return Transition::Pop;
+        }
+
+        self.time = self.start + Duration::seconds((self.frame as f64) / FRAMES_PER_SECOND);
+        if let Some(pt) = self.replay.sleigh_pos(self.time) {
+            ctx.canvas.center_on_map_pt(pt);
+        }
+        let deliveries = self.replay.deliveries();
+        let num_deliveries = deliveries
+            .iter()
+            .take_while(|(t, _, _)| *t <= self.time)
+            .count();
+        if self.num_deliveries != Some(num_deliveries) {
+            self.num_deliveries = Some(num_deliveries);
+            let mut batch = GeomBatch::new();
+            for (_, b, _) in &deliveries[0..num_deliveries] {
+                batch.push(
+                    app.session.colors.visited,
+                    app.map.get_b(*b).polygon.clone(),
+                );
+            }
+            self.draw_deliveries = ctx.upload(batch);
+        }
+        let label =
+            Text::from(format!("Frame {} / {}", self.frame + 1, self.num_frames)).into_widget(ctx);
+        self.panel.replace(ctx, "progress", label);
+
+        // The update draws once more with this state before saving the window
+        ctx.request_update(UpdateType::ScreenCaptureCurrent {
+            filename: format!("{}/{:04}.png", self.dir, self.frame),
+        });
+        // And keep going without waiting for input
+        ctx.request_update(UpdateType::Game);
+        self.frame += 1;
+
+        app.session.update_music(ctx);
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        g.redraw(&self.bldgs.draw_all);
+        self.bldgs.labels.draw(g);
+        g.redraw(&self.draw_deliveries);
+        self.replay.draw_sleigh(g, self.time);
+        // Keep the progress out of the frames
+        if !g.is_screencap() {
+            self.panel.draw(g);
+            app.session.music.draw(g);
+        }
+    }
+}
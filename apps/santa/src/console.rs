@@ -0,0 +1,119 @@
+use anyhow::Result;
+
+use map_model::BuildingID;
+use widgetry::{
+    DrawBaselayer, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel, State, Text,
+    TextBox, VerticalAlignment, Widget,
+};
+
+use crate::game::Game;
+use crate::{App, Transition};
+
+const HELP: &str =
+    "energy <amount or max>, teleport <building id>, complete-zone, set-speed <speed>";
+
+/// Cheats for playtesting levels and tuning, without recompiling
+pub enum Command {
+    /// Set blood sugar, or fill it up to the max
+    Energy(Option<usize>),
+    Teleport(BuildingID),
+    /// Deliver to every remaining house in the zone Santa is in, or the closest one
+    CompleteZone,
+    /// In km/h or mph, depending on the map's units
+    SetSpeed(f64),
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["energy", "max"] => Ok(Command::Energy(None)),
+            ["energy", amount] => Ok(Command::Energy(Some(amount.parse()?))),
+            ["teleport", id] => Ok(Command::Teleport(BuildingID(id.parse()?))),
+            ["complete-zone"] => Ok(Command::CompleteZone),
+            ["set-speed", speed] => {
+                let speed: f64 = speed.parse()?;
+                if speed <= 0.0 {
+                    bail!("The speed has to be positive");
+                }
+                Ok(Command::SetSpeed(speed))
+            }
+            _ => bail!("Unknown command. Try {}", HELP),
+        }
+    }
+}
+
+/// Pushed on top of the game in dev mode, by pressing backtick. Time stops while it's open.
+pub struct Console {
+    panel: Panel,
+}
+
+impl Console {
+    pub fn new_state(ctx: &mut EventCtx) -> Box<dyn State<App>> {
+        let panel = Panel::new_builder(Widget::col(vec![
+            Widget::row(vec![
+                Line("Console").small_heading().into_widget(ctx),
+                ctx.style().btn_close_widget(ctx),
+            ]),
+            TextBox::default_widget(ctx, "command", String::new()),
+            Text::from(Line(HELP).secondary())
+                .wrap_to_pct(ctx, 40)
+                .into_widget(ctx)
+                .named("result"),
+            ctx.style()
+                .btn_outline
+                .text("run")
+                .hotkey(Key::Enter)
+                .build_def(ctx),
+        ]))
+        .aligned(HorizontalAlignment::Center, VerticalAlignment::TopInset)
+        .build(ctx);
+        Box::new(Console { panel })
+    }
+}
+
+impl State<App> for Console {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        if let Outcome::Clicked(x) = self.panel.event(ctx) {
+            match x.as_ref() {
+                "close" => {
+                    return Transition::Pop;
+                }
+                "run" => match Command::parse(&self.panel.text_box("command")) {
+                    Ok(cmd) => {
+                        return Transition::Multi(vec![
+                            Transition::Pop,
+                            Transition::ModifyState(Box::new(move |state, ctx, app| {
+                                state
+                                    .downcast_mut::<Game>()
+                                    .unwrap()
+                                    .run_command(ctx, app, cmd);
+                            })),
+                        ]);
+                    }
+                    Err(err) => {
+                        let label = Text::from(
+                            Line(err.to_string()).fg(ctx.style().text_destructive_color),
+                        )
+                        .wrap_to_pct(ctx, 40)
+                        .into_widget(ctx);
+                        self.panel.replace(ctx, "result", label);
+                    }
+                },
+                _ => unreachable!(),
+            }
+        }
+
+        app.session.update_music(ctx);
+        Transition::Keep
+    }
+
+    fn draw_baselayer(&self) -> DrawBaselayer {
+        DrawBaselayer::PreviousState
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        self.panel.draw(g);
+        app.session.music.draw(g);
+    }
+}
@@ -1,23 +1,291 @@
-use geom::{Angle, Speed};
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use geom::{Angle, Duration, Pt2D, Speed};
+use map_model::RoadID;
 use widgetry::{EventCtx, Key};
 
+use crate::buildings::{Buildings, PresentType};
+use crate::windows::DeliveryWindows;
+use crate::App;
+
 // TODO The timestep accumulation seems fine. What's wrong? Clamping errors repeated?
 const HACK: f64 = 5.0;
 
+// How quickly the sleigh reaches the desired velocity, as a fraction per second. Lower means more
+// sluggish and drifty when turning.
+const ACCELERATION: f64 = 6.0;
+// How quickly the sleigh coasts to a stop after letting go
+const DECELERATION: f64 = 4.0;
+// Below this speed in m/s, just stop
+const STOPPED: f64 = 0.1;
+
+// Degrees per second. Tapping turns slowly for precision, holding ramps up to the max.
+const MIN_TURN_RATE: f64 = 90.0;
+const MAX_TURN_RATE: f64 = 270.0;
+const TURN_RAMP_UP: Duration = Duration::const_seconds(0.5);
+// Backing up is slower than going forwards
+const REVERSE_SPEED_MULTIPLIER: f64 = 0.4;
+// Each press of the one button turns this far
+const ONE_BUTTON_TURN_DEGREES: f64 = 45.0;
+// With the mouse, go full speed towards a cursor at least this far away in map-space meters, and
+// stop when it's closer than the dead zone
+const MOUSE_FULL_SPEED_DISTANCE: f64 = 30.0;
+const MOUSE_DEAD_ZONE: f64 = 2.0;
+
+/// How the player steers
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum ControlScheme {
+    /// Move in the direction pressed
+    #[default]
+    Instant,
+    /// Turn left and right, go forwards and backwards
+    Rotate,
+    /// Always moving forwards, and one key turns to the next of 8 directions
+    OneButton,
+    /// Fly towards the mouse cursor, faster the further away it is
+    Mouse,
+}
+
+impl ControlScheme {
+    pub fn parse(x: &str) -> Result<ControlScheme> {
+        match x {
+            "instant" => Ok(ControlScheme::Instant),
+            "rotate" => Ok(ControlScheme::Rotate),
+            "onebutton" => Ok(ControlScheme::OneButton),
+            "mouse" => Ok(ControlScheme::Mouse),
+            _ => bail!(
+                "Invalid --controls={}. Choices: instant, rotate, onebutton, mouse",
+                x
+            ),
+        }
+    }
+}
+
+/// What a controller can see of the run each frame. People playing go by the screen instead, but
+/// a bot decides where to go from this.
+pub struct SleighState<'a> {
+    pub pos: Pt2D,
+    /// The road the sleigh is on, or every road touching its intersection
+    pub roads: Vec<RoadID>,
+    pub bldgs: &'a Buildings,
+    /// How many of each present are left
+    pub presents: &'a BTreeMap<PresentType, usize>,
+    pub energy: usize,
+    /// At or below this much blood sugar, it's time to find a store
+    pub low_energy_warning: usize,
+    /// Only on levels where some houses take deliveries part of the time
+    pub windows: Option<&'a DeliveryWindows>,
+    /// How much of the time limit has passed, from 0 to 1
+    pub pct_elapsed: f64,
+}
+
+/// Turns player input into movement each frame. Usually that's the keyboard, gamepad, or touch,
+/// but a bot can drive instead.
+pub trait Controller {
+    /// How far to move this frame, if at all. The speed already includes any multipliers, like
+    /// boosting. `analog` is the direction and strength (0 to 1) of a gamepad stick or touch
+    /// joystick, if one is being used.
+    fn displacement(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &App,
+        sleigh: &SleighState,
+        analog: Option<(Angle, f64)>,
+        speed: Speed,
+    ) -> Option<(f64, f64)>;
+
+    /// The direction to face when not snapped to a road
+    fn facing(&self) -> Angle;
+
+    /// If the controller has a heading independent of the direction of movement, it should be
+    /// drawn.
+    fn heading_indicator(&self) -> Option<Angle> {
+        None
+    }
+
+    /// Nobody's at the keys to switch presents or drop them, so a bot has the present it wants
+    /// ready, and houses get theirs automatically.
+    fn is_bot(&self) -> bool {
+        false
+    }
+
+    /// Which present to get ready, if the controller cares
+    fn choose_present(&self) -> Option<PresentType> {
+        None
+    }
+}
+
+pub fn new_controller(scheme: ControlScheme, keys: KeyBindings) -> Box<dyn Controller> {
+    match scheme {
+        ControlScheme::Instant => Box::new(InstantController::new(keys)),
+        ControlScheme::Rotate => Box::new(RotateController::new(keys)),
+        ControlScheme::OneButton => Box::new(OneButtonController::new(keys)),
+        ControlScheme::Mouse => Box::new(MouseController::new(keys)),
+    }
+}
+
+/// Which keys trigger each action. Any of the keys listed for an action work.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+// Actions added later fall back to their default keys
+#[serde(default)]
+pub struct KeyBindings {
+    pub up: Vec<Key>,
+    pub down: Vec<Key>,
+    pub left: Vec<Key>,
+    pub right: Vec<Key>,
+    pub boost: Vec<Key>,
+    pub pause: Vec<Key>,
+    pub switch_present: Vec<Key>,
+    pub restart: Vec<Key>,
+    pub go_home: Vec<Key>,
+    pub snack: Vec<Key>,
+    pub pick_nearby: Vec<Key>,
+    pub drop_present: Vec<Key>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        KeyBindings {
+            up: vec![Key::UpArrow, Key::W],
+            down: vec![Key::DownArrow, Key::S],
+            left: vec![Key::LeftArrow, Key::A],
+            right: vec![Key::RightArrow, Key::D],
+            boost: vec![Key::Space],
+            pause: vec![Key::Escape],
+            switch_present: vec![Key::Tab],
+            restart: vec![Key::R],
+            go_home: vec![Key::T],
+            snack: vec![Key::E],
+            pick_nearby: vec![Key::Q],
+            drop_present: vec![Key::F],
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Every remappable action
+    pub const ACTIONS: [&'static str; 12] = [
+        "up",
+        "down",
+        "left",
+        "right",
+        "boost",
+        "pause",
+        "switch present",
+        "restart",
+        "go home",
+        "snack",
+        "pick nearby",
+        "drop present",
+    ];
+
+    /// For two players sharing one keyboard. The first moves with WASD and the second with the
+    /// arrow keys; everything else stays the same.
+    pub fn split_keyboard(&self) -> (KeyBindings, KeyBindings) {
+        let mut first = self.clone();
+        first.up = vec![Key::W];
+        first.down = vec![Key::S];
+        first.left = vec![Key::A];
+        first.right = vec![Key::D];
+
+        let mut second = self.clone();
+        second.up = vec![Key::UpArrow];
+        second.down = vec![Key::DownArrow];
+        second.left = vec![Key::LeftArrow];
+        second.right = vec![Key::RightArrow];
+
+        (first, second)
+    }
+
+    pub fn get(&self, action: &str) -> &Vec<Key> {
+        match action {
+            "up" => &self.up,
+            "down" => &self.down,
+            "left" => &self.left,
+            "right" => &self.right,
+            "boost" => &self.boost,
+            "pause" => &self.pause,
+            "switch present" => &self.switch_present,
+            "restart" => &self.restart,
+            "go home" => &self.go_home,
+            "snack" => &self.snack,
+            "pick nearby" => &self.pick_nearby,
+            "drop present" => &self.drop_present,
+            _ => panic!("Unknown action {}", action),
+        }
+    }
+
+    pub fn set(&mut self, action: &str, key: Key) {
+        let keys = match action {
+            "up" => &mut self.up,
+            "down" => &mut self.down,
+            "left" => &mut self.left,
+            "right" => &mut self.right,
+            "boost" => &mut self.boost,
+            "pause" => &mut self.pause,
+            "switch present" => &mut self.switch_present,
+            "restart" => &mut self.restart,
+            "go home" => &mut self.go_home,
+            "snack" => &mut self.snack,
+            "pick nearby" => &mut self.pick_nearby,
+            "drop present" => &mut self.drop_present,
+            _ => panic!("Unknown action {}", action),
+        };
+        *keys = vec![key];
+    }
+
+    /// The action this key is bound to, if any
+    pub fn action_using(&self, key: Key) -> Option<&'static str> {
+        KeyBindings::ACTIONS
+            .into_iter()
+            .find(|action| self.get(action).contains(&key))
+    }
+
+    pub fn describe(&self, action: &str) -> String {
+        self.get(action)
+            .iter()
+            .map(|key| key.describe())
+            .collect::<Vec<_>>()
+            .join(" or ")
+    }
+
+    pub fn is_down(&self, ctx: &EventCtx, action: &str) -> bool {
+        self.get(action).iter().any(|key| ctx.is_key_down(*key))
+    }
+
+    pub fn pressed(&self, ctx: &mut EventCtx, action: &str) -> bool {
+        self.get(action).iter().any(|key| ctx.input.pressed(*key))
+    }
+}
+
 pub struct InstantController {
     /// Which of the 8 directions are we facing, based on the last set of keys pressed down?
     pub facing: Angle,
+    keys: KeyBindings,
+    /// In (hacked) meters per second. The sleigh has some weight, so this lags behind the input.
+    velocity: (f64, f64),
 }
 
 impl InstantController {
-    pub fn new() -> InstantController {
+    pub fn new(keys: KeyBindings) -> InstantController {
         InstantController {
             facing: Angle::ZERO,
+            keys,
+            velocity: (0.0, 0.0),
         }
     }
 
-    pub fn displacement(&mut self, ctx: &mut EventCtx, speed: Speed) -> Option<(f64, f64)> {
-        let dt = ctx.input.nonblocking_is_update_event()?;
+    /// Ease towards moving in a direction, with a strength from 0 to 1, or coast to a stop when
+    /// there's no input
+    pub fn steer(
+        &mut self,
+        dt: Duration,
+        input: Option<(Angle, f64)>,
+        speed: Speed,
+    ) -> Option<(f64, f64)> {
         // Work around a few bugs here.
         //
         // 1) The Santa sprites are all facing 180 degrees, not 0, so invert X.
@@ -25,26 +293,248 @@ impl InstantController {
         //
         // It's confusing, but self.facing winds up working for rotating the sprite, and the output
         // displacement works.
-        self.facing = angle_from_arrow_keys(ctx)?.opposite();
-        let magnitude = (dt * HACK * speed).inner_meters();
-        let (sin, cos) = self.facing.normalized_radians().sin_cos();
-        Some((-magnitude * cos, -magnitude * sin))
+        let (target, rate) = if let Some((angle, pct)) = input {
+            self.facing = angle.opposite();
+            let magnitude = pct * HACK * speed.inner_meters_per_second();
+            let (sin, cos) = self.facing.normalized_radians().sin_cos();
+            ((-magnitude * cos, -magnitude * sin), ACCELERATION)
+        } else {
+            ((0.0, 0.0), DECELERATION)
+        };
+
+        // Ease towards the target velocity. Changing direction drifts a bit.
+        let blend = (rate * dt.inner_seconds()).min(1.0);
+        self.velocity.0 += blend * (target.0 - self.velocity.0);
+        self.velocity.1 += blend * (target.1 - self.velocity.1);
+        if input.is_none() && self.velocity.0.hypot(self.velocity.1) < STOPPED {
+            self.velocity = (0.0, 0.0);
+            return None;
+        }
+
+        Some((
+            self.velocity.0 * dt.inner_seconds(),
+            self.velocity.1 * dt.inner_seconds(),
+        ))
+    }
+}
+
+impl Controller for InstantController {
+    fn displacement(
+        &mut self,
+        ctx: &mut EventCtx,
+        _: &App,
+        _: &SleighState,
+        analog: Option<(Angle, f64)>,
+        speed: Speed,
+    ) -> Option<(f64, f64)> {
+        let dt = ctx.input.nonblocking_is_update_event()?;
+        let input = if let Some(angle) = angle_from_arrow_keys(ctx, &self.keys) {
+            Some((angle, 1.0))
+        } else {
+            analog
+        };
+        self.steer(dt, input, speed)
+    }
+
+    fn facing(&self) -> Angle {
+        self.facing
+    }
+}
+
+pub struct RotateController {
+    /// The direction of travel. The sprite faces the opposite way.
+    heading: Angle,
+    keys: KeyBindings,
+    /// How long the player has been turning in the same direction
+    turning_for: Duration,
+}
+
+impl RotateController {
+    pub fn new(keys: KeyBindings) -> RotateController {
+        RotateController {
+            heading: Angle::ZERO,
+            keys,
+            turning_for: Duration::ZERO,
+        }
+    }
+}
+
+impl Controller for RotateController {
+    fn displacement(
+        &mut self,
+        ctx: &mut EventCtx,
+        _: &App,
+        _: &SleighState,
+        analog: Option<(Angle, f64)>,
+        speed: Speed,
+    ) -> Option<(f64, f64)> {
+        let dt = ctx.input.nonblocking_is_update_event()?;
+
+        // Both range from -1 to 1
+        let mut turn = 0.0;
+        let mut throttle = 0.0;
+        if self.keys.is_down(ctx, "left") {
+            turn -= 1.0;
+        }
+        if self.keys.is_down(ctx, "right") {
+            turn += 1.0;
+        }
+        if self.keys.is_down(ctx, "up") {
+            throttle += 1.0;
+        }
+        if self.keys.is_down(ctx, "down") {
+            throttle -= 1.0;
+        }
+        if turn == 0.0 && throttle == 0.0 {
+            if let Some((angle, pct)) = analog {
+                // Stick left and right turns, up and down is the throttle. Negative y is up.
+                let (sin, cos) = angle.normalized_radians().sin_cos();
+                turn = pct * cos;
+                throttle = -pct * sin;
+            }
+        }
+
+        if turn == 0.0 {
+            self.turning_for = Duration::ZERO;
+        } else {
+            self.turning_for += dt;
+            let ramp = (self.turning_for.inner_seconds() / TURN_RAMP_UP.inner_seconds()).min(1.0);
+            let rate = MIN_TURN_RATE + ramp * (MAX_TURN_RATE - MIN_TURN_RATE);
+            self.heading = self.heading.rotate_degs(turn * rate * dt.inner_seconds());
+        }
+
+        if throttle == 0.0 {
+            // Still report no movement, so the sprite rotates in place
+            return if turn == 0.0 { None } else { Some((0.0, 0.0)) };
+        }
+        if throttle < 0.0 {
+            throttle *= REVERSE_SPEED_MULTIPLIER;
+        }
+        let magnitude = throttle * HACK * speed.inner_meters_per_second() * dt.inner_seconds();
+        let (sin, cos) = self.heading.normalized_radians().sin_cos();
+        Some((magnitude * cos, magnitude * sin))
+    }
+
+    fn facing(&self) -> Angle {
+        self.heading.opposite()
+    }
+
+    fn heading_indicator(&self) -> Option<Angle> {
+        Some(self.heading)
+    }
+}
+
+/// For anyone who can only press one key. The sleigh never stops, and the key for turning right
+/// steps clockwise through the 8 directions.
+pub struct OneButtonController {
+    /// The direction of travel. The sprite faces the opposite way.
+    heading: Angle,
+    keys: KeyBindings,
+    /// Holding the key down only turns once
+    was_down: bool,
+}
+
+impl OneButtonController {
+    pub fn new(keys: KeyBindings) -> OneButtonController {
+        OneButtonController {
+            heading: Angle::ZERO,
+            keys,
+            was_down: false,
+        }
+    }
+}
+
+impl Controller for OneButtonController {
+    fn displacement(
+        &mut self,
+        ctx: &mut EventCtx,
+        _: &App,
+        _: &SleighState,
+        _: Option<(Angle, f64)>,
+        speed: Speed,
+    ) -> Option<(f64, f64)> {
+        let dt = ctx.input.nonblocking_is_update_event()?;
+
+        let down = self.keys.is_down(ctx, "right");
+        if down && !self.was_down {
+            self.heading = self.heading.rotate_degs(ONE_BUTTON_TURN_DEGREES);
+        }
+        self.was_down = down;
+
+        let magnitude = HACK * speed.inner_meters_per_second() * dt.inner_seconds();
+        let (sin, cos) = self.heading.normalized_radians().sin_cos();
+        Some((magnitude * cos, magnitude * sin))
+    }
+
+    fn facing(&self) -> Angle {
+        self.heading.opposite()
+    }
+
+    fn heading_indicator(&self) -> Option<Angle> {
+        Some(self.heading)
+    }
+}
+
+/// Steers towards the cursor, for trackpads and anyone who'd rather not use the keyboard. The
+/// keys and gamepad still work, and take over while they're used. Over a panel, the sleigh coasts
+/// to a stop.
+pub struct MouseController {
+    steering: InstantController,
+}
+
+impl MouseController {
+    pub fn new(keys: KeyBindings) -> MouseController {
+        MouseController {
+            steering: InstantController::new(keys),
+        }
+    }
+}
+
+impl Controller for MouseController {
+    fn displacement(
+        &mut self,
+        ctx: &mut EventCtx,
+        _: &App,
+        sleigh: &SleighState,
+        analog: Option<(Angle, f64)>,
+        speed: Speed,
+    ) -> Option<(f64, f64)> {
+        let dt = ctx.input.nonblocking_is_update_event()?;
+        let input = angle_from_arrow_keys(ctx, &self.steering.keys)
+            .map(|angle| (angle, 1.0))
+            .or(analog)
+            .or_else(|| {
+                let cursor = ctx.canvas.get_cursor_in_map_space()?;
+                let dist = sleigh.pos.dist_to(cursor).inner_meters();
+                if dist < MOUSE_DEAD_ZONE {
+                    return None;
+                }
+                Some((
+                    sleigh.pos.angle_to(cursor),
+                    (dist / MOUSE_FULL_SPEED_DISTANCE).min(1.0),
+                ))
+            });
+        self.steering.steer(dt, input, speed)
+    }
+
+    fn facing(&self) -> Angle {
+        self.steering.facing
     }
 }
 
-pub fn angle_from_arrow_keys(ctx: &EventCtx) -> Option<Angle> {
+pub fn angle_from_arrow_keys(ctx: &EventCtx, keys: &KeyBindings) -> Option<Angle> {
     let mut x: f64 = 0.0;
     let mut y: f64 = 0.0;
-    if ctx.is_key_down(Key::LeftArrow) || ctx.is_key_down(Key::A) {
+    if keys.is_down(ctx, "left") {
         x -= 1.0;
     }
-    if ctx.is_key_down(Key::RightArrow) || ctx.is_key_down(Key::D) {
+    if keys.is_down(ctx, "right") {
         x += 1.0;
     }
-    if ctx.is_key_down(Key::UpArrow) || ctx.is_key_down(Key::W) {
+    if keys.is_down(ctx, "up") {
         y -= 1.0;
     }
-    if ctx.is_key_down(Key::DownArrow) || ctx.is_key_down(Key::S) {
+    if keys.is_down(ctx, "down") {
         y += 1.0;
     }
 
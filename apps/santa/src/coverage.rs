@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use geom::{Circle, Distance, Duration, Polygon, Pt2D};
+use map_gui::tools::{FileSaver, FileSaverContents};
+use map_model::connectivity::{self, Spot};
+use map_model::{BuildingID, BuildingType, DirectedRoadID, Direction, PathConstraints};
+use widgetry::tools::{ColorLegend, PopupMsg};
+use widgetry::{
+    Color, Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel,
+    State, Text, UpdateType, VerticalAlignment, Widget,
+};
+
+use crate::buildings::{BldgState, Buildings};
+use crate::levels::Level;
+use crate::{App, Transition};
+
+// The isochrone rings, as a fraction of the level's time limit
+const RINGS: [f64; 3] = [0.25, 0.5, 1.0];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Status {
+    Delivered,
+    // Could've been reached in time, but wasn't
+    Missed,
+    // Too far away to ride to from the start in the whole time limit
+    Unreachable,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        match self {
+            Status::Delivered => "delivered",
+            Status::Missed => "missed",
+            Status::Unreachable => "unreachable",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Status::Delivered => Color::hex("#2E8B3A"),
+            Status::Missed => Color::hex("#F0A830"),
+            Status::Unreachable => Color::hex("#B03A2E"),
+        }
+    }
+}
+
+/// After a run, which homes got presents, which were missed, and which were just too far from the
+/// start for this vehicle. Meant for looking at how well a neighborhood's streets connect homes,
+/// not just for the score.
+#[derive(Clone)]
+pub struct Coverage {
+    level: String,
+    start: Pt2D,
+    time_limit: Duration,
+    // Riding time from the start, for every building reachable within the time limit
+    costs: HashMap<BuildingID, Duration>,
+    houses: Vec<(BuildingID, Status)>,
+}
+
+impl Coverage {
+    pub fn new(app: &App, level: &Level, bldgs: &Buildings) -> Coverage {
+        let start = app.map.localise_lon_lat_to_map(level.start);
+        let mut starts = Vec::new();
+        if let Some(i) = app.map.find_i_by_pt2d(start) {
+            // The sleigh can ride either way down a road
+            for road in &app.map.get_i(i).roads {
+                for dir in [Direction::Fwd, Direction::Back] {
+                    starts.push(Spot::DirectedRoad(DirectedRoadID { road: *road, dir }));
+                }
+            }
+        }
+        let costs = if starts.is_empty() {
+            HashMap::new()
+        } else {
+            connectivity::all_vehicle_costs_from(
+                &app.map,
+                starts,
+                level.time_limit,
+                PathConstraints::Bike,
+            )
+        };
+
+        let mut houses = Vec::new();
+        for (b, state) in &bldgs.buildings {
+            let status = match state {
+                BldgState::Done => Status::Delivered,
+                BldgState::Undelivered(..) if costs.contains_key(b) => Status::Missed,
+                BldgState::Undelivered(..) => Status::Unreachable,
+                _ => continue,
+            };
+            houses.push((*b, status));
+        }
+        houses.sort_by_key(|(b, _)| *b);
+
+        Coverage {
+            level: level.title.clone(),
+            start,
+            time_limit: level.time_limit,
+            costs,
+            houses,
+        }
+    }
+
+    fn count(&self, status: Status) -> usize {
+        self.houses.iter().filter(|(_, s)| *s == status).count()
+    }
+
+    // The outline around every building reachable within each ring's time. None for rings too
+    // small to outline.
+    fn rings(&self, app: &App) -> Vec<(Duration, Option<Polygon>)> {
+        RINGS
+            .iter()
+            .map(|pct| {
+                let limit = self.time_limit * *pct;
+                let mut polygons: Vec<Polygon> = self
+                    .costs
+                    .iter()
+                    .filter(|(_, cost)| **cost <= limit)
+                    .map(|(b, _)| app.map.get_b(*b).polygon.clone())
+                    .collect();
+                polygons.push(Circle::new(self.start, Distance::meters(5.0)).to_polygon());
+                (limit, Polygon::convex_hull(polygons).ok())
+            })
+            .collect()
+    }
+
+    /// Every home with how it went, the isochrone rings, and the start, in map coordinates
+    pub fn to_geojson(&self, app: &App) -> Result<String> {
+        let mut pairs = Vec::new();
+
+        for (b, status) in &self.houses {
+            let bldg = app.map.get_b(*b);
+            let mut props = serde_json::Map::new();
+            props.insert("type".to_string(), "home".into());
+            props.insert("building".to_string(), b.0.into());
+            props.insert("status".to_string(), status.name().into());
+            if let BuildingType::Residential {
+                num_housing_units, ..
+            } = bldg.bldg_type
+            {
+                props.insert("housing_units".to_string(), num_housing_units.into());
+            }
+            if let Some(cost) = self.costs.get(b) {
+                props.insert("travel_seconds".to_string(), cost.inner_seconds().into());
+            }
+            pairs.push((bldg.polygon.to_geojson(None), props));
+        }
+
+        for (limit, ring) in self.rings(app) {
+            if let Some(ring) = ring {
+                let mut props = serde_json::Map::new();
+                props.insert("type".to_string(), "isochrone".into());
+                props.insert("within_seconds".to_string(), limit.inner_seconds().into());
+                pairs.push((ring.to_geojson(None), props));
+            }
+        }
+
+        let mut props = serde_json::Map::new();
+        props.insert("type".to_string(), "start".into());
+        props.insert("level".to_string(), self.level.clone().into());
+        pairs.push((self.start.to_geojson(None), props));
+
+        Ok(geom::geometries_with_properties_to_geojson(pairs).to_string())
+    }
+}
+
+/// Shows a coverage map over the whole level, exporting it as GeoJSON or a picture
+pub struct CoverageMap {
+    panel: Panel,
+    coverage: Coverage,
+    draw: Drawable,
+
+    // To put the camera back afterwards
+    orig_center: Pt2D,
+    orig_zoom: f64,
+}
+
+impl CoverageMap {
+    pub fn new_state(ctx: &mut EventCtx, app: &App, coverage: Coverage) -> Box<dyn State<App>> {
+        let mut batch = GeomBatch::new();
+        for (b, status) in &coverage.houses {
+            batch.push(status.color(), app.map.get_b(*b).polygon.clone());
+        }
+        let ring_color = Color::CYAN;
+        for (_, ring) in coverage.rings(app) {
+            if let Some(ring) = ring {
+                batch.push(ring_color, ring.to_outline(Distance::meters(3.0)));
+            }
+        }
+        batch.push(
+            Color::WHITE,
+            Circle::new(coverage.start, Distance::meters(10.0)).to_polygon(),
+        );
+
+        let mut txt = Text::from(Line("Coverage map").small_heading());
+        txt.add_line(format!(
+            "{} homes delivered, {} missed, {} too far to reach",
+            coverage.count(Status::Delivered),
+            coverage.count(Status::Missed),
+            coverage.count(Status::Unreachable)
+        ));
+        txt.add_line(format!(
+            "Rings show how far you can ride from the start in {}",
+            RINGS
+                .iter()
+                .map(|pct| (coverage.time_limit * *pct).to_string(&app.opts.units))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+
+        let panel = Panel::new_builder(Widget::col(vec![
+            txt.into_widget(ctx),
+            Widget::row(vec![
+                ColorLegend::row(ctx, Status::Delivered.color(), "delivered"),
+                ColorLegend::row(ctx, Status::Missed.color(), "missed"),
+                ColorLegend::row(ctx, Status::Unreachable.color(), "unreachable"),
+            ]),
+            ColorLegend::row(ctx, ring_color, "isochrone rings"),
+            Text::new().into_widget(ctx).named("saved"),
+            // Nothing gets saved in kiosk mode
+            if app.session.kiosk {
+                Widget::nothing()
+            } else {
+                Widget::row(vec![
+                    ctx.style()
+                        .btn_outline
+                        .text("Export coverage (GeoJSON)")
+                        .build_def(ctx),
+                    // The web version can't save screenshots
+                    if cfg!(target_arch = "wasm32") {
+                        Widget::nothing()
+                    } else {
+                        ctx.style()
+                            .btn_outline
+                            .text("Save a picture")
+                            .build_def(ctx)
+                    },
+                ])
+            },
+            ctx.style()
+                .btn_outline
+                .text("Back")
+                .hotkey(Key::Escape)
+                .build_def(ctx),
+        ]))
+        .aligned(HorizontalAlignment::Right, VerticalAlignment::Top)
+        .build(ctx);
+
+        let orig_center = ctx.canvas.center_to_map_pt();
+        let orig_zoom = ctx.canvas.cam_zoom;
+        // Fit the whole map on screen
+        let bounds = app.map.get_bounds();
+        ctx.canvas.cam_zoom = (ctx.canvas.window_width / bounds.width())
+            .min(ctx.canvas.window_height / bounds.height());
+        ctx.canvas.center_on_map_pt(bounds.center());
+
+        Box::new(CoverageMap {
+            panel,
+            coverage,
+            draw: ctx.upload(batch),
+            orig_center,
+            orig_zoom,
+        })
+    }
+}
+
+impl State<App> for CoverageMap {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        ctx.canvas_movement();
+
+        if let Outcome::Clicked(x) = self.panel.event(ctx) {
+            match x.as_ref() {
+                "Export coverage (GeoJSON)" => {
+                    return Transition::Push(match self.coverage.to_geojson(app) {
+                        Ok(contents) => FileSaver::with_default_messages(
+                            ctx,
+                            format!(
+                                "santa_{}_coverage.geojson",
+                                app.map.get_name().as_filename()
+                            ),
+                            None,
+                            FileSaverContents::String(contents),
+                        ),
+                        Err(err) => {
+                            PopupMsg::new_state(ctx, "Export failed", vec![err.to_string()])
+                        }
+                    });
+                }
+                "Save a picture" => {
+                    let filename = format!(
+                        "screenshots/santa/{}_coverage_{}.png",
+                        app.map.get_name().as_filename(),
+                        chrono::Local::now().format("%Y%m%d_%H%M%S")
+                    );
+                    // A popup would end up in the picture, so just mention it here. The panel
+                    // isn't drawn into the picture anyway.
+                    let label = Text::from(format!("Saved to {}", filename)).into_widget(ctx);
+                    self.panel.replace(ctx, "saved", label);
+                    ctx.request_update(UpdateType::ScreenCaptureCurrent { filename });
+                }
+                "Back" => {
+                    ctx.canvas.cam_zoom = self.orig_zoom;
+                    ctx.canvas.center_on_map_pt(self.orig_center);
+                    return Transition::Pop;
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        app.session.update_music(ctx);
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        g.redraw(&self.draw);
+        // Keep the panel out of the picture
+        if !g.is_screencap() {
+            self.panel.draw(g);
+            app.session.music.draw(g);
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+
+use map_gui::tools::{find_exe, FilePicker, RunCommand};
+use map_gui::AppLike;
+use map_model::{BuildingID, Map};
+use widgetry::tools::PopupMsg;
+use widgetry::{EventCtx, GfxCtx, Key, Line, Panel, SimpleState, State, Text, Widget};
+
+use crate::before_level::Picker;
+use crate::buildings::{closest_intersection, BldgState, Buildings};
+use crate::levels::Level;
+use crate::load_map::load_map;
+use crate::mode::Mode;
+use crate::{App, Transition};
+
+/// Play anywhere, using a map that didn't come with the game. Either point at a map file that
+/// was already imported, or import an OpenStreetMap extract right here. This needs the importer
+/// from the command line tools installed next to the game.
+pub struct CustomMap;
+
+impl CustomMap {
+    pub fn new_state(ctx: &mut EventCtx, app: &App) -> Box<dyn State<App>> {
+        let locale = app.session.locale;
+        <dyn SimpleState<_>>::new_state(
+            Panel::new_builder(Widget::col(vec![
+                Widget::row(vec![
+                    Line(locale.tr("Your own map"))
+                        .small_heading()
+                        .into_widget(ctx),
+                    ctx.style().btn_close_widget(ctx),
+                ]),
+                Text::from(Line(
+                    "Deliver presents around your hometown. The goal and time limit get worked \
+                     out from the size of the map.",
+                ))
+                .wrap_to_pct(ctx, 40)
+                .into_widget(ctx),
+                ctx.style()
+                    .btn_solid_primary
+                    .text("Load a map file")
+                    .hotkey(Key::L)
+                    .build_def(ctx),
+                ctx.style()
+                    .btn_outline
+                    .text("Import an OpenStreetMap extract")
+                    .hotkey(Key::I)
+                    .build_def(ctx),
+                Line("Extracts have to be .osm XML files")
+                    .secondary()
+                    .into_widget(ctx),
+            ]))
+            .build(ctx),
+            Box::new(CustomMap),
+        )
+    }
+}
+
+impl SimpleState<App> for CustomMap {
+    fn on_click(&mut self, ctx: &mut EventCtx, _: &mut App, x: &str, _: &mut Panel) -> Transition {
+        match x {
+            "close" => Transition::Pop,
+            "Load a map file" => Transition::Push(FilePicker::new_state(
+                ctx,
+                Some(abstio::path("system")),
+                Box::new(|ctx, app, maybe_file| {
+                    if let Ok(Some((_, bytes))) = maybe_file {
+                        let result: Result<()> =
+                            ctx.loading_screen("load your map", |ctx, timer| {
+                                let mut map: Map = abstutil::from_binary(&bytes)?;
+                                map.map_loaded_directly(timer);
+                                app.map_switched(ctx, map, timer);
+                                Ok(())
+                            });
+                        match result {
+                            Ok(()) => start_level(ctx, app),
+                            Err(err) => Transition::Replace(PopupMsg::new_state(
+                                ctx,
+                                "Error",
+                                vec![
+                                    "That doesn't look like a map file".to_string(),
+                                    err.to_string(),
+                                ],
+                            )),
+                        }
+                    } else {
+                        // Nothing picked, so go back to the choices
+                        Transition::Pop
+                    }
+                }),
+            )),
+            "Import an OpenStreetMap extract" => Transition::Push(FilePicker::new_state(
+                ctx,
+                None,
+                Box::new(|ctx, _, maybe_file| {
+                    if let Ok(Some((filename, bytes))) = maybe_file {
+                        // The picker only hands over the contents, but the importer wants a path
+                        let path = abstio::path_player(format!("osm/{}", filename));
+                        if let Err(err) = abstio::write_raw(path.clone(), &bytes) {
+                            return Transition::Replace(PopupMsg::new_state(
+                                ctx,
+                                "Error",
+                                vec![format!("Couldn't copy {}: {}", filename, err)],
+                            ));
+                        }
+                        let name = abstutil::basename(&filename);
+                        // The command pops itself when it's done
+                        Transition::Replace(RunCommand::new_state(
+                            ctx,
+                            false,
+                            vec![find_exe("cli"), "oneshot-import".to_string(), path],
+                            Box::new(move |ctx, app, success, _| {
+                                if !success {
+                                    // The popup already explained the failure
+                                    return Transition::Keep;
+                                }
+                                Transition::Push(load_map(
+                                    ctx,
+                                    app,
+                                    abstio::MapName::new("zz", "oneshot", &name),
+                                    Box::new(start_level),
+                                ))
+                            }),
+                        ))
+                    } else {
+                        Transition::Pop
+                    }
+                }),
+            )),
+            _ => unreachable!(),
+        }
+    }
+
+    fn other_event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        app.session.update_music(ctx);
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        app.session.music.draw(g);
+    }
+}
+
+/// Once the map is loaded, makes sure there's something to do on it before setting up the level.
+/// Replaces the loading state.
+fn start_level(ctx: &mut EventCtx, app: &mut App) -> Transition {
+    let mut num_houses = 0;
+    let mut stores = Vec::new();
+    for (b, state) in Buildings::initial_states(&app.map, &HashSet::new(), Mode::Normal.seed()) {
+        match state {
+            BldgState::Undelivered(_, _) => {
+                num_houses += 1;
+            }
+            BldgState::Store(_) | BldgState::Snacked(_) => {
+                stores.push(b);
+            }
+            BldgState::Done | BldgState::Ignore => {}
+        }
+    }
+    if num_houses == 0 {
+        return Transition::Replace(PopupMsg::new_state(
+            ctx,
+            "Nobody lives here",
+            vec!["This map doesn't have any houses or apartments to deliver to."],
+        ));
+    }
+
+    // Start from the depot closest to the middle of the map, so there's food on hand right away
+    let mut level = Level::for_map(&app.map);
+    let center = app.map.get_boundary_polygon().center();
+    let depot = stores
+        .into_iter()
+        .filter(|b| is_eligible_depot(&app.map, *b))
+        .min_by_key(|b| app.map.get_b(*b).label_center.dist_to(center));
+    if let Some(b) = depot {
+        let i = closest_intersection(&app.map, b);
+        level.start = app
+            .map
+            .get_i(i)
+            .polygon
+            .center()
+            .to_gps(app.map.get_gps_bounds());
+    }
+
+    let picker = Transition::Replace(Picker::new_state(ctx, app, level, app.session.last_mode));
+    if depot.is_some() {
+        return picker;
+    }
+    Transition::Multi(vec![
+        picker,
+        Transition::Push(PopupMsg::new_state(
+            ctx,
+            "Warning",
+            vec![
+                "This map doesn't have any stores or restaurants.",
+                "There's nowhere to refill blood sugar, so you'll only get as far as your first \
+                 supply takes you.",
+            ],
+        )),
+    ])
+}
+
+/// Santa has to be able to ride away from the store
+fn is_eligible_depot(map: &Map, b: BuildingID) -> bool {
+    map.get_i(closest_intersection(map, b))
+        .roads
+        .iter()
+        .any(|r| !map.get_r(*r).is_light_rail())
+}
@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+
+use geom::Duration;
+use map_model::{BuildingType, Map};
+
+use crate::levels::Level;
+use crate::tuning::Tuning;
+
+// The share of all housing units the player needs to deliver to
+const GOAL_FRACTION: f64 = 0.2;
+const MIN_GOAL: usize = 50;
+const BASE_TIME_LIMIT: Duration = Duration::const_seconds(60.0);
+const TIME_PER_KM2: Duration = Duration::const_seconds(30.0);
+const MAX_TIME_LIMIT: Duration = Duration::const_seconds(300.0);
+// Maps about this big get the vehicle's normal energy. Stores are further apart on bigger maps.
+const REFERENCE_AREA_KM2: f64 = 2.0;
+
+/// Targets for a level computed from the size and density of its map, so the same rules work
+/// from a tiny test map to a whole city. The built-in levels are tuned by hand instead.
+pub struct Difficulty {
+    pub goal: usize,
+    pub time_limit: Duration,
+    /// Scales the vehicle's max energy
+    pub energy_multiplier: f64,
+
+    num_homes: usize,
+    area_km2: f64,
+}
+
+impl Difficulty {
+    /// Only for levels that ask to be scaled
+    pub fn for_level(level: &Level, map: &Map) -> Option<Difficulty> {
+        if level.scale_to_map {
+            Some(Difficulty::new(map))
+        } else {
+            None
+        }
+    }
+
+    pub fn new(map: &Map) -> Difficulty {
+        let mut num_homes = 0;
+        let mut num_housing_units = 0;
+        for b in map.all_buildings() {
+            if let BuildingType::Residential {
+                num_housing_units: units,
+                ..
+            } = b.bldg_type
+            {
+                if units > 0 {
+                    num_homes += 1;
+                    num_housing_units += units;
+                }
+            }
+        }
+        let area_km2 = map.get_boundary_polygon().area() / 1_000_000.0;
+
+        // Round to something that reads nicely
+        let goal = (((num_housing_units as f64) * GOAL_FRACTION / 10.0).round() as usize * 10)
+            .max(MIN_GOAL);
+        let time_limit = (BASE_TIME_LIMIT + TIME_PER_KM2 * area_km2).min(MAX_TIME_LIMIT);
+        let energy_multiplier = (area_km2 / REFERENCE_AREA_KM2).sqrt().clamp(0.75, 2.0);
+
+        Difficulty {
+            goal,
+            time_limit,
+            energy_multiplier,
+            num_homes,
+            area_km2,
+        }
+    }
+
+    pub fn apply(&self, level: &mut Level) {
+        level.goal = self.goal;
+        level.time_limit = self.time_limit;
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "Scaled to this map: {} homes over {:.1} km², with {:.0}% of the usual blood sugar",
+            abstutil::prettyprint_usize(self.num_homes),
+            self.area_km2,
+            100.0 * self.energy_multiplier
+        )
+    }
+}
+
+/// Picked before starting a level, to make every run more or less forgiving. High scores from
+/// each are kept apart.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize, Default)]
+pub enum DifficultyPreset {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl DifficultyPreset {
+    pub const ALL: [DifficultyPreset; 3] = [
+        DifficultyPreset::Easy,
+        DifficultyPreset::Normal,
+        DifficultyPreset::Hard,
+    ];
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            DifficultyPreset::Easy => "easy",
+            DifficultyPreset::Normal => "normal",
+            DifficultyPreset::Hard => "hard",
+        }
+    }
+
+    /// Scales the vehicle's max energy
+    pub fn energy_multiplier(self) -> f64 {
+        match self {
+            DifficultyPreset::Easy => 1.25,
+            DifficultyPreset::Normal => 1.0,
+            DifficultyPreset::Hard => 0.8,
+        }
+    }
+
+    /// Scales the level's time limit
+    pub fn time_multiplier(self) -> f64 {
+        match self {
+            DifficultyPreset::Easy => 1.25,
+            DifficultyPreset::Normal => 1.0,
+            DifficultyPreset::Hard => 0.8,
+        }
+    }
+
+    /// Scales how much blood sugar stores, bus stops, and pickups give back
+    pub fn recharge_multiplier(self) -> f64 {
+        match self {
+            DifficultyPreset::Easy => 1.25,
+            DifficultyPreset::Normal => 1.0,
+            DifficultyPreset::Hard => 0.75,
+        }
+    }
+
+    /// Scales how many people and cars get in the way
+    pub fn obstacle_multiplier(self) -> f64 {
+        match self {
+            DifficultyPreset::Easy => 0.5,
+            DifficultyPreset::Normal => 1.0,
+            DifficultyPreset::Hard => 1.5,
+        }
+    }
+
+    /// Applied on top of any tuning overrides, so balancing the normal difficulty balances the
+    /// others too
+    pub fn apply(self, tuning: &mut Tuning) {
+        let recharge = self.recharge_multiplier();
+        tuning.supermarket_refill *= recharge;
+        tuning.restaurant_refill *= recharge;
+        tuning.convenience_store_refill *= recharge;
+        tuning.bus_stop_recharge_rate *= recharge;
+        tuning.pickup_energy = ((tuning.pickup_energy as f64) * recharge).round() as usize;
+    }
+
+    /// Normal keeps using the plain level title, so older high scores still count
+    pub fn score_key(self, level: &str) -> String {
+        match self {
+            DifficultyPreset::Normal => level.to_string(),
+            _ => format!("{} ({})", level, self.describe()),
+        }
+    }
+}
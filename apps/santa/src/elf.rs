@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+
+use geom::{Circle, Distance, Duration, Pt2D, Speed, Time};
+use map_model::connectivity::{self, Spot};
+use map_model::{BuildingID, DirectedRoadID, Direction, PathConstraints, RoadID};
+use widgetry::{Color, GeomBatch, GfxCtx, Line, Text};
+
+use crate::buildings::{BldgState, Buildings};
+use crate::App;
+
+// Only look for houses this close to the player, so the elf doesn't wander off
+const SEARCH_TIME_LIMIT: Duration = Duration::const_seconds(30.0);
+// How often to look for a new house when there's nothing to do
+const SEARCH_PERIOD: Duration = Duration::const_seconds(2.0);
+// The elf gets one level better every time the player earns this much
+const UPGRADE_SCORE: usize = 100;
+const MAX_LEVEL: usize = 4;
+const RADIUS: Distance = Distance::const_meters(2.0);
+
+/// An optional helper that runs around delivering to the smallest houses near the player, leaving
+/// the bigger ones to them. Elves don't have to stay on the roads, but they only go after houses
+/// the player could reach quickly. The elf brings its own presents and cookies.
+pub struct Elf {
+    pos: Pt2D,
+    target: Option<BuildingID>,
+    next_search: Time,
+    level: usize,
+}
+
+impl Elf {
+    pub fn new(start: Pt2D) -> Elf {
+        Elf {
+            pos: start,
+            target: None,
+            next_search: Time::START_OF_DAY,
+            level: 1,
+        }
+    }
+
+    /// Returns true if the elf just got better, based on the player's score
+    pub fn upgrade(&mut self, score: usize) -> bool {
+        let level = (1 + score / UPGRADE_SCORE).min(MAX_LEVEL);
+        if level > self.level {
+            self.level = level;
+            return true;
+        }
+        false
+    }
+
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    pub fn saved(&self) -> SavedElf {
+        SavedElf {
+            pos: self.pos,
+            level: self.level,
+        }
+    }
+
+    /// Puts the elf back where a saved run left off. It'll look for a new house right away.
+    pub fn restore(&mut self, saved: SavedElf) {
+        self.pos = saved.pos;
+        self.level = saved.level.min(MAX_LEVEL);
+        self.target = None;
+        self.next_search = Time::START_OF_DAY;
+    }
+
+    // Faster at every level
+    fn speed(&self) -> Speed {
+        Speed::miles_per_hour(10.0 + 5.0 * (self.level as f64))
+    }
+
+    // The biggest house the elf will handle. Anything bigger is worth the player's time.
+    fn max_units(&self) -> usize {
+        self.level
+    }
+
+    /// Moves the elf, and returns a house it just reached. The caller should deliver there.
+    pub fn update(
+        &mut self,
+        app: &App,
+        dt: Duration,
+        bldgs: &Buildings,
+        player_roads: Vec<RoadID>,
+    ) -> Option<BuildingID> {
+        // Someone else may have gotten there first
+        if let Some(b) = self.target {
+            if !self.wants(bldgs, b) {
+                self.target = None;
+            }
+        }
+        if self.target.is_none() && app.time >= self.next_search {
+            self.next_search = app.time + SEARCH_PERIOD;
+            self.target = self.find_target(app, bldgs, player_roads);
+        }
+
+        let b = self.target?;
+        let goal = app.map.get_b(b).label_center;
+        let step = self.speed() * dt;
+        let dist = self.pos.dist_to(goal);
+        if dist <= step {
+            self.pos = goal;
+            self.target = None;
+            return Some(b);
+        }
+        self.pos = self.pos.project_away(step, self.pos.angle_to(goal));
+        None
+    }
+
+    fn wants(&self, bldgs: &Buildings, b: BuildingID) -> bool {
+        match bldgs.buildings.get(&b) {
+            Some(BldgState::Undelivered(num_housing_units, _)) => {
+                *num_housing_units <= self.max_units()
+            }
+            _ => false,
+        }
+    }
+
+    // The small house that's cheapest for the player to reach
+    fn find_target(
+        &self,
+        app: &App,
+        bldgs: &Buildings,
+        player_roads: Vec<RoadID>,
+    ) -> Option<BuildingID> {
+        let mut starts = Vec::new();
+        for road in player_roads {
+            for dir in [Direction::Fwd, Direction::Back] {
+                starts.push(Spot::DirectedRoad(DirectedRoadID { road, dir }));
+            }
+        }
+        let costs = connectivity::all_vehicle_costs_from(
+            &app.map,
+            starts,
+            SEARCH_TIME_LIMIT,
+            PathConstraints::Bike,
+        );
+        costs
+            .into_iter()
+            .filter(|(b, _)| self.wants(bldgs, *b))
+            .min_by_key(|(b, cost)| (*cost, *b))
+            .map(|(b, _)| b)
+    }
+
+    pub fn draw(&self, g: &mut GfxCtx, app: &App) {
+        let color = Color::hex("#2E8B3A");
+        let mut batch = GeomBatch::new();
+        // Show where it's headed, so the player knows to go elsewhere
+        if let Some(b) = self.target {
+            batch.push(
+                color.alpha(0.5),
+                Circle::new(app.map.get_b(b).label_center, RADIUS).to_polygon(),
+            );
+        }
+        batch.push(Color::WHITE, Circle::new(self.pos, RADIUS).to_polygon());
+        batch.push(color, Circle::new(self.pos, RADIUS * 0.8).to_polygon());
+        batch.append(
+            Text::from(Line("E").fg(Color::WHITE))
+                .render_autocropped(g)
+                .scale(0.08)
+                .centered_on(self.pos),
+        );
+        batch.draw(g);
+    }
+}
+
+/// Where the elf is and how good it's gotten, for a resumed run
+#[derive(Serialize, Deserialize)]
+pub struct SavedElf {
+    pos: Pt2D,
+    level: usize,
+}
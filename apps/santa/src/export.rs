@@ -0,0 +1,67 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use geom::PolyLine;
+
+use crate::replay::Replay;
+use crate::App;
+
+/// A summary of one run, for analyzing outside of the game
+#[derive(Serialize)]
+struct Summary {
+    level: String,
+    map: String,
+    score: usize,
+    duration_seconds: f64,
+    deliveries: Vec<Delivery>,
+}
+
+#[derive(Serialize)]
+struct Delivery {
+    building: usize,
+    time_seconds: f64,
+    presents: usize,
+}
+
+pub fn summary_json(app: &App, replay: &Replay) -> Result<String> {
+    let summary = Summary {
+        level: replay.level.clone(),
+        map: app.map.get_name().as_filename(),
+        score: replay.score,
+        duration_seconds: replay.duration().inner_seconds(),
+        deliveries: replay
+            .deliveries()
+            .into_iter()
+            .map(|(t, b, presents)| Delivery {
+                building: b.0,
+                time_seconds: t.inner_seconds(),
+                presents,
+            })
+            .collect(),
+    };
+    Ok(serde_json::to_string_pretty(&summary)?)
+}
+
+/// The traveled path and every delivered house, in map coordinates
+pub fn path_geojson(app: &App, replay: &Replay) -> Result<String> {
+    let mut pairs = Vec::new();
+
+    let mut pts = replay.points();
+    pts.dedup();
+    if pts.len() >= 2 {
+        let mut props = serde_json::Map::new();
+        props.insert("type".to_string(), "path".into());
+        pairs.push((PolyLine::unchecked_new(pts).to_geojson(None), props));
+    }
+
+    for (t, b, presents) in replay.deliveries() {
+        let mut props = serde_json::Map::new();
+        props.insert("type".to_string(), "delivery".into());
+        props.insert("building".to_string(), b.0.into());
+        props.insert("time_seconds".to_string(), t.inner_seconds().into());
+        props.insert("presents".to_string(), presents.into());
+        pairs.push((app.map.get_b(b).label_center.to_geojson(None), props));
+    }
+
+    Ok(geom::geometries_with_properties_to_geojson(pairs).to_string())
+}
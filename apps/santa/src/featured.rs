@@ -0,0 +1,73 @@
+use chrono::Datelike;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
+use crate::levels::Level;
+use crate::App;
+
+// A twist on the usual rules, for the featured level
+#[derive(Clone, Copy)]
+enum Modifier {
+    DoubleWind,
+    HalfEnergy,
+    NightOnly,
+}
+
+impl Modifier {
+    const ALL: [Modifier; 3] = [
+        Modifier::DoubleWind,
+        Modifier::HalfEnergy,
+        Modifier::NightOnly,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Modifier::DoubleWind => "double wind",
+            Modifier::HalfEnergy => "half blood sugar",
+            Modifier::NightOnly => "night only",
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Modifier::DoubleWind => "The wind blows twice as hard as usual.",
+            Modifier::HalfEnergy => "You can only carry half as much blood sugar.",
+            Modifier::NightOnly => "It's the middle of the night the whole time.",
+        }
+    }
+
+    fn apply(self, level: &mut Level) {
+        match self {
+            Modifier::DoubleWind => {
+                level.script.wind_multiplier = Some(2.0);
+            }
+            Modifier::HalfEnergy => {
+                level.script.energy_multiplier = Some(0.5);
+            }
+            Modifier::NightOnly => {
+                level.script.night = true;
+            }
+        }
+    }
+}
+
+/// Every week, one of the regular levels is featured with a modifier. The pick only depends on
+/// the ISO week, so everybody gets the same one, and there's something new without anybody
+/// adding levels. It has its own title, so scores and replays stay apart from the usual level.
+pub fn this_week(app: &App) -> Option<Level> {
+    let week = chrono::Local::now().iso_week();
+    let seed = (week.year() as u64) * 100 + (week.week() as u64);
+    let mut rng = XorShiftRng::seed_from_u64(seed);
+
+    let mut level = app.session.levels.choose(&mut rng)?.clone();
+    let modifier = *Modifier::ALL.choose(&mut rng).unwrap();
+    level.title = format!("{} ({})", level.title, modifier.name());
+    level.description = format!(
+        "Featured this week: {} {}",
+        modifier.describe(),
+        level.description
+    );
+    modifier.apply(&mut level);
+    Some(level)
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use geom::{Distance, Polygon, Pt2D};
+use map_model::BuildingID;
+use widgetry::{Color, Drawable, EventCtx, GeomBatch, GfxCtx};
+
+use crate::buildings::{BldgState, Buildings};
+use crate::App;
+
+// Square cells this wide, in meters
+const CELL_SIZE: f64 = 40.0;
+// Everything this close to where the player has been is revealed
+const REVEAL_RADIUS: Distance = Distance::const_meters(150.0);
+const FOG_COLOR: Color = Color::rgb_f(0.05, 0.05, 0.1);
+
+/// In exploration mode, only areas near where the player has been are shown at full brightness.
+/// Everywhere else is dimmed, with the labels hidden, so finding houses is part of the challenge.
+pub struct Fog {
+    num_cols: usize,
+    num_rows: usize,
+    revealed: Vec<bool>,
+    bldgs_per_cell: HashMap<(usize, usize), Vec<BuildingID>>,
+    // Revealing only happens when the player enters a new cell
+    last_cell: Option<(usize, usize)>,
+    draw: Drawable,
+}
+
+impl Fog {
+    /// Hides every label, until its building is revealed
+    pub fn new(ctx: &mut EventCtx, app: &App, bldgs: &mut Buildings) -> Fog {
+        let bounds = app.map.get_bounds();
+        let num_cols = (bounds.max_x / CELL_SIZE).ceil() as usize + 1;
+        let num_rows = (bounds.max_y / CELL_SIZE).ceil() as usize + 1;
+
+        let mut bldgs_per_cell: HashMap<(usize, usize), Vec<BuildingID>> = HashMap::new();
+        for b in app.map.all_buildings() {
+            bldgs_per_cell
+                .entry(cell(b.label_center))
+                .or_insert_with(Vec::new)
+                .push(b.id);
+            bldgs.labels.hide(b.id);
+        }
+
+        let mut fog = Fog {
+            num_cols,
+            num_rows,
+            revealed: vec![false; num_cols * num_rows],
+            bldgs_per_cell,
+            last_cell: None,
+            draw: Drawable::empty(ctx),
+        };
+        fog.redraw(ctx);
+        fog
+    }
+
+    pub fn update(&mut self, ctx: &mut EventCtx, bldgs: &mut Buildings, pos: Pt2D) {
+        let current = cell(pos);
+        if self.last_cell == Some(current) {
+            return;
+        }
+        self.last_cell = Some(current);
+
+        let radius = (REVEAL_RADIUS.inner_meters() / CELL_SIZE).ceil() as usize;
+        let mut changed = false;
+        for x in current.0.saturating_sub(radius)..=(current.0 + radius).min(self.num_cols - 1) {
+            for y in current.1.saturating_sub(radius)..=(current.1 + radius).min(self.num_rows - 1)
+            {
+                let idx = y * self.num_cols + x;
+                if self.revealed[idx] || cell_center((x, y)).dist_to(pos) > REVEAL_RADIUS {
+                    continue;
+                }
+                self.revealed[idx] = true;
+                changed = true;
+                for b in self.bldgs_per_cell.get(&(x, y)).into_iter().flatten() {
+                    // Delivered houses already lost their label
+                    if !matches!(bldgs.buildings[b], BldgState::Done) {
+                        bldgs.labels.show(*b);
+                    }
+                }
+            }
+        }
+        if changed {
+            self.redraw(ctx);
+        }
+    }
+
+    pub fn draw(&self, g: &mut GfxCtx) {
+        g.redraw(&self.draw);
+    }
+
+    fn redraw(&mut self, ctx: &mut EventCtx) {
+        // Merge runs of hidden cells in each row, to keep the batch small
+        let mut batch = GeomBatch::new();
+        for y in 0..self.num_rows {
+            let mut x = 0;
+            while x < self.num_cols {
+                if self.revealed[y * self.num_cols + x] {
+                    x += 1;
+                    continue;
+                }
+                let start = x;
+                while x < self.num_cols && !self.revealed[y * self.num_cols + x] {
+                    x += 1;
+                }
+                batch.push(
+                    FOG_COLOR.alpha(0.7),
+                    Polygon::rectangle(((x - start) as f64) * CELL_SIZE, CELL_SIZE)
+                        .translate((start as f64) * CELL_SIZE, (y as f64) * CELL_SIZE),
+                );
+            }
+        }
+        self.draw = ctx.upload(batch);
+    }
+}
+
+fn cell(pt: Pt2D) -> (usize, usize) {
+    (
+        (pt.x().max(0.0) / CELL_SIZE) as usize,
+        (pt.y().max(0.0) / CELL_SIZE) as usize,
+    )
+}
+
+fn cell_center((x, y): (usize, usize)) -> Pt2D {
+    Pt2D::new(
+        ((x as f64) + 0.5) * CELL_SIZE,
+        ((y as f64) + 0.5) * CELL_SIZE,
+    )
+}
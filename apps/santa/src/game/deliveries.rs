@@ -0,0 +1,443 @@
+use abstutil::prettyprint_usize;
+use geom::{Distance, Duration, Pt2D};
+use map_model::BuildingID;
+use widgetry::tools::ChooseSomething;
+use widgetry::{Choice, Color, EventCtx, GeomBatch, Key, Line, Text};
+
+use crate::achievements::Achievement;
+use crate::animation::{present, Effect};
+use crate::buildings::{BldgState, PresentType, StoreType};
+use crate::controls::ControlScheme;
+use crate::game::Game;
+use crate::music::Sound;
+use crate::{App, Transition};
+
+impl Game {
+    /// Going home costs presents, so ask first
+    pub fn confirm_go_home(&mut self, ctx: &mut EventCtx, app: &App) -> Transition {
+        if let Some(t) = self.state.last_go_home {
+            let left = self.state.tuning.go_home_cooldown - (app.time - t);
+            if left > Duration::ZERO {
+                self.animator.add(
+                    app.time,
+                    Duration::seconds(1.0),
+                    Effect::Float {
+                        start: self.ride.player.get_pos(),
+                        rise: Distance::meters(15.0),
+                        orig: Text::from(
+                            Line(app.session.locale.tr_args(
+                                "Can't go home for another {}",
+                                &[Duration::seconds(left.inner_seconds().ceil()).to_string()],
+                            ))
+                            .fg(Color::WHITE),
+                        )
+                        .bg(Color::BLACK)
+                        .render_autocropped(ctx)
+                        .scale(0.2),
+                    },
+                );
+                return Transition::Keep;
+            }
+        }
+
+        let penalty = self
+            .state
+            .tuning
+            .go_home_penalty
+            .min(self.state.rules.score);
+        Transition::Push(ChooseSomething::new_state(
+            ctx,
+            app.session.locale.tr_args(
+                "Go back to the start and refill, for {} presents?",
+                &[prettyprint_usize(penalty)],
+            ),
+            vec![
+                Choice::new(app.session.locale.tr("Go home"), true),
+                Choice::new(app.session.locale.tr("Never mind"), false).key(Key::Escape),
+            ],
+            Box::new(|go_home, _, _| {
+                if !go_home {
+                    return Transition::Pop;
+                }
+                Transition::Multi(vec![
+                    Transition::Pop,
+                    Transition::ModifyState(Box::new(|state, ctx, app| {
+                        let game = state.downcast_mut::<Game>().unwrap();
+                        game.go_home(ctx, app);
+                    })),
+                ])
+            }),
+        ))
+    }
+
+    pub fn go_home(&mut self, ctx: &mut EventCtx, app: &App) {
+        self.state.rules.score = self
+            .state
+            .rules
+            .score
+            .saturating_sub(self.state.tuning.go_home_penalty);
+        self.state.last_go_home = Some(app.time);
+        self.state.rules.combo = 0;
+        // Without stores, home is just a way out of a dead end
+        let refill = !self.state.level.script.no_stores;
+        if refill {
+            self.state.rules.energy = self.state.vehicle.max_energy;
+            self.state.rules.restock_presents(&self.state.tuning);
+        }
+        self.ride.player.teleport(app, self.start);
+        if let Some(ref mut second) = self.second_player {
+            second.player.teleport(app, self.start);
+            if refill {
+                second.energy = self.state.vehicle.max_energy;
+            }
+        }
+        self.ride.camera.jump();
+        self.update_status_panel(ctx, app);
+        self.update_presents_panel(ctx, app);
+    }
+
+    /// Riding past a restaurant gives a moment to press the snack key
+    pub fn offer_snack(&mut self, ctx: &mut EventCtx, app: &App, b: BuildingID) {
+        if self.state.snack_offer.map(|(prev, _)| prev) != Some(b) {
+            self.animator.add(
+                app.time,
+                Duration::seconds(1.0),
+                Effect::Float {
+                    start: app.map.get_b(b).label_center,
+                    rise: Distance::meters(10.0),
+                    orig: Text::from(
+                        Line(app.session.locale.tr_args(
+                            "Press {} for a snack",
+                            &[app.session.keys.describe("snack")],
+                        ))
+                        .fg(Color::BLACK),
+                    )
+                    .bg(app.session.colors.store)
+                    .render_autocropped(ctx)
+                    .scale(0.15),
+                },
+            );
+        }
+        self.state.snack_offer = Some((b, app.time));
+    }
+
+    pub fn eat_snack(&mut self, ctx: &mut EventCtx, app: &mut App) {
+        let b = match self.state.snack_offer.take() {
+            Some((b, t)) if app.time - t <= self.state.tuning.snack_window => b,
+            _ => {
+                return;
+            }
+        };
+        let bldg = self.state.bldgs.buildings.get_mut(&b).unwrap();
+        if self
+            .state
+            .rules
+            .eat_snack(&self.state.tuning, app.time, bldg)
+        {
+            app.session.music.play_sound(Sound::Recharge);
+            self.animator.add(
+                app.time,
+                Duration::seconds(1.0),
+                Effect::Float {
+                    start: self.ride.player.get_pos(),
+                    rise: Distance::meters(15.0),
+                    orig: Text::from(
+                        Line(app.session.locale.tr_args(
+                            "Snack! {}x presents",
+                            &[self.state.tuning.snack_multiplier.to_string()],
+                        ))
+                        .fg(Color::BLACK),
+                    )
+                    .bg(app.session.colors.store)
+                    .render_autocropped(ctx)
+                    .scale(0.2),
+                },
+            );
+            self.update_power_ups_panel(ctx, app);
+        }
+    }
+
+    /// Celebrates the first time an achievement is earned. Nothing counts in the tutorial.
+    pub fn unlock(&mut self, ctx: &mut EventCtx, app: &mut App, achievement: Achievement) {
+        if self.tutorial.is_some() || !app.session.unlock_achievement(achievement) {
+            return;
+        }
+        self.animator.add_screenspace(
+            app.time,
+            Duration::seconds(3.0),
+            Effect::Scale {
+                lerp_scale: (1.0, 2.0),
+                center: {
+                    let pt = ctx.canvas.center_to_screen_pt();
+                    Pt2D::new(pt.x, pt.y / 3.0)
+                },
+                orig: Text::from(app.session.locale.tr_args(
+                    "Achievement unlocked: {}",
+                    &[achievement.name().to_string()],
+                ))
+                .bg(app.session.colors.score)
+                .render_autocropped(ctx),
+            },
+        );
+    }
+
+    /// Within the vehicle's delivery radius, the closest house the current present would go to
+    pub fn nearest_eligible(&self, app: &App) -> Option<BuildingID> {
+        self.ride
+            .player
+            .driveways_nearby(app, self.state.vehicle.delivery_radius)
+            .into_iter()
+            .map(|(b, _)| b)
+            .find(|b| match self.state.bldgs.buildings[b] {
+                BldgState::Undelivered(_, wanted) => {
+                    self.state.can_deliver(wanted)
+                        && self.state.rules.closed_window(*b, app.time).is_none()
+                }
+                _ => false,
+            })
+    }
+
+    /// Everything ridden past, plus the closest house in reach when presents drop automatically.
+    /// Otherwise houses only get presents from the drop key.
+    pub fn buildings_reached(&self, app: &App, mut passed: Vec<BuildingID>) -> Vec<BuildingID> {
+        // Nobody with only one button can spare it for dropping presents
+        if app.session.auto_drop
+            || app.session.control_scheme == ControlScheme::OneButton
+            || self.ride.player.is_bot()
+        {
+            if let Some(b) = self.nearest_eligible(app) {
+                if !passed.contains(&b) {
+                    passed.push(b);
+                }
+            }
+        } else {
+            passed.retain(|b| !matches!(self.state.bldgs.buildings[b], BldgState::Undelivered(..)));
+        }
+        passed
+    }
+
+    /// Time slows down for a moment, so the delivery really sinks in
+    pub fn celebrate_big_delivery(&mut self, app: &mut App, b: BuildingID) {
+        app.session.music.play_sound(Sound::BigDelivery);
+        if app.session.reduce_motion {
+            return;
+        }
+        // The opponent's clock keeps going at the usual speed
+        if self.race.is_none() {
+            self.slow_motion_until = Some(app.time + self.state.tuning.slow_motion_duration);
+        }
+        self.animator.add(
+            app.time,
+            self.state.tuning.slow_motion_duration,
+            Effect::Burst {
+                center: app.map.get_b(b).label_center,
+                radius: Distance::meters(30.0),
+                colors: PresentType::ALL.into_iter().map(|p| p.color()).collect(),
+            },
+        );
+    }
+
+    /// Deliver to houses and refill at stores the player just passed
+    pub fn visit_buildings(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &mut App,
+        buildings: Vec<BuildingID>,
+    ) {
+        for b in buildings {
+            match self.state.bldgs.buildings[&b] {
+                BldgState::Undelivered(_, wanted) => {
+                    if let Some(window) = self.state.rules.closed_window(b, app.time) {
+                        if self.state.last_wrong_present != Some(b) {
+                            self.state.last_wrong_present = Some(b);
+                            self.animator.add(
+                                app.time,
+                                Duration::seconds(1.0),
+                                Effect::Float {
+                                    start: app.map.get_b(b).label_center,
+                                    rise: Distance::meters(10.0),
+                                    orig: Text::from(Line(window.describe()).fg(Color::BLACK))
+                                        .bg(Color::WHITE)
+                                        .render_autocropped(ctx)
+                                        .scale(0.2),
+                                },
+                            );
+                        }
+                        continue;
+                    }
+                    if self.state.rules.has_energy()
+                        && !self.state.can_deliver(wanted)
+                        && self.state.last_wrong_present != Some(b)
+                    {
+                        self.state.last_wrong_present = Some(b);
+                        let msg = if wanted == self.state.selected_present {
+                            app.session
+                                .locale
+                                .tr_args("Out of {}s", &[wanted.name().to_string()])
+                        } else {
+                            app.session
+                                .locale
+                                .tr_args("Wants a {}", &[wanted.name().to_string()])
+                        };
+                        self.animator.add(
+                            app.time,
+                            Duration::seconds(1.0),
+                            Effect::Float {
+                                start: app.map.get_b(b).label_center,
+                                rise: Distance::meters(10.0),
+                                orig: Text::from(Line(msg).fg(Color::BLACK))
+                                    .bg(wanted.color())
+                                    .render_autocropped(ctx)
+                                    .scale(0.2),
+                            },
+                        );
+                    }
+                    let combo = self.state.rules.combo;
+                    if let Some(increase) = self.state.present_dropped(ctx, app, b) {
+                        if let Some(ref mut race) = self.race {
+                            race.claim(b, increase);
+                        }
+                        app.session
+                            .stats_feed
+                            .delivery(b, increase, self.state.rules.score);
+                        if increase >= self.state.tuning.big_delivery_score {
+                            self.celebrate_big_delivery(app, b);
+                        } else {
+                            app.session.music.play_sound(Sound::Delivery);
+                        }
+                        self.animator.add(
+                            app.time,
+                            Duration::seconds(0.4),
+                            Effect::Toss {
+                                orig: present(app.session.colors.score),
+                                from: self.ride.player.get_pos(),
+                                to: app.map.get_b(b).label_center,
+                                height: Distance::meters(15.0),
+                            },
+                        );
+                        if self.state.rules.combo > combo {
+                            self.animator.add(
+                                app.time,
+                                Duration::seconds(0.5),
+                                Effect::Scale {
+                                    lerp_scale: (1.0, 2.0),
+                                    center: self.ride.player.get_pos(),
+                                    orig: Text::from(format!("x{}", self.state.rules.combo + 1))
+                                        .bg(app.session.colors.score)
+                                        .render_autocropped(ctx)
+                                        .scale(0.1),
+                                },
+                            );
+                        }
+                        let path_speed = Duration::seconds(0.2);
+                        self.animator.add(
+                            app.time,
+                            path_speed,
+                            Effect::FollowPath {
+                                color: app.session.colors.score,
+                                width: map_model::NORMAL_LANE_THICKNESS,
+                                pl: app.map.get_b(b).driveway_geom.reversed(),
+                            },
+                        );
+                        self.animator.add(
+                            app.time + path_speed,
+                            Duration::seconds(1.0),
+                            Effect::Float {
+                                start: app.map.get_b(b).label_center,
+                                rise: Distance::meters(20.0),
+                                orig: Text::from(format!("+{}", prettyprint_usize(increase)))
+                                    .bg(app.session.colors.score)
+                                    .render_autocropped(ctx)
+                                    .scale(0.3),
+                            },
+                        );
+
+                        if let Some(bonus) = self.state.zone_delivered(b) {
+                            self.unlock(ctx, app, Achievement::CompleteNeighborhood);
+                            let zone = self.state.zones.get(b).unwrap();
+                            self.animator.add(
+                                app.time,
+                                Duration::seconds(1.5),
+                                Effect::Flash {
+                                    orig: GeomBatch::from(vec![(
+                                        app.session.colors.score,
+                                        zone.boundary.to_outline(Distance::meters(3.0)),
+                                    )]),
+                                    alpha_scale: (0.2, 1.0),
+                                    cycles: 3,
+                                },
+                            );
+                            self.animator.add(
+                                app.time,
+                                Duration::seconds(1.5),
+                                Effect::Scale {
+                                    lerp_scale: (1.0, 2.0),
+                                    center: zone.center,
+                                    orig: Text::from(app.session.locale.tr_args(
+                                        "Finished {}! +{}",
+                                        &[zone.name.clone(), prettyprint_usize(bonus)],
+                                    ))
+                                    .bg(app.session.colors.score)
+                                    .render_autocropped(ctx)
+                                    .scale(0.15),
+                                },
+                            );
+                        }
+                    }
+                }
+                BldgState::Store(_) | BldgState::Snacked(_)
+                    if self.state.level.script.no_stores => {}
+                BldgState::Store(store_type) | BldgState::Snacked(store_type) => {
+                    if let BldgState::Store(StoreType::Restaurant) = self.state.bldgs.buildings[&b]
+                    {
+                        self.offer_snack(ctx, app, b);
+                    }
+                    let (refill, restocked) = self.state.rules.visit_store(
+                        &self.state.tuning,
+                        self.state.vehicle.max_energy,
+                        b,
+                        store_type,
+                        app.time,
+                    );
+                    if restocked > 0 {
+                        self.update_presents_panel(ctx, app);
+                    }
+                    if refill > 0 {
+                        app.session.music.play_sound(Sound::Recharge);
+                        self.state.replay.recharged(app.time, b, refill);
+                        self.state.warned_low_energy = false;
+                        let path_speed = Duration::seconds(0.2);
+                        self.animator.add(
+                            app.time,
+                            path_speed,
+                            Effect::FollowPath {
+                                color: app.session.colors.energy,
+                                width: map_model::NORMAL_LANE_THICKNESS,
+                                pl: app.map.get_b(b).driveway_geom.clone(),
+                            },
+                        );
+                        self.animator.add(
+                            app.time + path_speed,
+                            Duration::seconds(0.5),
+                            Effect::Scale {
+                                lerp_scale: (1.0, 4.0),
+                                center: app.map.get_b(b).label_center,
+                                orig: Text::from(
+                                    app.session
+                                        .locale
+                                        .tr_args("Refilled {}", &[prettyprint_usize(refill)]),
+                                )
+                                .bg(app.session.colors.energy)
+                                .render_autocropped(ctx)
+                                .scale(0.1),
+                            },
+                        );
+                    } else if restocked > 0 {
+                        app.session.music.play_sound(Sound::Recharge);
+                    }
+                }
+                BldgState::Done | BldgState::Ignore => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,587 @@
+use instant::Instant;
+
+use abstutil::prettyprint_usize;
+use geom::{Circle, Distance, Duration, Pt2D, Time};
+use map_gui::tools::MinimapControls;
+use map_model::BuildingID;
+use widgetry::tools::ColorLegend;
+use widgetry::{
+    Color, EventCtx, GeomBatch, HorizontalAlignment, Line, Panel, Text, TextExt, TextSpan,
+    VerticalAlignment, Widget,
+};
+
+use crate::animation::Effect;
+use crate::arcade::time_left;
+use crate::buildings::{BldgState, PresentType, StoreType};
+use crate::game::Game;
+use crate::meters::{custom_bar, hud_text, make_bar, pct_bar, plain_bar, warning_color};
+use crate::mode::Mode;
+use crate::App;
+
+// Time trials record a split every time another quarter of the goal is reached
+const NUM_SPLITS: usize = 4;
+
+impl Game {
+    pub fn update_time_panel(&mut self, ctx: &mut EventCtx, app: &App) {
+        let started = Instant::now();
+        // Only show whole seconds, so the text only changes once a second
+        let (shown, warning, pct) = if self.state.rules.mode.has_time_limit() {
+            let (left, pct) = time_left(app, self.state.level.time_limit);
+            (left, pct >= 0.75, pct)
+        } else {
+            let elapsed = app.time - Time::START_OF_DAY;
+            // Count up instead
+            (
+                Duration::seconds(elapsed.inner_seconds().floor()),
+                false,
+                0.0,
+            )
+        };
+
+        if self.time_shown.0 != Some((shown, warning)) {
+            self.time_shown.0 = Some((shown, warning));
+            let text_color = if warning { Color::RED } else { Color::WHITE };
+            let label = hud_text(
+                ctx,
+                app,
+                Line(format!("{}", shown)).fg(text_color).small_heading(),
+            )
+            .centered_vert();
+            self.time_panel.replace(ctx, "time label", label);
+        }
+
+        // Redrawing the circle is cheap, but there's no need to do it every frame
+        let pct_shown = (pct * 100.0).round() as usize;
+        if self.time_shown.1 != Some(pct_shown) {
+            self.time_shown.1 = Some(pct_shown);
+            // TODO I couldn't quite work out how to get the partial outline from Figma working
+            let center = Pt2D::new(0.0, 0.0);
+            let outer = Distance::meters(30.0);
+            let mut batch = GeomBatch::new();
+            batch.push(Color::WHITE, Circle::new(center, outer).to_polygon());
+            batch.push(
+                Color::hex("#5D92C2"),
+                Circle::new(center, outer).to_partial_tessellation(pct),
+            );
+            let draw = batch.autocrop().into_widget(ctx);
+            self.time_panel.replace(ctx, "time circle", draw);
+        }
+        self.perf.record_panel(started);
+    }
+
+    pub fn update_status_panel(&mut self, ctx: &mut EventCtx, app: &App) {
+        let started = Instant::now();
+        // Zen mode only tracks how much of the map is covered
+        if self.state.rules.mode == Mode::Zen {
+            let done = self.state.total_houses - self.state.houses_left();
+            self.update_bar(
+                ctx,
+                "score",
+                pct_bar,
+                app.session.colors.score,
+                done,
+                self.state.total_houses,
+            );
+            self.perf.record_panel(started);
+            return;
+        }
+
+        let goal = if self.state.met_goal() {
+            self.state.bldgs.total_housing_units
+        } else {
+            self.state.level.goal
+        };
+        self.update_bar(
+            ctx,
+            "score",
+            make_bar,
+            app.session.colors.score,
+            self.state.rules.score,
+            goal,
+        );
+
+        let color = warning_color(
+            app.session.colors.energy,
+            app.session.colors.bad,
+            self.state.rules.energy,
+            self.state.tuning.low_energy_warning,
+            self.state.energy_flash_on,
+        );
+        self.update_bar(
+            ctx,
+            "energy",
+            plain_bar,
+            color,
+            self.state.rules.energy,
+            self.state.vehicle.max_energy,
+        );
+
+        if let Some(energy) = self.second_player.as_ref().map(|second| second.energy) {
+            let color = warning_color(
+                app.session.colors.energy,
+                app.session.colors.bad,
+                energy,
+                self.state.tuning.low_energy_warning,
+                false,
+            );
+            self.update_bar(
+                ctx,
+                "energy 2",
+                plain_bar,
+                color,
+                energy,
+                self.state.vehicle.max_energy,
+            );
+        }
+        self.perf.record_panel(started);
+    }
+
+    // Only rebuilds a meter in the status panel when what it shows changes
+    pub fn update_bar(
+        &mut self,
+        ctx: &mut EventCtx,
+        name: &'static str,
+        build: fn(&mut EventCtx, Color, usize, usize) -> Widget,
+        color: Color,
+        value: usize,
+        max: usize,
+    ) {
+        let shown = (color, value, max);
+        if self.bars_shown.get(name) == Some(&shown) {
+            return;
+        }
+        self.bars_shown.insert(name, shown);
+        let bar = build(ctx, color, value, max);
+        self.status_panel.replace(ctx, name, bar);
+    }
+
+    // In the middle of the screen, for dev tools and scripted levels
+    pub fn flash_message(&mut self, ctx: &mut EventCtx, app: &App, msg: String) {
+        self.animator.add_screenspace(
+            app.time,
+            Duration::seconds(1.0),
+            Effect::Scale {
+                lerp_scale: (1.0, 2.0),
+                center: {
+                    let pt = ctx.canvas.center_to_screen_pt();
+                    Pt2D::new(pt.x, pt.y / 2.0)
+                },
+                orig: Text::from(msg).bg(Color::BLACK).render_autocropped(ctx),
+            },
+        );
+    }
+
+    pub fn update_presents_panel(&mut self, ctx: &mut EventCtx, app: &App) {
+        let mut spans = Vec::new();
+        for present in PresentType::ALL {
+            let count = self.state.rules.presents[&present];
+            let label = format!("{} {}s", count, present.name());
+            let line = if present == self.state.selected_present {
+                Line(format!("[{}]", label))
+                    .fg(present.color())
+                    .underlined()
+            } else {
+                Line(label).fg(if count == 0 {
+                    Color::RED
+                } else {
+                    present.color().alpha(0.7)
+                })
+            };
+            spans.push(line);
+            spans.push(Line("  "));
+        }
+        spans.pop();
+        let mut txt = Text::from_all(spans);
+        txt.add_line(
+            Line(app.session.locale.tr_args(
+                "Press {} to switch presents",
+                &[app.session.keys.describe("switch present")],
+            ))
+            .secondary(),
+        );
+        self.status_panel
+            .replace(ctx, "presents", hud_text(ctx, app, txt));
+    }
+
+    pub fn update_power_ups_panel(&mut self, ctx: &mut EventCtx, app: &App) {
+        let secs_left = |until: Option<Time>| {
+            until
+                .map(|t| (t - app.time).inner_seconds().max(0.0).ceil() as usize)
+                .unwrap_or(0)
+        };
+        let shown = (
+            secs_left(self.state.rules.double_score_until),
+            secs_left(self.state.rules.speed_until),
+            secs_left(self.state.rules.snack_until),
+        );
+        if shown == self.state.power_ups_shown {
+            return;
+        }
+        self.state.power_ups_shown = shown;
+
+        let mut txt = Text::new();
+        if shown.0 > 0 {
+            txt.add_line(
+                Line(
+                    app.session
+                        .locale
+                        .tr_args("Double presents: {}s", &[shown.0.to_string()]),
+                )
+                .fg(app.session.colors.score),
+            );
+        }
+        if shown.1 > 0 {
+            txt.add_line(
+                Line(
+                    app.session
+                        .locale
+                        .tr_args("Speed boost: {}s", &[shown.1.to_string()]),
+                )
+                .fg(app.session.colors.boost),
+            );
+        }
+        let snack = if shown.2 > 0 {
+            // The same circle that marks restaurants on the map
+            let icon = GeomBatch::from(vec![(
+                app.session.colors.store,
+                Circle::new(Pt2D::new(0.0, 0.0), Distance::meters(8.0)).to_polygon(),
+            )]);
+            Widget::row(vec![
+                icon.autocrop().into_widget(ctx).centered_vert(),
+                hud_text(
+                    ctx,
+                    app,
+                    Line(app.session.locale.tr_args(
+                        "Snack: {}x presents for {}s",
+                        &[
+                            self.state.tuning.snack_multiplier.to_string(),
+                            shown.2.to_string(),
+                        ],
+                    ))
+                    .fg(app.session.colors.store),
+                ),
+            ])
+        } else {
+            Widget::nothing()
+        };
+        self.status_panel.replace(
+            ctx,
+            "power ups",
+            Widget::col(vec![hud_text(ctx, app, txt), snack]),
+        );
+    }
+
+    pub fn update_delivery_rate_panel(&mut self, ctx: &mut EventCtx, app: &App) {
+        while let Some(t) = self.state.recent_deliveries.front() {
+            if app.time - *t > Duration::minutes(1) {
+                self.state.recent_deliveries.pop_front();
+            } else {
+                break;
+            }
+        }
+        let rate = self.state.recent_deliveries.len();
+        if Some(rate) == self.state.delivery_rate_shown {
+            return;
+        }
+        self.state.delivery_rate_shown = Some(rate);
+        let label = hud_text(
+            ctx,
+            app,
+            app.session.locale.tr_args(
+                "{} deliveries in the last minute",
+                &[prettyprint_usize(rate)],
+            ),
+        );
+        self.status_panel.replace(ctx, "delivery rate", label);
+    }
+
+    pub fn update_splits_panel(&mut self, ctx: &mut EventCtx, app: &App) {
+        let mut txt = Text::new();
+        for (idx, split) in self.state.splits.iter().enumerate() {
+            txt.add_line(Line(format!("{}%: {}", split_pct(idx), split)));
+            if let Some(best) = self.state.best_splits.get(idx) {
+                txt.append(split_delta(app, *split, *best));
+            }
+        }
+        self.status_panel
+            .replace(ctx, "splits", hud_text(ctx, app, txt));
+    }
+
+    /// In time trials, notice when the score crosses the next threshold. Reaching the goal ends
+    /// the run.
+    pub fn check_splits(&mut self, ctx: &mut EventCtx, app: &mut App) {
+        if self.state.rules.mode != Mode::TimeTrial || self.tutorial.is_some() {
+            return;
+        }
+        let orig_splits = self.state.splits.len();
+        while self.state.splits.len() < NUM_SPLITS
+            && self.state.rules.score
+                >= self.state.level.goal * split_pct(self.state.splits.len()) / 100
+        {
+            self.state.splits.push(app.time - Time::START_OF_DAY);
+        }
+        if self.state.splits.len() == orig_splits {
+            return;
+        }
+        self.update_splits_panel(ctx, app);
+
+        let idx = self.state.splits.len() - 1;
+        let split = self.state.splits[idx];
+        let mut txt = Text::from(Line(format!("{}", split)));
+        if let Some(best) = self.state.best_splits.get(idx) {
+            txt.append(split_delta(app, split, *best));
+        }
+        self.animator.add(
+            app.time,
+            Duration::seconds(2.0),
+            Effect::Float {
+                start: self.ride.player.get_pos(),
+                rise: Distance::meters(15.0),
+                orig: txt.bg(Color::BLACK).render_autocropped(ctx).scale(0.2),
+            },
+        );
+
+        if self.state.splits.len() == NUM_SPLITS {
+            let new_best = app.session.record_splits(
+                self.state.difficulty.score_key(&self.state.level.title),
+                self.state.splits.clone(),
+            );
+            self.state.game_over = true;
+            self.animator.add(
+                app.time,
+                Duration::seconds(3.0),
+                Effect::Scale {
+                    lerp_scale: (1.0, 4.0),
+                    center: self.ride.player.get_pos(),
+                    orig: Text::from(if new_best {
+                        app.session
+                            .locale
+                            .tr_args("New best: {}!", &[split.to_string()])
+                    } else {
+                        app.session
+                            .locale
+                            .tr_args("Finished in {}", &[split.to_string()])
+                    })
+                    .bg(Color::GREEN)
+                    .render_autocropped(ctx)
+                    .scale(0.1),
+                },
+            );
+        }
+    }
+
+    pub fn update_combo_panel(&mut self, ctx: &mut EventCtx, app: &App) {
+        let label = hud_text(
+            ctx,
+            app,
+            if self.state.rules.combo == 0 {
+                Text::new()
+            } else {
+                Text::from(
+                    Line(app.session.locale.tr_args(
+                        "Combo x{} -- {}x presents!",
+                        &[
+                            (self.state.rules.combo + 1).to_string(),
+                            format!(
+                                "{:.1}",
+                                self.state.rules.combo_multiplier(&self.state.tuning)
+                            ),
+                        ],
+                    ))
+                    .fg(app.session.colors.score),
+                )
+            },
+        );
+        self.status_panel.replace(ctx, "combo", label);
+    }
+
+    pub fn update_wind_panel(&mut self, ctx: &mut EventCtx) {
+        let arrow = self.state.wind.to_widget(ctx);
+        self.status_panel.replace(ctx, "wind", arrow);
+    }
+
+    pub fn update_boost_panel(&mut self, ctx: &mut EventCtx, app: &App) {
+        let boost_bar = if self.state.boost_cooldown > Duration::ZERO {
+            custom_bar(
+                ctx,
+                app.session.colors.boost.alpha(0.5),
+                1.0 - self.state.boost_cooldown / self.state.tuning.boost_cooldown,
+                Text::from(app.session.locale.tr("Catching your breath...")),
+            )
+        } else {
+            custom_bar(
+                ctx,
+                app.session.colors.boost,
+                self.state.boost / self.state.tuning.max_boost,
+                if self.state.boost == Duration::ZERO {
+                    Text::from(app.session.locale.tr("Find a bike or bus lane"))
+                } else {
+                    Text::from(
+                        app.session
+                            .locale
+                            .tr_args("Hold {} to boost", &[app.session.keys.describe("boost")]),
+                    )
+                },
+            )
+        };
+        self.minimap.mut_panel().replace(ctx, "boost", boost_bar);
+    }
+
+    pub fn describe_bldg(&self, app: &App, id: BuildingID) -> Text {
+        let bldg = app.map.get_b(id);
+        let mut txt = Text::from(
+            Line(if let Some(ref names) = bldg.name {
+                names.get(app.opts.language.as_ref()).to_string()
+            } else {
+                bldg.address.clone()
+            })
+            .small_heading(),
+        );
+        match self.state.bldgs.buildings[&id] {
+            BldgState::Undelivered(num_housing_units, wanted) => {
+                txt.add_line(
+                    Line(app.session.locale.tr_args(
+                        "Waiting for {} {}s",
+                        &[
+                            prettyprint_usize(num_housing_units),
+                            wanted.name().to_string(),
+                        ],
+                    ))
+                    .fg(wanted.color()),
+                );
+                if let Some(window) = self.windows.as_ref().and_then(|w| w.get(id)) {
+                    txt.add_line(Line(window.describe()).secondary());
+                }
+            }
+            BldgState::Store(_) | BldgState::Snacked(_) if self.state.level.script.no_stores => {
+                txt.add_line(Line(app.session.locale.tr("Closed for the night")).secondary());
+            }
+            BldgState::Store(store_type) | BldgState::Snacked(store_type) => {
+                txt.add_line(
+                    Line(
+                        app.session.locale.tr_args(
+                            "{}: ride past to refill {}% of blood sugar",
+                            &[
+                                store_type.describe().to_string(),
+                                (100.0 * self.state.tuning.refill(store_type))
+                                    .round()
+                                    .to_string(),
+                            ],
+                        ),
+                    )
+                    .fg(app.session.colors.store),
+                );
+                let (energy, presents) = self.state.rules.store_stock(
+                    &self.state.tuning,
+                    self.state.vehicle.max_energy,
+                    id,
+                    app.time,
+                );
+                txt.add_line(
+                    Line(if self.state.upgrades.inventory > 0 {
+                        app.session.locale.tr_args(
+                            "On the shelves: {} blood sugar, {} presents",
+                            &[prettyprint_usize(energy), prettyprint_usize(presents)],
+                        )
+                    } else if energy == 0 && presents == 0 {
+                        app.session.locale.tr("Sold out for now").to_string()
+                    } else if self.state.rules.stock_low(&self.state.tuning, id, app.time) {
+                        app.session.locale.tr("Running low").to_string()
+                    } else {
+                        app.session.locale.tr("Well stocked").to_string()
+                    })
+                    .secondary(),
+                );
+                if let BldgState::Store(StoreType::Restaurant) = self.state.bldgs.buildings[&id] {
+                    txt.add_line(app.session.locale.tr_args(
+                        "Press {} while riding past for a snack: {}x presents for {}",
+                        &[
+                            app.session.keys.describe("snack"),
+                            self.state.tuning.snack_multiplier.to_string(),
+                            self.state.tuning.snack_duration.to_string(),
+                        ],
+                    ));
+                }
+            }
+            BldgState::Done => {
+                txt.add_line(Line(app.session.locale.tr("Already delivered")));
+            }
+            BldgState::Ignore => {}
+        }
+        if let Some(zone) = self.state.zones.get(id) {
+            txt.add_line(Line(format!(
+                "{}: {}% delivered",
+                zone.name,
+                (100.0 * zone.pct_done()).round()
+            )));
+        }
+        txt.add_line(Line(format!(
+            "{} away",
+            bldg.label_center
+                .dist_to(self.ride.player.get_pos())
+                .to_string(&app.opts.units)
+        )));
+        txt
+    }
+}
+
+/// The percent of the goal needed to reach a split
+fn split_pct(idx: usize) -> usize {
+    100 * (idx + 1) / NUM_SPLITS
+}
+
+/// Compared to the best run, in the good color if faster
+fn split_delta(app: &App, split: Duration, best: Duration) -> TextSpan {
+    if split <= best {
+        Line(format!(" (-{})", best - split)).fg(app.session.colors.good)
+    } else {
+        Line(format!(" (+{})", split - best)).fg(app.session.colors.bad)
+    }
+}
+
+pub fn make_pause_panel(ctx: &mut EventCtx, app: &App) -> Panel {
+    Panel::new_builder(
+        ctx.style()
+            .btn_plain
+            .icon_text("system/assets/speed/pause.svg", "Pause")
+            .hotkey(app.session.keys.pause[0])
+            .build_widget(ctx, "pause")
+            .container(),
+    )
+    // TODO Very brittle layout to wind up to the right of the volume panel...
+    .aligned(
+        HorizontalAlignment::Percent(0.05),
+        VerticalAlignment::BottomInset,
+    )
+    .build(ctx)
+}
+
+pub struct MinimapController;
+
+impl MinimapControls<App> for MinimapController {
+    fn has_zorder(&self, _: &App) -> bool {
+        false
+    }
+
+    fn make_legend(&self, ctx: &mut EventCtx, app: &App) -> Widget {
+        Widget::col(vec![
+            Widget::row(vec![
+                ColorLegend::row(ctx, app.session.colors.house, "house"),
+                ColorLegend::row(ctx, app.session.colors.apartment, "apartment"),
+                ColorLegend::row(ctx, app.session.colors.store, "store"),
+            ])
+            .evenly_spaced(),
+            // TODO If the player messes with the minimap, the panel gets recreated, and we'll
+            // clobber the boost bar. No easy way to plumb everything we need for
+            // update_boost_panel here. It's not super common to actually mess with those controls,
+            // so fine with this for now.
+            Widget::row(vec![
+                "Boost".text_widget(ctx),
+                GeomBatch::new()
+                    .into_widget(ctx)
+                    .named("boost")
+                    .align_right(),
+            ]),
+        ])
+    }
+}
@@ -0,0 +1,711 @@
+use std::collections::HashMap;
+
+use instant::Instant;
+
+use geom::{ArrowCap, Circle, Distance, Duration, PolyLine, Pt2D, Speed, Time};
+use map_gui::tools::Minimap;
+use map_model::{BuildingID, IntersectionID};
+use widgetry::{Color, EventCtx, GeomBatch, GfxCtx, Key, Outcome, Panel, State, Text, UpdateType};
+
+use self::hud::{make_pause_panel, MinimapController};
+use self::state::{GameState, SecondPlayer};
+use crate::achievements::Achievement;
+use crate::after_level::{AllOrNothingResults, RecordPath, Results, Strategize};
+use crate::animation::{Animator, Effect, SnowEffect};
+use crate::arcade::Ride;
+use crate::buildings::closest_intersection;
+use crate::bus_stops::BusStops;
+use crate::campaign::CampaignProgress;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::capture::Capture;
+use crate::console::{Command, Console};
+use crate::coverage::Coverage;
+use crate::difficulty::DifficultyPreset;
+use crate::elf::Elf;
+use crate::fog::Fog;
+use crate::ghost::Ghost;
+use crate::help::HelpOverlay;
+use crate::hints::{DemandHeatmap, MapEdge, PresentHint, RouteHint};
+use crate::kiosk::{IdleTimer, KioskRestart, KIOSK_IDLE_RESET};
+use crate::levels::WinCondition;
+use crate::mode::Mode;
+use crate::music::Sound;
+use crate::nearby::NearbyHouses;
+use crate::obstacles::Obstacles;
+use crate::pause::{Pausable, PauseMenu};
+use crate::perf::PerfOverlay;
+use crate::pickups::Pickups;
+use crate::race::Race;
+use crate::signals::Signals;
+use crate::stats::RunStats;
+use crate::ticker::Ticker;
+use crate::title::TitleScreen;
+use crate::touch::TouchControls;
+use crate::trail::Trail;
+use crate::tuning::Tuning;
+use crate::tutorial::Tutorial;
+use crate::windows::DeliveryWindows;
+use crate::zones::ZonesLayer;
+use crate::{App, Transition};
+
+mod deliveries;
+mod hud;
+mod play;
+mod setup;
+mod state;
+
+const NIGHT_COLOR: Color = Color::rgb_f(0.02, 0.05, 0.15);
+
+pub struct Game {
+    status_panel: Panel,
+    time_panel: Panel,
+    pause_panel: Panel,
+    minimap: Minimap<App, MinimapController>,
+
+    animator: Animator,
+    snow: SnowEffect,
+    obstacles: Obstacles,
+    pickups: Pickups,
+    signals: Signals,
+    bus_stops: BusStops,
+    touch: TouchControls,
+
+    state: GameState,
+    ride: Ride,
+    // Only in co-op mode
+    second_player: Option<SecondPlayer>,
+    // Only in race mode, if the server could be reached
+    race: Option<Race>,
+    elf: Option<Elf>,
+    tutorial: Option<Tutorial>,
+    // A building the mouse is over, to describe in a tooltip
+    hovering: Option<BuildingID>,
+    edge: MapEdge,
+    // Only in exploration mode
+    fog: Option<Fog>,
+    // Some houses only take deliveries early or late
+    windows: Option<DeliveryWindows>,
+    // Announces demand surges in endless mode
+    ticker: Ticker,
+    nearby: NearbyHouses,
+    trail: Trail,
+    // After a big delivery, time passes slower until then
+    slow_motion_until: Option<Time>,
+    // Hides everything but the map, while the photo mode is on top
+    photo_mode: bool,
+    // Only used in kiosk mode
+    kiosk_idle: IdleTimer,
+    // Only when playing a campaign
+    campaign: Option<CampaignProgress>,
+    // The border the player last tried to leave the map through
+    campaign_border: Option<IntersectionID>,
+    // Where the level started, to go back to
+    start: IntersectionID,
+    perf: PerfOverlay,
+    help: HelpOverlay,
+    // What the panels last showed, to skip rebuilding them when nothing visible changed
+    time_shown: (Option<(Duration, bool)>, Option<usize>),
+    bars_shown: HashMap<&'static str, (Color, usize, usize)>,
+}
+
+impl Game {
+    /// Afterwards, the camera goes back to following Santa
+    pub fn set_photo_mode(&mut self, ctx: &mut EventCtx, enabled: bool) {
+        self.photo_mode = enabled;
+        if !enabled {
+            self.ride.camera.apply(ctx);
+        }
+    }
+
+    fn has_time_limit(&self) -> bool {
+        // The tutorial has no high score to record, so it just keeps going
+        self.tutorial.is_none() && self.state.rules.mode.has_time_limit()
+    }
+
+    pub fn run_command(&mut self, ctx: &mut EventCtx, app: &App, cmd: Command) {
+        let msg = match cmd {
+            Command::Energy(amount) => {
+                let max = self.state.vehicle.max_energy;
+                self.state.rules.energy = amount.unwrap_or(max).min(max);
+                format!("Blood sugar is now {}", self.state.rules.energy)
+            }
+            Command::Teleport(b) => {
+                if app.map.maybe_get_b(b).is_some() {
+                    self.ride
+                        .player
+                        .teleport(app, closest_intersection(&app.map, b));
+                    self.ride.camera.jump();
+                    format!("Teleported to {}", b)
+                } else {
+                    format!("There's no {}", b)
+                }
+            }
+            Command::CompleteZone => match self.state.zones.closest(self.ride.player.get_pos()) {
+                Some(zone) => {
+                    let name = zone.name.clone();
+                    for b in zone.houses.clone() {
+                        self.state.elf_delivered(ctx, app, b);
+                    }
+                    format!("Delivered everything along {}", name)
+                }
+                None => "There aren't any zones".to_string(),
+            },
+            Command::SetSpeed(speed) => {
+                self.state.vehicle.speed = if app.opts.units.metric {
+                    Speed::km_per_hour(speed)
+                } else {
+                    Speed::miles_per_hour(speed)
+                };
+                format!(
+                    "Speed is now {}",
+                    self.state.vehicle.speed.to_string(&app.opts.units)
+                )
+            }
+        };
+        self.update_status_panel(ctx, app);
+        self.update_boost_panel(ctx, app);
+        self.flash_message(ctx, app, msg);
+    }
+}
+
+impl State<App> for Game {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        if self.state.game_over {
+            if let Some(dt) = ctx.input.nonblocking_is_update_event() {
+                app.time += dt;
+                self.animator.event(ctx, app.time);
+                self.snow.event(ctx, app.time);
+                self.ride
+                    .player
+                    .override_pos(self.ride.player.get_pos().project_away(
+                        dt * self.state.vehicle.speed,
+                        self.ride.player.get_angle().opposite(),
+                    ));
+            }
+
+            if self.animator.is_done() {
+                // A campaign is scored as a whole, not per map
+                let (score, level) = match self.campaign {
+                    Some(ref progress) => (
+                        progress.total_score(self.state.rules.score),
+                        progress.summary_level(&self.state.level),
+                    ),
+                    None => (self.state.rules.score, self.state.level.clone()),
+                };
+                if self.state.rules.mode == Mode::Normal
+                    && self.tutorial.is_none()
+                    && self.campaign.is_none()
+                    && self.state.difficulty == DifficultyPreset::Normal
+                {
+                    let mut ghost =
+                        std::mem::replace(&mut self.state.ghost, Ghost::new(String::new()));
+                    ghost.score = self.state.rules.score;
+                    app.session
+                        .record_ghost(self.state.level.title.clone(), ghost);
+                }
+                if self.tutorial.is_none() && !app.session.kiosk {
+                    self.state.replay.save();
+                }
+                // The tutorial just goes back to the title screen
+                let restart = if app.session.kiosk && self.tutorial.is_none() {
+                    Some(KioskRestart::new(
+                        self.state.level.clone(),
+                        self.state.vehicle.name.clone(),
+                        self.state.bldgs.upzones.clone(),
+                        self.state.rules.mode,
+                    ))
+                } else {
+                    None
+                };
+                let coverage = Coverage::new(app, &self.state.level, &self.state.bldgs);
+                return Transition::Multi(vec![
+                    Transition::Replace(Strategize::new_state(
+                        ctx,
+                        app,
+                        score,
+                        &level,
+                        &self.state.bldgs,
+                        std::mem::replace(&mut self.state.record_path, RecordPath::new()),
+                        std::mem::replace(&mut self.state.stats, RunStats::new()),
+                        coverage,
+                        self.state.difficulty,
+                        restart,
+                    )),
+                    Transition::Push(if level.script.win == WinCondition::EveryHouse {
+                        AllOrNothingResults::new_state(
+                            ctx,
+                            app,
+                            &level,
+                            self.state.houses_left(),
+                            app.time - Time::START_OF_DAY,
+                        )
+                    } else {
+                        Results::new_state(
+                            ctx,
+                            app,
+                            score,
+                            self.state.met_goal(),
+                            &level,
+                            self.state.rules.mode,
+                            self.state.seed,
+                            self.state.difficulty,
+                        )
+                    }),
+                ]);
+            }
+
+            ctx.request_update(UpdateType::Game);
+            return Transition::Keep;
+        }
+
+        if app.session.kiosk && self.kiosk_idle.event(ctx, KIOSK_IDLE_RESET) {
+            app.session.music.specify_volume(crate::music::OUT_OF_GAME);
+            return Transition::Clear(vec![TitleScreen::new_state(ctx, app)]);
+        }
+
+        // Time stops while the help is up
+        if self.help.event(ctx, app) {
+            app.session.update_music(ctx);
+            return Transition::Keep;
+        }
+
+        self.touch.event(ctx);
+        // The settings may have been changed from the pause menu
+        // Co-op players keep their split keyboard controls
+        if self.second_player.is_none() && self.ride.player.refresh_controls(app) {
+            self.pause_panel = make_pause_panel(ctx, app);
+        }
+
+        // Most things depend on time passing and don't care about other events
+        if let Some(dt) = ctx.input.nonblocking_is_update_event() {
+            let started = Instant::now();
+            let game_dt = if self
+                .slow_motion_until
+                .map(|t| app.time < t)
+                .unwrap_or(false)
+            {
+                dt * self.state.tuning.slow_motion_factor
+            } else {
+                dt
+            };
+            self.update(ctx, app, game_dt);
+            self.perf.record_update(dt, started);
+            if let Some(t) = self.check_campaign_exit(ctx, app) {
+                return t;
+            }
+
+            // Nothing's left to do, so stop the clock right away
+            if self.state.level.script.win == WinCondition::EveryHouse && self.state.met_goal() {
+                self.state.game_over = true;
+                self.animator.add(
+                    app.time,
+                    Duration::seconds(3.0),
+                    Effect::Scale {
+                        lerp_scale: (1.0, 4.0),
+                        center: self.ride.player.get_pos(),
+                        orig: Text::from(app.session.locale.tr("Every house delivered!"))
+                            .bg(app.session.colors.good)
+                            .render_autocropped(ctx)
+                            .scale(0.1),
+                    },
+                );
+                return Transition::Keep;
+            }
+
+            if self.has_time_limit() && app.time - Time::START_OF_DAY >= self.state.level.time_limit
+            {
+                self.state.game_over = true;
+                if self.state.rules.energy == 0 {
+                    self.unlock(ctx, app, Achievement::FinishWithNoEnergy);
+                }
+                self.animator.add(
+                    app.time,
+                    Duration::seconds(3.0),
+                    Effect::Scale {
+                        lerp_scale: (1.0, 4.0),
+                        center: self.ride.player.get_pos(),
+                        orig: Text::from(app.session.locale.tr("Time's up!"))
+                            .bg(Color::RED)
+                            .render_autocropped(ctx)
+                            .scale(0.1),
+                    },
+                );
+            }
+
+            if !self.state.warned_low_time
+                && self.has_time_limit()
+                && self.state.level.time_limit - (app.time - Time::START_OF_DAY)
+                    <= self.state.tuning.low_time_warning
+            {
+                self.state.warned_low_time = true;
+                self.animator.add(
+                    app.time,
+                    Duration::seconds(2.0),
+                    Effect::Flash {
+                        alpha_scale: (0.1, 0.5),
+                        cycles: 2,
+                        orig: GeomBatch::from(vec![(
+                            Color::RED,
+                            app.map.get_boundary_polygon().clone(),
+                        )]),
+                    },
+                );
+                self.animator.add_screenspace(
+                    app.time,
+                    Duration::seconds(2.0),
+                    Effect::Scale {
+                        lerp_scale: (1.0, 4.0),
+                        center: {
+                            let pt = ctx.canvas.center_to_screen_pt();
+                            Pt2D::new(pt.x, pt.y / 2.0)
+                        },
+                        orig: Text::from(app.session.locale.tr("Almost out of time!"))
+                            .bg(Color::RED)
+                            .render_autocropped(ctx),
+                    },
+                );
+            }
+
+            if !self.state.warned_low_energy
+                && self.state.rules.energy < self.state.tuning.low_energy_warning
+            {
+                self.state.warned_low_energy = true;
+                app.session.music.play_sound(Sound::LowEnergy);
+                self.animator.add(
+                    app.time,
+                    Duration::seconds(2.0),
+                    Effect::Flash {
+                        alpha_scale: (0.1, 0.5),
+                        cycles: 2,
+                        orig: GeomBatch::from(vec![(
+                            Color::RED,
+                            app.map.get_boundary_polygon().clone(),
+                        )]),
+                    },
+                );
+                self.animator.add_screenspace(
+                    app.time,
+                    Duration::seconds(2.0),
+                    Effect::Scale {
+                        lerp_scale: (1.0, 4.0),
+                        center: {
+                            let pt = ctx.canvas.center_to_screen_pt();
+                            Pt2D::new(pt.x, pt.y / 2.0)
+                        },
+                        orig: Text::from(app.session.locale.tr("Low on blood sugar, refill soon!"))
+                            .bg(Color::RED)
+                            .render_autocropped(ctx),
+                    },
+                );
+            }
+
+            ctx.request_update(UpdateType::Game);
+            return Transition::Keep;
+        }
+
+        if let Some(t) = self.minimap.event(ctx, app) {
+            return t;
+        }
+        if ctx.redo_mouseover() {
+            self.hovering = ctx
+                .canvas
+                .get_cursor_in_map_space()
+                .and_then(|pt| self.state.bldgs.index.building_at(&app.map, pt));
+        }
+
+        if let Some(ref mut tutorial) = self.tutorial {
+            if let Some(t) = tutorial.event(ctx, app) {
+                return t;
+            }
+        }
+
+        if app.session.keys.pressed(ctx, "switch present") {
+            self.state.selected_present = self.state.selected_present.next();
+            self.state.last_wrong_present = None;
+            self.update_presents_panel(ctx, app);
+        }
+
+        if app.session.keys.pressed(ctx, "snack") {
+            self.eat_snack(ctx, app);
+        }
+
+        if app.session.keys.pressed(ctx, "drop present") {
+            if let Some(b) = self.nearest_eligible(app) {
+                self.visit_buildings(ctx, app, vec![b]);
+            }
+        }
+
+        if app.session.keys.pressed(ctx, "pick nearby") {
+            if let Some(wanted) = self.nearby.pick_next(ctx, app, &self.state.bldgs) {
+                self.state.selected_present = wanted;
+                self.state.last_wrong_present = None;
+                self.update_presents_panel(ctx, app);
+            }
+        }
+
+        if app.session.keys.pressed(ctx, "restart") {
+            return Game::restart_level();
+        }
+        if app.session.keys.pressed(ctx, "go home") && self.tutorial.is_none() {
+            return self.confirm_go_home(ctx, app);
+        }
+
+        if ctx.input.pressed(Key::F3) {
+            self.perf.toggle();
+        }
+        // Frames are saved as local files
+        #[cfg(not(target_arch = "wasm32"))]
+        if ctx.input.pressed(Key::F9) {
+            return Transition::Push(Capture::new_state(ctx, app, self.state.replay.clone()));
+        }
+
+        if app.opts.dev && ctx.input.pressed(Key::F5) {
+            self.state.set_tuning(Tuning::load(&self.state.level.title));
+            self.update_status_panel(ctx, app);
+            self.update_boost_panel(ctx, app);
+            self.flash_message(ctx, app, "Reloaded tuning".to_string());
+        }
+        if app.opts.dev && ctx.input.pressed(Key::Backtick) {
+            return Transition::Push(Console::new_state(ctx));
+        }
+
+        match self.status_panel.event(ctx) {
+            Outcome::Clicked(x) => match x.as_ref() {
+                "plan a route" => {
+                    self.state.route_hint = Some(RouteHint::new(
+                        ctx,
+                        app,
+                        &self.state.bldgs,
+                        self.ride.player.current_roads(app),
+                        self.ride.player.get_pos(),
+                    ));
+                }
+                _ => unreachable!(),
+            },
+            Outcome::Changed(x) => match x.as_ref() {
+                "point to presents" => {
+                    self.state.present_hint = if self.status_panel.is_checked("point to presents") {
+                        Some(PresentHint::new(ctx))
+                    } else {
+                        None
+                    };
+                }
+                "show where presents are needed" => {
+                    self.state.demand_heatmap = if self
+                        .status_panel
+                        .is_checked("show where presents are needed")
+                    {
+                        Some(DemandHeatmap::new(ctx))
+                    } else {
+                        None
+                    };
+                }
+                "show delivery zones" => {
+                    self.state.zones_layer = if self.status_panel.is_checked("show delivery zones")
+                    {
+                        Some(ZonesLayer::new(ctx))
+                    } else {
+                        None
+                    };
+                }
+                _ => unreachable!(),
+            },
+            _ => {}
+        }
+
+        if let Outcome::Clicked(x) = self.pause_panel.event(ctx) {
+            match x.as_ref() {
+                "pause" => {
+                    return Transition::Push(PauseMenu::new_state::<Game>(ctx, app));
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        if let Some((_, dy)) = ctx.input.get_mouse_scroll() {
+            if self.second_player.is_some() {
+                self.frame_players(ctx);
+            } else {
+                self.ride.camera.scroll_zoom(ctx, app, dy);
+            }
+        }
+
+        app.session.update_music(ctx);
+
+        ctx.request_update(UpdateType::Game);
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        let started = Instant::now();
+        if !self.photo_mode {
+            self.status_panel.draw(g);
+            if self.state.rules.mode != Mode::Zen {
+                self.time_panel.draw(g);
+            }
+            self.pause_panel.draw(g);
+            app.session.music.draw(g);
+            if let Some(ref tutorial) = self.tutorial {
+                tutorial.draw(g);
+            }
+
+            let santa_tracker = g.upload(GeomBatch::from(vec![(
+                Color::RED,
+                Circle::new(self.ride.player.get_pos(), Distance::meters(20.0)).to_polygon(),
+            )]));
+            let mut layers = vec![
+                &self.state.bldgs.draw_all,
+                &self.state.bldgs.draw_minimap_dots,
+            ];
+            layers.extend(self.state.done_houses.minimap_layers());
+            layers.push(&santa_tracker);
+            self.minimap.draw_with_extra_layers(g, app, layers);
+        }
+
+        g.redraw(&self.state.bldgs.draw_all);
+        if app.session.show_score_labels {
+            self.state.bldgs.labels.draw(g);
+        }
+        // Night falls over the level. Delivered houses are drawn on top, so they stay lit up.
+        g.draw_polygon(
+            NIGHT_COLOR.alpha(self.state.darkness(app)),
+            app.map.get_boundary_polygon().clone(),
+        );
+        if let Some(ref windows) = self.windows {
+            g.redraw(&windows.draw);
+        }
+        if let Some(ref fog) = self.fog {
+            fog.draw(g);
+        }
+        if let Some(ref heatmap) = self.state.demand_heatmap {
+            g.redraw(&heatmap.draw);
+        }
+        if let Some(ref layer) = self.state.zones_layer {
+            g.redraw(&layer.draw);
+        }
+        self.state.done_houses.draw(g);
+        if app.session.show_nearby_houses {
+            self.nearby.draw(
+                g,
+                self.ride.player.get_pos(),
+                self.state.vehicle.delivery_radius,
+            );
+        }
+        if let Some(ref route) = self.state.route_hint {
+            g.redraw(&route.draw);
+        }
+
+        if app.session.show_trail {
+            self.trail.draw(g);
+        }
+        if let Some(ref ghost) = self.state.best_ghost {
+            ghost.draw(g, app);
+        }
+        if true {
+            self.state
+                .vehicle
+                .draw_at(g, app.time - self.state.idle_time, &self.ride.player);
+        } else {
+            // Debug
+            g.draw_polygon(
+                Color::RED,
+                Circle::new(self.ride.player.get_pos(), Distance::meters(2.0)).to_polygon(),
+            );
+        }
+
+        if let Some(ref race) = self.race {
+            race.draw(g, &self.state.vehicle, app.time);
+        }
+        if let Some(ref elf) = self.elf {
+            elf.draw(g, app);
+        }
+        if let Some(ref second) = self.second_player {
+            let pos = second.player.get_pos();
+            self.state
+                .vehicle
+                .draw_at(g, app.time - self.state.idle_time, &second.player);
+            // Tell the players apart
+            if let Ok(ring) =
+                Circle::new(pos, Distance::meters(5.0)).to_outline(Distance::meters(0.5))
+            {
+                g.draw_polygon(Color::CYAN, ring);
+            }
+        }
+
+        if let Some(heading) = self.ride.player.heading_indicator() {
+            let pos = self.ride.player.get_pos();
+            if let Ok(pl) = PolyLine::new(vec![
+                pos.project_away(Distance::meters(5.0), heading),
+                pos.project_away(Distance::meters(12.0), heading),
+            ]) {
+                g.draw_polygon(
+                    Color::WHITE.alpha(0.8),
+                    pl.make_arrow(Distance::meters(1.5), ArrowCap::Triangle),
+                );
+            }
+        }
+
+        self.bus_stops.draw(g);
+        self.signals.draw(g);
+        self.pickups.draw(g);
+        self.obstacles.draw(g);
+        self.snow.draw(g);
+        self.animator.draw(g);
+        if self.photo_mode {
+            return;
+        }
+        self.touch.draw(g);
+        if let Some(ref arrow) = self.state.energyless_arrow {
+            g.redraw(&arrow.draw);
+        }
+        if let Some(ref hint) = self.state.present_hint {
+            g.redraw(&hint.draw);
+        }
+        if self.edge.near {
+            g.redraw(&self.edge.draw);
+        }
+        self.ticker.draw(g, app.time);
+
+        if let Some(b) = self.hovering {
+            g.draw_mouse_tooltip(self.describe_bldg(app, b));
+        }
+        self.help.draw(g);
+        self.perf.draw(g, started);
+    }
+
+    fn on_destroy(&mut self, _: &mut EventCtx, app: &mut App) {
+        app.session.music.specify_volume(crate::music::OUT_OF_GAME);
+    }
+}
+
+impl Pausable for Game {
+    /// Keeps the run to resume later, then goes back to the title screen. The game must be
+    /// underneath whatever called this, right on top of the screen that started it.
+    fn quit_to_title() -> Transition {
+        Transition::Multi(vec![
+            Transition::ConsumeState(Box::new(|state, _, app| {
+                let game = state.downcast::<Game>().ok().unwrap();
+                if let Some(run) = game.saved_run(app) {
+                    run.save();
+                }
+                Vec::new()
+            })),
+            // Build the title screen after saving, so it offers to resume
+            Transition::ConsumeState(Box::new(|_, ctx, app| {
+                vec![crate::title::TitleScreen::new_state(ctx, app)]
+            })),
+        ])
+    }
+
+    /// Starts the level over, from the game itself or the pause menu on top of it
+    fn restart_level() -> Transition {
+        Transition::ConsumeState(Box::new(|state, ctx, app| {
+            let game = state.downcast::<Game>().ok().unwrap();
+            vec![game.restart(ctx, app)]
+        }))
+    }
+
+    fn has_photo_mode() -> bool {
+        true
+    }
+}
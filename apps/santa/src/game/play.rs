@@ -0,0 +1,715 @@
+use abstutil::prettyprint_usize;
+use geom::{Angle, Distance, Duration, Pt2D, Speed, Time};
+use widgetry::{Color, EventCtx, Line, Text};
+
+use crate::achievements::Achievement;
+use crate::animation::Effect;
+use crate::arcade::Ride;
+use crate::controls::SleighState;
+use crate::game::state::{GameState, SecondPlayer};
+use crate::game::Game;
+use crate::hints::EnergylessArrow;
+use crate::levels::ScriptedEvent;
+use crate::meters::hud_text;
+use crate::mode::Mode;
+use crate::music::Sound;
+use crate::pickups::PickupType;
+use crate::player::Player;
+use crate::race::Status;
+use crate::stats_feed::Update;
+use crate::trail::{TRAIL_EVERY, TRAIL_LENGTH};
+use crate::tutorial::Progress;
+use crate::windows::DeliveryWindows;
+use crate::App;
+
+const ENERGY_FLASH_PERIOD: Duration = Duration::const_seconds(0.6);
+// In co-op, zoom in as far as this while still fitting both players on screen
+const CO_OP_MAX_ZOOM: f64 = 10.0;
+// Extra space around both players, in meters
+const CO_OP_FRAMING_MARGIN: f64 = 100.0;
+
+impl Game {
+    /// The second player only delivers and refills. Power-ups, signals, and obstacles just affect
+    /// the first.
+    pub fn update_second_player(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &mut App,
+        second: &mut SecondPlayer,
+        wind: (f64, f64),
+    ) {
+        let top_speed =
+            self.state
+                .vehicle
+                .speed_at(app, &second.player, self.state.tuning.grade_speed_factor);
+        let speed = if second.energy > 0 {
+            top_speed
+        } else {
+            top_speed * self.state.tuning.hangry_speed_multiplier
+        };
+        let sleigh = sleigh_state(
+            app,
+            &self.state,
+            self.windows.as_ref(),
+            &second.player,
+            second.energy,
+        );
+        let buildings = second
+            .player
+            .update_with_speed(ctx, app, &sleigh, None, speed, wind);
+
+        // Deliveries and refills work the same way, so temporarily swap in the second player
+        std::mem::swap(&mut self.ride.player, &mut second.player);
+        std::mem::swap(&mut self.state.rules.energy, &mut second.energy);
+        let buildings = self.buildings_reached(app, buildings);
+        self.visit_buildings(ctx, app, buildings);
+        std::mem::swap(&mut self.ride.player, &mut second.player);
+        std::mem::swap(&mut self.state.rules.energy, &mut second.energy);
+    }
+
+    pub fn update_race(&mut self, ctx: &mut EventCtx, app: &App) {
+        let race = if let Some(ref mut race) = self.race {
+            race
+        } else {
+            return;
+        };
+        race.sync_position(
+            app.time,
+            self.ride.player.get_pos(),
+            self.ride.player.get_angle(),
+        );
+        let claimed = race.receive();
+        let status = race.status_change();
+        let opponent_score = race.opponent_score;
+        for b in &claimed {
+            self.state.mark_delivered(ctx, app, *b);
+        }
+
+        let locale = app.session.locale;
+        let label = match status {
+            None if claimed.is_empty() => {
+                return;
+            }
+            Some(Status::Connecting) => Line(locale.tr("Connecting to the race server...")),
+            Some(Status::Failed) => {
+                Line(locale.tr("Couldn't reach the race server")).fg(Color::RED)
+            }
+            Some(Status::Lost) => Line(locale.tr("Lost the race connection")).fg(Color::RED),
+            Some(Status::Connected) | None => Line(locale.tr_args(
+                "Opponent delivered {} presents",
+                &[prettyprint_usize(opponent_score)],
+            )),
+        };
+        let label = hud_text(ctx, app, label);
+        self.status_panel.replace(ctx, "opponent", label);
+    }
+
+    pub fn update_stats_feed(&mut self, app: &mut App) {
+        let state = &self.state;
+        let pos = self.ride.player.get_pos();
+        let seconds_left = if self.has_time_limit() {
+            Some(
+                (state.level.time_limit - (app.time - Time::START_OF_DAY))
+                    .inner_seconds()
+                    .max(0.0),
+            )
+        } else {
+            None
+        };
+        let map = &app.map;
+        app.session
+            .stats_feed
+            .sync_status(app.time, || Update::Status {
+                level: state.level.title.clone(),
+                score: state.rules.score,
+                goal: state.level.goal,
+                energy: state.rules.energy,
+                max_energy: state.vehicle.max_energy,
+                seconds_left,
+                position: pos.to_gps(map.get_gps_bounds()),
+            });
+    }
+
+    pub fn update_elf(&mut self, ctx: &mut EventCtx, app: &App, dt: Duration) {
+        let elf = if let Some(ref mut elf) = self.elf {
+            elf
+        } else {
+            return;
+        };
+        if let Some(b) = elf.update(
+            app,
+            dt,
+            &self.state.bldgs,
+            self.ride.player.current_roads(app),
+        ) {
+            if let Some(increase) = self.state.elf_delivered(ctx, app, b) {
+                if let Some(ref mut race) = self.race {
+                    race.claim(b, increase);
+                }
+                self.animator.add(
+                    app.time,
+                    Duration::seconds(1.0),
+                    Effect::Float {
+                        start: app.map.get_b(b).label_center,
+                        rise: Distance::meters(10.0),
+                        orig: Text::from(format!("+{}", prettyprint_usize(increase)))
+                            .bg(app.session.colors.score)
+                            .render_autocropped(ctx)
+                            .scale(0.2),
+                    },
+                );
+            }
+        }
+        if elf.upgrade(self.state.rules.score) {
+            self.animator.add(
+                app.time,
+                Duration::seconds(1.5),
+                Effect::Scale {
+                    lerp_scale: (1.0, 2.0),
+                    center: self.ride.player.get_pos(),
+                    orig: Text::from(app.session.locale.tr_args(
+                        "Your elf is now level {} -- faster, and helps with bigger houses",
+                        &[elf.level().to_string()],
+                    ))
+                    .bg(app.session.colors.score)
+                    .render_autocropped(ctx)
+                    .scale(0.1),
+                },
+            );
+        }
+    }
+
+    /// Keep both co-op players on screen, zooming out as they get further apart
+    pub fn frame_players(&self, ctx: &mut EventCtx) {
+        if let Some(ref second) = self.second_player {
+            let (pt1, pt2) = (self.ride.player.get_pos(), second.player.get_pos());
+            let zoom = (ctx.canvas.window_width
+                / ((pt1.x() - pt2.x()).abs() + CO_OP_FRAMING_MARGIN))
+                .min(ctx.canvas.window_height / ((pt1.y() - pt2.y()).abs() + CO_OP_FRAMING_MARGIN))
+                .min(CO_OP_MAX_ZOOM)
+                .max(ctx.canvas.settings.min_zoom_for_detail);
+            ctx.canvas.cam_zoom = zoom;
+            ctx.canvas.center_on_map_pt(Pt2D::new(
+                (pt1.x() + pt2.x()) / 2.0,
+                (pt1.y() + pt2.y()) / 2.0,
+            ));
+        }
+    }
+
+    // Custom levels can script things to happen partway through
+    pub fn run_scripted_events(&mut self, ctx: &mut EventCtx, app: &App) {
+        let elapsed = app.time - Time::START_OF_DAY;
+        loop {
+            let event = match self.state.level.script.events.get(self.state.next_event) {
+                Some((t, event)) if *t <= elapsed => event.clone(),
+                _ => {
+                    return;
+                }
+            };
+            self.state.next_event += 1;
+            match event {
+                ScriptedEvent::Message(msg) => {
+                    self.flash_message(ctx, app, msg);
+                }
+                ScriptedEvent::WindShift(degrees) => {
+                    self.state.wind.shift(app.time, Angle::degrees(degrees));
+                }
+                ScriptedEvent::DoubleScore(duration) => {
+                    self.state.rules.double_score_until = Some(app.time + duration);
+                }
+            }
+        }
+    }
+
+    pub fn update(&mut self, ctx: &mut EventCtx, app: &mut App, dt: Duration) {
+        Ride::tick(app, dt);
+        let boost_held =
+            app.session.keys.is_down(ctx, "boost") || app.session.gamepad.boost || self.touch.boost;
+        let analog = self.touch.stick.or(app.session.gamepad.stick);
+
+        let orig_boost = (self.state.boost, self.state.boost_cooldown);
+        let orig_combo = self.state.rules.combo;
+        let (orig_score, orig_energy) = (self.state.rules.score, self.state.rules.energy);
+        let orig_second_energy = self.second_player.as_ref().map(|second| second.energy);
+        let orig_pos = self.ride.player.get_pos();
+
+        self.update_time_panel(ctx, app);
+
+        let grade = self.ride.player.current_grade(app);
+        let top_speed = self.state.vehicle.speed_at(
+            app,
+            &self.ride.player,
+            self.state.tuning.grade_speed_factor,
+        );
+        let base_speed = if self.state.rules.has_energy() {
+            top_speed
+        } else {
+            top_speed * self.state.tuning.hangry_speed_multiplier
+        };
+        let base_speed = if self
+            .state
+            .rules
+            .power_up_active(app.time, self.state.rules.speed_until)
+        {
+            base_speed * self.state.tuning.speed_pickup_multiplier
+        } else {
+            base_speed
+        };
+        let grade_pct = (grade * 100.0).round() as isize;
+        if grade_pct != self.state.grade_pct {
+            self.state.grade_pct = grade_pct;
+            let txt = if grade_pct > 0 {
+                Text::from(
+                    Line(
+                        app.session
+                            .locale
+                            .tr_args("Uphill: {}% grade", &[grade_pct.to_string()]),
+                    )
+                    .fg(app.session.colors.bad),
+                )
+            } else if grade_pct < 0 {
+                Text::from(
+                    Line(
+                        app.session
+                            .locale
+                            .tr_args("Downhill: {}% grade", &[(-grade_pct).to_string()]),
+                    )
+                    .fg(app.session.colors.good),
+                )
+            } else {
+                Text::new()
+            };
+            self.status_panel
+                .replace(ctx, "grade", hud_text(ctx, app, txt));
+        }
+        if self.state.boost_cooldown > Duration::ZERO {
+            self.state.boost_cooldown = (self.state.boost_cooldown - dt).max(Duration::ZERO);
+        }
+        let boosting = boost_held
+            && self.state.boost > Duration::ZERO
+            && self.state.boost_cooldown == Duration::ZERO;
+        if boosting && !self.state.boosting {
+            app.session.music.play_sound(Sound::Boost);
+        }
+        self.state.boosting = boosting;
+        let exerting = boosting && !self.ride.player.on_good_road(app);
+        let speed = if boosting {
+            if exerting {
+                self.state.boost -= dt;
+                self.state.boost = self.state.boost.max(Duration::ZERO);
+                if self.state.boost == Duration::ZERO {
+                    self.state.boost_cooldown = self.state.tuning.boost_cooldown;
+                }
+            }
+            base_speed * self.state.tuning.boost_speed_multiplier
+        } else {
+            base_speed
+        };
+
+        let met_goal = self.state.met_goal();
+        self.run_scripted_events(ctx, app);
+        let orig_wind = self.state.wind.describe();
+        self.state.wind.update(app.time, dt);
+        if self.state.wind.describe() != orig_wind {
+            self.update_wind_panel(ctx);
+        }
+        let wind = self
+            .state
+            .wind
+            .displacement(dt, self.state.tuning.max_wind_speed);
+
+        // After running into something, the player can't move for a moment
+        let stunned = self
+            .state
+            .last_hit
+            .map(|t| app.time - t < self.state.tuning.stun_duration)
+            .unwrap_or(false);
+        let buildings_passed = if stunned {
+            Vec::new()
+        } else {
+            let sleigh = sleigh_state(
+                app,
+                &self.state,
+                self.windows.as_ref(),
+                &self.ride.player,
+                self.state.rules.energy,
+            );
+            self.ride.steer(ctx, app, &sleigh, analog, speed, wind)
+        };
+        if exerting {
+            // Mostly the ground actually covered burns energy, plus a little just for holding it
+            self.state.boost_exertion += self.ride.player.get_pos().dist_to(orig_pos)
+                + self.state.tuning.boost_distance_per_energy
+                    * (self.state.tuning.boost_energy_per_second * dt.inner_seconds());
+            if self.state.boost_exertion >= self.state.tuning.boost_distance_per_energy {
+                self.state.boost_exertion =
+                    self.state.boost_exertion - self.state.tuning.boost_distance_per_energy;
+                self.state.rules.drain_energy(1);
+            }
+        }
+        if let Some(wanted) = self.ride.player.choose_present() {
+            if wanted != self.state.selected_present {
+                self.state.selected_present = wanted;
+                self.state.last_wrong_present = None;
+                self.update_presents_panel(ctx, app);
+            }
+        }
+        let buildings_reached = self.buildings_reached(app, buildings_passed);
+        self.visit_buildings(ctx, app, buildings_reached);
+        self.update_race(ctx, app);
+        self.update_stats_feed(app);
+        self.update_elf(ctx, app, dt);
+        if let Some(mut second) = self.second_player.take() {
+            self.update_second_player(ctx, app, &mut second, wind);
+            self.second_player = Some(second);
+            self.frame_players(ctx);
+        } else {
+            self.ride
+                .follow(ctx, app, dt, &self.state.vehicle, self.hovering.is_some());
+        }
+        if self.state.rules.combo > 0
+            && self
+                .state
+                .rules
+                .last_delivery
+                .map(|t| app.time - t > self.state.tuning.combo_window)
+                .unwrap_or(true)
+        {
+            self.state.rules.combo = 0;
+        }
+        if self.state.rules.combo != orig_combo {
+            self.update_combo_panel(ctx, app);
+        }
+        if self.state.rules.mode == Mode::Zen {
+            if self.state.rules.score != orig_score && self.state.houses_left() == 0 {
+                self.flash_message(
+                    ctx,
+                    app,
+                    app.session.locale.tr("Every house delivered!").to_string(),
+                );
+            }
+        } else if !met_goal && self.state.met_goal() {
+            // TODO What should we say here? Should we add some kind of animation to call this
+            // out?
+            let msg = if self
+                .campaign
+                .as_ref()
+                .map(|c| c.has_next_leg())
+                .unwrap_or(false)
+            {
+                "Goal met! Ride off the map to continue"
+            } else {
+                "Goal met! Keep going"
+            };
+            let label = hud_text(ctx, app, app.session.locale.tr(msg));
+            self.status_panel.replace(ctx, "score label", label);
+        }
+
+        if self.ride.player.on_good_road(app) && !boost_held {
+            self.state.boost += dt * self.state.tuning.acquire_boost_rate;
+            self.state.boost = self.state.boost.min(self.state.tuning.max_boost);
+        }
+
+        for pickup in self.pickups.collect(ctx, app, self.ride.player.get_pos()) {
+            match pickup {
+                PickupType::Energy => {
+                    self.state.rules.energy = (self.state.rules.energy
+                        + self.state.tuning.pickup_energy)
+                        .min(self.state.vehicle.max_energy);
+                }
+                PickupType::DoubleScore => {
+                    self.state.rules.double_score_until =
+                        Some(app.time + self.state.tuning.power_up_duration);
+                }
+                PickupType::Speed => {
+                    self.state.rules.speed_until =
+                        Some(app.time + self.state.tuning.power_up_duration);
+                }
+            }
+            app.session.music.play_sound(Sound::Recharge);
+            self.animator.add(
+                app.time,
+                Duration::seconds(1.0),
+                Effect::Float {
+                    start: self.ride.player.get_pos(),
+                    rise: Distance::meters(15.0),
+                    orig: Text::from(Line(pickup.describe()).fg(Color::BLACK))
+                        .bg(Color::WHITE)
+                        .render_autocropped(ctx)
+                        .scale(0.2),
+                },
+            );
+        }
+        self.update_power_ups_panel(ctx, app);
+
+        if self.state.rules.energy < self.state.vehicle.max_energy {
+            let trickle = self.bus_stops.recharge(
+                self.ride.player.get_pos(),
+                dt,
+                self.state.tuning.bus_stop_recharge_rate,
+            );
+            self.state.rules.energy =
+                (self.state.rules.energy + trickle).min(self.state.vehicle.max_energy);
+        }
+
+        match self.ride.player.current_intersection() {
+            Some(i) => {
+                if self.state.last_intersection != Some(i) {
+                    self.state.last_intersection = Some(i);
+                    if let Some(from) = self.state.last_road {
+                        if self
+                            .signals
+                            .is_red(app, self.state.tuning.signal_cycle, i, from)
+                        {
+                            self.state
+                                .rules
+                                .drain_energy(self.state.tuning.red_light_energy_cost);
+                            self.animator.add(
+                                app.time,
+                                Duration::seconds(1.0),
+                                Effect::Float {
+                                    start: self.ride.player.get_pos(),
+                                    rise: Distance::meters(15.0),
+                                    orig: Text::from(
+                                        Line(app.session.locale.tr("Ran a red light!"))
+                                            .fg(Color::WHITE),
+                                    )
+                                    .bg(Color::RED)
+                                    .render_autocropped(ctx)
+                                    .scale(0.2),
+                                },
+                            );
+                        }
+                    }
+                }
+            }
+            None => {
+                self.state.last_intersection = None;
+                self.state.last_road = self.ride.player.current_roads(app).pop();
+            }
+        }
+        self.signals
+            .update(ctx, app, self.state.tuning.signal_cycle);
+
+        if self
+            .obstacles
+            .update(ctx, app, dt, self.ride.player.get_pos())
+        {
+            // Don't get hit again by the same thing right away
+            let immune = self
+                .state
+                .last_hit
+                .map(|t| app.time - t < self.state.tuning.stun_duration * 2.0)
+                .unwrap_or(false);
+            if !immune {
+                self.state.last_hit = Some(app.time);
+                self.ride.camera.shake(app);
+                self.state
+                    .rules
+                    .drain_energy(self.state.tuning.collision_energy_cost);
+                self.animator.add(
+                    app.time,
+                    Duration::seconds(0.5),
+                    Effect::Scale {
+                        lerp_scale: (1.0, 2.0),
+                        center: self.ride.player.get_pos(),
+                        orig: Text::from(Line(app.session.locale.tr("Bonk!")).fg(Color::BLACK))
+                            .bg(Color::RED)
+                            .render_autocropped(ctx)
+                            .scale(0.15),
+                    },
+                );
+            }
+        }
+
+        self.animator.event(ctx, app.time);
+        self.snow.event(ctx, app.time);
+        // Point to the closest store before running out completely, so there's a chance to recover
+        if self.state.rules.energy < self.state.tuning.low_energy_warning
+            && !self.state.level.script.no_stores
+        {
+            if self.state.energyless_arrow.is_none() {
+                self.state.energyless_arrow = Some(EnergylessArrow::new(
+                    ctx,
+                    app.time,
+                    self.state.bldgs.all_stores(),
+                ));
+            }
+            self.state.energyless_arrow.as_mut().unwrap().update(
+                ctx,
+                app,
+                self.ride.player.get_pos(),
+            );
+        } else {
+            self.state.energyless_arrow = None;
+        }
+        if self.state.rules.mode != Mode::Zen
+            && self.state.rules.has_energy() == self.state.hangry_shown
+        {
+            self.state.hangry_shown = !self.state.rules.has_energy();
+            let label = if self.state.hangry_shown {
+                hud_text(
+                    ctx,
+                    app,
+                    Line(
+                        app.session
+                            .locale
+                            .tr("SANTA'S HANGRY - grab some cookies from a store!"),
+                    )
+                    .fg(Color::RED),
+                )
+            } else {
+                hud_text(ctx, app, app.session.locale.tr("Blood sugar"))
+            };
+            self.status_panel.replace(ctx, "energy label", label);
+        }
+        if let Some(ref mut hint) = self.state.present_hint {
+            hint.update(ctx, app, &self.state.bldgs, self.ride.player.get_pos());
+        }
+        self.nearby
+            .update(ctx, app, &self.state.bldgs, &self.ride.player, |wanted| {
+                self.state.can_deliver(wanted)
+            });
+        let mut positions = vec![self.ride.player.get_pos()];
+        if let Some(ref second) = self.second_player {
+            positions.push(second.player.get_pos());
+        }
+        self.edge.update(&positions);
+        if let Some(ref mut fog) = self.fog {
+            for pos in positions {
+                fog.update(ctx, &mut self.state.bldgs, pos);
+            }
+        }
+        let mut regrew = self.state.regrow(ctx, app);
+        if self.state.rules.mode == Mode::Endless {
+            self.update_delivery_rate_panel(ctx, app);
+            if let Some(headline) = self.state.demand_surge(ctx, app) {
+                self.ticker.announce(headline);
+                regrew = true;
+            }
+        }
+        self.ticker.event(ctx, app.time);
+        if let Some(ref mut heatmap) = self.state.demand_heatmap {
+            if self.state.rules.score != orig_score || regrew {
+                heatmap.dirty = true;
+            }
+            heatmap.update(ctx, app, &self.state.bldgs);
+        }
+        if let Some(ref mut layer) = self.state.zones_layer {
+            if self.state.rules.score != orig_score || regrew {
+                layer.dirty = true;
+            }
+            layer.update(ctx, app, &self.state.zones);
+        }
+        let pct_elapsed = self.state.rules.pct_elapsed(app.time);
+        if let Some(ref mut windows) = self.windows {
+            if self.state.rules.score != orig_score {
+                windows.dirty = true;
+            }
+            windows.update(ctx, app, &self.state.bldgs, pct_elapsed);
+        }
+        if self
+            .state
+            .route_hint
+            .as_ref()
+            .map(|route| route.finished(&self.state.bldgs))
+            .unwrap_or(false)
+        {
+            self.state.route_hint = None;
+        }
+
+        if self.state.rules.score != orig_score {
+            self.check_splits(ctx, app);
+            if self.state.rules.houses_delivered >= 100 {
+                self.unlock(ctx, app, Achievement::Deliver100Houses);
+            }
+            // The combo counts deliveries after the first
+            if self.state.rules.combo + 1 >= 10 {
+                self.unlock(ctx, app, Achievement::Combo10);
+            }
+            if self.state.met_goal() {
+                self.unlock(ctx, app, Achievement::MeetGoal);
+            }
+        }
+        if (self.state.boost, self.state.boost_cooldown) != orig_boost {
+            self.update_boost_panel(ctx, app);
+        }
+        let energy_flash_on = self.state.rules.energy < self.state.tuning.low_energy_warning
+            && (app.time - Time::START_OF_DAY) % ENERGY_FLASH_PERIOD < ENERGY_FLASH_PERIOD / 2.0;
+        let flash_changed = energy_flash_on != self.state.energy_flash_on;
+        self.state.energy_flash_on = energy_flash_on;
+        if self.state.rules.score != orig_score
+            || self.state.rules.energy != orig_energy
+            || self.second_player.as_ref().map(|second| second.energy) != orig_second_energy
+            || flash_changed
+        {
+            self.update_status_panel(ctx, app);
+        }
+        if self.ride.player.get_pos() == orig_pos {
+            self.state.idle_time += dt;
+        } else {
+            // Holding down a key to ride somewhere doesn't count as new input
+            self.kiosk_idle.reset();
+        }
+
+        if let Some(ref mut tutorial) = self.tutorial {
+            let progress = Progress {
+                dt,
+                moved: self.ride.player.get_pos() != orig_pos,
+                delivered: self.state.rules.score > orig_score,
+                refilled: self.state.rules.energy > orig_energy,
+                energy: self.state.rules.energy,
+            };
+            if let Some(energy) = tutorial.update(ctx, app, progress) {
+                self.state.rules.energy = energy;
+                self.update_status_panel(ctx, app);
+            }
+        }
+
+        self.state.record_path.add_pt(self.ride.player.get_pos());
+        self.state.stats.record(
+            app.time,
+            self.ride.player.get_pos(),
+            self.state.rules.score,
+            self.state.rules.energy,
+        );
+        self.state.ghost.record(
+            app.time,
+            self.ride.player.get_pos(),
+            self.ride.player.get_angle(),
+        );
+        if app.session.show_trail {
+            self.trail.update(
+                ctx,
+                self.state
+                    .ghost
+                    .recent_points(app.time, TRAIL_LENGTH, TRAIL_EVERY),
+            );
+        }
+        self.state.replay.record(
+            app.time,
+            self.ride.player.get_pos(),
+            self.ride.player.get_angle(),
+        );
+    }
+}
+
+// What the controllers get to see
+fn sleigh_state<'a>(
+    app: &App,
+    state: &'a GameState,
+    windows: Option<&'a DeliveryWindows>,
+    player: &Player,
+    energy: usize,
+) -> SleighState<'a> {
+    SleighState {
+        pos: player.get_pos(),
+        roads: player.current_roads(app),
+        bldgs: &state.bldgs,
+        presents: &state.rules.presents,
+        energy,
+        low_energy_warning: state.tuning.low_energy_warning,
+        windows,
+        pct_elapsed: ((app.time - Time::START_OF_DAY) / state.level.time_limit).min(1.0),
+    }
+}
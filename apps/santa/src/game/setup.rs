@@ -0,0 +1,592 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
+use geom::{Pt2D, Time};
+use map_gui::tools::Minimap;
+use map_model::{BuildingID, BuildingType};
+use widgetry::tools::PopupMsg;
+use widgetry::{
+    Color, EventCtx, GeomBatch, HorizontalAlignment, Image, Key, Line, Panel, State, Text, Toggle,
+    VerticalAlignment, Widget,
+};
+
+use crate::animation::{Animator, SnowEffect};
+use crate::arcade::Ride;
+use crate::buildings::{BldgState, Buildings};
+use crate::bus_stops::BusStops;
+use crate::campaign::{CampaignProgress, NextLeg};
+use crate::difficulty::DifficultyPreset;
+use crate::elf::Elf;
+use crate::fog::Fog;
+use crate::game::hud::{make_pause_panel, MinimapController};
+use crate::game::state::{GameState, SecondPlayer};
+use crate::game::Game;
+use crate::help::HelpOverlay;
+use crate::hints::MapEdge;
+use crate::kiosk::IdleTimer;
+use crate::levels::Level;
+use crate::meters::hud_text;
+use crate::mode::Mode;
+use crate::nearby::NearbyHouses;
+use crate::obstacles::Obstacles;
+use crate::perf::PerfOverlay;
+use crate::pickups::Pickups;
+use crate::player::Player;
+use crate::race::Race;
+use crate::saved_run::{self, SavedRun};
+use crate::signals::Signals;
+use crate::ticker::Ticker;
+use crate::touch::TouchControls;
+use crate::trail::Trail;
+use crate::tutorial::Tutorial;
+use crate::vehicles::Vehicle;
+use crate::windows::{self, DeliveryWindows};
+use crate::{App, Transition};
+
+impl Game {
+    pub fn new_state(
+        ctx: &mut EventCtx,
+        app: &mut App,
+        level: Level,
+        vehicle: Vehicle,
+        upzones: HashSet<BuildingID>,
+        mode: Mode,
+        tutorial: Option<Tutorial>,
+    ) -> Box<dyn State<App>> {
+        let seed = app.session.seed(mode);
+        // Each daily challenge gets the same extra stores, no matter what the player upzoned
+        let upzones = if mode == Mode::Daily {
+            daily_upzones(app, seed)
+        } else {
+            upzones
+        };
+        let bldgs = Buildings::new(ctx, app, upzones, seed);
+        let difficulty = app.session.difficulty;
+        Box::new(Game::with_buildings(
+            ctx, app, level, vehicle, bldgs, mode, seed, difficulty, tutorial,
+        ))
+    }
+
+    /// One map of a campaign, picking up where the last one left off
+    pub fn new_campaign_leg(
+        ctx: &mut EventCtx,
+        app: &mut App,
+        level: Level,
+        vehicle: Vehicle,
+        upzones: HashSet<BuildingID>,
+        progress: CampaignProgress,
+    ) -> Box<dyn State<App>> {
+        let seed = app.session.seed(Mode::Normal);
+        let bldgs = Buildings::new(ctx, app, upzones, seed);
+        let difficulty = app.session.difficulty;
+        let mut game = Game::with_buildings(
+            ctx,
+            app,
+            level,
+            vehicle,
+            bldgs,
+            Mode::Normal,
+            seed,
+            difficulty,
+            None,
+        );
+        game.continue_campaign(ctx, app, progress);
+        Box::new(game)
+    }
+
+    /// Picks up a run that was quit partway through. The map must already be loaded.
+    pub fn resume(
+        ctx: &mut EventCtx,
+        app: &mut App,
+        level: Level,
+        run: SavedRun,
+    ) -> Box<dyn State<App>> {
+        SavedRun::clear();
+        // The map may have been rebuilt since the run was saved
+        if run
+            .delivered
+            .iter()
+            .chain(&run.snacked)
+            .chain(&run.upzones)
+            .chain(run.stock.visited())
+            .any(|b| app.map.maybe_get_b(*b).is_none())
+            || !run.position.still_on(&app.map)
+        {
+            return PopupMsg::new_state(
+                ctx,
+                "Can't resume this run",
+                vec!["The map has changed since it was saved."],
+            );
+        }
+        let vehicle = match Vehicle::maybe_get(&run.vehicle) {
+            Some(vehicle) => vehicle,
+            None => {
+                return PopupMsg::new_state(
+                    ctx,
+                    "Can't resume this run",
+                    vec![format!("There's no {} to ride anymore.", run.vehicle)],
+                );
+            }
+        };
+
+        // Set up everything the same way as the original run
+        let bldgs = Buildings::new(ctx, app, run.upzones, run.seed);
+        let mut game = Game::with_buildings(
+            ctx,
+            app,
+            level,
+            vehicle,
+            bldgs,
+            run.mode,
+            run.seed,
+            run.difficulty,
+            None,
+        );
+
+        app.time = Time::START_OF_DAY + run.elapsed;
+        let state = &mut game.state;
+        state.next_event = run.next_event;
+        state.rules.score = run.score;
+        state.rules.houses_delivered = run.houses_delivered;
+        state.rules.energy = run.energy.min(state.vehicle.max_energy);
+        state.rules.presents = run.presents;
+        state.selected_present = run.selected_present;
+        state.boost = run.boost.min(state.tuning.max_boost);
+        state.rules.combo = run.combo;
+        state.rules.last_delivery = run.last_delivery;
+        state.rules.double_score_until = run.double_score_until;
+        state.rules.speed_until = run.speed_until;
+        state.rules.snack_until = run.snack_until;
+        state.rules.stock = run.stock;
+        state.ghost = run.ghost;
+        state.replay = run.replay;
+        // This also brings back how far along each zone is
+        for b in run.delivered {
+            state.mark_delivered(ctx, app, b);
+        }
+        for b in run.snacked {
+            if let Some(BldgState::Store(store)) = state.bldgs.buildings.get(&b) {
+                let store = *store;
+                state.bldgs.buildings.insert(b, BldgState::Snacked(store));
+            }
+        }
+        game.ride.player.restore_position(app, run.position);
+        if let (Some(elf), Some(saved)) = (game.elf.as_mut(), run.elf) {
+            elf.restore(saved);
+        }
+        game.ride.camera.jump();
+
+        game.update_time_panel(ctx, app);
+        game.update_status_panel(ctx, app);
+        game.update_boost_panel(ctx, app);
+        game.update_presents_panel(ctx, app);
+        Box::new(game)
+    }
+
+    /// Everything needed to pick this run up later, if it can be
+    pub fn saved_run(&self, app: &App) -> Option<SavedRun> {
+        if !self.state.rules.mode.can_resume()
+            || self.state.game_over
+            || self.tutorial.is_some()
+            || self.campaign.is_some()
+            || app.session.kiosk
+        {
+            return None;
+        }
+        let state = &self.state;
+        let mut delivered = BTreeSet::new();
+        let mut snacked = BTreeSet::new();
+        for (b, bldg) in &state.bldgs.buildings {
+            match bldg {
+                BldgState::Done => {
+                    delivered.insert(*b);
+                }
+                BldgState::Snacked(_) => {
+                    snacked.insert(*b);
+                }
+                _ => {}
+            }
+        }
+        Some(SavedRun {
+            version: saved_run::VERSION,
+            level: state.level.title.clone(),
+            start: app
+                .map
+                .get_i(self.start)
+                .polygon
+                .center()
+                .to_gps(app.map.get_gps_bounds()),
+            vehicle: state.vehicle.name.clone(),
+            upzones: state.bldgs.upzones.clone(),
+            mode: state.rules.mode,
+            seed: state.seed,
+            difficulty: state.difficulty,
+
+            elapsed: app.time - Time::START_OF_DAY,
+            next_event: state.next_event,
+            score: state.rules.score,
+            houses_delivered: state.rules.houses_delivered,
+            energy: state.rules.energy,
+            presents: state.rules.presents.clone(),
+            selected_present: state.selected_present,
+            boost: state.boost,
+            combo: state.rules.combo,
+            last_delivery: state.rules.last_delivery,
+            double_score_until: state.rules.double_score_until,
+            speed_until: state.rules.speed_until,
+            snack_until: state.rules.snack_until,
+            delivered,
+            snacked,
+            stock: state.rules.stock.clone(),
+            position: self.ride.player.saved_position(),
+            elf: self.elf.as_ref().map(|elf| elf.saved()),
+
+            ghost: state.ghost.clone(),
+            replay: state.replay.clone(),
+        })
+    }
+
+    // Drawing every building and its label is slow on big maps, so keep all of that and just
+    // reset what each building wants
+    pub fn restart(self, ctx: &mut EventCtx, app: &mut App) -> Box<dyn State<App>> {
+        if self.tutorial.is_some() {
+            return Tutorial::new_state(ctx, app);
+        }
+        let state = self.state;
+        let mut bldgs = state.bldgs;
+        bldgs.reset(&app.map, state.seed);
+        let mut level = state.level;
+        level.goal = state.orig_goal;
+        let mut game = Game::with_buildings(
+            ctx,
+            app,
+            level,
+            Vehicle::get(&state.vehicle.name),
+            bldgs,
+            state.rules.mode,
+            state.seed,
+            state.difficulty,
+            None,
+        );
+        // Only this leg starts over
+        if let Some(progress) = self.campaign {
+            game.continue_campaign(ctx, app, progress);
+        }
+        Box::new(game)
+    }
+
+    pub fn continue_campaign(&mut self, ctx: &mut EventCtx, app: &App, progress: CampaignProgress) {
+        if let Some(energy) = progress.energy {
+            self.state.rules.energy = energy.min(self.state.vehicle.max_energy);
+            self.update_status_panel(ctx, app);
+        }
+        self.flash_message(
+            ctx,
+            app,
+            app.session.locale.tr_args(
+                "{}, {} of {}",
+                &[
+                    progress.campaign.title.clone(),
+                    (progress.leg + 1).to_string(),
+                    progress.campaign.legs.len().to_string(),
+                ],
+            ),
+        );
+        self.campaign = Some(progress);
+    }
+
+    /// In a campaign, riding into the edge of the map after meeting the goal continues on the
+    /// next map
+    pub fn check_campaign_exit(&mut self, ctx: &mut EventCtx, app: &mut App) -> Option<Transition> {
+        if !self.campaign.as_ref()?.has_next_leg() || !self.state.met_goal() {
+            return None;
+        }
+        let i = match self.ride.player.current_intersection() {
+            Some(i) if app.map.get_i(i).is_border() => i,
+            _ => {
+                self.campaign_border = None;
+                return None;
+            }
+        };
+        // If loading the next map gets cancelled, don't start it again until the player rides
+        // back onto a border
+        if self.campaign_border == Some(i) {
+            return None;
+        }
+        self.campaign_border = Some(i);
+        let mut progress = self.campaign.clone().unwrap();
+        progress.finish_leg(
+            &self.state.level,
+            self.state.rules.score,
+            self.state.rules.energy,
+        );
+        Some(Transition::Push(NextLeg::new_state(ctx, app, progress)))
+    }
+
+    pub fn with_buildings(
+        ctx: &mut EventCtx,
+        app: &mut App,
+        level: Level,
+        vehicle: Vehicle,
+        bldgs: Buildings,
+        mode: Mode,
+        seed: u64,
+        difficulty: DifficultyPreset,
+        tutorial: Option<Tutorial>,
+    ) -> Game {
+        app.session.current_vehicle = vehicle.name.clone();
+        app.time = Time::START_OF_DAY;
+        app.session.music.specify_volume(crate::music::IN_GAME);
+
+        let status_panel = Panel::new_builder(Widget::col(vec![
+            hud_text(ctx, app, "15-min Santa").centered_vert(),
+            Widget::row(vec![
+                // TODO The blur is messed up
+                Image::from_path("system/assets/tools/map.svg")
+                    .into_widget(ctx)
+                    .centered_vert(),
+                hud_text(ctx, app, Line(&level.title)),
+            ])
+            .padding(10)
+            .bg(Color::hex("#003046")),
+            if mode == Mode::Daily {
+                hud_text(
+                    ctx,
+                    app,
+                    Line(
+                        app.session
+                            .locale
+                            .tr_args("Daily challenge, seed {}", &[seed.to_string()]),
+                    )
+                    .secondary(),
+                )
+            } else {
+                Widget::nothing()
+            },
+            hud_text(
+                ctx,
+                app,
+                app.session.locale.tr(if mode == Mode::Zen {
+                    "Completion"
+                } else {
+                    "Complete Deliveries"
+                }),
+            )
+            .named("score label"),
+            GeomBatch::new().into_widget(ctx).named("score"),
+            Text::new().into_widget(ctx).named("combo"),
+            Text::new().into_widget(ctx).named("delivery rate"),
+            Text::new().into_widget(ctx).named("splits"),
+            Text::new().into_widget(ctx).named("opponent"),
+            Text::new().into_widget(ctx).named("power ups"),
+            if mode == Mode::Zen {
+                Widget::nothing()
+            } else {
+                Widget::col(vec![
+                    hud_text(ctx, app, app.session.locale.tr("Blood sugar")).named("energy label"),
+                    GeomBatch::new().into_widget(ctx).named("energy"),
+                ])
+            },
+            if mode == Mode::CoOp {
+                Widget::col(vec![
+                    hud_text(
+                        ctx,
+                        app,
+                        app.session.locale.tr("Second player's blood sugar"),
+                    ),
+                    GeomBatch::new().into_widget(ctx).named("energy 2"),
+                ])
+            } else {
+                Widget::nothing()
+            },
+            Text::new().into_widget(ctx).named("presents"),
+            Text::new().into_widget(ctx).named("grade"),
+            Widget::row(vec![
+                hud_text(ctx, app, app.session.locale.tr("Wind")).centered_vert(),
+                GeomBatch::new().into_widget(ctx).named("wind"),
+            ]),
+            Toggle::checkbox(ctx, "point to presents", Key::H, false),
+            Toggle::checkbox(ctx, "show where presents are needed", Key::G, false),
+            Toggle::checkbox(ctx, "show delivery zones", Key::Z, false),
+            ctx.style()
+                .btn_outline
+                .text("plan a route")
+                .hotkey(Key::P)
+                .build_def(ctx),
+        ]))
+        .aligned(HorizontalAlignment::RightInset, VerticalAlignment::TopInset)
+        .build(ctx);
+
+        let time_panel = Panel::new_builder(Widget::row(vec![
+            GeomBatch::new().into_widget(ctx).named("time circle"),
+            hud_text(ctx, app, app.session.locale.tr("Time"))
+                .centered_vert()
+                .named("time label"),
+        ]))
+        .aligned(HorizontalAlignment::LeftInset, VerticalAlignment::TopInset)
+        .build(ctx);
+
+        let pause_panel = make_pause_panel(ctx, app);
+
+        let start = app
+            .map
+            .find_i_by_pt2d(app.map.localise_lon_lat_to_map(level.start))
+            .expect("To find starting point");
+
+        let elf = if app.session.elf_helper && tutorial.is_none() {
+            Some(Elf::new(app.map.get_i(start).polygon.center()))
+        } else {
+            None
+        };
+        let race = if mode == Mode::Race {
+            match Race::connect(&app.session.race_server, level.title.clone()) {
+                Ok(race) => Some(race),
+                Err(err) => {
+                    warn!("Couldn't connect to the race server: {}", err);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let (player, second_player) = if mode == Mode::CoOp {
+            let (keys1, keys2) = app.session.keys.split_keyboard();
+            let player = Player::new_with_keys(ctx, app, start, keys1);
+            let second = Player::new_with_keys(ctx, app, start, keys2);
+            (
+                player,
+                Some(SecondPlayer {
+                    player: second,
+                    energy: vehicle.max_energy,
+                }),
+            )
+        } else {
+            (Player::new(ctx, app, start), None)
+        };
+
+        let mut state = GameState::new(ctx, app, level, vehicle, bldgs, mode, seed, difficulty);
+        let fog = if mode == Mode::Explore {
+            Some(Fog::new(ctx, app, &mut state.bldgs))
+        } else {
+            None
+        };
+        // Without a time limit, there's no early or late. The tutorial ignores its time limit.
+        let windows = if mode.has_time_limit() && tutorial.is_none() {
+            let picked = windows::pick(&state.bldgs.buildings, seed);
+            state.rules.set_windows(picked.clone());
+            Some(DeliveryWindows::new(ctx, app, &state.bldgs, picked))
+        } else {
+            None
+        };
+        // Only race against runs with the same setup
+        if mode == Mode::Normal
+            && seed == mode.seed()
+            && tutorial.is_none()
+            && state.difficulty == DifficultyPreset::Normal
+        {
+            state.best_ghost = app.session.ghosts.get(&state.level.title).cloned();
+        }
+
+        let mut game = Game {
+            status_panel,
+            time_panel,
+            pause_panel,
+            minimap: Minimap::new(ctx, app, MinimapController),
+
+            animator: Animator::new(ctx),
+            snow: SnowEffect::new(ctx, app.session.num_snowflakes()),
+            obstacles: Obstacles::new(
+                ctx,
+                app,
+                start,
+                seed,
+                state.difficulty.obstacle_multiplier(),
+            ),
+            pickups: Pickups::new(ctx, app, start, seed),
+            signals: Signals::new(ctx, app),
+            bus_stops: BusStops::new(ctx, app),
+            touch: TouchControls::new(ctx, app.session.colors.boost),
+
+            state,
+            ride: Ride::new(player),
+            second_player,
+            race,
+            elf,
+            tutorial,
+            hovering: None,
+            edge: MapEdge::new(ctx, app),
+            fog,
+            windows,
+            ticker: Ticker::new(),
+            nearby: NearbyHouses::new(ctx),
+            trail: Trail::new(ctx),
+            slow_motion_until: None,
+            photo_mode: false,
+            kiosk_idle: IdleTimer::new(),
+            campaign: None,
+            campaign_border: None,
+            start,
+            perf: PerfOverlay::new(),
+            help: HelpOverlay::new(),
+            time_shown: (None, None),
+            bars_shown: HashMap::new(),
+        };
+        game.update_time_panel(ctx, app);
+        game.update_status_panel(ctx, app);
+        game.minimap
+            .set_zoom(ctx, app, game.state.level.minimap_zoom);
+        game.update_boost_panel(ctx, app);
+        game.update_presents_panel(ctx, app);
+        game.update_wind_panel(ctx);
+        if mode == Mode::Race && game.race.is_none() {
+            let label = hud_text(
+                ctx,
+                app,
+                Line(app.session.locale.tr("Couldn't reach the race server")).fg(Color::RED),
+            );
+            game.status_panel.replace(ctx, "opponent", label);
+        }
+        game
+    }
+}
+
+// How many extra stores the daily challenge gets
+const DAILY_UPZONES: usize = 3;
+
+/// Randomly turn some houses into stores
+fn daily_upzones(app: &App, seed: u64) -> HashSet<BuildingID> {
+    let mut choices: Vec<BuildingID> = app
+        .map
+        .all_buildings()
+        .iter()
+        .filter_map(|b| match b.bldg_type {
+            BuildingType::Residential {
+                num_housing_units, ..
+            } if num_housing_units > 0 => Some(b.id),
+            _ => None,
+        })
+        .collect();
+    let mut rng = XorShiftRng::seed_from_u64(seed);
+    choices.shuffle(&mut rng);
+    choices.into_iter().take(DAILY_UPZONES).collect()
+}
+
+/// The name of the area around a point, from OpenStreetMap if possible
+pub fn neighborhood_name(app: &App, pt: Pt2D) -> String {
+    let named = app
+        .map
+        .all_areas()
+        .iter()
+        .filter_map(|a| a.osm_tags.get("name").map(|name| (a, name)));
+    if let Some((_, name)) = named
+        .clone()
+        .find(|(a, _)| a.polygon.contains_pt(pt))
+        .or_else(|| named.min_by_key(|(a, _)| a.polygon.center().dist_to(pt)))
+    {
+        return name.clone();
+    }
+    app.map.get_name().describe()
+}
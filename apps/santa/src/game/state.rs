@@ -0,0 +1,460 @@
+use std::collections::VecDeque;
+
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
+use geom::{Distance, Duration, Time};
+use map_model::{BuildingID, BuildingType, IntersectionID, RoadID};
+use widgetry::EventCtx;
+
+use crate::after_level::RecordPath;
+use crate::buildings::{BldgState, Buildings, DoneHouses, PresentType};
+use crate::difficulty::{Difficulty, DifficultyPreset};
+use crate::game::setup::neighborhood_name;
+use crate::ghost::Ghost;
+use crate::hints::{DemandHeatmap, EnergylessArrow, PresentHint, RouteHint};
+use crate::levels::{Level, WinCondition};
+use crate::mode::Mode;
+use crate::player::Player;
+use crate::race::Race;
+use crate::replay::Replay;
+use crate::rules::Rules;
+use crate::shop::Upgrades;
+use crate::stats::RunStats;
+use crate::tuning::Tuning;
+use crate::vehicles::Vehicle;
+use crate::wind::Wind;
+use crate::zones::{Zones, ZonesLayer};
+use crate::App;
+
+// The alpha of the night tint at the start and end of a level
+const DUSK_DARKNESS: f32 = 0.1;
+const MIDNIGHT_DARKNESS: f32 = 0.5;
+
+pub struct SecondPlayer {
+    pub player: Player,
+    pub energy: usize,
+}
+
+pub struct GameState {
+    // The goal and vehicle stats may be overridden by the tuning
+    pub level: Level,
+    pub vehicle: Vehicle,
+    pub bldgs: Buildings,
+    pub zones: Zones,
+    pub tuning: Tuning,
+    pub seed: u64,
+    pub orig_goal: usize,
+    // From the difficulty, and for levels scaled to the map
+    pub energy_multiplier: f64,
+    // Always normal in the daily challenge and races
+    pub difficulty: DifficultyPreset,
+    // Bought in the shop, applied on top of the tuning
+    pub upgrades: Upgrades,
+
+    // Scoring, blood sugar, presents, and power-ups
+    pub rules: Rules,
+    // Index into the level's scripted events of the next one to happen
+    pub next_event: usize,
+    // Houses wanting presents at the start
+    pub total_houses: usize,
+    pub selected_present: PresentType,
+    // Don't keep complaining about the same house while riding past it
+    pub last_wrong_present: Option<BuildingID>,
+    pub boost: Duration,
+    // Remaining time before boost can be used again
+    pub boost_cooldown: Duration,
+    // How far we've boosted since last burning energy for it
+    pub boost_exertion: Distance,
+    pub boosting: bool,
+    // When energy is low, the meter flashes
+    pub energy_flash_on: bool,
+    // Rounded, so the HUD only changes when needed
+    pub grade_pct: isize,
+    pub wind: Wind,
+    // The last restaurant ridden past, and when
+    pub snack_offer: Option<(BuildingID, Time)>,
+    // Whole seconds left on each power-up, as currently shown
+    pub power_ups_shown: (usize, usize, usize),
+    // To notice the player entering an intersection, and where from
+    pub last_road: Option<RoadID>,
+    pub last_intersection: Option<IntersectionID>,
+    // For endless mode. When each delivered house will want presents again, and how many
+    pub regrow_queue: VecDeque<(Time, BuildingID, usize, PresentType)>,
+    // How many demand surges have happened so far
+    pub surges: usize,
+    pub recent_deliveries: VecDeque<Time>,
+    pub delivery_rate_shown: Option<usize>,
+    // When the player last ran into an obstacle
+    pub last_hit: Option<Time>,
+    pub last_go_home: Option<Time>,
+    // For time trials. How long it took to reach each split, this run and in the fastest one
+    pub splits: Vec<Duration>,
+    pub best_splits: Vec<Duration>,
+
+    pub done_houses: DoneHouses,
+    pub energyless_arrow: Option<EnergylessArrow>,
+    pub present_hint: Option<PresentHint>,
+    pub demand_heatmap: Option<DemandHeatmap>,
+    pub route_hint: Option<RouteHint>,
+    pub zones_layer: Option<ZonesLayer>,
+
+    // For animation
+    pub idle_time: Duration,
+
+    pub game_over: bool,
+    pub warned_low_time: bool,
+    pub warned_low_energy: bool,
+    // Once out of energy, the meter's label changes
+    pub hangry_shown: bool,
+
+    pub record_path: RecordPath,
+    pub stats: RunStats,
+    pub ghost: Ghost,
+    pub replay: Replay,
+    // The best previous run to race against
+    pub best_ghost: Option<Ghost>,
+}
+
+impl GameState {
+    pub fn new(
+        ctx: &mut EventCtx,
+        app: &App,
+        mut level: Level,
+        vehicle: Vehicle,
+        bldgs: Buildings,
+        mode: Mode,
+        seed: u64,
+        difficulty: DifficultyPreset,
+    ) -> GameState {
+        let tuning = Tuning::load(&level.title);
+        // Everyone has to start out even in the daily challenge and races
+        let everyone_even = mode == Mode::Daily || mode == Mode::Race;
+        let difficulty = if everyone_even {
+            DifficultyPreset::Normal
+        } else {
+            difficulty
+        };
+        let mut energy_multiplier = difficulty.energy_multiplier();
+        if let Some(scaled) = Difficulty::for_level(&level, &app.map) {
+            scaled.apply(&mut level);
+            energy_multiplier *= scaled.energy_multiplier;
+        }
+        if let Some(multiplier) = level.script.energy_multiplier {
+            energy_multiplier *= multiplier;
+        }
+        level.time_limit = level.time_limit * difficulty.time_multiplier();
+        if level.script.win == WinCondition::EveryHouse {
+            level.goal = bldgs.total_housing_units;
+        }
+        let zones = Zones::new(app, &bldgs);
+        let total_houses = bldgs
+            .buildings
+            .values()
+            .filter(|state| matches!(state, BldgState::Undelivered(..)))
+            .count();
+        let ghost = Ghost::new(vehicle.name.clone());
+        let replay = Replay::new(
+            level.title.clone(),
+            vehicle.name.clone(),
+            bldgs.upzones.clone(),
+            seed,
+        );
+        let best_splits = app
+            .session
+            .best_splits
+            .get(&difficulty.score_key(&level.title))
+            .cloned()
+            .unwrap_or_else(Vec::new);
+        let rules = Rules::new(&level, mode);
+        let mut state = GameState {
+            seed,
+            orig_goal: level.goal,
+            energy_multiplier,
+            difficulty,
+            upgrades: if everyone_even {
+                Upgrades::default()
+            } else {
+                app.session.upgrades
+            },
+            level,
+            vehicle,
+            bldgs,
+            zones,
+            tuning: Tuning::default(),
+
+            rules,
+            next_event: 0,
+            total_houses,
+            selected_present: PresentType::Toy,
+            last_wrong_present: None,
+            boost: Duration::ZERO,
+            boost_cooldown: Duration::ZERO,
+            boost_exertion: Distance::ZERO,
+            boosting: false,
+            energy_flash_on: false,
+            grade_pct: 0,
+            wind: Wind::new(Time::START_OF_DAY, seed),
+            snack_offer: None,
+            power_ups_shown: (0, 0, 0),
+            last_road: None,
+            last_intersection: None,
+            regrow_queue: VecDeque::new(),
+            surges: 0,
+            recent_deliveries: VecDeque::new(),
+            delivery_rate_shown: None,
+            last_hit: None,
+            last_go_home: None,
+            splits: Vec::new(),
+            best_splits,
+
+            done_houses: DoneHouses::new(ctx),
+            energyless_arrow: None,
+            present_hint: None,
+            demand_heatmap: None,
+            route_hint: None,
+            zones_layer: None,
+
+            idle_time: Duration::ZERO,
+
+            game_over: false,
+            warned_low_time: false,
+            warned_low_energy: false,
+            hangry_shown: false,
+
+            record_path: RecordPath::new(),
+            stats: RunStats::new(),
+            ghost,
+            replay,
+            best_ghost: None,
+        };
+        state.set_tuning(tuning);
+        state.rules.energy = state.vehicle.max_energy;
+        state.rules.restock_presents(&state.tuning);
+        state
+    }
+
+    pub fn set_tuning(&mut self, mut tuning: Tuning) {
+        self.upgrades.apply(&mut tuning);
+        self.difficulty.apply(&mut tuning);
+        if let Some(multiplier) = self.level.script.wind_multiplier {
+            tuning.max_wind_speed *= multiplier;
+        }
+        // Start from the original stats, so removing an override takes effect after reloading
+        let orig_vehicle = Vehicle::get(&self.vehicle.name);
+        self.vehicle.speed = tuning.speed_multiplier * orig_vehicle.speed;
+        self.vehicle.max_energy = tuning.max_energy.unwrap_or(
+            ((orig_vehicle.max_energy as f64) * self.energy_multiplier).round() as usize,
+        );
+        self.level.goal = tuning.goal.unwrap_or(self.orig_goal);
+
+        self.rules.energy = self.rules.energy.min(self.vehicle.max_energy);
+        self.boost = self.boost.min(tuning.max_boost);
+        self.tuning = tuning;
+    }
+
+    // If something changed, return the update to the score
+    pub fn present_dropped(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &App,
+        id: BuildingID,
+    ) -> Option<usize> {
+        if let BldgState::Undelivered(num_housing_units, wanted) = self.bldgs.buildings[&id] {
+            // The wrong kind of present doesn't count
+            if wanted != self.selected_present {
+                return None;
+            }
+            let increase =
+                self.rules
+                    .deliver(&self.tuning, app.time, id, num_housing_units, wanted)?;
+            self.replay.delivered(app.time, id, increase);
+            self.stats.delivered(app.time, id, increase);
+            self.bldgs.buildings.insert(id, BldgState::Done);
+            self.bldgs.index.remove(id);
+            self.bldgs.labels.hide(id);
+            self.done_houses.add(ctx, app, id);
+            if self.rules.mode == Mode::Endless {
+                self.recent_deliveries.push_back(app.time);
+                let next_units =
+                    ((num_housing_units as f64) * self.tuning.endless_decay).floor() as usize;
+                if next_units > 0 {
+                    self.regrow_queue.push_back((
+                        app.time + self.tuning.endless_regrow_time,
+                        id,
+                        next_units,
+                        wanted,
+                    ));
+                }
+            }
+            return Some(increase);
+        }
+        None
+    }
+
+    /// In endless mode, delivered houses eventually want more presents. Returns true if anything
+    /// changed.
+    pub fn regrow(&mut self, ctx: &mut EventCtx, app: &App) -> bool {
+        let mut regrew = false;
+        while let Some((t, _, _, _)) = self.regrow_queue.front() {
+            if *t > app.time {
+                break;
+            }
+            let (_, b, num_housing_units, wanted) = self.regrow_queue.pop_front().unwrap();
+            self.bldgs
+                .buildings
+                .insert(b, BldgState::Undelivered(num_housing_units, wanted));
+            self.bldgs.index.insert(&app.map, b);
+            self.bldgs.labels.show(b);
+            self.zones.undelivered(b);
+            regrew = true;
+        }
+        if regrew {
+            self.redraw_done_houses(ctx, app);
+        }
+        regrew
+    }
+
+    /// In endless mode, every so often a cluster of delivered houses all want presents again at
+    /// once, and more than usual. Returns a headline announcing it.
+    pub fn demand_surge(&mut self, ctx: &mut EventCtx, app: &App) -> Option<String> {
+        let next = Time::START_OF_DAY + self.tuning.surge_period * ((self.surges + 1) as f64);
+        if app.time < next {
+            return None;
+        }
+        self.surges += 1;
+
+        // The buildings come from a HashMap, so sort them to keep the picks the same
+        let mut done: Vec<BuildingID> = self
+            .bldgs
+            .buildings
+            .iter()
+            .filter_map(|(b, state)| {
+                if let BldgState::Done = state {
+                    Some(*b)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        done.sort();
+        let mut rng = XorShiftRng::seed_from_u64(self.seed ^ (self.surges as u64));
+        let center = app.map.get_b(*done.choose(&mut rng)?).label_center;
+
+        for b in done {
+            let bldg = app.map.get_b(b);
+            if bldg.label_center.dist_to(center) > self.tuning.surge_radius {
+                continue;
+            }
+            if let BuildingType::Residential {
+                num_housing_units, ..
+            } = bldg.bldg_type
+            {
+                let units =
+                    ((num_housing_units as f64) * self.tuning.surge_multiplier).round() as usize;
+                let wanted = *PresentType::ALL.choose(&mut rng).unwrap();
+                // The surge replaces the usual regrowth
+                self.regrow_queue.retain(|(_, x, _, _)| *x != b);
+                self.bldgs
+                    .buildings
+                    .insert(b, BldgState::Undelivered(units, wanted));
+                self.bldgs.index.insert(&app.map, b);
+                self.bldgs.labels.show(b);
+                self.zones.undelivered(b);
+            }
+        }
+        self.redraw_done_houses(ctx, app);
+
+        Some(
+            app.session
+                .locale
+                .tr_args("Demand surge in {}!", &[neighborhood_name(app, center)]),
+        )
+    }
+
+    // Delivered houses can't be removed individually, so start over
+    pub fn redraw_done_houses(&mut self, ctx: &mut EventCtx, app: &App) {
+        self.done_houses = DoneHouses::new(ctx);
+        for (b, state) in &self.bldgs.buildings {
+            if let BldgState::Done = state {
+                self.done_houses.add(ctx, app, *b);
+            }
+        }
+    }
+
+    pub fn can_deliver(&self, wanted: PresentType) -> bool {
+        wanted == self.selected_present && self.rules.presents[&wanted] > 0
+    }
+
+    /// The elf helper doesn't use up the player's presents or energy, and doesn't count towards
+    /// combos. Returns the increase in score.
+    pub fn elf_delivered(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &App,
+        id: BuildingID,
+    ) -> Option<usize> {
+        if let BldgState::Undelivered(num_housing_units, _) = self.bldgs.buildings[&id] {
+            self.rules.score += num_housing_units;
+            self.replay.delivered(app.time, id, num_housing_units);
+            self.stats.delivered(app.time, id, num_housing_units);
+            self.bldgs.buildings.insert(id, BldgState::Done);
+            self.bldgs.index.remove(id);
+            self.bldgs.labels.hide(id);
+            self.done_houses.add(ctx, app, id);
+            // Still award the bonus for finishing off a zone, just without the fanfare
+            self.zone_delivered(id);
+            return Some(num_housing_units);
+        }
+        None
+    }
+
+    /// In a race, the opponent got to a house first
+    // By an opponent in a race, or earlier in a resumed run
+    pub fn mark_delivered(&mut self, ctx: &mut EventCtx, app: &App, id: BuildingID) {
+        if let Some(BldgState::Undelivered(..)) = self.bldgs.buildings.get(&id) {
+            self.bldgs.buildings.insert(id, BldgState::Done);
+            self.bldgs.index.remove(id);
+            self.bldgs.labels.hide(id);
+            self.done_houses.add(ctx, app, id);
+            self.zones.delivered(id);
+        }
+    }
+
+    /// If this delivery finished off a zone, award and return a bonus
+    pub fn zone_delivered(&mut self, id: BuildingID) -> Option<usize> {
+        let zone = self.zones.delivered(id)?;
+        let bonus = ((zone.total_units as f64) * self.tuning.zone_bonus)
+            .round()
+            .max(1.0) as usize;
+        self.rules.score += bonus;
+        Some(bonus)
+    }
+
+    /// Levels start at dusk and get darker as time runs out, unless they're set at night
+    pub fn darkness(&self, app: &App) -> f32 {
+        if self.level.script.night {
+            return MIDNIGHT_DARKNESS;
+        }
+        let pct = ((app.time - Time::START_OF_DAY) / self.level.time_limit).min(1.0) as f32;
+        DUSK_DARKNESS + pct * (MIDNIGHT_DARKNESS - DUSK_DARKNESS)
+    }
+
+    pub fn met_goal(&self) -> bool {
+        match self.level.script.win {
+            WinCondition::Score => self.rules.score >= self.level.goal,
+            WinCondition::Houses(n) => self.rules.houses_delivered >= n,
+            WinCondition::Zones(n) => self.zones.num_complete() >= n,
+            WinCondition::EveryHouse => self.houses_left() == 0,
+        }
+    }
+
+    pub fn houses_left(&self) -> usize {
+        self.bldgs
+            .buildings
+            .values()
+            .filter(|state| matches!(state, BldgState::Undelivered(..)))
+            .count()
+    }
+}
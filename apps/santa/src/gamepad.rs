@@ -0,0 +1,71 @@
+use geom::Angle;
+
+// Ignore small amounts of stick drift
+const DEAD_ZONE: f64 = 0.2;
+
+/// Optional gamepad support. The latest stick and trigger state is captured once per frame by
+/// `poll`, then read by the controllers.
+#[derive(Default)]
+pub struct Gamepad {
+    #[cfg(not(target_arch = "wasm32"))]
+    inner: Option<gilrs::Gilrs>,
+
+    /// The direction of the left stick (in the same orientation as the arrow keys) and how far
+    /// it's pushed, from 0 to 1. None if the stick is inside the dead zone.
+    pub stick: Option<(Angle, f64)>,
+    /// Is either trigger held down?
+    pub boost: bool,
+    /// Is the south face button (A on Xbox controllers) pressed?
+    pub action: bool,
+}
+
+impl Gamepad {
+    pub fn new() -> Gamepad {
+        #[cfg(not(target_arch = "wasm32"))]
+        match gilrs::Gilrs::new() {
+            Ok(inner) => {
+                return Gamepad {
+                    inner: Some(inner),
+                    ..Default::default()
+                };
+            }
+            Err(err) => {
+                warn!("No gamepad support: {}", err);
+            }
+        }
+
+        Gamepad::default()
+    }
+
+    pub fn poll(&mut self, enabled: bool) {
+        self.stick = None;
+        self.boost = false;
+        self.action = false;
+        if !enabled {
+            return;
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(ref mut gilrs) = self.inner {
+            use gilrs::{Axis, Button};
+
+            // Drain events to update the cached state
+            while gilrs.next_event().is_some() {}
+
+            for (_, pad) in gilrs.gamepads() {
+                let x = pad.value(Axis::LeftStickX) as f64;
+                // Up on the stick is positive, but up on the screen is negative
+                let y = -pad.value(Axis::LeftStickY) as f64;
+                let magnitude = (x * x + y * y).sqrt();
+                if magnitude > DEAD_ZONE && self.stick.is_none() {
+                    // Rescale to use the full range outside the dead zone
+                    let pct = ((magnitude - DEAD_ZONE) / (1.0 - DEAD_ZONE)).min(1.0);
+                    self.stick = Some((Angle::new_rads(y.atan2(x)), pct));
+                }
+                self.boost |=
+                    pad.is_pressed(Button::LeftTrigger2) || pad.is_pressed(Button::RightTrigger2);
+                self.action |= pad.is_pressed(Button::South);
+            }
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+
+use geom::{Angle, Duration, Pt2D, Time};
+use widgetry::{GeomBatch, GfxCtx, RewriteColor};
+
+use crate::vehicles::Vehicle;
+use crate::App;
+
+// How often the position is sampled. Playback interpolates between samples.
+const SAMPLE_PERIOD: Duration = Duration::const_seconds(0.1);
+const GHOST_ALPHA: f32 = 0.4;
+
+/// Where the sleigh was throughout a run, compact enough to keep one per level in the save file.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Ghost {
+    pub score: usize,
+    vehicle: String,
+    // Every SAMPLE_PERIOD from the start of the level: x and y in centimeters, then the angle in
+    // degrees
+    samples: Vec<[i32; 3]>,
+}
+
+impl Ghost {
+    pub fn new(vehicle: String) -> Ghost {
+        Ghost {
+            score: 0,
+            vehicle,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Call every tick. Only records when the next sample is due.
+    pub fn record(&mut self, now: Time, pos: Pt2D, angle: Angle) {
+        let elapsed = now - Time::START_OF_DAY;
+        while SAMPLE_PERIOD * (self.samples.len() as f64) <= elapsed {
+            self.samples.push([
+                (pos.x() * 100.0).round() as i32,
+                (pos.y() * 100.0).round() as i32,
+                angle.normalized_degrees().round() as i32,
+            ]);
+        }
+    }
+
+    /// Every recorded position, in order
+    pub fn points(&self) -> Vec<Pt2D> {
+        self.samples
+            .iter()
+            .map(|[x, y, _]| Pt2D::new((*x as f64) / 100.0, (*y as f64) / 100.0))
+            .collect()
+    }
+
+    /// Where the sleigh was over the `length` leading up to `now`, oldest first. Only every
+    /// `every`th sample is kept.
+    pub fn recent_points(&self, now: Time, length: Duration, every: usize) -> Vec<Pt2D> {
+        let idx = |t: Duration| (t.max(Duration::ZERO) / SAMPLE_PERIOD).floor() as usize;
+        let elapsed = now - Time::START_OF_DAY;
+        let end = (idx(elapsed) + 1).min(self.samples.len());
+        let start = idx(elapsed - length).min(end);
+        self.samples[start..end]
+            .iter()
+            .step_by(every)
+            .map(|[x, y, _]| Pt2D::new((*x as f64) / 100.0, (*y as f64) / 100.0))
+            .collect()
+    }
+
+    /// Where the ghost is at some point in the run, or None once it's finished
+    pub fn pos(&self, now: Time) -> Option<(Pt2D, Angle)> {
+        let elapsed = now - Time::START_OF_DAY;
+        let idx = (elapsed / SAMPLE_PERIOD).floor() as usize;
+        let first = self.samples.get(idx)?;
+        let second = self.samples.get(idx + 1).unwrap_or(first);
+        let pct = (elapsed - SAMPLE_PERIOD * (idx as f64)) / SAMPLE_PERIOD;
+
+        let lerp = |a: i32, b: i32| (a as f64 + pct * (b - a) as f64) / 100.0;
+        let pt = Pt2D::new(lerp(first[0], second[0]), lerp(first[1], second[1]));
+        // Don't spin the long way around when interpolating angles
+        let mut diff = second[2] - first[2];
+        if diff > 180 {
+            diff -= 360;
+        } else if diff < -180 {
+            diff += 360;
+        }
+        let angle = Angle::degrees(first[2] as f64 + pct * diff as f64);
+        Some((pt, angle))
+    }
+
+    /// Translucent, to race against
+    pub fn draw(&self, g: &mut GfxCtx, app: &App) {
+        if let Some(batch) = self.render(g, app.time) {
+            batch.color(RewriteColor::ChangeAlpha(GHOST_ALPHA)).draw(g);
+        }
+    }
+
+    /// Looks like the real player, for replays
+    pub fn draw_sleigh(&self, g: &mut GfxCtx, now: Time) {
+        if let Some(batch) = self.render(g, now) {
+            batch.draw(g);
+        }
+    }
+
+    fn render(&self, g: &mut GfxCtx, now: Time) -> Option<GeomBatch> {
+        let (pt, angle) = self.pos(now)?;
+        Some(
+            Vehicle::get(&self.vehicle)
+                .animate(g.prerender, now)
+                .centered_on(pt)
+                .rotate_around_batch_center(angle),
+        )
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::{HashMap, HashSet};
+
+use geom::{Duration, Time};
+use map_model::connectivity::{self, Spot};
+use map_model::{BuildingID, DirectedRoadID, Direction, Map, PathConstraints};
+
+use crate::buildings::{BldgState, Buildings};
+use crate::rules::Rules;
+use crate::vehicles::Vehicle;
+use crate::windows;
+// Everything needed to set up a simulation from outside this crate
+pub use crate::levels::Level;
+pub use crate::mode::Mode;
+pub use crate::tuning::Tuning;
+
+// Don't plan trips longer than this
+const MAX_TRIP: Duration = Duration::const_seconds(5.0 * 60.0);
+
+/// Plays a level to the end without a window, so the rules for scoring, blood sugar, and
+/// deliveries can be checked from tests and scripts. An automated player hops between
+/// buildings, taking as long as riding the roads there would take, and always goes for the
+/// open house worth the most presents per second of travel. It refills at the nearest store
+/// with something left on the shelves when blood sugar runs low, and always stops for a snack.
+///
+/// Scoring, combos, power-ups, delivery windows, and store stock all come from the same `Rules`
+/// as the real game. Zone bonuses, pickups, and anything else that needs to draw aren't
+/// simulated.
+///
+/// Nothing here is random, so the same map, level, seed, and tuning always produce the same
+/// `Outcome`.
+pub struct Simulation<'a> {
+    map: &'a Map,
+    level: Level,
+    tuning: Tuning,
+    max_energy: usize,
+    rules: Rules,
+
+    bldgs: HashMap<BuildingID, BldgState>,
+    time: Time,
+    at: Vec<Spot>,
+
+    refills: usize,
+    snacks: usize,
+}
+
+/// How a simulated run went
+#[derive(Debug, PartialEq)]
+pub struct Outcome {
+    pub score: usize,
+    pub deliveries: usize,
+    pub refills: usize,
+    pub snacks: usize,
+    pub elapsed: Duration,
+    pub met_goal: bool,
+}
+
+impl<'a> Simulation<'a> {
+    pub fn new(
+        map: &'a Map,
+        level: Level,
+        vehicle: &str,
+        mode: Mode,
+        seed: u64,
+        tuning: Tuning,
+    ) -> Simulation<'a> {
+        let start = map
+            .find_i_by_pt2d(map.localise_lon_lat_to_map(level.start))
+            .expect("To find starting point");
+        let mut at = Vec::new();
+        for road in &map.get_i(start).roads {
+            for dir in [Direction::Fwd, Direction::Back] {
+                at.push(Spot::DirectedRoad(DirectedRoadID { road: *road, dir }));
+            }
+        }
+        let vehicle = Vehicle::get(vehicle);
+        let max_energy = tuning.max_energy.unwrap_or(vehicle.max_energy);
+        let bldgs = Buildings::initial_states(map, &HashSet::new(), seed);
+        Simulation::from_parts(map, level, mode, seed, tuning, max_energy, bldgs, at)
+    }
+
+    fn from_parts(
+        map: &'a Map,
+        level: Level,
+        mode: Mode,
+        seed: u64,
+        tuning: Tuning,
+        max_energy: usize,
+        bldgs: HashMap<BuildingID, BldgState>,
+        at: Vec<Spot>,
+    ) -> Simulation<'a> {
+        let mut rules = Rules::new(&level, mode);
+        // Without a time limit, there's no early or late
+        if mode.has_time_limit() {
+            rules.set_windows(windows::pick(&bldgs, seed));
+        }
+        rules.energy = max_energy;
+        rules.restock_presents(&tuning);
+
+        Simulation {
+            map,
+            level,
+            tuning,
+            max_energy,
+            rules,
+
+            bldgs,
+            time: Time::START_OF_DAY,
+            at,
+
+            refills: 0,
+            snacks: 0,
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.time - Time::START_OF_DAY
+    }
+
+    /// Runs until time is up, or there's nothing left to do
+    pub fn run(mut self) -> Outcome {
+        while self.step() {}
+        Outcome {
+            score: self.rules.score,
+            deliveries: self.rules.houses_delivered,
+            refills: self.refills,
+            snacks: self.snacks,
+            elapsed: self.elapsed(),
+            met_goal: self.rules.score >= self.level.goal,
+        }
+    }
+
+    /// Travels to and visits one building. Returns false when the run is over.
+    pub fn step(&mut self) -> bool {
+        let costs = connectivity::all_vehicle_costs_from(
+            self.map,
+            self.at.clone(),
+            MAX_TRIP,
+            PathConstraints::Bike,
+        );
+        let hungry = self.rules.energy <= self.tuning.low_energy_warning
+            || self.rules.presents.values().all(|count| *count == 0);
+        let next = if hungry {
+            self.closest_store(&costs)
+                .or_else(|| self.best_house(&costs))
+        } else {
+            self.best_house(&costs)
+                .or_else(|| self.closest_store(&costs))
+        };
+        let (b, cost) = match next {
+            Some(pair) => pair,
+            None => {
+                return false;
+            }
+        };
+
+        self.time += cost;
+        if self.rules.mode.has_time_limit() && self.elapsed() >= self.level.time_limit {
+            self.time = Time::START_OF_DAY + self.level.time_limit;
+            return false;
+        }
+        self.at = vec![Spot::Building(b)];
+        self.visit(b);
+        true
+    }
+
+    // Everything that happens once the bot reaches a building
+    fn visit(&mut self, b: BuildingID) {
+        match self.bldgs[&b].clone() {
+            BldgState::Undelivered(num_housing_units, wanted) => {
+                if self
+                    .rules
+                    .deliver(&self.tuning, self.time, b, num_housing_units, wanted)
+                    .is_some()
+                {
+                    self.bldgs.insert(b, BldgState::Done);
+                }
+            }
+            BldgState::Store(store_type) | BldgState::Snacked(store_type) => {
+                let bldg = self.bldgs.get_mut(&b).unwrap();
+                if self.rules.eat_snack(&self.tuning, self.time, bldg) {
+                    self.snacks += 1;
+                }
+                let (refill, restocked) =
+                    self.rules
+                        .visit_store(&self.tuning, self.max_energy, b, store_type, self.time);
+                if refill > 0 || restocked > 0 {
+                    self.refills += 1;
+                }
+            }
+            // Riding past these does nothing, same as in the game
+            BldgState::Done | BldgState::Ignore => {}
+        }
+    }
+
+    fn best_house(&self, costs: &HashMap<BuildingID, Duration>) -> Option<(BuildingID, Duration)> {
+        costs
+            .iter()
+            .filter_map(|(b, cost)| match self.bldgs[b] {
+                BldgState::Undelivered(num_housing_units, wanted)
+                    if self.rules.has_energy()
+                        && self.rules.presents[&wanted] > 0
+                        && self.rules.closed_window(*b, self.time + *cost).is_none() =>
+                {
+                    // Avoid dividing by zero for houses right here
+                    let per_second = (num_housing_units as f64) / (cost.inner_seconds() + 1.0);
+                    Some(((per_second * 1000.0).round() as usize, *b, *cost))
+                }
+                _ => None,
+            })
+            .max_by_key(|(value, b, _)| (*value, std::cmp::Reverse(*b)))
+            .map(|(_, b, cost)| (b, cost))
+    }
+
+    // Only stores that would top up blood sugar or presents once the bot gets there
+    fn closest_store(
+        &self,
+        costs: &HashMap<BuildingID, Duration>,
+    ) -> Option<(BuildingID, Duration)> {
+        costs
+            .iter()
+            .filter(|(b, cost)| match self.bldgs[b] {
+                BldgState::Store(store_type) | BldgState::Snacked(store_type) => {
+                    self.rules.store_offers(
+                        &self.tuning,
+                        self.max_energy,
+                        **b,
+                        store_type,
+                        self.time + **cost,
+                    ) != (0, 0)
+                }
+                _ => false,
+            })
+            .min_by_key(|(b, cost)| (**cost, **b))
+            .map(|(b, cost)| (*b, *cost))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::buildings::{PresentType, StoreType};
+
+    fn level() -> Level {
+        let mut level = Level::tutorial();
+        level.time_limit = Duration::minutes(9);
+        level
+    }
+
+    // A simulation that never travels, just to check what visiting a building does
+    fn simulation(map: &Map, bldgs: Vec<(BuildingID, BldgState)>) -> Simulation {
+        Simulation::from_parts(
+            map,
+            level(),
+            Mode::Zen,
+            0,
+            Tuning::default(),
+            100,
+            bldgs.into_iter().collect(),
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn test_visits() {
+        let map = Map::blank();
+        let house = BuildingID(0);
+        let restaurant = BuildingID(1);
+        let mut sim = simulation(
+            &map,
+            vec![
+                (house, BldgState::Undelivered(10, PresentType::Toy)),
+                (restaurant, BldgState::Store(StoreType::Restaurant)),
+                (BuildingID(2), BldgState::Ignore),
+            ],
+        );
+
+        sim.visit(restaurant);
+        assert_eq!(1, sim.snacks);
+        assert!(matches!(
+            sim.bldgs[&restaurant],
+            BldgState::Snacked(StoreType::Restaurant)
+        ));
+        // The snack counts on the next delivery
+        sim.visit(house);
+        assert_eq!(20, sim.rules.score);
+        assert!(matches!(sim.bldgs[&house], BldgState::Done));
+
+        // Coming back doesn't give another snack or count the house twice
+        sim.visit(restaurant);
+        sim.visit(house);
+        sim.visit(BuildingID(2));
+        assert_eq!(1, sim.snacks);
+        assert_eq!(20, sim.rules.score);
+        assert_eq!(1, sim.rules.houses_delivered);
+        // Zen, so nothing ran low enough to need a refill
+        assert_eq!(0, sim.refills);
+    }
+}
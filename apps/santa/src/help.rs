@@ -0,0 +1,120 @@
+use geom::Polygon;
+use widgetry::{
+    Color, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel, Text, TextExt,
+    VerticalAlignment, Widget,
+};
+
+use crate::controls::{ControlScheme, KeyBindings};
+use crate::App;
+
+/// Press ? during a level to see the keys and a reminder of how things work. The game stops
+/// underneath until it's closed.
+pub struct HelpOverlay {
+    panel: Option<Panel>,
+}
+
+impl HelpOverlay {
+    pub fn new() -> HelpOverlay {
+        HelpOverlay { panel: None }
+    }
+
+    /// Returns true while the overlay is open, meaning the game shouldn't handle this event
+    pub fn event(&mut self, ctx: &mut EventCtx, app: &App) -> bool {
+        match self.panel {
+            Some(ref mut panel) => {
+                let close = match panel.event(ctx) {
+                    Outcome::Clicked(x) => x == "close",
+                    _ => false,
+                };
+                if close || ctx.input.pressed(Key::Slash) || app.session.keys.pressed(ctx, "pause")
+                {
+                    self.panel = None;
+                }
+                true
+            }
+            None => {
+                if ctx.input.pressed(Key::Slash) {
+                    // Build it each time, since the keys can be changed from the pause menu
+                    self.panel = Some(make_panel(ctx, app));
+                    return true;
+                }
+                false
+            }
+        }
+    }
+
+    pub fn draw(&self, g: &mut GfxCtx) {
+        if let Some(ref panel) = self.panel {
+            g.fork_screenspace();
+            g.draw_polygon(
+                Color::BLACK.alpha(0.6),
+                Polygon::rectangle(g.canvas.window_width, g.canvas.window_height),
+            );
+            g.unfork();
+            panel.draw(g);
+        }
+    }
+}
+
+fn make_panel(ctx: &mut EventCtx, app: &App) -> Panel {
+    let keys = &app.session.keys;
+    let hotkey = ctx.style().text_hotkey_color;
+
+    let mut bindings = Text::new();
+    for action in KeyBindings::ACTIONS {
+        bindings.add_line(Line(keys.describe(action)).fg(hotkey));
+        bindings.append(Line(format!(": {}", describe_action(app, action))));
+    }
+    bindings.add_line(Line("?").fg(hotkey));
+    bindings.append(Line(": show or hide this help"));
+
+    Panel::new_builder(Widget::col(vec![
+        Widget::row(vec![
+            Line("Help").small_heading().into_widget(ctx),
+            ctx.style().btn_close_widget(ctx),
+        ]),
+        "Keys".text_widget(ctx),
+        bindings.into_widget(ctx),
+        "How it works".text_widget(ctx),
+        Text::from_multiline(vec![
+            Line(
+                "Every delivery scores a present for each home in the building, so apartments are \
+                 worth the most. Quick deliveries in a row build a combo for extra presents.",
+            ),
+            Line(
+                "Each delivery also burns blood sugar. Ride past a store to refill it and restock \
+                 presents. With no blood sugar left, nothing gets delivered.",
+            ),
+            Line("Bike and bus lanes charge up your boost, but boosting burns blood sugar too."),
+        ])
+        .wrap_to_pct(ctx, 40)
+        .into_widget(ctx),
+    ]))
+    .aligned(HorizontalAlignment::Center, VerticalAlignment::Center)
+    .build(ctx)
+}
+
+// Moving depends on the control scheme
+fn describe_action(app: &App, action: &str) -> &'static str {
+    match (app.session.control_scheme, action) {
+        (ControlScheme::Rotate, "up") => "go forwards",
+        (ControlScheme::Rotate, "down") => "go backwards",
+        (ControlScheme::Rotate, "left") => "turn left",
+        (ControlScheme::Rotate, "right") => "turn right",
+        (ControlScheme::OneButton, "right") => "turn to the next direction",
+        (ControlScheme::OneButton, "up" | "down" | "left") => "not used with one-button controls",
+        (_, "up") => "move up",
+        (_, "down") => "move down",
+        (_, "left") => "move left",
+        (_, "right") => "move right",
+        (_, "boost") => "boost",
+        (_, "pause") => "pause",
+        (_, "switch present") => "switch presents",
+        (_, "restart") => "restart the level",
+        (_, "go home") => "go back to the start, for a price",
+        (_, "snack") => "grab a snack after passing a restaurant",
+        (_, "pick nearby") => "get ready the present a nearby house wants",
+        (_, "drop present") => "drop a present at the closest house",
+        _ => "",
+    }
+}
@@ -0,0 +1,335 @@
+use geom::{ArrowCap, Circle, Distance, Duration, PolyLine, Pt2D, Time};
+use map_gui::tools::{make_heatmap, HeatmapOptions};
+use map_model::connectivity::{self, Spot};
+use map_model::{BuildingID, DirectedRoadID, Direction, PathConstraints, RoadID};
+use widgetry::{Color, Drawable, EventCtx, GeomBatch, RewriteColor};
+
+use crate::buildings::{BldgState, Buildings};
+use crate::App;
+
+pub struct EnergylessArrow {
+    pub draw: Drawable,
+    started: Time,
+    last_update: Time,
+    all_stores: Vec<BuildingID>,
+}
+
+impl EnergylessArrow {
+    pub fn new(ctx: &EventCtx, started: Time, all_stores: Vec<BuildingID>) -> EnergylessArrow {
+        EnergylessArrow {
+            draw: Drawable::empty(ctx),
+            started,
+            last_update: Time::START_OF_DAY,
+            all_stores,
+        }
+    }
+
+    pub fn update(&mut self, ctx: &mut EventCtx, app: &App, sleigh: Pt2D) {
+        if self.last_update == app.time {
+            return;
+        }
+        self.last_update = app.time;
+        // Find the closest store as the crow -- or Santa -- flies. Point to the end of the
+        // driveway, since sometimes it's hard to quickly spot which road a building is connected
+        // to.
+        // TODO Or pathfind and show them that?
+        // Maps from the command line might not have any stores at all
+        let store = match self
+            .all_stores
+            .iter()
+            .min_by_key(|b| app.map.get_b(**b).driveway_geom.last_pt().fast_dist(sleigh))
+        {
+            Some(b) => app.map.get_b(*b),
+            None => {
+                return;
+            }
+        };
+
+        // Vibrate in size slightly
+        let period = Duration::seconds(0.5);
+        let pct = ((app.time - self.started) % period) / period;
+        // -1 to 1
+        let shift = (pct * std::f64::consts::PI).sin();
+        let thickness = Distance::meters(5.0 + shift);
+
+        let goto = store.driveway_geom.last_pt();
+        if let Some(draw) = draw_arrow(ctx, sleigh, goto, thickness, Color::RED.alpha(0.8)) {
+            self.draw = draw;
+        }
+    }
+}
+
+// Don't search through every building every single frame
+const HINT_SEARCH_PERIOD: Duration = Duration::const_seconds(1.0);
+
+/// Optionally points towards an undelivered building worth visiting, to help on unfamiliar maps.
+pub struct PresentHint {
+    pub draw: Drawable,
+    target: Option<BuildingID>,
+    last_search: Option<Time>,
+}
+
+impl PresentHint {
+    pub fn new(ctx: &EventCtx) -> PresentHint {
+        PresentHint {
+            draw: Drawable::empty(ctx),
+            target: None,
+            last_search: None,
+        }
+    }
+
+    pub fn update(&mut self, ctx: &mut EventCtx, app: &App, bldgs: &Buildings, sleigh: Pt2D) {
+        let target_delivered = self
+            .target
+            .map(|b| !matches!(bldgs.buildings[&b], BldgState::Undelivered(..)))
+            .unwrap_or(true);
+        if target_delivered
+            || self
+                .last_search
+                .map(|t| app.time - t >= HINT_SEARCH_PERIOD)
+                .unwrap_or(true)
+        {
+            self.last_search = Some(app.time);
+            // Balance how many presents a building needs against how far away it is. Don't let
+            // very close buildings dominate.
+            self.target = bldgs
+                .buildings
+                .iter()
+                .filter_map(|(b, state)| match state {
+                    BldgState::Undelivered(num_housing_units, _) => {
+                        let dist = app
+                            .map
+                            .get_b(*b)
+                            .label_center
+                            .dist_to(sleigh)
+                            .max(Distance::meters(50.0));
+                        Some((*b, (*num_housing_units as f64) / dist.inner_meters()))
+                    }
+                    _ => None,
+                })
+                .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+                .map(|(b, _)| b);
+        }
+
+        if let Some(b) = self.target {
+            let goto = app.map.get_b(b).label_center;
+            // Too close to point at, so don't leave an old arrow up
+            self.draw = draw_arrow(
+                ctx,
+                sleigh,
+                goto,
+                Distance::meters(3.0),
+                app.session.colors.score.alpha(0.8),
+            )
+            .unwrap_or_else(|| Drawable::empty(ctx));
+        } else {
+            self.draw = Drawable::empty(ctx);
+        }
+    }
+}
+
+// Start showing the edge of the map this close to it
+const MAP_EDGE_WARNING: Distance = Distance::const_meters(50.0);
+
+/// Outlines the edge of the map when a player gets close, so it's clear why they can't go further.
+pub struct MapEdge {
+    boundary: PolyLine,
+    pub draw: Drawable,
+    pub near: bool,
+}
+
+impl MapEdge {
+    pub fn new(ctx: &EventCtx, app: &App) -> MapEdge {
+        let polygon = app.map.get_boundary_polygon();
+        MapEdge {
+            boundary: PolyLine::unchecked_new(polygon.get_outer_ring().clone().into_points()),
+            draw: ctx.upload(GeomBatch::from(vec![(
+                Color::WHITE.alpha(0.5),
+                polygon.to_outline(Distance::meters(3.0)),
+            )])),
+            near: false,
+        }
+    }
+
+    pub fn update(&mut self, positions: &[Pt2D]) {
+        self.near = positions
+            .iter()
+            .any(|pt| self.boundary.project_pt(*pt).dist_to(*pt) < MAP_EDGE_WARNING);
+    }
+}
+
+// Recalculating the heatmap is slow on large maps, so don't do it after every single delivery
+const HEATMAP_UPDATE_PERIOD: Duration = Duration::const_seconds(1.0);
+
+/// Optionally shows where the most undelivered presents are, to help plan a route.
+pub struct DemandHeatmap {
+    pub draw: Drawable,
+    // Something was delivered since the last update
+    pub dirty: bool,
+    last_update: Option<Time>,
+}
+
+impl DemandHeatmap {
+    pub fn new(ctx: &EventCtx) -> DemandHeatmap {
+        DemandHeatmap {
+            draw: Drawable::empty(ctx),
+            dirty: true,
+            last_update: None,
+        }
+    }
+
+    pub fn update(&mut self, ctx: &mut EventCtx, app: &App, bldgs: &Buildings) {
+        if !self.dirty
+            || self
+                .last_update
+                .map(|t| app.time - t < HEATMAP_UPDATE_PERIOD)
+                .unwrap_or(false)
+        {
+            return;
+        }
+        self.dirty = false;
+        self.last_update = Some(app.time);
+
+        // Weight each building by how many presents it needs
+        let mut pts = Vec::new();
+        for (b, state) in &bldgs.buildings {
+            if let BldgState::Undelivered(num_housing_units, _) = state {
+                let pt = app.map.get_b(*b).label_center;
+                pts.extend(std::iter::repeat(pt).take(*num_housing_units));
+            }
+        }
+        let mut batch = GeomBatch::new();
+        // We don't have room for the legend
+        let _ = make_heatmap(
+            ctx,
+            &mut batch,
+            app.map.get_bounds(),
+            pts,
+            &HeatmapOptions::new().with_color_scheme(app.session.palette.heatmap_gradient()),
+        );
+        self.draw = ctx.upload(batch.color(RewriteColor::ChangeAlpha(0.5)));
+    }
+}
+
+// How many buildings to plan a route through
+const ROUTE_HINT_STOPS: usize = 8;
+// Don't consider buildings further than this from each stop
+const ROUTE_HINT_TIME_LIMIT: Duration = Duration::const_seconds(30.0 * 60.0);
+
+/// On demand, plans a route through the buildings needing the most presents, to show how travel
+/// time along the roads -- not just straight-line distance -- affects the order to visit them.
+/// The route doesn't update by itself; the player asks for a new one.
+pub struct RouteHint {
+    pub draw: Drawable,
+    stops: Vec<BuildingID>,
+}
+
+impl RouteHint {
+    pub fn new(
+        ctx: &mut EventCtx,
+        app: &App,
+        bldgs: &Buildings,
+        sleigh_roads: Vec<RoadID>,
+        sleigh: Pt2D,
+    ) -> RouteHint {
+        // Start with the biggest buildings, breaking ties consistently
+        let mut candidates: Vec<(usize, BuildingID)> = bldgs
+            .buildings
+            .iter()
+            .filter_map(|(b, state)| match state {
+                BldgState::Undelivered(num_housing_units, _) => Some((*num_housing_units, *b)),
+                _ => None,
+            })
+            .collect();
+        candidates
+            .sort_by_key(|(num_housing_units, b)| (std::cmp::Reverse(*num_housing_units), *b));
+        let mut candidates: Vec<BuildingID> = candidates
+            .into_iter()
+            .take(ROUTE_HINT_STOPS)
+            .map(|(_, b)| b)
+            .collect();
+
+        // Greedily go to the closest remaining candidate each time. The player can ride either
+        // way down a road, so start from both directions.
+        let mut starts = Vec::new();
+        for road in sleigh_roads {
+            for dir in [Direction::Fwd, Direction::Back] {
+                starts.push(Spot::DirectedRoad(DirectedRoadID { road, dir }));
+            }
+        }
+        let mut stops = Vec::new();
+        while !candidates.is_empty() {
+            let costs = connectivity::all_vehicle_costs_from(
+                &app.map,
+                starts,
+                ROUTE_HINT_TIME_LIMIT,
+                PathConstraints::Bike,
+            );
+            let next = match candidates
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, b)| costs.get(b).map(|cost| (idx, *cost)))
+                .min_by_key(|(_, cost)| *cost)
+            {
+                Some((idx, _)) => candidates.remove(idx),
+                // Everything left is unreachable from here
+                None => break,
+            };
+            stops.push(next);
+            starts = vec![Spot::Building(next)];
+        }
+
+        let mut batch = GeomBatch::new();
+        let color = app.session.colors.score;
+        let mut pts = vec![sleigh];
+        for b in &stops {
+            let pt = app.map.get_b(*b).label_center;
+            batch.push(color, Circle::new(pt, Distance::meters(3.0)).to_polygon());
+            pts.push(pt);
+        }
+        pts.dedup();
+        if let Ok(pl) = PolyLine::new(pts) {
+            batch.extend(
+                color,
+                pl.dashed_lines(
+                    Distance::meters(1.5),
+                    Distance::meters(4.0),
+                    Distance::meters(2.0),
+                ),
+            );
+        }
+
+        RouteHint {
+            draw: ctx.upload(batch),
+            stops,
+        }
+    }
+
+    /// Once every stop is delivered, the route isn't useful anymore
+    pub fn finished(&self, bldgs: &Buildings) -> bool {
+        self.stops
+            .iter()
+            .all(|b| !matches!(bldgs.buildings[b], BldgState::Undelivered(..)))
+    }
+}
+
+/// A short arrow starting near the sleigh, pointing towards something
+pub fn draw_arrow(
+    ctx: &mut EventCtx,
+    sleigh: Pt2D,
+    goto: Pt2D,
+    thickness: Distance,
+    color: Color,
+) -> Option<Drawable> {
+    let angle = sleigh.angle_to(goto);
+    // TODO When we're too close, we get an awkward arrowcap; the intention was for it to
+    // disappear...
+    PolyLine::new(vec![
+        sleigh.project_away(Distance::meters(20.0), angle),
+        goto,
+    ])
+    .and_then(|pl| pl.maybe_exact_slice(Distance::ZERO, Distance::meters(20.0).min(pl.length())))
+    .ok()
+    .and_then(|slice| slice.maybe_make_arrow(thickness, ArrowCap::Triangle))
+    .map(|arrow| ctx.upload(GeomBatch::from(vec![(color, arrow)])))
+}
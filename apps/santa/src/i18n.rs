@@ -0,0 +1,362 @@
+use serde::{Deserialize, Serialize};
+
+/// Languages for the game's own text. Names from the map, like streets and stores, follow the
+/// separate map language option.
+///
+/// Strings are looked up by their English version, so untranslated ones just fall back to
+/// English. Strings with `{}` are templates; fill them in with `tr_args`.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Locale {
+    English,
+    Spanish,
+}
+
+impl Default for Locale {
+    fn default() -> Locale {
+        Locale::English
+    }
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::English, Locale::Spanish];
+
+    /// In the language itself, so players can find theirs
+    pub fn name(self) -> &'static str {
+        match self {
+            Locale::English => "English",
+            Locale::Spanish => "Español",
+        }
+    }
+
+    pub fn tr(self, english: &'static str) -> &'static str {
+        let table = match self {
+            Locale::English => {
+                return english;
+            }
+            Locale::Spanish => SPANISH,
+        };
+        match table.iter().find(|(key, _)| *key == english) {
+            Some((_, translation)) => translation,
+            None => {
+                warn!("No {:?} translation for {}", self, english);
+                english
+            }
+        }
+    }
+
+    /// Translates a template, then replaces each `{}` with the next argument
+    pub fn tr_args(self, english: &'static str, args: &[String]) -> String {
+        let mut result = String::new();
+        let mut args = args.iter();
+        let mut pieces = self.tr(english).split("{}");
+        if let Some(first) = pieces.next() {
+            result.push_str(first);
+        }
+        for piece in pieces {
+            if let Some(arg) = args.next() {
+                result.push_str(arg);
+            }
+            result.push_str(piece);
+        }
+        result
+    }
+}
+
+const SPANISH: &[(&str, &str)] = &[
+    // Title screen
+    ("Start the tutorial", "Empezar el tutorial"),
+    ("Tutorial", "Tutorial"),
+    ("How to play", "Cómo jugar"),
+    ("Your own map", "Tu propio mapa"),
+    ("Achievements", "Logros"),
+    ("Settings", "Ajustes"),
+    ("Credits", "Créditos"),
+    ("Resume run on {}", "Continuar la partida en {}"),
+    // Before and after a level
+    ("Ready for {}?", "¿Listo para {}?"),
+    ("Goal: deliver {} presents", "Meta: entrega {} regalos"),
+    (
+        "Goal: deliver to {} buildings",
+        "Meta: entrega en {} edificios",
+    ),
+    ("Goal: finish {} streets", "Meta: completa {} calles"),
+    (
+        "Goal: deliver to every house",
+        "Meta: entrega en todas las casas",
+    ),
+    ("Time limit: {}", "Tiempo límite: {}"),
+    ("Results for {}", "Resultados de {}"),
+    ("You delivered {} presents", "Entregaste {} regalos"),
+    ("High scores:", "Mejores puntuaciones:"),
+    ("Difficulty: {}", "Dificultad: {}"),
+    ("easy", "fácil"),
+    ("normal", "normal"),
+    ("hard", "difícil"),
+    ("Back to title screen", "Volver a la pantalla de inicio"),
+    ("Watch replay", "Ver la repetición"),
+    ("Upgrade shop", "Tienda de mejoras"),
+    ("See how the run went", "Ver cómo fue la partida"),
+    ("Every delivery", "Todas las entregas"),
+    ("Coverage map", "Mapa de cobertura"),
+    ("Export summary (JSON)", "Exportar resumen (JSON)"),
+    ("Export path (GeoJSON)", "Exportar ruta (GeoJSON)"),
+    ("Export failed", "Error al exportar"),
+    ("Error", "Error"),
+    (
+        "This run couldn't be saved",
+        "No se pudo guardar esta partida",
+    ),
+    ("Level complete!", "¡Nivel completado!"),
+    ("house", "casa"),
+    ("apartment", "apartamento"),
+    ("store", "tienda"),
+    ("upzoned store", "tienda ampliada"),
+    ("delivered!", "¡entregado!"),
+    ("Not quite...", "Casi..."),
+    (
+        "You only delivered {} / {} presents",
+        "Solo entregaste {} / {} regalos",
+    ),
+    (
+        "You didn't deliver to {} buildings in time",
+        "No entregaste en {} edificios a tiempo",
+    ),
+    (
+        "You didn't finish {} streets in time",
+        "No completaste {} calles a tiempo",
+    ),
+    (
+        "You didn't reach every house in time",
+        "No llegaste a todas las casas a tiempo",
+    ),
+    (
+        "Review your route and try again.",
+        "Revisa tu ruta e inténtalo de nuevo.",
+    ),
+    (
+        "Hint: look for any apartments you missed!",
+        "Pista: ¡busca los apartamentos que te faltaron!",
+    ),
+    ("Thank you, Santa!", "¡Gracias, Papá Noel!"),
+    (
+        "You delivered {} presents, more than the goal of {}!",
+        "¡Entregaste {} regalos, más que la meta de {}!",
+    ),
+    (
+        "You delivered {} presents and met the goal!",
+        "¡Entregaste {} regalos y cumpliste la meta!",
+    ),
+    ("Wow, a new high score!", "¡Vaya, un nuevo récord!"),
+    (
+        "But can you beat the high score of {}?",
+        "¿Pero puedes superar el récord de {}?",
+    ),
+    (
+        "You beat your friend's challenge of {}!",
+        "¡Superaste el desafío de {} de tu amigo!",
+    ),
+    (
+        "Your friend's challenge of {} still stands",
+        "El desafío de {} de tu amigo sigue en pie",
+    ),
+    (
+        "Seed {} -- start with --seed={} to play the same setup again",
+        "Semilla {}: empieza con --seed={} para volver a jugar la misma partida",
+    ),
+    (
+        "Share this page's URL to challenge a friend to the same setup",
+        "Comparte la URL de esta página para desafiar a un amigo con la misma partida",
+    ),
+    ("OK", "Vale"),
+    (
+        "Copy a link to challenge a friend",
+        "Copiar un enlace para desafiar a un amigo",
+    ),
+    ("Submit to the leaderboard", "Enviar a la clasificación"),
+    ("{} is covered!", "¡{} está cubierto!"),
+    (
+        "Every house got its present, with {} to spare.",
+        "Cada casa recibió su regalo, y sobraron {}.",
+    ),
+    ("{} holds out", "{} resiste"),
+    (
+        "One house never got its present.",
+        "Una casa se quedó sin su regalo.",
+    ),
+    (
+        "{} houses never got their presents.",
+        "{} casas se quedaron sin sus regalos.",
+    ),
+    (
+        "There's nowhere to restock, so every present and every bite counts.",
+        "No hay dónde reabastecerse, así que cada regalo y cada bocado cuentan.",
+    ),
+    (
+        "Plan a route that doesn't double back.",
+        "Planea una ruta que no vuelva sobre sus pasos.",
+    ),
+    ("Choose where to start", "Elige dónde empezar"),
+    (
+        "Click a store to start next to it",
+        "Haz clic en una tienda para empezar a su lado",
+    ),
+    ("Random", "Al azar"),
+    ("Cancel", "Cancelar"),
+    // While playing
+    ("Complete Deliveries", "Entregas completadas"),
+    ("Completion", "Progreso"),
+    ("Blood sugar", "Azúcar en sangre"),
+    (
+        "Second player's blood sugar",
+        "Azúcar en sangre del segundo jugador",
+    ),
+    ("Wind", "Viento"),
+    ("Time", "Tiempo"),
+    ("Daily challenge, seed {}", "Desafío diario, semilla {}"),
+    ("Goal met! Keep going", "¡Meta cumplida! Sigue así"),
+    (
+        "Goal met! Ride off the map to continue",
+        "¡Meta cumplida! Sal del mapa para continuar",
+    ),
+    (
+        "SANTA'S HANGRY - grab some cookies from a store!",
+        "¡PAPÁ NOEL TIENE HAMBRE! Busca galletas en una tienda",
+    ),
+    (
+        "Low on blood sugar, refill soon!",
+        "Poco azúcar en sangre, ¡recarga pronto!",
+    ),
+    ("Time's up!", "¡Se acabó el tiempo!"),
+    ("Almost out of time!", "¡Casi no queda tiempo!"),
+    ("Every house delivered!", "¡Entregado en todas las casas!"),
+    ("Ran a red light!", "¡Te saltaste un semáforo en rojo!"),
+    ("Bonk!", "¡Pum!"),
+    ("Catching your breath...", "Recuperando el aliento..."),
+    ("Find a bike or bus lane", "Busca un carril bici o bus"),
+    ("Hold {} to boost", "Mantén {} para acelerar"),
+    ("Already delivered", "Ya entregado"),
+    ("Uphill: {}% grade", "Cuesta arriba: {}% de pendiente"),
+    ("Downhill: {}% grade", "Cuesta abajo: {}% de pendiente"),
+    ("{}, {} of {}", "{}, {} de {}"),
+    (
+        "Couldn't reach the race server",
+        "No se pudo conectar al servidor de carreras",
+    ),
+    (
+        "Connecting to the race server...",
+        "Conectando al servidor de carreras...",
+    ),
+    (
+        "Lost the race connection",
+        "Se perdió la conexión de la carrera",
+    ),
+    (
+        "Opponent delivered {} presents",
+        "El rival entregó {} regalos",
+    ),
+    (
+        "Your elf is now level {} -- faster, and helps with bigger houses",
+        "Tu elfo ahora es de nivel {}: más rápido, y ayuda con casas más grandes",
+    ),
+    (
+        "Can't go home for another {}",
+        "No puedes volver a casa hasta dentro de {}",
+    ),
+    (
+        "Go back to the start and refill, for {} presents?",
+        "¿Volver al inicio y recargar, por {} regalos?",
+    ),
+    ("Go home", "Volver a casa"),
+    ("Never mind", "Da igual"),
+    (
+        "Press {} to switch presents",
+        "Pulsa {} para cambiar de regalo",
+    ),
+    ("Double presents: {}s", "Regalos dobles: {}s"),
+    ("Speed boost: {}s", "Turbo: {}s"),
+    (
+        "Snack: {}x presents for {}s",
+        "Merienda: {}x regalos durante {}s",
+    ),
+    ("Press {} for a snack", "Pulsa {} para merendar"),
+    ("Snack! {}x presents", "¡Merienda! {}x regalos"),
+    (
+        "{} deliveries in the last minute",
+        "{} entregas en el último minuto",
+    ),
+    ("New best: {}!", "¡Nuevo récord: {}!"),
+    ("Finished in {}", "Terminado en {}"),
+    ("Achievement unlocked: {}", "Logro desbloqueado: {}"),
+    ("Combo x{} -- {}x presents!", "Combo x{}: ¡{}x regalos!"),
+    ("Waiting for {} {}s", "Esperando {} {}s"),
+    ("Out of {}s", "No quedan {}s"),
+    ("Wants a {}", "Quiere un {}"),
+    ("Finished {}! +{}", "¡{} terminado! +{}"),
+    ("Refilled {}", "Recargado {}"),
+    // Stores
+    ("Closed for the night", "Cerrado por la noche"),
+    (
+        "{}: ride past to refill {}% of blood sugar",
+        "{}: pasa por delante para recargar un {}% de azúcar en sangre",
+    ),
+    (
+        "On the shelves: {} blood sugar, {} presents",
+        "En los estantes: {} de azúcar en sangre, {} regalos",
+    ),
+    ("Sold out for now", "Agotado por ahora"),
+    ("Running low", "Quedan pocas existencias"),
+    ("Well stocked", "Bien abastecido"),
+    (
+        "Press {} while riding past for a snack: {}x presents for {}",
+        "Pulsa {} al pasar para merendar: {}x regalos durante {}",
+    ),
+    // Pause menu
+    ("Game Paused", "Juego en pausa"),
+    ("Resume", "Continuar"),
+    ("Restart level", "Reiniciar nivel"),
+    ("Photo mode", "Modo foto"),
+    ("Quit to title", "Salir al inicio"),
+    // Settings
+    ("Audio", "Sonido"),
+    ("Controls", "Controles"),
+    ("Camera", "Cámara"),
+    ("Camera lag", "Retraso de la cámara"),
+    ("Camera lookahead", "Anticipación de la cámara"),
+    ("Graphics", "Gráficos"),
+    ("Snow", "Nieve"),
+    ("Accessibility", "Accesibilidad"),
+    ("Online", "En línea"),
+    ("Music volume", "Volumen de la música"),
+    ("Sound effects volume", "Volumen de los efectos"),
+    ("Colors", "Colores"),
+    ("Language", "Idioma"),
+    ("Text size", "Tamaño del texto"),
+    ("Leaderboard server", "Servidor de clasificación"),
+    ("Race server", "Servidor de carreras"),
+    ("Your name", "Tu nombre"),
+    // Key bindings
+    ("Press a key to {}", "Pulsa una tecla para {}"),
+    ("{} is already used to {}", "{} ya se usa para {}"),
+    ("change", "cambiar"),
+    ("Reset to defaults", "Restablecer"),
+    ("Done", "Hecho"),
+    ("move up", "moverte arriba"),
+    ("move down", "moverte abajo"),
+    ("move left", "moverte a la izquierda"),
+    ("move right", "moverte a la derecha"),
+    ("boost", "acelerar"),
+    ("pause", "pausar"),
+    ("switch present type", "cambiar de tipo de regalo"),
+    ("restart the level", "reiniciar el nivel"),
+    ("go back to the start", "volver al inicio"),
+    ("grab a snack at a restaurant", "merendar en un restaurante"),
+    (
+        "get ready for the next house nearby",
+        "prepararte para la siguiente casa cercana",
+    ),
+    (
+        "drop a present at the closest house",
+        "dejar un regalo en la casa más cercana",
+    ),
+    ("Demand surge in {}!", "¡Aumento de demanda en {}!"),
+];
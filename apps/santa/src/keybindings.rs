@@ -0,0 +1,173 @@
+use widgetry::{EventCtx, GfxCtx, Key, Line, Panel, SimpleState, State, Text, TextExt, Widget};
+
+use crate::controls::KeyBindings;
+use crate::{App, Transition};
+
+/// Lets the player remap the keys used in the game.
+pub struct KeyBindingsEditor {
+    // When we're waiting for the player to press a key for this action
+    waiting_for: Option<&'static str>,
+}
+
+impl KeyBindingsEditor {
+    pub fn new_state(ctx: &mut EventCtx, app: &App) -> Box<dyn State<App>> {
+        Self::new_state_waiting(ctx, app, None, None)
+    }
+
+    /// `problem` explains why the last key pressed wasn't used
+    fn new_state_waiting(
+        ctx: &mut EventCtx,
+        app: &App,
+        waiting_for: Option<&'static str>,
+        problem: Option<String>,
+    ) -> Box<dyn State<App>> {
+        let locale = app.session.locale;
+        let mut col = vec![Widget::row(vec![
+            Line(locale.tr("Controls"))
+                .big_heading_plain()
+                .into_widget(ctx),
+            ctx.style().btn_close_widget(ctx),
+        ])];
+
+        if let Some(action) = waiting_for {
+            col.push(
+                Text::from(
+                    Line(locale.tr_args(
+                        "Press a key to {}",
+                        &[locale.tr(describe_action(action)).to_string()],
+                    ))
+                    .small_heading(),
+                )
+                .into_widget(ctx),
+            );
+            if let Some(problem) = problem {
+                col.push(Line(problem).fg(app.session.colors.bad).into_widget(ctx));
+            }
+            col.push(
+                ctx.style()
+                    .btn_outline
+                    .text(locale.tr("Cancel"))
+                    .build_widget(ctx, "Cancel"),
+            );
+        } else {
+            for action in KeyBindings::ACTIONS {
+                col.push(Widget::row(vec![
+                    locale
+                        .tr(describe_action(action))
+                        .text_widget(ctx)
+                        .centered_vert(),
+                    Line(app.session.keys.describe(action))
+                        .fg(ctx.style().text_hotkey_color)
+                        .into_widget(ctx)
+                        .centered_vert(),
+                    ctx.style()
+                        .btn_outline
+                        .text(locale.tr("change"))
+                        .build_widget(ctx, format!("change {}", action))
+                        .align_right(),
+                ]));
+            }
+            col.push(Widget::row(vec![
+                ctx.style()
+                    .btn_outline
+                    .text(locale.tr("Reset to defaults"))
+                    .disabled(app.session.keys == KeyBindings::default())
+                    .build_widget(ctx, "Reset to defaults"),
+                ctx.style()
+                    .btn_solid_primary
+                    .text(locale.tr("Done"))
+                    .hotkey(Key::Enter)
+                    .build_widget(ctx, "Done")
+                    .align_right(),
+            ]));
+        }
+
+        <dyn SimpleState<_>>::new_state(
+            Panel::new_builder(Widget::col(col)).build(ctx),
+            Box::new(KeyBindingsEditor { waiting_for }),
+        )
+    }
+}
+
+impl SimpleState<App> for KeyBindingsEditor {
+    fn on_click(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &mut App,
+        x: &str,
+        _: &mut Panel,
+    ) -> Transition {
+        match x {
+            "close" | "Done" => Transition::Pop,
+            "Cancel" => Transition::Replace(Self::new_state(ctx, app)),
+            "Reset to defaults" => {
+                app.session.keys = KeyBindings::default();
+                app.session.save();
+                Transition::Replace(Self::new_state(ctx, app))
+            }
+            x => {
+                let action = x.strip_prefix("change ").unwrap();
+                let action = KeyBindings::ACTIONS
+                    .into_iter()
+                    .find(|a| *a == action)
+                    .unwrap();
+                Transition::Replace(Self::new_state_waiting(ctx, app, Some(action), None))
+            }
+        }
+    }
+
+    fn other_event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        app.session.update_music(ctx);
+
+        if let Some(action) = self.waiting_for {
+            if let Some(key) = ctx.input.any_pressed() {
+                // Each key only does one thing, so don't let the new binding quietly take over
+                // another action
+                if let Some(other) = app.session.keys.action_using(key) {
+                    if other != action {
+                        let locale = app.session.locale;
+                        let problem = locale.tr_args(
+                            "{} is already used to {}",
+                            &[
+                                key.describe(),
+                                locale.tr(describe_action(other)).to_string(),
+                            ],
+                        );
+                        return Transition::Replace(Self::new_state_waiting(
+                            ctx,
+                            app,
+                            Some(action),
+                            Some(problem),
+                        ));
+                    }
+                }
+                app.session.keys.set(action, key);
+                app.session.save();
+                return Transition::Replace(Self::new_state(ctx, app));
+            }
+        }
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        app.session.music.draw(g);
+    }
+}
+
+fn describe_action(action: &str) -> &'static str {
+    match action {
+        "up" => "move up",
+        "down" => "move down",
+        "left" => "move left",
+        "right" => "move right",
+        "boost" => "boost",
+        "pause" => "pause",
+        "switch present" => "switch present type",
+        "restart" => "restart the level",
+        "go home" => "go back to the start",
+        "snack" => "grab a snack at a restaurant",
+        "pick nearby" => "get ready for the next house nearby",
+        "drop present" => "drop a present at the closest house",
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::HashSet;
+
+use geom::Duration;
+use map_model::BuildingID;
+use widgetry::{EventCtx, State, UpdateType};
+
+use crate::game::Game;
+use crate::levels::Level;
+use crate::mode::Mode;
+use crate::vehicles::Vehicle;
+use crate::App;
+
+/// In kiosk mode, anywhere nobody's touched for this long goes back to the title screen
+pub const KIOSK_IDLE_RESET: Duration = Duration::const_seconds(90.0);
+/// In kiosk mode, how long the results after a run stay up before moving on
+pub const KIOSK_RESULTS_TIME: Duration = Duration::const_seconds(15.0);
+
+/// Counts how long it's been since the last input
+pub struct IdleTimer {
+    idle: Duration,
+}
+
+impl IdleTimer {
+    pub fn new() -> IdleTimer {
+        IdleTimer {
+            idle: Duration::ZERO,
+        }
+    }
+
+    /// True once nothing's happened for `limit`, then starts counting again
+    pub fn event(&mut self, ctx: &mut EventCtx, limit: Duration) -> bool {
+        if let Some(dt) = ctx.input.nonblocking_is_update_event() {
+            self.idle += dt;
+            if self.idle >= limit {
+                self.idle = Duration::ZERO;
+                return true;
+            }
+        } else {
+            self.idle = Duration::ZERO;
+        }
+        // Keep counting, even without any input
+        ctx.request_update(UpdateType::Game);
+        false
+    }
+
+    /// For activity that isn't a new input, like holding down a key
+    pub fn reset(&mut self) {
+        self.idle = Duration::ZERO;
+    }
+}
+
+/// Everything needed to play the same level again once the results have been up for a while
+pub struct KioskRestart {
+    level: Level,
+    vehicle: String,
+    upzones: HashSet<BuildingID>,
+    mode: Mode,
+}
+
+impl KioskRestart {
+    pub fn new(
+        level: Level,
+        vehicle: String,
+        upzones: HashSet<BuildingID>,
+        mode: Mode,
+    ) -> KioskRestart {
+        KioskRestart {
+            level,
+            vehicle,
+            upzones,
+            mode,
+        }
+    }
+
+    pub fn start(self, ctx: &mut EventCtx, app: &mut App) -> Box<dyn State<App>> {
+        Game::new_state(
+            ctx,
+            app,
+            self.level,
+            Vehicle::get(&self.vehicle),
+            self.upzones,
+            self.mode,
+            None,
+        )
+    }
+}
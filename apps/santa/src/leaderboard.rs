@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+use abstutil::prettyprint_usize;
+use widgetry::tools::{FutureLoader, PopupMsg};
+use widgetry::{EventCtx, State};
+
+use crate::mode::Mode;
+use crate::{App, Transition};
+
+// How many of the top scores to ask for
+const TOP_N: usize = 10;
+
+/// An optional online leaderboard. Nothing is ever sent unless the player turns this on in the
+/// settings and points it at a server.
+#[derive(Serialize, Deserialize, Default)]
+pub struct LeaderboardSettings {
+    pub enabled: bool,
+    /// The server to talk to. Scores are POSTed to `{url}/scores`, which responds with the top
+    /// scores for the same map, level, and mode.
+    pub url: String,
+    /// Shown next to scores on the leaderboard
+    pub player_name: String,
+}
+
+impl LeaderboardSettings {
+    pub fn is_active(&self) -> bool {
+        self.enabled && !self.url.is_empty()
+    }
+}
+
+#[derive(Serialize)]
+struct Submission {
+    player_name: String,
+    score: usize,
+    map: String,
+    level: String,
+    mode: String,
+    version: String,
+    top_n: usize,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    player_name: String,
+    score: usize,
+}
+
+/// Sends the score to the leaderboard, then shows the top scores.
+pub fn submit(
+    ctx: &mut EventCtx,
+    app: &App,
+    level: String,
+    mode: Mode,
+    score: usize,
+) -> Box<dyn State<App>> {
+    let url = format!(
+        "{}/scores",
+        app.session.leaderboard.url.trim_end_matches('/')
+    );
+    let submission = Submission {
+        player_name: app.session.leaderboard.player_name.clone(),
+        score,
+        map: app.map.get_name().as_filename(),
+        level,
+        mode: format!("{:?}", mode),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        top_n: TOP_N,
+    };
+
+    let (_, outer_progress_rx) = futures_channel::mpsc::channel(1);
+    let (_, inner_progress_rx) = futures_channel::mpsc::channel(1);
+    FutureLoader::<App, Vec<Entry>>::new_state(
+        ctx,
+        Box::pin(async move {
+            let resp = abstio::http_post(url, serde_json::to_string(&submission)?).await?;
+            let entries: Vec<Entry> = serde_json::from_str(&resp)?;
+            let wrapper: Box<dyn Send + FnOnce(&App) -> Vec<Entry>> = Box::new(move |_| entries);
+            Ok(wrapper)
+        }),
+        outer_progress_rx,
+        inner_progress_rx,
+        "Contacting the leaderboard",
+        Box::new(|ctx, _, result| {
+            Transition::Replace(match result {
+                Ok(entries) => PopupMsg::new_state(
+                    ctx,
+                    "Top scores",
+                    entries
+                        .into_iter()
+                        .enumerate()
+                        .map(|(idx, entry)| {
+                            format!(
+                                "{}) {}: {}",
+                                idx + 1,
+                                entry.player_name,
+                                prettyprint_usize(entry.score)
+                            )
+                        })
+                        .collect(),
+                ),
+                Err(err) => PopupMsg::new_state(
+                    ctx,
+                    "Couldn't reach the leaderboard",
+                    vec![err.to_string()],
+                ),
+            })
+        }),
+    )
+}
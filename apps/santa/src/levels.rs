@@ -1,7 +1,14 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 use abstio::MapName;
+use abstutil::Timer;
 use geom::{Duration, LonLat};
+use map_model::Map;
+
+use crate::buildings::{closest_intersection, StoreType};
 
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
 pub struct Level {
@@ -13,6 +20,13 @@ pub struct Level {
     pub minimap_zoom: usize,
     pub time_limit: Duration,
     pub goal: usize,
+    /// Ignore the time limit and goal, and instead work them out from the map. This is meant for
+    /// levels added by modding on maps that haven't been tuned by hand.
+    #[serde(default)]
+    pub scale_to_map: bool,
+    /// Extra rules, only used by custom levels so far
+    #[serde(default)]
+    pub script: Script,
 
     pub unlock_upzones: usize,
     pub unlock_vehicles: Vec<String>,
@@ -32,6 +46,8 @@ impl Level {
                 minimap_zoom: 1,
                 time_limit: Duration::seconds(90.0),
                 goal: 350,
+                scale_to_map: false,
+                script: Script::default(),
 
                 unlock_upzones: 1,
                 unlock_vehicles: vec![],
@@ -47,6 +63,8 @@ impl Level {
                 minimap_zoom: 1,
                 time_limit: Duration::minutes(2),
                 goal: 1500,
+                scale_to_map: false,
+                script: Script::default(),
 
                 unlock_upzones: 1,
                 unlock_vehicles: vec!["cargo bike".to_string()],
@@ -62,6 +80,8 @@ impl Level {
                 minimap_zoom: 2,
                 time_limit: Duration::minutes(3),
                 goal: 1500,
+                scale_to_map: false,
+                script: Script::default(),
 
                 unlock_upzones: 1,
                 unlock_vehicles: vec!["sleigh".to_string()],
@@ -77,6 +97,8 @@ impl Level {
                 minimap_zoom: 1,
                 time_limit: Duration::minutes(3),
                 goal: 1000,
+                scale_to_map: false,
+                script: Script::default(),
 
                 unlock_upzones: 1,
                 unlock_vehicles: vec![],
@@ -92,6 +114,8 @@ impl Level {
                 minimap_zoom: 1,
                 time_limit: Duration::minutes(3),
                 goal: 1500,
+                scale_to_map: false,
+                script: Script::default(),
 
                 unlock_upzones: 1,
                 unlock_vehicles: vec![],
@@ -107,6 +131,8 @@ impl Level {
                 minimap_zoom: 1,
                 time_limit: Duration::seconds(90.0),
                 goal: 1300,
+                scale_to_map: false,
+                script: Script::default(),
 
                 unlock_upzones: 3,
                 unlock_vehicles: vec![],
@@ -122,10 +148,253 @@ impl Level {
                 minimap_zoom: 2,
                 time_limit: Duration::minutes(4),
                 goal: 5000,
+                scale_to_map: false,
+                script: Script::default(),
 
                 unlock_upzones: 5,
                 unlock_vehicles: vec![],
             },
         ]
     }
+
+    /// A short level just for learning the controls. It isn't part of `all`, so it has no high
+    /// scores and unlocks nothing.
+    pub fn tutorial() -> Level {
+        Level {
+            title: "Tutorial".to_string(),
+            description: "Learn the ropes in Queen Anne".to_string(),
+            map: MapName::seattle("qa"),
+            music: "jingle_bells".to_string(),
+            start: LonLat::new(-122.3649489, 47.6395838),
+            minimap_zoom: 1,
+            // The tutorial ignores the time limit
+            time_limit: Duration::minutes(10),
+            goal: 50,
+            scale_to_map: false,
+            script: Script::default(),
+
+            unlock_upzones: 0,
+            unlock_vehicles: vec![],
+        }
+    }
+
+    /// Unlocked by beating every other level. Montlake is cut off by water on three sides, so
+    /// with the bridges up it's an island with nowhere to restock.
+    pub fn boss() -> Level {
+        Level {
+            title: "Montlake Island".to_string(),
+            description: "The bridges are up and every store has closed for the night. Reach \
+                          every single house with only what's in your sack!"
+                .to_string(),
+            map: MapName::seattle("montlake"),
+            music: "carol_bells".to_string(),
+            start: LonLat::new(-122.3020559, 47.639528),
+            minimap_zoom: 1,
+            time_limit: Duration::seconds(150.0),
+            // Every present on the map, worked out once it's loaded
+            goal: 0,
+            scale_to_map: false,
+            script: Script {
+                no_stores: true,
+                presents: Some(30),
+                win: WinCondition::EveryHouse,
+                ..Default::default()
+            },
+
+            unlock_upzones: 0,
+            unlock_vehicles: vec![],
+        }
+    }
+
+    /// A level on any map, for trying out maps that don't have a level yet. The goal and time
+    /// limit get worked out from the map, and the player starts near the middle.
+    pub fn for_map(map: &Map) -> Level {
+        let center = map.get_boundary_polygon().center();
+        let start = map
+            .all_intersections()
+            .iter()
+            .min_by_key(|i| i.polygon.center().dist_to(center))
+            .expect("Map has no intersections")
+            .polygon
+            .center();
+        Level {
+            title: map.get_name().map.clone(),
+            description: format!("Deliver presents around {}", map.get_name().describe()),
+            map: map.get_name().clone(),
+            music: "jingle_bells".to_string(),
+            start: start.to_gps(map.get_gps_bounds()),
+            minimap_zoom: 1,
+            // Replaced by scale_to_map
+            time_limit: Duration::ZERO,
+            goal: 0,
+            scale_to_map: true,
+            script: Script::default(),
+
+            unlock_upzones: 0,
+            unlock_vehicles: vec![],
+        }
+    }
+}
+
+/// Level authors can script what happens partway through a level and how to win it.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct Script {
+    /// The OSM ID of a store to start next to, instead of at `start`
+    #[serde(default)]
+    pub depot: Option<i64>,
+    /// Each happens this long after the level starts. Sorted by time.
+    #[serde(default)]
+    pub events: Vec<(Duration, ScriptedEvent)>,
+    #[serde(default)]
+    pub win: WinCondition,
+    /// Stores don't refill anything, and going home doesn't either
+    #[serde(default)]
+    pub no_stores: bool,
+    /// Carry this many of each kind of present, instead of the usual amount
+    #[serde(default)]
+    pub presents: Option<usize>,
+    /// The wind blows this many times as hard as usual
+    #[serde(default)]
+    pub wind_multiplier: Option<f64>,
+    /// The vehicle holds this many times as much blood sugar as usual
+    #[serde(default)]
+    pub energy_multiplier: Option<f64>,
+    /// It's as dark as midnight the whole time
+    #[serde(default)]
+    pub night: bool,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub enum ScriptedEvent {
+    /// Shown in the middle of the screen
+    Message(String),
+    /// The wind turns to blow towards this many degrees, clockwise from east
+    WindShift(f64),
+    /// Every delivery counts double for this long
+    DoubleScore(Duration),
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub enum WinCondition {
+    /// Deliver the goal's worth of presents
+    Score,
+    /// Deliver to this many buildings, no matter how big
+    Houses(usize),
+    /// Deliver to every house along this many streets
+    Zones(usize),
+    /// Deliver to every house on the map. The level ends as soon as the last one is done.
+    EveryHouse,
+}
+
+impl Default for WinCondition {
+    fn default() -> WinCondition {
+        WinCondition::Score
+    }
+}
+
+impl Level {
+    /// Levels written by hand in the player's data directory. Any that don't make sense are
+    /// skipped, with a warning.
+    pub fn load_custom() -> Vec<Level> {
+        let levels: Vec<Level> = match abstio::maybe_read_json(
+            abstio::path_player("santa_custom_levels.json"),
+            &mut Timer::throwaway(),
+        ) {
+            Ok(levels) => levels,
+            Err(err) => {
+                // Most players won't have any
+                debug!("No custom levels: {}", err);
+                return Vec::new();
+            }
+        };
+
+        let mut titles: HashSet<String> = Level::all().into_iter().map(|l| l.title).collect();
+        titles.insert(Level::tutorial().title);
+        titles.insert(Level::boss().title);
+        let mut valid = Vec::new();
+        for level in levels {
+            if let Err(err) = level.validate() {
+                warn!("Skipping custom level {}: {}", level.title, err);
+            } else if !titles.insert(level.title.clone()) {
+                warn!("Skipping custom level {}: the title is taken", level.title);
+            } else {
+                valid.push(level);
+            }
+        }
+        valid
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.title.is_empty() {
+            bail!("No title");
+        }
+        if !self.scale_to_map {
+            if self.goal == 0 && self.script.win != WinCondition::EveryHouse {
+                bail!("The goal has to be more than 0");
+            }
+            if self.time_limit <= Duration::ZERO {
+                bail!("The time limit has to be more than 0");
+            }
+        }
+        match self.script.win {
+            WinCondition::Score | WinCondition::EveryHouse => {}
+            WinCondition::Houses(n) | WinCondition::Zones(n) => {
+                if n == 0 {
+                    bail!("Winning has to take at least one delivery");
+                }
+            }
+        }
+        let mut last = Duration::ZERO;
+        if self.script.presents == Some(0) {
+            bail!("Santa has to carry at least one of each present");
+        }
+        for (t, _) in &self.script.events {
+            if *t < last {
+                bail!("Events have to be in order");
+            }
+            if !self.scale_to_map && *t > self.time_limit {
+                bail!("An event at {} happens after the time limit", t);
+            }
+            last = *t;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if !abstio::file_exists(self.map.path()) {
+            bail!("{} isn't downloaded", self.map.describe());
+        }
+        Ok(())
+    }
+
+    /// If the script names a depot, start next to it. Falls back to `start` if the depot isn't
+    /// on the map or isn't a store.
+    pub fn resolve_depot(&mut self, map: &Map) {
+        let id = match self.script.depot {
+            Some(id) => id,
+            None => {
+                return;
+            }
+        };
+        match map
+            .all_buildings()
+            .iter()
+            .find(|b| b.orig_id.inner_id() == id)
+        {
+            Some(b)
+                if b.amenities
+                    .iter()
+                    .any(|a| StoreType::from_amenity(&a.amenity_type).is_some()) =>
+            {
+                let i = closest_intersection(map, b.id);
+                self.start = map.get_i(i).polygon.center().to_gps(map.get_gps_bounds());
+            }
+            Some(_) => {
+                warn!("Depot {} isn't a store, so starting at the usual spot", id);
+            }
+            None => {
+                warn!(
+                    "Depot {} isn't on the map, so starting at the usual spot",
+                    id
+                );
+            }
+        }
+    }
 }
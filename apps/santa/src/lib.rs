@@ -1,44 +1,219 @@
+#![allow(clippy::too_many_arguments)]
+
 #[macro_use]
 extern crate anyhow;
 #[macro_use]
 extern crate log;
 
+use anyhow::Result;
+use structopt::StructOpt;
+
 use widgetry::Settings;
 
+mod achievements;
 mod after_level;
 mod animation;
+mod arcade;
+mod attract;
 mod before_level;
+mod bot;
 mod buildings;
+mod bus_stops;
+mod camera;
+mod campaign;
+mod capture;
+mod console;
 mod controls;
+mod coverage;
+#[cfg(not(target_arch = "wasm32"))]
+mod custom_map;
+mod difficulty;
+mod elf;
+mod export;
+mod featured;
+mod fog;
 mod game;
+mod gamepad;
+mod ghost;
+#[cfg(feature = "headless")]
+pub mod headless;
+mod help;
+mod hints;
+mod i18n;
+mod keybindings;
+mod kiosk;
+mod leaderboard;
 mod levels;
+mod load_map;
 mod meters;
+mod mode;
 mod music;
+mod nearby;
+mod obstacles;
+mod pause;
+mod perf;
+mod photo;
+mod pickups;
 mod player;
+mod plow;
+mod race;
+mod replay;
+mod rules;
+mod saved_run;
 mod session;
+mod settings;
+mod share;
+mod shop;
+mod signals;
+mod stats;
+mod stats_feed;
+mod stock;
+mod ticker;
 mod title;
+mod touch;
+mod trail;
+mod tuning;
+mod tutorial;
 mod vehicles;
+mod wind;
+mod windows;
+mod zones;
 
 type App = map_gui::SimpleApp<session::Session>;
 type Transition = widgetry::Transition<App>;
 
+#[derive(StructOpt)]
+#[structopt(name = "santa", about = "15-minute Santa")]
+struct Args {
+    /// Seeds all of the randomness in every level, instead of the usual seed for each mode. The
+    /// same map and seed always set up the same stores, presents, pickups, wind, and obstacles.
+    #[structopt(long)]
+    seed: Option<u64>,
+    /// Skip the title screen and go straight to setting up this level. Give its number, starting
+    /// from 1, or its title.
+    #[structopt(long)]
+    level: Option<String>,
+    /// Skip the title screen and play on the map at this path. Maps without a level of their own
+    /// get a goal and time limit worked out from their size.
+    #[structopt(long)]
+    map: Option<String>,
+    /// Start in this mode: normal, endless, time_trial, daily, co_op, race, explore, or zen
+    #[structopt(long, parse(try_from_str = mode::Mode::parse))]
+    mode: Option<mode::Mode>,
+    /// Steer with instant, rotate, onebutton, or mouse controls
+    #[structopt(long, parse(try_from_str = controls::ControlScheme::parse))]
+    controls: Option<controls::ControlScheme>,
+    /// A score to beat, from a link someone shared after their run
+    #[structopt(long)]
+    challenge: Option<usize>,
+    /// For unattended public demos. Nothing gets saved, there's no way to leave the game or load
+    /// another map, runs start over by themselves, and it goes back to the title screen when
+    /// nobody's playing.
+    #[structopt(long)]
+    kiosk: bool,
+    /// Serve the score, energy, position, and deliveries of the current run over a WebSocket on
+    /// this local port, for stream overlays and dashboards
+    #[structopt(long)]
+    stats_port: Option<u16>,
+    /// Let a bot drive. It goes for whichever house is worth the most presents for the distance,
+    /// and refills at the nearest store when it runs low.
+    #[structopt(long)]
+    bot: bool,
+}
+
+impl Args {
+    // The built-in level asked for, if any
+    fn level(&self) -> Result<Option<levels::Level>> {
+        let name = match self.level {
+            Some(ref name) => name,
+            None => {
+                return Ok(None);
+            }
+        };
+        let all = levels::Level::all();
+        let found = match name.parse::<usize>() {
+            Ok(idx) => idx.checked_sub(1).and_then(|idx| all.get(idx)),
+            Err(_) => all.iter().find(|level| &level.title == name),
+        };
+        match found {
+            Some(level) => Ok(Some(level.clone())),
+            None => bail!(
+                "Invalid --level={}. Choices: 1 to {}, or one of {}",
+                name,
+                all.len(),
+                all.into_iter()
+                    .map(|level| level.title)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+
+    fn map(&self) -> Result<Option<abstio::MapName>> {
+        match self.map {
+            Some(ref path) => match abstio::MapName::from_path(path) {
+                Some(name) => Ok(Some(name)),
+                None => bail!("Invalid --map={}. Give the path to a map file.", path),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
 pub fn main() {
     let settings = Settings::new("15-minute Santa");
     run(settings);
 }
 
 fn run(mut settings: Settings) {
+    let args = Args::from_iter(abstutil::cli_args());
+    // A typo on the command line shouldn't look like a crash
+    let start_level = match args.level() {
+        Ok(level) => level,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let custom_map = match args.map() {
+        Ok(map) => map,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let start_mode = args.mode;
     let mut opts = map_gui::options::Options::load_or_default();
     opts.color_scheme = map_gui::colors::ColorSchemeChoice::NightMode;
-    // Note we don't take any CLI arguments at all. Always start on the first level's map
+    // Always start on the first level's map
 
     settings = settings
         .read_svg(Box::new(abstio::slurp_bytes))
         .window_icon(abstio::path("system/assets/pregame/icon.png"))
         .canvas_settings(opts.canvas_settings.clone());
-    widgetry::run(settings, |ctx| {
-        let session = session::Session::load();
-        session.save();
+    widgetry::run(settings, move |ctx| {
+        let mut session = session::Session::load();
+        session.kiosk = args.kiosk;
+        session.bot = args.bot;
+        // Kiosk mode never touches santa.json, not even to create it
+        if !args.kiosk {
+            session.save();
+        }
+        session.seed_override = args.seed;
+        session.challenge = args.challenge;
+        if let Some(port) = args.stats_port {
+            match stats_feed::StatsFeed::start(port) {
+                Ok(feed) => {
+                    session.stats_feed = feed;
+                }
+                Err(err) => {
+                    warn!("Couldn't serve live stats on port {}: {}", port, err);
+                }
+            }
+        }
+        if let Some(scheme) = args.controls {
+            session.control_scheme = scheme;
+        }
 
         // On native, we may not have this file. Start with a blank map if so. When we try to pick
         // a level on the title screen, we'll download it if needed.
@@ -48,15 +223,39 @@ fn run(mut settings: Settings) {
         {
             start_map = None;
         }
+        // A map from the command line has to exist already
+        if let Some(ref name) = custom_map {
+            start_map = Some(name.clone());
+        }
 
-        map_gui::SimpleApp::new(ctx, opts, start_map, None, session, |ctx, app| {
+        map_gui::SimpleApp::new(ctx, opts, start_map, None, session, move |ctx, app| {
             if app.opts.dev {
                 app.session.unlock_all();
             }
             app.session.music = music::Music::start(ctx, app.session.play_music, "jingle_bells");
             app.session.music.specify_volume(music::OUT_OF_GAME);
+            app.session
+                .music
+                .set_player_volume(app.session.music_volume, app.session.sound_volume);
+            app.session.gamepad = gamepad::Gamepad::new();
 
-            vec![title::TitleScreen::new_state(ctx, app)]
+            // Going back from the level setup should still reach the title screen
+            let level = match start_level {
+                Some(ref level) => Some(level.clone()),
+                None if custom_map.is_some() => Some(
+                    levels::Level::all()
+                        .into_iter()
+                        .find(|level| &level.map == app.map.get_name())
+                        .unwrap_or_else(|| levels::Level::for_map(&app.map)),
+                ),
+                None => None,
+            };
+            let mut states = vec![title::TitleScreen::new_state(ctx, app)];
+            if let Some(level) = level {
+                let mode = start_mode.unwrap_or(app.session.last_mode);
+                states.push(before_level::Picker::new_state(ctx, app, level, mode));
+            }
+            states
         })
     });
 }
@@ -0,0 +1,210 @@
+use abstio::MapName;
+use map_gui::load::MapLoader;
+use widgetry::{EventCtx, State};
+
+use crate::{App, Transition};
+
+/// Switches to another map, then calls `on_load`. On native, big maps take a while to read, so
+/// this happens on another thread, with a progress bar and a way to cancel and go back to
+/// whatever was there before -- like the game on the current map.
+pub fn load_map(
+    ctx: &mut EventCtx,
+    app: &App,
+    name: MapName,
+    on_load: Box<dyn FnOnce(&mut EventCtx, &mut App) -> Transition>,
+) -> Box<dyn State<App>> {
+    #[cfg(not(target_arch = "wasm32"))]
+    if app.map.get_name() != &name {
+        return native::LoadMap::new_state(ctx, app, name, on_load);
+    }
+    // On the web, the map is already fetched asynchronously
+    MapLoader::new_state(ctx, app, name, on_load)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use std::io::{BufReader, Read};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc::{Receiver, TryRecvError};
+    use std::sync::Arc;
+
+    use anyhow::Result;
+    use instant::Instant;
+
+    use abstio::MapName;
+    use abstutil::{prettyprint_usize, Timer};
+    use geom::Duration;
+    use map_gui::AppLike;
+    use map_model::Map;
+    use widgetry::tools::PopupMsg;
+    use widgetry::{
+        DrawBaselayer, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel, State,
+        Text, UpdateType, VerticalAlignment, Widget,
+    };
+
+    use crate::meters::custom_bar;
+    use crate::{App, Transition};
+
+    pub struct LoadMap {
+        panel: Panel,
+        name: MapName,
+        started: Instant,
+        // Updated by the loading thread
+        bytes_read: Arc<AtomicUsize>,
+        total_bytes: usize,
+        // Rounded, only redrawn when it changes
+        pct_shown: usize,
+        result: Receiver<Result<Map>>,
+        // Wrapped in an Option just to make calling from event() work
+        on_load: Option<Box<dyn FnOnce(&mut EventCtx, &mut App) -> Transition>>,
+    }
+
+    impl LoadMap {
+        pub fn new_state(
+            ctx: &mut EventCtx,
+            app: &App,
+            name: MapName,
+            on_load: Box<dyn FnOnce(&mut EventCtx, &mut App) -> Transition>,
+        ) -> Box<dyn State<App>> {
+            let path = name.path();
+            let total_bytes = std::fs::metadata(&path)
+                .map(|m| m.len() as usize)
+                .unwrap_or(0);
+            let bytes_read = Arc::new(AtomicUsize::new(0));
+
+            let (tx, result) = std::sync::mpsc::channel();
+            let progress = bytes_read.clone();
+            std::thread::spawn(move || {
+                let result = read_map(path, progress);
+                // If loading was cancelled, nobody's listening anymore
+                let _ = tx.send(result);
+            });
+
+            let panel = Panel::new_builder(Widget::col(vec![
+                Line(format!("Loading {}", name.describe()))
+                    .small_heading()
+                    .into_widget(ctx),
+                custom_bar(ctx, app.session.colors.score, 0.0, Text::new()).named("progress"),
+                ctx.style()
+                    .btn_outline
+                    .text("Cancel")
+                    .hotkey(Key::Escape)
+                    .build_def(ctx),
+            ]))
+            .aligned(HorizontalAlignment::Center, VerticalAlignment::Center)
+            .build(ctx);
+
+            Box::new(LoadMap {
+                panel,
+                name,
+                started: Instant::now(),
+                bytes_read,
+                total_bytes,
+                pct_shown: 0,
+                result,
+                on_load: Some(on_load),
+            })
+        }
+    }
+
+    impl State<App> for LoadMap {
+        fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+            if let Outcome::Clicked(x) = self.panel.event(ctx) {
+                match x.as_ref() {
+                    "Cancel" => {
+                        info!("Cancelled loading {}", self.name.describe());
+                        return Transition::Pop;
+                    }
+                    _ => unreachable!(),
+                }
+            }
+
+            match self.result.try_recv() {
+                Ok(Ok(map)) => {
+                    info!(
+                        "Loaded {} in {}",
+                        self.name.describe(),
+                        Duration::realtime_elapsed(self.started)
+                    );
+                    // Drawing needs to happen here. It's much faster than reading the map.
+                    let on_load = self.on_load.take().unwrap();
+                    ctx.loading_screen(format!("set up {}", self.name.describe()), |ctx, timer| {
+                        app.map_switched(ctx, map, timer);
+                        (on_load)(ctx, app)
+                    })
+                }
+                Ok(Err(err)) => Transition::Replace(PopupMsg::new_state(
+                    ctx,
+                    "Error",
+                    vec![
+                        format!("Couldn't load {}", self.name.describe()),
+                        err.to_string(),
+                    ],
+                )),
+                Err(TryRecvError::Disconnected) => Transition::Replace(PopupMsg::new_state(
+                    ctx,
+                    "Error",
+                    vec![format!("Loading {} crashed", self.name.describe())],
+                )),
+                Err(TryRecvError::Empty) => {
+                    let bytes_read = self.bytes_read.load(Ordering::Relaxed);
+                    let pct = if self.total_bytes == 0 {
+                        0
+                    } else {
+                        (100 * bytes_read / self.total_bytes).min(100)
+                    };
+                    if pct != self.pct_shown {
+                        self.pct_shown = pct;
+                        let bar = custom_bar(
+                            ctx,
+                            app.session.colors.score,
+                            (pct as f64) / 100.0,
+                            Text::from(format!(
+                                "{} / {} MB",
+                                prettyprint_usize(bytes_read / 1_000_000),
+                                prettyprint_usize(self.total_bytes / 1_000_000)
+                            )),
+                        );
+                        self.panel.replace(ctx, "progress", bar);
+                    }
+                    // Keep checking, even without any input
+                    ctx.request_update(UpdateType::Game);
+                    Transition::Keep
+                }
+            }
+        }
+
+        fn draw_baselayer(&self) -> DrawBaselayer {
+            DrawBaselayer::PreviousState
+        }
+
+        fn draw(&self, g: &mut GfxCtx, _: &App) {
+            self.panel.draw(g);
+        }
+    }
+
+    fn read_map(path: String, bytes_read: Arc<AtomicUsize>) -> Result<Map> {
+        let file = std::fs::File::open(path)?;
+        let reader = CountingReader {
+            inner: BufReader::new(file),
+            bytes_read,
+        };
+        let mut map: Map = abstutil::from_binary_reader(reader)?;
+        map.map_loaded_directly(&mut Timer::throwaway());
+        Ok(map)
+    }
+
+    // Reports how much has been read so far, for the progress bar
+    struct CountingReader<R> {
+        inner: R,
+        bytes_read: Arc<AtomicUsize>,
+    }
+
+    impl<R: Read> Read for CountingReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.bytes_read.fetch_add(n, Ordering::Relaxed);
+            Ok(n)
+        }
+    }
+}
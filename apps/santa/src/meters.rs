@@ -2,34 +2,56 @@ use abstutil::prettyprint_usize;
 use geom::Polygon;
 use widgetry::{Color, EventCtx, GeomBatch, Text, Widget};
 
+use crate::App;
+
+const TOTAL_WIDTH: f64 = 300.0;
+const HEIGHT: f64 = 32.0;
+
 pub fn custom_bar(ctx: &mut EventCtx, filled_color: Color, pct_full: f64, txt: Text) -> Widget {
-    let total_width = 300.0;
-    let height = 32.0;
-    let radius = 4.0;
+    let mut batch = bar_batch(filled_color, pct_full);
+    let label = txt.render_autocropped(ctx);
+    let dims = label.get_dims();
+    batch.append(label.translate(10.0, HEIGHT / 2.0 - dims.height / 2.0));
+    batch.into_widget(ctx)
+}
 
+fn bar_batch(filled_color: Color, pct_full: f64) -> GeomBatch {
+    let radius = 4.0;
     let mut batch = GeomBatch::new();
     // Background
     batch.push(
         Color::hex("#666666"),
-        Polygon::rounded_rectangle(total_width, height, radius),
+        Polygon::rounded_rectangle(TOTAL_WIDTH, HEIGHT, radius),
     );
     // Foreground
-    if let Some(poly) = Polygon::maybe_rounded_rectangle(pct_full * total_width, height, radius) {
+    if let Some(poly) = Polygon::maybe_rounded_rectangle(pct_full * TOTAL_WIDTH, HEIGHT, radius) {
         batch.push(filled_color, poly);
     }
-    // Text
-    let label = txt.render_autocropped(ctx);
-    let dims = label.get_dims();
-    batch.append(label.translate(10.0, height / 2.0 - dims.height / 2.0));
-    batch.into_widget(ctx)
+    batch
 }
 
-pub fn make_bar(ctx: &mut EventCtx, filled_color: Color, value: usize, max: usize) -> Widget {
-    let pct_full = if max == 0 {
-        0.0
+/// Shifts towards `warning` as `value` drops below `low`, and flashes while `flash_on` is true, to
+/// call attention to a meter the player needs to deal with.
+pub fn warning_color(
+    normal: Color,
+    warning: Color,
+    value: usize,
+    low: usize,
+    flash_on: bool,
+) -> Color {
+    if value >= low || low == 0 {
+        return normal;
+    }
+    let color = normal.lerp(warning, 1.0 - (value as f64) / (low as f64));
+    if flash_on {
+        color.tint(0.5)
     } else {
-        (value as f64) / (max as f64)
-    };
+        color
+    }
+}
+
+pub fn make_bar(ctx: &mut EventCtx, filled_color: Color, value: usize, max: usize) -> Widget {
+    let pct_full = pct_full(value, max);
     let txt = Text::from(format!(
         "{} / {}",
         prettyprint_usize(value),
@@ -37,3 +59,33 @@ pub fn make_bar(ctx: &mut EventCtx, filled_color: Color, value: usize, max: usiz
     ));
     custom_bar(ctx, filled_color, pct_full, txt)
 }
+
+/// Like `make_bar`, but labelled with a percentage instead of the count
+pub fn pct_bar(ctx: &mut EventCtx, filled_color: Color, value: usize, max: usize) -> Widget {
+    let pct_full = pct_full(value, max);
+    let txt = Text::from(format!("{}%", (100.0 * pct_full).floor()));
+    custom_bar(ctx, filled_color, pct_full, txt)
+}
+
+/// The same size as `make_bar`, but without the count. Rendering text is the slow part, so this is
+/// cheap enough to rebuild constantly.
+pub fn plain_bar(ctx: &mut EventCtx, filled_color: Color, value: usize, max: usize) -> Widget {
+    bar_batch(filled_color, pct_full(value, max)).into_widget(ctx)
+}
+
+fn pct_full(value: usize, max: usize) -> f64 {
+    // Combos can push the score past the total
+    if max == 0 {
+        0.0
+    } else {
+        ((value as f64) / (max as f64)).min(1.0)
+    }
+}
+
+/// Text in the status and time panels, at the player's chosen size
+pub fn hud_text(ctx: &EventCtx, app: &App, txt: impl Into<Text>) -> Widget {
+    txt.into()
+        .render(ctx)
+        .scale(app.session.text_size.scale())
+        .into_widget(ctx)
+}
@@ -0,0 +1,126 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Different ways to play the same level
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Mode {
+    /// Race against the clock to meet the goal
+    Normal,
+    /// No timer. Delivered houses want more presents after a while, but fewer each time.
+    Endless,
+    /// Meet the goal as quickly as possible, comparing splits along the way against the best run
+    TimeTrial,
+    /// The stores, demands, pickups, and wind all come from today's date, so everyone playing on
+    /// the same day gets the same challenge
+    Daily,
+    /// Two players on one keyboard, working together
+    CoOp,
+    /// Race someone else online for the same houses
+    Race,
+    /// Only places near where Santa has been are lit up, so the houses have to be found first
+    Explore,
+    /// No blood sugar and no timer, just riding around delivering for as long as you like
+    Zen,
+}
+
+impl Default for Mode {
+    fn default() -> Mode {
+        Mode::Normal
+    }
+}
+
+// Every player gets the same setup for each map, outside of the daily challenge
+const DEFAULT_SEED: u64 = 42;
+
+impl Mode {
+    pub const ALL: [Mode; 8] = [
+        Mode::Normal,
+        Mode::Endless,
+        Mode::TimeTrial,
+        Mode::Daily,
+        Mode::CoOp,
+        Mode::Race,
+        Mode::Explore,
+        Mode::Zen,
+    ];
+
+    /// The name used on the command line, like `time_trial`
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Normal => "normal",
+            Mode::Endless => "endless",
+            Mode::TimeTrial => "time_trial",
+            Mode::Daily => "daily",
+            Mode::CoOp => "co_op",
+            Mode::Race => "race",
+            Mode::Explore => "explore",
+            Mode::Zen => "zen",
+        }
+    }
+
+    pub fn parse(x: &str) -> Result<Mode> {
+        for mode in Mode::ALL {
+            if mode.name() == x {
+                return Ok(mode);
+            }
+        }
+        bail!(
+            "Invalid --mode={}. Choices: {}",
+            x,
+            Mode::ALL
+                .into_iter()
+                .map(|mode| mode.name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            Mode::Normal => "normal",
+            Mode::Endless => "endless (no time limit, but houses want more presents later)",
+            Mode::TimeTrial => "time trial (reach the goal as fast as possible)",
+            Mode::Daily => "daily challenge (the same stores and demands for everyone today)",
+            Mode::CoOp => "two players (WASD and arrow keys, sharing the score)",
+            Mode::Race => "online race (set up a race server in the settings first)",
+            Mode::Explore => "exploration (only places you've been near are lit up)",
+            Mode::Zen => "zen (no blood sugar or time limit, just see how much you can cover)",
+        }
+    }
+
+    pub fn has_time_limit(self) -> bool {
+        match self {
+            Mode::Normal | Mode::Daily | Mode::CoOp | Mode::Race | Mode::Explore => true,
+            Mode::Endless | Mode::TimeTrial | Mode::Zen => false,
+        }
+    }
+
+    /// Whether quitting partway through keeps the run to pick up later. The other modes have too
+    /// much going on besides the houses and the sleigh, like other players or houses waiting to
+    /// want presents again.
+    pub fn can_resume(self) -> bool {
+        match self {
+            Mode::Normal | Mode::Daily | Mode::Zen => true,
+            Mode::Endless | Mode::TimeTrial | Mode::CoOp | Mode::Race | Mode::Explore => false,
+        }
+    }
+
+    /// Seeds all of the randomness in a level. The daily seed is just the date written as a
+    /// number, like 20211224, so players can tell at a glance which challenge a score is from. It's
+    /// the date in UTC, so everybody plays the same challenge no matter their timezone.
+    pub fn seed(self) -> u64 {
+        match self {
+            Mode::Daily => {
+                let today = chrono::Utc::now().date_naive();
+                today.format("%Y%m%d").to_string().parse().unwrap()
+            }
+            Mode::Normal
+            | Mode::Endless
+            | Mode::TimeTrial
+            | Mode::CoOp
+            | Mode::Race
+            | Mode::Explore
+            | Mode::Zen => DEFAULT_SEED,
+        }
+    }
+}
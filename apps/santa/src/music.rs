@@ -1,6 +1,7 @@
 use std::io::Cursor;
 
 use anyhow::Result;
+use rodio::source::{SineWave, Source};
 use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink};
 
 use widgetry::{EventCtx, GfxCtx, HorizontalAlignment, Outcome, Panel, Toggle, VerticalAlignment};
@@ -11,6 +12,30 @@ use widgetry::{EventCtx, GfxCtx, HorizontalAlignment, Outcome, Panel, Toggle, Ve
 pub const OUT_OF_GAME: f32 = 0.5;
 pub const IN_GAME: f32 = 1.0;
 
+/// Short effects played on top of the music
+#[derive(Clone, Copy)]
+pub enum Sound {
+    Delivery,
+    Recharge,
+    LowEnergy,
+    Boost,
+    BigDelivery,
+}
+
+impl Sound {
+    /// The effects are synthesized, so there are no extra assets to download. (frequency in Hz,
+    /// duration in milliseconds) for each tone, played in sequence.
+    fn tones(self) -> Vec<(f32, u64)> {
+        match self {
+            Sound::Delivery => vec![(880.0, 60), (1320.0, 90)],
+            Sound::Recharge => vec![(523.0, 70), (659.0, 70), (784.0, 70), (1047.0, 120)],
+            Sound::LowEnergy => vec![(220.0, 150), (0.0, 80), (220.0, 150)],
+            Sound::Boost => vec![(300.0, 40), (450.0, 40), (600.0, 60)],
+            Sound::BigDelivery => vec![(784.0, 80), (988.0, 80), (1175.0, 80), (1568.0, 250)],
+        }
+    }
+}
+
 pub struct Music {
     inner: Option<Inner>,
 }
@@ -26,8 +51,13 @@ struct Inner {
     _stream: OutputStream,
     stream_handle: OutputStreamHandle,
     sink: Sink,
+    // From the toggle. Tracked apart from the volume, since the player might turn that to 0.
+    muted: bool,
     unmuted_volume: f32,
     current_song: String,
+    // Set by the player, from 0 to 1
+    music_volume: f32,
+    sound_volume: f32,
 
     panel: Panel,
 }
@@ -77,6 +107,23 @@ impl Music {
         }
     }
 
+    /// Both range from 0 to 1
+    pub fn set_player_volume(&mut self, music_volume: f32, sound_volume: f32) {
+        if let Some(ref mut inner) = self.inner {
+            inner.music_volume = music_volume;
+            inner.sound_volume = sound_volume;
+            inner.update_volume();
+        }
+    }
+
+    pub fn play_sound(&mut self, sound: Sound) {
+        if let Some(ref mut inner) = self.inner {
+            if let Err(err) = inner.play_sound(sound) {
+                warn!("Couldn't play a sound effect: {}", err);
+            }
+        }
+    }
+
     pub fn change_song(&mut self, song: &str) {
         if let Some(ref mut inner) = self.inner {
             if let Err(err) = inner.change_song(song) {
@@ -129,27 +176,58 @@ impl Inner {
             _stream: stream,
             stream_handle,
             sink,
+            muted: !play_music,
             unmuted_volume: 1.0,
             current_song: song.to_string(),
+            music_volume: 1.0,
+            sound_volume: 1.0,
             panel,
         })
     }
 
     fn unmute(&mut self) {
-        self.sink.set_volume(self.unmuted_volume);
+        self.muted = false;
+        self.update_volume();
     }
 
     fn mute(&mut self) {
-        self.sink.set_volume(0.0);
+        self.muted = true;
+        self.update_volume();
     }
 
     fn specify_volume(&mut self, volume: f32) {
         self.unmuted_volume = volume;
-        if self.sink.volume() != 0.0 {
-            self.unmute();
+        self.update_volume();
+    }
+
+    fn update_volume(&mut self) {
+        if self.muted {
+            self.sink.set_volume(0.0);
+        } else {
+            // If the player turned the volume all the way down, that's the same as muting
+            self.sink
+                .set_volume(self.unmuted_volume * self.music_volume);
         }
     }
 
+    fn play_sound(&mut self, sound: Sound) -> Result<()> {
+        if self.sound_volume == 0.0 {
+            return Ok(());
+        }
+        // Each effect gets its own sink, so they can overlap with each other and the music
+        let sink = Sink::try_new(&self.stream_handle)?;
+        for (freq, ms) in sound.tones() {
+            let tone = SineWave::new(freq)
+                .take_duration(std::time::Duration::from_millis(ms))
+                // Pure sine waves are loud
+                .amplify(if freq == 0.0 { 0.0 } else { 0.2 });
+            sink.append(tone);
+        }
+        sink.set_volume(self.sound_volume);
+        sink.detach();
+        Ok(())
+    }
+
     fn change_song(&mut self, song: &str) -> Result<()> {
         if self.current_song == song {
             return Ok(());
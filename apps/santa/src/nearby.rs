@@ -0,0 +1,118 @@
+use geom::{Circle, Distance, Pt2D};
+use map_model::BuildingID;
+use widgetry::{Color, Drawable, EventCtx, GeomBatch, GfxCtx};
+
+use crate::buildings::{BldgState, Buildings, PresentType};
+use crate::player::Player;
+use crate::App;
+
+/// How far around the sleigh to look for houses coming up
+pub const NEARBY_RADIUS: Distance = Distance::const_meters(30.0);
+
+/// Riding past the spot where a house's driveway meets the road delivers to it, but those spots
+/// aren't obvious. Marks them for undelivered houses near the sleigh, brighter for the houses the
+/// current present would go to.
+pub struct NearbyHouses {
+    // Closest first, with the driveway spot and whether it'd get a present right now
+    candidates: Vec<(BuildingID, Pt2D, bool)>,
+    // Cycling through the candidates picks which one to get the present ready for
+    picked: Option<BuildingID>,
+    draw: Drawable,
+}
+
+impl NearbyHouses {
+    pub fn new(ctx: &mut EventCtx) -> NearbyHouses {
+        NearbyHouses {
+            candidates: Vec::new(),
+            picked: None,
+            draw: Drawable::empty(ctx),
+        }
+    }
+
+    /// Only redraws when the candidates change
+    pub fn update<F: Fn(PresentType) -> bool>(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &App,
+        bldgs: &Buildings,
+        player: &Player,
+        can_deliver: F,
+    ) {
+        let candidates: Vec<(BuildingID, Pt2D, bool)> = player
+            .driveways_nearby(app, NEARBY_RADIUS)
+            .into_iter()
+            .filter_map(|(b, pt)| match bldgs.buildings[&b] {
+                BldgState::Undelivered(_, wanted) => Some((b, pt, can_deliver(wanted))),
+                _ => None,
+            })
+            .collect();
+        if candidates == self.candidates {
+            return;
+        }
+        if !candidates.iter().any(|(b, _, _)| Some(*b) == self.picked) {
+            self.picked = None;
+        }
+        self.candidates = candidates;
+        self.redraw(ctx, app, bldgs);
+    }
+
+    /// Houses right next to each other often share a driveway spot, but want different presents.
+    /// Picks the next nearby house, returning the present it wants.
+    pub fn pick_next(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &App,
+        bldgs: &Buildings,
+    ) -> Option<PresentType> {
+        if self.candidates.is_empty() {
+            return None;
+        }
+        let idx = self
+            .picked
+            .and_then(|picked| self.candidates.iter().position(|(b, _, _)| *b == picked))
+            .map(|idx| (idx + 1) % self.candidates.len())
+            .unwrap_or(0);
+        let b = self.candidates[idx].0;
+        self.picked = Some(b);
+        self.redraw(ctx, app, bldgs);
+        match bldgs.buildings[&b] {
+            BldgState::Undelivered(_, wanted) => Some(wanted),
+            _ => unreachable!(),
+        }
+    }
+
+    fn redraw(&mut self, ctx: &mut EventCtx, app: &App, bldgs: &Buildings) {
+        let mut batch = GeomBatch::new();
+        for (b, pt, eligible) in &self.candidates {
+            let wanted = match bldgs.buildings[b] {
+                BldgState::Undelivered(_, wanted) => wanted,
+                _ => unreachable!(),
+            };
+            let alpha = if *eligible { 0.9 } else { 0.3 };
+            let bldg = app.map.get_b(*b);
+            batch.push(
+                wanted.color().alpha(alpha),
+                bldg.polygon.to_outline(Distance::meters(0.5)),
+            );
+            batch.push(
+                wanted.color().alpha(alpha),
+                Circle::new(*pt, Distance::meters(1.0)).to_polygon(),
+            );
+            if Some(*b) == self.picked {
+                batch.push(Color::WHITE, bldg.polygon.to_outline(Distance::meters(1.0)));
+            }
+        }
+        self.draw = ctx.upload(batch);
+    }
+
+    /// Also shows how close a driveway has to be to get a present
+    pub fn draw(&self, g: &mut GfxCtx, sleigh: Pt2D, delivery_radius: Distance) {
+        if let Ok(ring) = Circle::new(sleigh, NEARBY_RADIUS).to_outline(Distance::meters(0.3)) {
+            g.draw_polygon(Color::WHITE.alpha(0.3), ring);
+        }
+        if let Ok(ring) = Circle::new(sleigh, delivery_radius).to_outline(Distance::meters(0.3)) {
+            g.draw_polygon(Color::WHITE.alpha(0.8), ring);
+        }
+        g.redraw(&self.draw);
+    }
+}
@@ -0,0 +1,165 @@
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+use geom::{Circle, Distance, Duration, Pt2D};
+use map_model::{IntersectionID, RoadID};
+use widgetry::{Color, Drawable, EventCtx, GeomBatch, GfxCtx};
+
+use crate::App;
+
+// One wandering obstacle for this many roads, so bigger maps get more
+const ROADS_PER_OBSTACLE: usize = 40;
+const MAX_OBSTACLES: usize = 30;
+// Like the player's movement, these are sped up a lot from real life
+const PEDESTRIAN_SPEED: f64 = 15.0;
+const CAR_SPEED: f64 = 40.0;
+const PEDESTRIAN_RADIUS: Distance = Distance::const_meters(1.5);
+const CAR_RADIUS: Distance = Distance::const_meters(3.0);
+
+/// People and cars wandering around the road network. They don't understand anything about
+/// lanes or traffic; they just follow the road, picking a random way to go at each intersection.
+pub struct Obstacles {
+    agents: Vec<Agent>,
+    rng: XorShiftRng,
+    draw: Drawable,
+}
+
+struct Agent {
+    is_car: bool,
+    road: RoadID,
+    dist: Distance,
+    // Heading towards the end of the road
+    fwd: bool,
+}
+
+impl Agent {
+    fn speed(&self) -> f64 {
+        if self.is_car {
+            CAR_SPEED
+        } else {
+            PEDESTRIAN_SPEED
+        }
+    }
+
+    fn radius(&self) -> Distance {
+        if self.is_car {
+            CAR_RADIUS
+        } else {
+            PEDESTRIAN_RADIUS
+        }
+    }
+
+    fn pos(&self, app: &App) -> Pt2D {
+        app.map
+            .get_r(self.road)
+            .center_pts
+            .must_dist_along(self.dist)
+            .0
+    }
+}
+
+impl Obstacles {
+    /// Nothing starts right next to the player. `density` scales how many there are.
+    pub fn new(
+        ctx: &EventCtx,
+        app: &App,
+        start: IntersectionID,
+        seed: u64,
+        density: f64,
+    ) -> Obstacles {
+        let mut rng = XorShiftRng::seed_from_u64(seed);
+        let near_start = &app.map.get_i(start).roads;
+        let mut roads: Vec<RoadID> = app
+            .map
+            .all_roads()
+            .iter()
+            .map(|r| r.id)
+            .filter(|r| !near_start.contains(r))
+            .collect();
+        roads.shuffle(&mut rng);
+
+        let num = (app.map.all_roads().len() / ROADS_PER_OBSTACLE)
+            .max(1)
+            .min(MAX_OBSTACLES);
+        let num = ((num as f64) * density).round().max(1.0) as usize;
+        let mut agents = Vec::new();
+        for road in roads.into_iter().take(num) {
+            let length = app.map.get_r(road).length();
+            agents.push(Agent {
+                is_car: rng.gen_bool(0.3),
+                road,
+                dist: rng.gen_range(0.0..1.0) * length,
+                fwd: rng.gen_bool(0.5),
+            });
+        }
+
+        Obstacles {
+            agents,
+            rng,
+            draw: Drawable::empty(ctx),
+        }
+    }
+
+    /// Returns true if anything ran into the player
+    pub fn update(&mut self, ctx: &mut EventCtx, app: &App, dt: Duration, player: Pt2D) -> bool {
+        let mut hit = false;
+        let mut batch = GeomBatch::new();
+        for agent in &mut self.agents {
+            let step = Distance::meters(agent.speed() * dt.inner_seconds());
+            let length = app.map.get_r(agent.road).length();
+            let next_dist = if agent.fwd {
+                agent.dist + step
+            } else {
+                agent.dist - step
+            };
+            if next_dist < Distance::ZERO || next_dist > length {
+                // Pick somewhere else to go from the intersection we just reached. If it's a dead
+                // end, turn around.
+                let road = app.map.get_r(agent.road);
+                let i = if agent.fwd { road.dst_i } else { road.src_i };
+                let choices: Vec<RoadID> = app
+                    .map
+                    .get_i(i)
+                    .roads
+                    .iter()
+                    .cloned()
+                    .filter(|r| *r != agent.road)
+                    .collect();
+                if let Some(next) = choices.choose(&mut self.rng) {
+                    agent.road = *next;
+                    let next = app.map.get_r(*next);
+                    agent.fwd = next.src_i == i;
+                    agent.dist = if agent.fwd {
+                        Distance::ZERO
+                    } else {
+                        next.length()
+                    };
+                } else {
+                    agent.fwd = !agent.fwd;
+                }
+            } else {
+                agent.dist = next_dist;
+            }
+
+            let pos = agent.pos(app);
+            if pos.dist_to(player) <= agent.radius() + Distance::meters(2.0) {
+                hit = true;
+            }
+            batch.push(
+                if agent.is_car {
+                    Color::hex("#5B5EA6")
+                } else {
+                    Color::hex("#9B2335")
+                },
+                Circle::new(pos, agent.radius()).to_polygon(),
+            );
+        }
+        self.draw = ctx.upload(batch);
+        hit
+    }
+
+    pub fn draw(&self, g: &mut GfxCtx) {
+        g.redraw(&self.draw);
+    }
+}
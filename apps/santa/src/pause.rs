@@ -0,0 +1,118 @@
+use widgetry::{DrawBaselayer, EventCtx, GfxCtx, Line, Panel, SimpleState, State, Widget};
+
+use crate::kiosk::{IdleTimer, KIOSK_IDLE_RESET};
+use crate::photo::PhotoMode;
+use crate::settings::Settings;
+use crate::{App, Transition};
+
+/// Anything the pause menu can sit on top of: the main game, or a minigame from `arcade`
+pub trait Pausable: State<App> {
+    /// Starts over in place, replacing whatever was paused
+    fn restart_level() -> Transition;
+    fn quit_to_title() -> Transition;
+    /// Only the main game can hide its HUD for pictures
+    fn has_photo_mode() -> bool {
+        false
+    }
+}
+
+/// Pushed on top of the game, so time doesn't pass while it's open.
+pub struct PauseMenu {
+    // Only used in kiosk mode
+    idle: IdleTimer,
+    // From whatever's paused underneath
+    restart_level: fn() -> Transition,
+    quit_to_title: fn() -> Transition,
+}
+
+impl PauseMenu {
+    pub fn new_state<T: Pausable>(ctx: &mut EventCtx, app: &mut App) -> Box<dyn State<App>> {
+        app.session.music.specify_volume(crate::music::OUT_OF_GAME);
+
+        let locale = app.session.locale;
+        <dyn SimpleState<_>>::new_state(
+            Panel::new_builder(Widget::col(vec![
+                Line(locale.tr("Game Paused"))
+                    .small_heading()
+                    .into_widget(ctx),
+                ctx.style()
+                    .btn_solid_primary
+                    .text(locale.tr("Resume"))
+                    .hotkey(app.session.keys.pause[0])
+                    .build_widget(ctx, "Resume"),
+                ctx.style()
+                    .btn_outline
+                    .text(locale.tr("Restart level"))
+                    .hotkey(app.session.keys.restart[0])
+                    .build_widget(ctx, "Restart level"),
+                if T::has_photo_mode() {
+                    ctx.style()
+                        .btn_outline
+                        .text(locale.tr("Photo mode"))
+                        .build_widget(ctx, "Photo mode")
+                } else {
+                    Widget::nothing()
+                },
+                ctx.style()
+                    .btn_outline
+                    .text(locale.tr("Settings"))
+                    .build_widget(ctx, "Settings"),
+                ctx.style()
+                    .btn_outline
+                    .text(locale.tr("Quit to title"))
+                    .build_widget(ctx, "Quit to title"),
+            ]))
+            .build(ctx),
+            Box::new(PauseMenu {
+                idle: IdleTimer::new(),
+                restart_level: T::restart_level,
+                quit_to_title: T::quit_to_title,
+            }),
+        )
+    }
+}
+
+impl SimpleState<App> for PauseMenu {
+    fn on_click(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &mut App,
+        x: &str,
+        _: &mut Panel,
+    ) -> Transition {
+        match x {
+            "Resume" => {
+                app.session.music.specify_volume(crate::music::IN_GAME);
+                Transition::Pop
+            }
+            // The game underneath starts over in place
+            "Restart level" => Transition::Multi(vec![Transition::Pop, (self.restart_level)()]),
+            "Photo mode" => {
+                let mut transitions = vec![Transition::Pop];
+                transitions.extend(PhotoMode::start(ctx, app));
+                Transition::Multi(transitions)
+            }
+            "Settings" => Transition::Push(Settings::new_state(ctx, app)),
+            // Finishing the tutorial unlocks levels, so refresh the title screen
+            "Quit to title" => Transition::Multi(vec![Transition::Pop, (self.quit_to_title)()]),
+            _ => unreachable!(),
+        }
+    }
+
+    fn other_event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        app.session.update_music(ctx);
+        if app.session.kiosk && self.idle.event(ctx, KIOSK_IDLE_RESET) {
+            return Transition::Clear(vec![crate::title::TitleScreen::new_state(ctx, app)]);
+        }
+        Transition::Keep
+    }
+
+    fn draw_baselayer(&self) -> DrawBaselayer {
+        DrawBaselayer::PreviousState
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        map_gui::tools::grey_out_map(g, app);
+        app.session.music.draw(g);
+    }
+}
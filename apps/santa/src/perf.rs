@@ -0,0 +1,106 @@
+use std::collections::VecDeque;
+
+use instant::Instant;
+
+use abstutil::elapsed_seconds;
+use geom::Duration;
+use widgetry::{Color, GfxCtx, Line, Text};
+
+// Average over about a second of frames
+const NUM_FRAMES: usize = 60;
+
+/// A debug overlay for tracking down stutters on big maps. It shows frames per second, how long
+/// updating and drawing take, and how much gets uploaded to the GPU each frame.
+pub struct PerfOverlay {
+    enabled: bool,
+    // All in seconds, for the most recent frames
+    frame_times: VecDeque<f64>,
+    update_times: VecDeque<f64>,
+    panel_times: VecDeque<f64>,
+    // Adds up panel updates until the current update finishes
+    current_panel_time: f64,
+}
+
+impl PerfOverlay {
+    pub fn new() -> PerfOverlay {
+        PerfOverlay {
+            enabled: false,
+            frame_times: VecDeque::new(),
+            update_times: VecDeque::new(),
+            panel_times: VecDeque::new(),
+            current_panel_time: 0.0,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+    }
+
+    /// Call after rebuilding a panel, with when that started
+    pub fn record_panel(&mut self, started: Instant) {
+        self.current_panel_time += elapsed_seconds(started);
+    }
+
+    /// Call after each update, with the time since the previous one and when this one started
+    pub fn record_update(&mut self, dt: Duration, started: Instant) {
+        push(&mut self.frame_times, dt.inner_seconds());
+        push(&mut self.update_times, elapsed_seconds(started));
+        push(&mut self.panel_times, self.current_panel_time);
+        self.current_panel_time = 0.0;
+    }
+
+    /// Call last while drawing, so the upload and draw call counts cover the whole frame
+    pub fn draw(&self, g: &mut GfxCtx, draw_started: Instant) {
+        if !self.enabled {
+            return;
+        }
+        let frame_time = average(&self.frame_times);
+        let fps = if frame_time > 0.0 {
+            1.0 / frame_time
+        } else {
+            0.0
+        };
+
+        let mut txt = Text::new();
+        txt.add_line(Line(format!("{:.0} FPS", fps)).small_heading());
+        txt.add_line(format!("Frame: {:.1}ms", 1000.0 * frame_time));
+        txt.add_line(format!(
+            "Update: {:.1}ms, {:.1}ms of it rebuilding panels",
+            1000.0 * average(&self.update_times),
+            1000.0 * average(&self.panel_times)
+        ));
+        txt.add_line(format!(
+            "Draw: {:.1}ms",
+            1000.0 * elapsed_seconds(draw_started)
+        ));
+        txt.add_line(format!(
+            "{} batches uploaded, {} draw calls",
+            g.get_num_uploads(),
+            g.get_num_draw_calls()
+        ));
+
+        let batch = txt.bg(Color::BLACK.alpha(0.7)).render(g);
+        let dims = batch.get_dims();
+        let batch = batch.translate(
+            (g.canvas.window_width - dims.width) / 2.0,
+            g.canvas.window_height - dims.height,
+        );
+        g.fork_screenspace();
+        batch.draw(g);
+        g.unfork();
+    }
+}
+
+fn push(list: &mut VecDeque<f64>, value: f64) {
+    list.push_back(value);
+    if list.len() > NUM_FRAMES {
+        list.pop_front();
+    }
+}
+
+fn average(list: &VecDeque<f64>) -> f64 {
+    if list.is_empty() {
+        return 0.0;
+    }
+    list.iter().sum::<f64>() / (list.len() as f64)
+}
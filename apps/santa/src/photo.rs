@@ -0,0 +1,111 @@
+use widgetry::{
+    DrawBaselayer, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel, State,
+    UpdateType, VerticalAlignment, Widget,
+};
+
+use crate::game::Game;
+use crate::{App, Transition};
+
+// How much more detail the exported screenshot has than the normal view
+const SCREENSHOT_ZOOM: f64 = 3.0;
+
+/// Pushed on top of a paused game. The camera comes loose from Santa so the player can pan and
+/// zoom anywhere, and all of the panels are hidden, to show off the decorated map.
+pub struct PhotoMode {
+    panel: Panel,
+}
+
+impl PhotoMode {
+    /// Hides the game's HUD until photo mode ends
+    pub fn start(ctx: &mut EventCtx, app: &mut App) -> Vec<Transition> {
+        vec![
+            Transition::ModifyState(Box::new(|state, ctx, _| {
+                state
+                    .downcast_mut::<Game>()
+                    .unwrap()
+                    .set_photo_mode(ctx, true);
+            })),
+            Transition::Push(PhotoMode::new_state(ctx, app)),
+        ]
+    }
+
+    fn new_state(ctx: &mut EventCtx, app: &mut App) -> Box<dyn State<App>> {
+        let mut buttons = Vec::new();
+        // Screenshots are written to a local directory
+        #[cfg(not(target_arch = "wasm32"))]
+        buttons.push(
+            ctx.style()
+                .btn_solid_primary
+                .text("Save a screenshot")
+                .hotkey(Key::S)
+                .build_def(ctx),
+        );
+        buttons.push(
+            ctx.style()
+                .btn_outline
+                .text("Back to the game")
+                .hotkey(Key::Escape)
+                .build_def(ctx),
+        );
+
+        let panel = Panel::new_builder(Widget::col(vec![
+            Line("Photo mode").small_heading().into_widget(ctx),
+            Line("Drag to pan and scroll to zoom")
+                .fg(ctx.style().text_hotkey_color)
+                .into_widget(ctx),
+            Widget::row(buttons),
+        ]))
+        .aligned(HorizontalAlignment::Center, VerticalAlignment::BottomInset)
+        .build(ctx);
+        app.session.music.specify_volume(crate::music::OUT_OF_GAME);
+        Box::new(PhotoMode { panel })
+    }
+}
+
+impl State<App> for PhotoMode {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        ctx.canvas_movement();
+
+        if let Outcome::Clicked(x) = self.panel.event(ctx) {
+            match x.as_ref() {
+                "Save a screenshot" => {
+                    // Tiles of the whole map, one per window's worth, at more detail than fits on
+                    // screen
+                    ctx.request_update(UpdateType::ScreenCaptureEverything {
+                        dir: format!("screenshots/santa/{}", app.map.get_name().as_filename()),
+                        zoom: SCREENSHOT_ZOOM,
+                        dims: ctx.canvas.get_window_dims(),
+                    });
+                }
+                "Back to the game" => {
+                    app.session.music.specify_volume(crate::music::IN_GAME);
+                    return Transition::Multi(vec![
+                        Transition::Pop,
+                        Transition::ModifyState(Box::new(|state, ctx, _| {
+                            state
+                                .downcast_mut::<Game>()
+                                .unwrap()
+                                .set_photo_mode(ctx, false);
+                        })),
+                    ]);
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        app.session.update_music(ctx);
+        Transition::Keep
+    }
+
+    fn draw_baselayer(&self) -> DrawBaselayer {
+        DrawBaselayer::PreviousState
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        // Keep the controls out of the screenshot
+        if !g.is_screencap() {
+            self.panel.draw(g);
+            app.session.music.draw(g);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+use geom::{Circle, Distance, Pt2D};
+use map_model::IntersectionID;
+use widgetry::{Color, Drawable, EventCtx, GeomBatch, GfxCtx, Line, Text};
+
+use crate::App;
+
+// One pickup for this many roads, so bigger maps get more
+const ROADS_PER_PICKUP: usize = 25;
+const RADIUS: Distance = Distance::const_meters(3.0);
+// How close the player has to fly to grab one
+const COLLECT_DIST: Distance = Distance::const_meters(5.0);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PickupType {
+    Energy,
+    DoubleScore,
+    Speed,
+}
+
+impl PickupType {
+    const ALL: [PickupType; 3] = [
+        PickupType::Energy,
+        PickupType::DoubleScore,
+        PickupType::Speed,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            PickupType::Energy => "+",
+            PickupType::DoubleScore => "2x",
+            PickupType::Speed => ">>",
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            PickupType::Energy => "Extra blood sugar",
+            PickupType::DoubleScore => "Double presents",
+            PickupType::Speed => "Speed boost",
+        }
+    }
+
+    fn color(self, app: &App) -> Color {
+        match self {
+            PickupType::Energy => app.session.colors.energy,
+            PickupType::DoubleScore => app.session.colors.score,
+            PickupType::Speed => app.session.colors.boost,
+        }
+    }
+}
+
+/// Power-ups scattered along the roads, grabbed by flying over them. Once collected, they're
+/// gone for the rest of the level.
+pub struct Pickups {
+    remaining: Vec<(Pt2D, PickupType)>,
+    draw: Drawable,
+}
+
+impl Pickups {
+    /// Nothing is placed right next to the player
+    pub fn new(ctx: &EventCtx, app: &App, start: IntersectionID, seed: u64) -> Pickups {
+        let mut rng = XorShiftRng::seed_from_u64(seed);
+        let near_start = &app.map.get_i(start).roads;
+        let mut roads: Vec<_> = app
+            .map
+            .all_roads()
+            .iter()
+            .filter(|r| !near_start.contains(&r.id))
+            .collect();
+        roads.shuffle(&mut rng);
+
+        let num = (app.map.all_roads().len() / ROADS_PER_PICKUP).max(1);
+        let mut remaining = Vec::new();
+        for road in roads.into_iter().take(num) {
+            let dist = rng.gen_range(0.0..1.0) * road.length();
+            let pt = road.center_pts.must_dist_along(dist).0;
+            remaining.push((pt, *PickupType::ALL.choose(&mut rng).unwrap()));
+        }
+
+        let mut pickups = Pickups {
+            remaining,
+            draw: Drawable::empty(ctx),
+        };
+        pickups.redraw(ctx, app);
+        pickups
+    }
+
+    /// Returns everything the player just flew over
+    pub fn collect(&mut self, ctx: &EventCtx, app: &App, player: Pt2D) -> Vec<PickupType> {
+        let mut collected = Vec::new();
+        self.remaining.retain(|(pt, pickup)| {
+            if pt.dist_to(player) <= COLLECT_DIST {
+                collected.push(*pickup);
+                false
+            } else {
+                true
+            }
+        });
+        if !collected.is_empty() {
+            self.redraw(ctx, app);
+        }
+        collected
+    }
+
+    fn redraw(&mut self, ctx: &EventCtx, app: &App) {
+        let mut batch = GeomBatch::new();
+        for (pt, pickup) in &self.remaining {
+            batch.push(Color::WHITE, Circle::new(*pt, RADIUS).to_polygon());
+            batch.push(
+                pickup.color(app),
+                Circle::new(*pt, RADIUS * 0.8).to_polygon(),
+            );
+            batch.append(
+                Text::from(Line(pickup.label()).fg(Color::BLACK))
+                    .render_autocropped(ctx)
+                    .scale(0.1)
+                    .centered_on(*pt),
+            );
+        }
+        self.draw = ctx.upload(batch);
+    }
+
+    pub fn draw(&self, g: &mut GfxCtx) {
+        g.redraw(&self.draw);
+    }
+}
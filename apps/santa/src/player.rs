@@ -1,12 +1,16 @@
 use std::collections::{HashMap, HashSet};
 
+use serde::{Deserialize, Serialize};
+
 use abstutil::MultiMap;
 use geom::{Angle, Circle, Distance, PolyLine, Pt2D, Speed};
 use map_gui::ID;
-use map_model::{BuildingID, Direction, IntersectionID, LaneType, RoadID};
+use map_model::{osm, BuildingID, Direction, IntersectionID, LaneType, Map, RoadID};
 use widgetry::EventCtx;
 
-use crate::controls::InstantController;
+use crate::bot::GreedyBot;
+use crate::buildings::PresentType;
+use crate::controls::{new_controller, ControlScheme, Controller, KeyBindings, SleighState};
 use crate::App;
 
 const ZOOM: f64 = 10.0;
@@ -17,11 +21,24 @@ pub struct Player {
     on: On,
     bldgs_along_road: BuildingsAlongRoad,
 
-    controls: InstantController,
+    controls: Box<dyn Controller>,
+    // What the controls were created from, to notice when the settings change
+    control_scheme: ControlScheme,
+    keys: KeyBindings,
 }
 
 impl Player {
     pub fn new(ctx: &mut EventCtx, app: &App, start: IntersectionID) -> Player {
+        Player::new_with_keys(ctx, app, start, app.session.keys.clone())
+    }
+
+    /// For local co-op, each player gets different keys. These won't change from the settings.
+    pub fn new_with_keys(
+        ctx: &mut EventCtx,
+        app: &App,
+        start: IntersectionID,
+        keys: KeyBindings,
+    ) -> Player {
         ctx.canvas.cam_zoom = ZOOM;
         let pos = app.map.get_i(start).polygon.center();
         ctx.canvas.center_on_map_pt(pos);
@@ -32,8 +49,27 @@ impl Player {
             on: On::Intersection(start),
             bldgs_along_road: BuildingsAlongRoad::new(app),
 
-            controls: InstantController::new(),
+            controls: if app.session.bot {
+                Box::new(GreedyBot::new())
+            } else {
+                new_controller(app.session.control_scheme, keys.clone())
+            },
+            control_scheme: app.session.control_scheme,
+            keys,
+        }
+    }
+
+    /// If the player changed control settings, switch to them. Returns true if so.
+    pub fn refresh_controls(&mut self, app: &App) -> bool {
+        if self.controls.is_bot()
+            || self.control_scheme == app.session.control_scheme && self.keys == app.session.keys
+        {
+            return false;
         }
+        self.control_scheme = app.session.control_scheme;
+        self.keys = app.session.keys.clone();
+        self.controls = new_controller(self.control_scheme, self.keys.clone());
+        true
     }
 
     /// Returns any buildings we passed
@@ -41,11 +77,14 @@ impl Player {
         &mut self,
         ctx: &mut EventCtx,
         app: &App,
+        sleigh: &SleighState,
+        analog: Option<(Angle, f64)>,
         speed: Speed,
+        wind: (f64, f64),
     ) -> Vec<BuildingID> {
-        if let Some((dx, dy)) = self.controls.displacement(ctx, speed) {
-            self.apply_displacement(ctx, app, dx, dy, true)
-        // TODO Do the center_on_map_pt here, actually
+        // The wind only pushes while moving, so it's safe to stop and look at the map
+        if let Some((dx, dy)) = self.controls.displacement(ctx, app, sleigh, analog, speed) {
+            self.apply_displacement(app, dx + wind.0, dy + wind.1, true)
         } else {
             Vec::new()
         }
@@ -99,7 +138,6 @@ impl Player {
 
     fn apply_displacement(
         &mut self,
-        ctx: &mut EventCtx,
         app: &App,
         dx: f64,
         dy: f64,
@@ -107,9 +145,13 @@ impl Player {
     ) -> Vec<BuildingID> {
         let new_pos = self.pos.offset(dx, dy);
         let mut buildings_passed = Vec::new();
-        if let Some(mut new_on) = self.pos_to_on(app, new_pos) {
+        // Roads are clipped to the map boundary, but the wind could still push past the end of
+        // one. Treat the edge like any other obstacle and bounce off.
+        if let Some(mut new_on) = self
+            .pos_to_on(app, new_pos)
+            .filter(|_| app.map.get_boundary_polygon().contains_pt(new_pos))
+        {
             self.pos = new_pos;
-            ctx.canvas.center_on_map_pt(self.pos);
 
             if let (On::Road(r1, dist1, _), On::Road(r2, dist2, _)) =
                 (self.on.clone(), new_on.clone())
@@ -133,10 +175,10 @@ impl Player {
             if recurse {
                 let orig = self.pos;
                 if dx != 0.0 {
-                    buildings_passed.extend(self.apply_displacement(ctx, app, dx, 0.0, false));
+                    buildings_passed.extend(self.apply_displacement(app, dx, 0.0, false));
                 }
                 if dy != 0.0 {
-                    buildings_passed.extend(self.apply_displacement(ctx, app, 0.0, dy, false));
+                    buildings_passed.extend(self.apply_displacement(app, 0.0, dy, false));
                 }
 
                 // Are we stuck?
@@ -144,7 +186,7 @@ impl Player {
                     if true {
                         // Resolve by just bouncing in the opposite direction. Jittery, but we keep
                         // moving.
-                        buildings_passed.extend(self.apply_displacement(ctx, app, -dx, -dy, false));
+                        buildings_passed.extend(self.apply_displacement(app, -dx, -dy, false));
                     } else {
                         // Find the exact point on the boundary where we go out of bounds
                         let old_ring = match self.on {
@@ -163,7 +205,6 @@ impl Player {
                             .get(0)
                         {
                             buildings_passed.extend(self.apply_displacement(
-                                ctx,
                                 app,
                                 pt.x() - self.pos.x(),
                                 pt.y() - self.pos.y(),
@@ -184,9 +225,8 @@ impl Player {
             } else {
                 angle
             };
-            ctx.canvas.center_on_map_pt(self.pos);
         } else {
-            self.facing = self.controls.facing;
+            self.facing = self.controls.facing();
         }
 
         buildings_passed
@@ -200,13 +240,58 @@ impl Player {
         self.facing
     }
 
-    /// Is the player currently on a road with a bus or bike lane?
-    pub fn on_good_road(&self, app: &App) -> bool {
-        let roads = match self.on {
+    /// The direction the controls are pointing, if it's separate from the direction of movement
+    pub fn heading_indicator(&self) -> Option<Angle> {
+        self.controls.heading_indicator()
+    }
+
+    /// Presents drop automatically for bots, no matter the setting
+    pub fn is_bot(&self) -> bool {
+        self.controls.is_bot()
+    }
+
+    /// The present a bot wants ready
+    pub fn choose_present(&self) -> Option<PresentType> {
+        self.controls.choose_present()
+    }
+
+    pub fn current_intersection(&self) -> Option<IntersectionID> {
+        match self.on {
+            On::Intersection(i) => Some(i),
+            On::Road(_, _, _) => None,
+        }
+    }
+
+    /// The road the player is on, or every road touching their intersection
+    pub fn current_roads(&self, app: &App) -> Vec<RoadID> {
+        match self.on {
             On::Road(r, _, _) => vec![r],
             On::Intersection(i) => app.map.get_i(i).roads.iter().cloned().collect(),
-        };
-        for r in roads {
+        }
+    }
+
+    /// Houses get their presents when the player rides past the spot where their driveway meets
+    /// the road. Returns the buildings along the current roads with that spot within `radius`,
+    /// closest first.
+    pub fn driveways_nearby(&self, app: &App, radius: Distance) -> Vec<(BuildingID, Pt2D)> {
+        let mut results = Vec::new();
+        for r in self.current_roads(app) {
+            let road = app.map.get_r(r);
+            for (dist, b) in self.bldgs_along_road.per_road.get(&r).into_iter().flatten() {
+                if let Ok((pt, _)) = road.center_pts.dist_along(*dist) {
+                    if pt.dist_to(self.pos) <= radius {
+                        results.push((*b, pt));
+                    }
+                }
+            }
+        }
+        results.sort_by_key(|(_, pt)| pt.dist_to(self.pos));
+        results
+    }
+
+    /// Is the player currently on a road with a bus or bike lane?
+    pub fn on_good_road(&self, app: &App) -> bool {
+        for r in self.current_roads(app) {
             for l in &app.map.get_r(r).lanes {
                 if l.lane_type == LaneType::Biking || l.lane_type == LaneType::Bus {
                     return true;
@@ -216,10 +301,104 @@ impl Player {
         false
     }
 
+    /// Big streets are plowed first, so they're faster to move along than snowy side streets.
+    pub fn surface_speed_multiplier(&self, app: &App) -> f64 {
+        let rank = match self.on {
+            On::Road(r, _, _) => app.map.get_r(r).get_rank(),
+            On::Intersection(i) => app.map.get_i(i).get_rank(&app.map),
+        };
+        match rank {
+            osm::RoadRank::Local => 0.8,
+            osm::RoadRank::Arterial => 1.0,
+            osm::RoadRank::Highway => 1.1,
+        }
+    }
+
+    /// How much the hills and the road surface speed up or slow down the player right now
+    pub fn terrain_multiplier(&self, app: &App, grade_speed_factor: f64) -> f64 {
+        (1.0 - grade_speed_factor * self.current_grade(app)).clamp(0.5, 1.5)
+            * self.surface_speed_multiplier(app)
+    }
+
+    /// The grade the player is currently moving along. Positive is uphill, negative downhill.
+    pub fn current_grade(&self, app: &App) -> f64 {
+        match self.on {
+            On::Road(r, _, dir) => {
+                let incline = app.map.get_r(r).percent_incline;
+                if dir == Direction::Fwd {
+                    incline
+                } else {
+                    -incline
+                }
+            }
+            On::Intersection(_) => 0.0,
+        }
+    }
+
+    /// Jump straight to an intersection, without passing anything along the way
+    pub fn teleport(&mut self, app: &App, i: IntersectionID) {
+        self.pos = app.map.get_i(i).polygon.center();
+        self.on = On::Intersection(i);
+    }
+
     /// For the game over animation
     pub fn override_pos(&mut self, pos: Pt2D) {
         self.pos = pos;
     }
+
+    pub fn saved_position(&self) -> SavedPosition {
+        match self.on {
+            On::Intersection(i) => SavedPosition::Intersection(i),
+            On::Road(road, dist, dir) => SavedPosition::Road {
+                road,
+                dist,
+                fwd: dir == Direction::Fwd,
+            },
+        }
+    }
+
+    /// Puts the sleigh back where a saved run left off
+    pub fn restore_position(&mut self, app: &App, saved: SavedPosition) {
+        match saved {
+            SavedPosition::Intersection(i) => {
+                self.teleport(app, i);
+            }
+            SavedPosition::Road { road, dist, fwd } => {
+                let (pt, angle) = app.map.get_r(road).center_pts.must_dist_along(dist);
+                self.pos = pt;
+                self.on = On::Road(
+                    road,
+                    dist,
+                    if fwd { Direction::Fwd } else { Direction::Back },
+                );
+                self.facing = if fwd { angle.opposite() } else { angle };
+            }
+        }
+    }
+}
+
+/// Where the sleigh is, exactly enough to put it back there in a resumed run
+#[derive(Serialize, Deserialize)]
+pub enum SavedPosition {
+    Intersection(IntersectionID),
+    Road {
+        road: RoadID,
+        dist: Distance,
+        fwd: bool,
+    },
+}
+
+impl SavedPosition {
+    /// False if the map has changed so much since the run was saved that this isn't on it anymore
+    pub fn still_on(&self, map: &Map) -> bool {
+        match self {
+            SavedPosition::Intersection(i) => map.maybe_get_i(*i).is_some(),
+            SavedPosition::Road { road, dist, .. } => map
+                .maybe_get_r(*road)
+                .map(|r| *dist <= r.length())
+                .unwrap_or(false),
+        }
+    }
 }
 
 #[derive(Clone, PartialEq)]
@@ -267,13 +446,8 @@ impl BuildingsAlongRoad {
     fn new(app: &App) -> BuildingsAlongRoad {
         let mut raw: MultiMap<RoadID, (Distance, BuildingID)> = MultiMap::new();
         for b in app.map.all_buildings() {
-            // TODO Happily assuming road and lane length is roughly the same
-            let road = app.map.get_parent(b.sidewalk_pos.lane());
-            let dist = match app.map.get_l(b.sidewalk_pos.lane()).dir {
-                Direction::Fwd => b.sidewalk_pos.dist_along(),
-                Direction::Back => road.length() - b.sidewalk_pos.dist_along(),
-            };
-            raw.insert(road.id, (dist, b.id));
+            let (road, dist) = driveway(app, b.id);
+            raw.insert(road, (dist, b.id));
         }
 
         let mut per_road = HashMap::new();
@@ -302,3 +476,16 @@ impl BuildingsAlongRoad {
         results
     }
 }
+
+/// Where a building's driveway meets the road, as a distance along the road's center line. Riding
+/// past this spot is what delivers presents and refills at stores.
+pub fn driveway(app: &App, b: BuildingID) -> (RoadID, Distance) {
+    let b = app.map.get_b(b);
+    // TODO Happily assuming road and lane length is roughly the same
+    let road = app.map.get_parent(b.sidewalk_pos.lane());
+    let dist = match app.map.get_l(b.sidewalk_pos.lane()).dir {
+        Direction::Fwd => b.sidewalk_pos.dist_along(),
+        Direction::Back => road.length() - b.sidewalk_pos.dist_along(),
+    };
+    (road.id, dist)
+}
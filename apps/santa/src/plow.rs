@@ -0,0 +1,94 @@
+use std::collections::HashSet;
+
+use geom::Distance;
+use map_model::RoadID;
+use widgetry::{Color, Drawable, EventCtx, GeomBatch, GfxCtx};
+
+use crate::arcade::Objective;
+use crate::player::Player;
+use crate::App;
+
+/// Every road starts out snowed in, and riding onto one clears it. There's nothing to deliver;
+/// it's just about covering as much of the map as possible before time runs out.
+pub struct SnowPlow {
+    plowed: HashSet<RoadID>,
+    distance: Distance,
+    total_roads: usize,
+    draw_snow: Drawable,
+}
+
+impl SnowPlow {
+    /// For `Arcade`
+    pub fn new_objective(ctx: &mut EventCtx, app: &App) -> Box<dyn Objective> {
+        Box::new(SnowPlow::new(ctx, app))
+    }
+
+    fn new(ctx: &mut EventCtx, app: &App) -> SnowPlow {
+        let mut plow = SnowPlow {
+            plowed: HashSet::new(),
+            distance: Distance::ZERO,
+            total_roads: app
+                .map
+                .all_roads()
+                .iter()
+                .filter(|r| !r.is_light_rail())
+                .count(),
+            draw_snow: Drawable::empty(ctx),
+        };
+        plow.redraw(ctx, app);
+        plow
+    }
+
+    fn redraw(&mut self, ctx: &mut EventCtx, app: &App) {
+        let mut batch = GeomBatch::new();
+        for road in app.map.all_roads() {
+            if !road.is_light_rail() && !self.plowed.contains(&road.id) {
+                batch.push(Color::WHITE.alpha(0.8), road.get_thick_polygon());
+            }
+        }
+        self.draw_snow = ctx.upload(batch);
+    }
+}
+
+impl Objective for SnowPlow {
+    fn name(&self) -> &'static str {
+        "Snow plow"
+    }
+
+    fn describe_score(&self, app: &App) -> String {
+        format!("{} plowed", self.distance.to_string(&app.opts.units))
+    }
+
+    fn update(&mut self, ctx: &mut EventCtx, app: &App, player: &Player) {
+        // At an intersection, this is every road touching it, so wait until the player's actually
+        // on one
+        let roads = player.current_roads(app);
+        if roads.len() != 1 {
+            return;
+        }
+        let road = app.map.get_r(roads[0]);
+        if road.is_light_rail() || !self.plowed.insert(road.id) {
+            return;
+        }
+        self.distance += road.length();
+        self.redraw(ctx, app);
+    }
+
+    fn draw(&self, g: &mut GfxCtx) {
+        g.redraw(&self.draw_snow);
+    }
+
+    fn results(&self, app: &App) -> Vec<String> {
+        vec![
+            format!(
+                "You plowed {} of {} roads",
+                self.plowed.len(),
+                self.total_roads
+            ),
+            format!(
+                "{} of road cleared",
+                self.distance.to_string(&app.opts.units)
+            ),
+        ]
+    }
+}
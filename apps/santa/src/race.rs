@@ -0,0 +1,328 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use geom::{Angle, Duration, Pt2D, Time};
+use map_model::BuildingID;
+use widgetry::{GfxCtx, RewriteColor};
+
+use crate::vehicles::Vehicle;
+
+// How often to tell the opponent where we are
+const SYNC_PERIOD: Duration = Duration::const_seconds(0.1);
+
+/// Race someone else on the same level over the network. Both players connect to a relay server
+/// that forwards every message to the other player who asked to race the same level. There's no
+/// prediction or authority; whoever's claim arrives first just wins the building.
+pub struct Race {
+    conn: Connection,
+    last_sync: Option<Time>,
+    opponent: Option<(Pt2D, Angle)>,
+    pub opponent_score: usize,
+    // What the player was last told about the connection
+    last_status: Option<Status>,
+}
+
+/// Whether the connection to the relay server is up
+#[derive(Clone, Copy, PartialEq)]
+pub enum Status {
+    Connecting,
+    Connected,
+    /// Never got through to the server
+    Failed,
+    /// Was connected once, but isn't anymore
+    Lost,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Message {
+    /// Sent once after connecting, so the server can pair up players
+    Join {
+        level: String,
+    },
+    Position {
+        x: f64,
+        y: f64,
+        angle: f64,
+    },
+    /// The sender delivered to a building and gained this many points
+    Claim(BuildingID, usize),
+}
+
+impl Race {
+    pub fn connect(server: &str, level: String) -> Result<Race> {
+        let mut race = Race {
+            conn: Connection::connect(server)?,
+            last_sync: None,
+            opponent: None,
+            opponent_score: 0,
+            last_status: None,
+        };
+        race.send(Message::Join { level });
+        Ok(race)
+    }
+
+    fn send(&mut self, msg: Message) {
+        match serde_json::to_string(&msg) {
+            Ok(txt) => self.conn.send(txt),
+            Err(err) => warn!("Couldn't encode race message: {}", err),
+        }
+    }
+
+    /// Call every tick. Only actually sends periodically.
+    pub fn sync_position(&mut self, now: Time, pos: Pt2D, angle: Angle) {
+        if self
+            .last_sync
+            .map(|t| now - t < SYNC_PERIOD)
+            .unwrap_or(false)
+        {
+            return;
+        }
+        self.last_sync = Some(now);
+        self.send(Message::Position {
+            x: pos.x(),
+            y: pos.y(),
+            angle: angle.normalized_degrees(),
+        });
+    }
+
+    pub fn claim(&mut self, b: BuildingID, increase: usize) {
+        self.send(Message::Claim(b, increase));
+    }
+
+    /// Only returns something when the connection changed since the last call
+    pub fn status_change(&mut self) -> Option<Status> {
+        let status = self.conn.status();
+        if self.last_status == Some(status) {
+            return None;
+        }
+        self.last_status = Some(status);
+        Some(status)
+    }
+
+    /// Returns the buildings the opponent claimed since the last call
+    pub fn receive(&mut self) -> Vec<BuildingID> {
+        let mut claimed = Vec::new();
+        for txt in self.conn.receive() {
+            match serde_json::from_str(&txt) {
+                Ok(Message::Position { x, y, angle }) => {
+                    self.opponent = Some((Pt2D::new(x, y), Angle::degrees(angle)));
+                }
+                Ok(Message::Claim(b, increase)) => {
+                    self.opponent_score += increase;
+                    claimed.push(b);
+                }
+                Ok(Message::Join { .. }) => {}
+                Err(err) => warn!("Bad race message {}: {}", txt, err),
+            }
+        }
+        claimed
+    }
+
+    pub fn draw(&self, g: &mut GfxCtx, vehicle: &Vehicle, now: Time) {
+        if let Some((pt, angle)) = self.opponent {
+            vehicle
+                .animate(g.prerender, now)
+                .color(RewriteColor::ChangeAlpha(0.7))
+                .centered_on(pt)
+                .rotate_around_batch_center(angle)
+                .draw(g);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+type Socket = tungstenite::WebSocket<tungstenite::stream::MaybeTlsStream<std::net::TcpStream>>;
+
+#[cfg(not(target_arch = "wasm32"))]
+enum Connection {
+    // Connecting happens on a background thread, since the game loop can't wait on the network
+    Connecting {
+        result: std::sync::mpsc::Receiver<Result<Socket>>,
+        // Messages sent before the socket finished opening
+        outbox: Vec<String>,
+    },
+    Open(Socket),
+    // Nothing gets sent or read anymore
+    Closed {
+        was_open: bool,
+    },
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Connection {
+    fn connect(url: &str) -> Result<Connection> {
+        let url = url.to_string();
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::Builder::new()
+            .name("race connection".to_string())
+            .spawn(move || {
+                let result = tungstenite::connect(url.as_str())
+                    .map_err(anyhow::Error::from)
+                    .and_then(|(socket, _)| {
+                        if let tungstenite::stream::MaybeTlsStream::Plain(ref stream) =
+                            socket.get_ref()
+                        {
+                            stream.set_nonblocking(true)?;
+                        }
+                        Ok(socket)
+                    });
+                // If the race was already abandoned, nobody's waiting for this
+                let _ = tx.send(result);
+            })?;
+        Ok(Connection::Connecting {
+            result: rx,
+            outbox: Vec::new(),
+        })
+    }
+
+    /// Picks up the socket once the background thread is done connecting
+    fn check_connected(&mut self) {
+        let (result, outbox) = match self {
+            Connection::Connecting { result, outbox } => match result.try_recv() {
+                Ok(result) => (result, std::mem::take(outbox)),
+                Err(std::sync::mpsc::TryRecvError::Empty) => {
+                    return;
+                }
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    (Err(anyhow!("connecting stopped partway")), Vec::new())
+                }
+            },
+            Connection::Open(_) | Connection::Closed { .. } => {
+                return;
+            }
+        };
+        match result {
+            Ok(socket) => {
+                *self = Connection::Open(socket);
+                for txt in outbox {
+                    self.send(txt);
+                }
+            }
+            Err(err) => {
+                warn!("Couldn't connect to the race server: {}", err);
+                *self = Connection::Closed { was_open: false };
+            }
+        }
+    }
+
+    fn status(&mut self) -> Status {
+        self.check_connected();
+        match self {
+            Connection::Connecting { .. } => Status::Connecting,
+            Connection::Open(_) => Status::Connected,
+            Connection::Closed { was_open: false } => Status::Failed,
+            Connection::Closed { was_open: true } => Status::Lost,
+        }
+    }
+
+    fn send(&mut self, txt: String) {
+        self.check_connected();
+        match self {
+            Connection::Connecting { outbox, .. } => {
+                outbox.push(txt);
+            }
+            Connection::Open(socket) => {
+                if let Err(err) = socket.send(tungstenite::Message::Text(txt)) {
+                    if !is_would_block(&err) {
+                        warn!("Lost the race connection: {}", err);
+                        *self = Connection::Closed { was_open: true };
+                    }
+                }
+            }
+            Connection::Closed { .. } => {}
+        }
+    }
+
+    fn receive(&mut self) -> Vec<String> {
+        self.check_connected();
+        let mut messages = Vec::new();
+        if let Connection::Open(socket) = self {
+            loop {
+                match socket.read() {
+                    Ok(tungstenite::Message::Text(txt)) => {
+                        messages.push(txt);
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        if !is_would_block(&err) {
+                            warn!("Lost the race connection: {}", err);
+                            *self = Connection::Closed { was_open: true };
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+        messages
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn is_would_block(err: &tungstenite::Error) -> bool {
+    matches!(err, tungstenite::Error::Io(err) if err.kind() == std::io::ErrorKind::WouldBlock)
+}
+
+#[cfg(target_arch = "wasm32")]
+struct Connection {
+    socket: web_sys::WebSocket,
+    inbox: std::rc::Rc<std::cell::RefCell<Vec<String>>>,
+    // Messages sent before the socket finished opening
+    outbox: Vec<String>,
+    // Dropping this would stop receiving messages
+    _on_message: wasm_bindgen::closure::Closure<dyn FnMut(web_sys::MessageEvent)>,
+    was_open: bool,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Connection {
+    fn connect(url: &str) -> Result<Connection> {
+        use wasm_bindgen::JsCast;
+
+        let socket = web_sys::WebSocket::new(url).map_err(|err| anyhow!("{:?}", err))?;
+        let inbox = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let inbox_copy = inbox.clone();
+        let on_message =
+            wasm_bindgen::closure::Closure::wrap(Box::new(move |ev: web_sys::MessageEvent| {
+                if let Some(txt) = ev.data().as_string() {
+                    inbox_copy.borrow_mut().push(txt);
+                }
+            })
+                as Box<dyn FnMut(web_sys::MessageEvent)>);
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        Ok(Connection {
+            socket,
+            inbox,
+            outbox: Vec::new(),
+            _on_message: on_message,
+            was_open: false,
+        })
+    }
+
+    fn status(&mut self) -> Status {
+        match self.socket.ready_state() {
+            web_sys::WebSocket::CONNECTING => Status::Connecting,
+            web_sys::WebSocket::OPEN => {
+                self.was_open = true;
+                Status::Connected
+            }
+            _ if self.was_open => Status::Lost,
+            _ => Status::Failed,
+        }
+    }
+
+    fn send(&mut self, txt: String) {
+        self.outbox.push(txt);
+        if self.socket.ready_state() != web_sys::WebSocket::OPEN {
+            return;
+        }
+        for txt in self.outbox.drain(..) {
+            if let Err(err) = self.socket.send_with_str(&txt) {
+                warn!("Couldn't send race message: {:?}", err);
+            }
+        }
+    }
+
+    fn receive(&mut self) -> Vec<String> {
+        std::mem::take(&mut *self.inbox.borrow_mut())
+    }
+}
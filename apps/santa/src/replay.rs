@@ -0,0 +1,324 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::{prettyprint_usize, Timer};
+use geom::{Angle, Duration, Pt2D, Time};
+use map_model::BuildingID;
+use widgetry::{
+    Drawable, EventCtx, GeomBatch, GfxCtx, HorizontalAlignment, Key, Line, Outcome, Panel, Slider,
+    State, Text, TextExt, UpdateType, VerticalAlignment, Widget,
+};
+
+use crate::buildings::Buildings;
+use crate::ghost::Ghost;
+use crate::trail::{Trail, TRAIL_EVERY, TRAIL_LENGTH};
+use crate::{App, Transition};
+
+// The range of the playback speed slider
+const MIN_SPEED: f64 = 0.5;
+const MAX_SPEED: f64 = 8.0;
+
+/// Everything that happened during one run, so it can be watched again later. The most recent run
+/// on each level is saved as its own file, so it's easy to share.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub level: String,
+    pub score: usize,
+    duration: Duration,
+    // Enough to recreate the same buildings as the run
+    upzones: HashSet<BuildingID>,
+    seed: u64,
+    path: Ghost,
+    events: Vec<(Duration, ReplayEvent)>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+enum ReplayEvent {
+    /// How much the score went up
+    Delivered(BuildingID, usize),
+    /// How much energy was refilled
+    Recharged(BuildingID, usize),
+}
+
+impl Replay {
+    pub fn new(level: String, vehicle: String, upzones: HashSet<BuildingID>, seed: u64) -> Replay {
+        Replay {
+            level,
+            score: 0,
+            duration: Duration::ZERO,
+            upzones,
+            seed,
+            path: Ghost::new(vehicle),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, now: Time, pos: Pt2D, angle: Angle) {
+        self.duration = now - Time::START_OF_DAY;
+        self.path.record(now, pos, angle);
+    }
+
+    pub fn delivered(&mut self, now: Time, b: BuildingID, increase: usize) {
+        self.score += increase;
+        self.events.push((
+            now - Time::START_OF_DAY,
+            ReplayEvent::Delivered(b, increase),
+        ));
+    }
+
+    pub fn recharged(&mut self, now: Time, b: BuildingID, refill: usize) {
+        self.events
+            .push((now - Time::START_OF_DAY, ReplayEvent::Recharged(b, refill)));
+    }
+
+    pub fn points(&self) -> Vec<Pt2D> {
+        self.path.points()
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// The same buildings the run started with
+    pub fn buildings(&self, ctx: &mut EventCtx, app: &App) -> Buildings {
+        Buildings::new(ctx, app, self.upzones.clone(), self.seed)
+    }
+
+    /// For drawing a trail behind the sleigh
+    pub fn recent_points(&self, time: Duration, length: Duration, every: usize) -> Vec<Pt2D> {
+        self.path
+            .recent_points(Time::START_OF_DAY + time, length, every)
+    }
+
+    /// Where Santa was, some time after the start of the run
+    pub fn sleigh_pos(&self, time: Duration) -> Option<Pt2D> {
+        self.path.pos(Time::START_OF_DAY + time).map(|(pt, _)| pt)
+    }
+
+    pub fn draw_sleigh(&self, g: &mut GfxCtx, time: Duration) {
+        self.path.draw_sleigh(g, Time::START_OF_DAY + time);
+    }
+
+    /// When each house was delivered to, and how much the score went up
+    pub fn deliveries(&self) -> Vec<(Duration, BuildingID, usize)> {
+        self.events
+            .iter()
+            .filter_map(|(t, ev)| match ev {
+                ReplayEvent::Delivered(b, increase) => Some((*t, *b, *increase)),
+                ReplayEvent::Recharged(_, _) => None,
+            })
+            .collect()
+    }
+
+    pub fn save(&self) {
+        abstio::write_json(path(&self.level), self);
+    }
+
+    pub fn load(level: &str) -> Option<Replay> {
+        abstio::maybe_read_json(path(level), &mut Timer::throwaway()).ok()
+    }
+}
+
+fn path(level: &str) -> String {
+    abstio::path_player(format!("santa_replays/{}.json", level))
+}
+
+/// Plays back a run, with controls to pause, skip around, and speed up.
+pub struct ReplayViewer {
+    panel: Panel,
+    replay: Replay,
+    bldgs: Buildings,
+
+    // Since the start of the run
+    time: Duration,
+    playing: bool,
+    speed: f64,
+
+    // How many events have happened as of the current time, as drawn
+    num_events: Option<usize>,
+    draw_events: Drawable,
+    trail: Trail,
+}
+
+impl ReplayViewer {
+    pub fn new_state(ctx: &mut EventCtx, app: &App, replay: Replay) -> Box<dyn State<App>> {
+        let bldgs = replay.buildings(ctx, app);
+        let panel = Panel::new_builder(Widget::col(vec![
+            Line(format!("Replay of {}", replay.level))
+                .small_heading()
+                .into_widget(ctx),
+            Text::new().into_widget(ctx).named("time"),
+            Slider::area(ctx, 300.0, 0.0, "scrub"),
+            Widget::row(vec![
+                ctx.style()
+                    .btn_outline
+                    .text("pause")
+                    .hotkey(Key::Space)
+                    .build_widget(ctx, "play/pause"),
+                "Speed".text_widget(ctx).centered_vert(),
+                Slider::area(
+                    ctx,
+                    150.0,
+                    (1.0 - MIN_SPEED) / (MAX_SPEED - MIN_SPEED),
+                    "speed",
+                ),
+            ]),
+            Text::new().into_widget(ctx).named("stats"),
+            ctx.style()
+                .btn_outline
+                .text("Back")
+                .hotkey(Key::Escape)
+                .build_def(ctx),
+        ]))
+        .aligned(HorizontalAlignment::Right, VerticalAlignment::Top)
+        .build(ctx);
+
+        let mut viewer = ReplayViewer {
+            panel,
+            replay,
+            bldgs,
+            time: Duration::ZERO,
+            playing: true,
+            speed: 1.0,
+            num_events: None,
+            draw_events: Drawable::empty(ctx),
+            trail: Trail::new(ctx),
+        };
+        viewer.update_time(ctx, app);
+        Box::new(viewer)
+    }
+
+    /// Call after the time changes for any reason
+    fn update_time(&mut self, ctx: &mut EventCtx, app: &App) {
+        let label =
+            Text::from(format!("{} / {}", self.time, self.replay.duration)).into_widget(ctx);
+        self.panel.replace(ctx, "time", label);
+        if app.session.show_trail {
+            self.trail.update(
+                ctx,
+                self.replay
+                    .recent_points(self.time, TRAIL_LENGTH, TRAIL_EVERY),
+            );
+        }
+
+        let num_events = self
+            .replay
+            .events
+            .iter()
+            .take_while(|(t, _)| *t <= self.time)
+            .count();
+        if self.num_events == Some(num_events) {
+            return;
+        }
+        self.num_events = Some(num_events);
+
+        let mut score = 0;
+        let mut refills = 0;
+        let mut batch = GeomBatch::new();
+        for (_, ev) in &self.replay.events[0..num_events] {
+            match ev {
+                ReplayEvent::Delivered(b, increase) => {
+                    score += increase;
+                    batch.push(
+                        app.session.colors.visited,
+                        app.map.get_b(*b).polygon.clone(),
+                    );
+                }
+                ReplayEvent::Recharged(_, _) => {
+                    refills += 1;
+                }
+            }
+        }
+        self.draw_events = ctx.upload(batch);
+
+        let mut txt = Text::new();
+        txt.add_line(format!(
+            "Delivered {} / {} presents",
+            prettyprint_usize(score),
+            prettyprint_usize(self.replay.score)
+        ));
+        txt.add_line(format!("Refilled at a store {} times", refills));
+        let stats = txt.into_widget(ctx);
+        self.panel.replace(ctx, "stats", stats);
+    }
+
+    fn set_playing(&mut self, ctx: &mut EventCtx, playing: bool) {
+        self.playing = playing;
+        let btn = ctx
+            .style()
+            .btn_outline
+            .text(if playing { "pause" } else { "play" })
+            .hotkey(Key::Space)
+            .build_widget(ctx, "play/pause");
+        self.panel.replace(ctx, "play/pause", btn);
+    }
+}
+
+impl State<App> for ReplayViewer {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        if let Some(dt) = ctx.input.nonblocking_is_update_event() {
+            if self.playing {
+                self.time = (self.time + dt * self.speed).min(self.replay.duration);
+                let pct = self.time / self.replay.duration;
+                self.panel.slider_mut("scrub").set_percent(ctx, pct);
+                if let Some(pt) = self.replay.sleigh_pos(self.time) {
+                    ctx.canvas.center_on_map_pt(pt);
+                }
+                self.update_time(ctx, app);
+                if self.time == self.replay.duration {
+                    self.set_playing(ctx, false);
+                }
+            }
+        }
+        if self.playing {
+            ctx.request_update(UpdateType::Game);
+        } else {
+            ctx.canvas_movement();
+        }
+
+        match self.panel.event(ctx) {
+            Outcome::Clicked(x) => match x.as_ref() {
+                "Back" => {
+                    return Transition::Pop;
+                }
+                "play/pause" => {
+                    if !self.playing && self.time == self.replay.duration {
+                        // Start over
+                        self.time = Duration::ZERO;
+                        self.update_time(ctx, app);
+                    }
+                    self.set_playing(ctx, !self.playing);
+                }
+                _ => unreachable!(),
+            },
+            Outcome::Changed(x) => match x.as_ref() {
+                "scrub" => {
+                    self.time = self.replay.duration * self.panel.slider("scrub").get_percent();
+                    self.update_time(ctx, app);
+                }
+                "speed" => {
+                    self.speed = MIN_SPEED
+                        + (MAX_SPEED - MIN_SPEED) * self.panel.slider("speed").get_percent();
+                }
+                _ => unreachable!(),
+            },
+            _ => {}
+        }
+
+        app.session.update_music(ctx);
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        g.redraw(&self.bldgs.draw_all);
+        self.bldgs.labels.draw(g);
+        g.redraw(&self.draw_events);
+        if app.session.show_trail {
+            self.trail.draw(g);
+        }
+        self.replay.draw_sleigh(g, self.time);
+        self.panel.draw(g);
+        app.session.music.draw(g);
+    }
+}
@@ -0,0 +1,449 @@
+use std::collections::{BTreeMap, HashMap};
+
+use geom::{Duration, Time};
+use map_model::BuildingID;
+
+use crate::buildings::{BldgState, PresentType, StoreType};
+use crate::levels::Level;
+use crate::mode::Mode;
+use crate::stock::StoreStock;
+use crate::tuning::Tuning;
+use crate::windows::DeliveryWindow;
+
+/// How a run is scored, and what holds the player back: presents, blood sugar, combos,
+/// power-ups, delivery windows, and what's left on each store's shelves. The game and the
+/// headless simulation both play by these, so they can't drift apart. Nothing here draws or
+/// knows about the map. The tuning gets passed in, since the game can reload it partway through.
+pub struct Rules {
+    pub mode: Mode,
+    // From the level
+    time_limit: Duration,
+    no_stores: bool,
+    presents_per_type: Option<usize>,
+    windows: HashMap<BuildingID, DeliveryWindow>,
+
+    // Number of deliveries
+    pub score: usize,
+    // Different than the score, which counts presents
+    pub houses_delivered: usize,
+    pub energy: usize,
+    // How many of each kind of present is left
+    pub presents: BTreeMap<PresentType, usize>,
+    // How many deliveries in a row were made quickly
+    pub combo: usize,
+    pub last_delivery: Option<Time>,
+    // When power-ups wear off
+    pub double_score_until: Option<Time>,
+    pub speed_until: Option<Time>,
+    pub snack_until: Option<Time>,
+    // What's left on each store's shelves
+    pub stock: StoreStock,
+}
+
+impl Rules {
+    /// Starts out with no blood sugar and an empty sack; fill them once the vehicle's known
+    pub fn new(level: &Level, mode: Mode) -> Rules {
+        Rules {
+            mode,
+            time_limit: level.time_limit,
+            no_stores: level.script.no_stores,
+            presents_per_type: level.script.presents,
+            windows: HashMap::new(),
+
+            score: 0,
+            houses_delivered: 0,
+            energy: 0,
+            presents: PresentType::ALL.into_iter().map(|p| (p, 0)).collect(),
+            combo: 0,
+            last_delivery: None,
+            double_score_until: None,
+            speed_until: None,
+            snack_until: None,
+            stock: StoreStock::new(),
+        }
+    }
+
+    /// Houses that only take deliveries during part of the time limit, from `windows::pick`
+    pub fn set_windows(&mut self, windows: HashMap<BuildingID, DeliveryWindow>) {
+        self.windows = windows;
+    }
+
+    /// How much of the time limit has passed, from 0 to 1
+    pub fn pct_elapsed(&self, now: Time) -> f64 {
+        ((now - Time::START_OF_DAY) / self.time_limit).min(1.0)
+    }
+
+    /// If the house only takes deliveries at another time, returns its window
+    pub fn closed_window(&self, b: BuildingID, now: Time) -> Option<DeliveryWindow> {
+        let window = *self.windows.get(&b)?;
+        if window.is_open(self.pct_elapsed(now)) {
+            None
+        } else {
+            Some(window)
+        }
+    }
+
+    pub fn has_energy(&self) -> bool {
+        self.energy > 0
+    }
+
+    pub fn drain_energy(&mut self, amount: usize) {
+        if self.mode != Mode::Zen {
+            self.energy = self.energy.saturating_sub(amount);
+        }
+    }
+
+    pub fn power_up_active(&self, now: Time, until: Option<Time>) -> bool {
+        until.map(|t| now < t).unwrap_or(false)
+    }
+
+    pub fn combo_multiplier(&self, tuning: &Tuning) -> f64 {
+        (1.0 + tuning.combo_step * (self.combo as f64)).min(tuning.max_combo_multiplier)
+    }
+
+    /// Drops off one present, if there's blood sugar left, a present of the right kind, and
+    /// someone home. Quick deliveries build up a combo, and power-ups multiply the score on top.
+    /// Returns the increase in score. Marking the house as done is up to the caller.
+    pub fn deliver(
+        &mut self,
+        tuning: &Tuning,
+        now: Time,
+        b: BuildingID,
+        num_housing_units: usize,
+        wanted: PresentType,
+    ) -> Option<usize> {
+        if !self.has_energy() || self.presents[&wanted] == 0 || self.closed_window(b, now).is_some()
+        {
+            return None;
+        }
+        *self.presents.get_mut(&wanted).unwrap() -= 1;
+
+        let combo_continues = self
+            .last_delivery
+            .map(|t| now - t <= tuning.combo_window)
+            .unwrap_or(false);
+        self.combo = if combo_continues { self.combo + 1 } else { 0 };
+        self.last_delivery = Some(now);
+
+        let mut multiplier = self.combo_multiplier(tuning);
+        if self.power_up_active(now, self.double_score_until) {
+            multiplier *= 2.0;
+        }
+        if self.power_up_active(now, self.snack_until) {
+            multiplier *= tuning.snack_multiplier;
+        }
+        let increase = ((num_housing_units as f64) * multiplier).round() as usize;
+        self.score += increase;
+        self.houses_delivered += 1;
+        self.drain_energy(1);
+        Some(increase)
+    }
+
+    /// Each restaurant only has one snack to spare. Returns true if this one did.
+    pub fn eat_snack(&mut self, tuning: &Tuning, now: Time, bldg: &mut BldgState) -> bool {
+        if self.no_stores {
+            return false;
+        }
+        if let BldgState::Store(StoreType::Restaurant) = bldg {
+            *bldg = BldgState::Snacked(StoreType::Restaurant);
+            self.snack_until = Some(now + tuning.snack_duration);
+            return true;
+        }
+        false
+    }
+
+    // The level may override how many of each present fit in the sack
+    fn presents_per_type(&self, tuning: &Tuning) -> usize {
+        self.presents_per_type.unwrap_or(tuning.presents_per_type)
+    }
+
+    /// Fill up on every kind of present. Returns true if anything was missing.
+    pub fn restock_presents(&mut self, tuning: &Tuning) -> bool {
+        self.restock_presents_up_to(tuning, usize::MAX) > 0
+    }
+
+    // Tops up whichever kind is running lowest first, giving out no more than `limit`. Returns
+    // how many were taken.
+    fn restock_presents_up_to(&mut self, tuning: &Tuning, limit: usize) -> usize {
+        let per_type = self.presents_per_type(tuning);
+        let mut taken = 0;
+        while taken < limit {
+            let count = self
+                .presents
+                .values_mut()
+                .min_by_key(|count| **count)
+                .unwrap();
+            if *count >= per_type {
+                break;
+            }
+            *count += 1;
+            taken += 1;
+        }
+        taken
+    }
+
+    /// How much blood sugar and how many presents are on a store's shelves right now
+    pub fn store_stock(
+        &self,
+        tuning: &Tuning,
+        max_energy: usize,
+        b: BuildingID,
+        now: Time,
+    ) -> (usize, usize) {
+        let (energy, presents) = self.stock.left(b, now, tuning.store_restock_time);
+        (
+            (energy * tuning.store_energy_stock * (max_energy as f64)).floor() as usize,
+            (presents * (tuning.store_present_stock as f64)).floor() as usize,
+        )
+    }
+
+    /// Less than half of either is left
+    pub fn stock_low(&self, tuning: &Tuning, b: BuildingID, now: Time) -> bool {
+        let (energy, presents) = self.stock.left(b, now, tuning.store_restock_time);
+        energy < 0.5 || presents < 0.5
+    }
+
+    /// How much blood sugar and how many presents visiting a store would give right now
+    pub fn store_offers(
+        &self,
+        tuning: &Tuning,
+        max_energy: usize,
+        b: BuildingID,
+        store_type: StoreType,
+        now: Time,
+    ) -> (usize, usize) {
+        if self.no_stores {
+            return (0, 0);
+        }
+        let (energy_left, presents_left) = self.store_stock(tuning, max_energy, b, now);
+        let refill_to = ((max_energy as f64) * tuning.refill(store_type)).round() as usize;
+        let refill = refill_to
+            .min(max_energy)
+            .saturating_sub(self.energy)
+            .min(energy_left);
+        let per_type = self.presents_per_type(tuning);
+        let missing: usize = self
+            .presents
+            .values()
+            .map(|count| per_type.saturating_sub(*count))
+            .sum();
+        (refill, missing.min(presents_left))
+    }
+
+    /// Refills as much as the store has on its shelves. Returns how much blood sugar and how
+    /// many presents were taken.
+    pub fn visit_store(
+        &mut self,
+        tuning: &Tuning,
+        max_energy: usize,
+        b: BuildingID,
+        store_type: StoreType,
+        now: Time,
+    ) -> (usize, usize) {
+        let (refill, presents) = self.store_offers(tuning, max_energy, b, store_type, now);
+        let restocked = self.restock_presents_up_to(tuning, presents);
+        self.energy += refill;
+
+        let energy_stock = tuning.store_energy_stock * (max_energy as f64);
+        let present_stock = tuning.store_present_stock as f64;
+        if refill > 0 || restocked > 0 {
+            self.stock.take(
+                b,
+                now,
+                tuning.store_restock_time,
+                if refill > 0 {
+                    (refill as f64) / energy_stock
+                } else {
+                    0.0
+                },
+                if restocked > 0 {
+                    (restocked as f64) / present_stock
+                } else {
+                    0.0
+                },
+            );
+        }
+        (refill, restocked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level() -> Level {
+        let mut level = Level::tutorial();
+        level.time_limit = Duration::minutes(9);
+        level
+    }
+
+    fn rules(mode: Mode) -> Rules {
+        let tuning = Tuning::default();
+        let mut rules = Rules::new(&level(), mode);
+        rules.energy = 100;
+        rules.restock_presents(&tuning);
+        rules
+    }
+
+    fn at(seconds: f64) -> Time {
+        Time::START_OF_DAY + Duration::seconds(seconds)
+    }
+
+    #[test]
+    fn test_combo_grows_with_quick_deliveries() {
+        let tuning = Tuning::default();
+        let mut rules = rules(Mode::Normal);
+        assert_eq!(
+            Some(10),
+            rules.deliver(&tuning, at(0.0), BuildingID(0), 10, PresentType::Toy)
+        );
+        assert_eq!(
+            Some(11),
+            rules.deliver(&tuning, at(5.0), BuildingID(1), 10, PresentType::Toy)
+        );
+        assert_eq!(
+            Some(12),
+            rules.deliver(&tuning, at(10.0), BuildingID(2), 10, PresentType::Book)
+        );
+        // Too slow, so the combo resets
+        assert_eq!(
+            Some(10),
+            rules.deliver(&tuning, at(60.0), BuildingID(3), 10, PresentType::Toy)
+        );
+        assert_eq!(43, rules.score);
+        assert_eq!(4, rules.houses_delivered);
+        assert_eq!(96, rules.energy);
+        assert_eq!(
+            tuning.presents_per_type - 3,
+            rules.presents[&PresentType::Toy]
+        );
+    }
+
+    #[test]
+    fn test_power_ups_multiply() {
+        let tuning = Tuning::default();
+        let mut rules = rules(Mode::Normal);
+        rules.double_score_until = Some(at(30.0));
+        rules.snack_until = Some(at(30.0));
+        assert_eq!(
+            Some(40),
+            rules.deliver(&tuning, at(0.0), BuildingID(0), 10, PresentType::Toy)
+        );
+        // Both wore off
+        assert_eq!(
+            Some(10),
+            rules.deliver(&tuning, at(60.0), BuildingID(1), 10, PresentType::Toy)
+        );
+    }
+
+    #[test]
+    fn test_no_delivery_without_presents_or_energy() {
+        let tuning = Tuning::default();
+        let mut rules = rules(Mode::Normal);
+        *rules.presents.get_mut(&PresentType::Book).unwrap() = 0;
+        assert_eq!(
+            None,
+            rules.deliver(&tuning, at(0.0), BuildingID(0), 10, PresentType::Book)
+        );
+        rules.energy = 0;
+        assert_eq!(
+            None,
+            rules.deliver(&tuning, at(0.0), BuildingID(0), 10, PresentType::Toy)
+        );
+        assert_eq!(0, rules.score);
+    }
+
+    #[test]
+    fn test_zen_never_drains() {
+        let tuning = Tuning::default();
+        let mut rules = rules(Mode::Zen);
+        rules.deliver(&tuning, at(0.0), BuildingID(0), 10, PresentType::Toy);
+        rules.drain_energy(50);
+        assert_eq!(100, rules.energy);
+    }
+
+    #[test]
+    fn test_closed_window() {
+        let tuning = Tuning::default();
+        let mut rules = rules(Mode::Normal);
+        rules.set_windows(
+            vec![
+                (BuildingID(0), DeliveryWindow::Early),
+                (BuildingID(1), DeliveryWindow::Late),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        // 9 minutes, so each third is 3 minutes
+        let middle = at(4.0 * 60.0);
+        assert_eq!(
+            Some(DeliveryWindow::Early),
+            rules.closed_window(BuildingID(0), middle)
+        );
+        assert_eq!(
+            None,
+            rules.deliver(&tuning, middle, BuildingID(1), 10, PresentType::Toy)
+        );
+        assert_eq!(
+            Some(10),
+            rules.deliver(&tuning, middle, BuildingID(2), 10, PresentType::Toy)
+        );
+        assert_eq!(
+            Some(10),
+            rules.deliver(&tuning, at(8.0 * 60.0), BuildingID(1), 10, PresentType::Toy)
+        );
+    }
+
+    #[test]
+    fn test_store_stock_runs_out_and_restocks() {
+        let mut tuning = Tuning::default();
+        // The shelves hold 2 full tanks
+        tuning.store_energy_stock = 2.0;
+        let store = BuildingID(0);
+        let mut rules = rules(Mode::Normal);
+        for _ in 0..2 {
+            rules.energy = 0;
+            assert_eq!(
+                (100, 0),
+                rules.visit_store(&tuning, 100, store, StoreType::Supermarket, at(0.0))
+            );
+        }
+        rules.energy = 0;
+        assert_eq!(
+            (0, 0),
+            rules.visit_store(&tuning, 100, store, StoreType::Supermarket, at(0.0))
+        );
+        assert!(rules.stock_low(&tuning, store, at(0.0)));
+        // Other stores have their own shelves
+        assert_eq!(
+            (100, 0),
+            rules.visit_store(&tuning, 100, BuildingID(1), StoreType::Supermarket, at(0.0))
+        );
+        rules.energy = 0;
+        assert_eq!(
+            (100, 0),
+            rules.store_offers(
+                &tuning,
+                100,
+                store,
+                StoreType::Supermarket,
+                at(0.0) + tuning.store_restock_time
+            )
+        );
+    }
+
+    #[test]
+    fn test_no_stores() {
+        let tuning = Tuning::default();
+        let mut level = level();
+        level.script.no_stores = true;
+        let mut rules = Rules::new(&level, Mode::Normal);
+        assert_eq!(
+            (0, 0),
+            rules.visit_store(&tuning, 100, BuildingID(0), StoreType::Supermarket, at(0.0))
+        );
+        let mut bldg = BldgState::Store(StoreType::Restaurant);
+        assert!(!rules.eat_snack(&tuning, at(0.0), &mut bldg));
+        assert_eq!(0, rules.energy);
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::Timer;
+use geom::{Duration, LonLat, Time};
+use map_model::BuildingID;
+use widgetry::tools::PopupMsg;
+use widgetry::{EventCtx, State};
+
+use crate::buildings::PresentType;
+use crate::difficulty::DifficultyPreset;
+use crate::elf::SavedElf;
+use crate::featured;
+use crate::game::Game;
+use crate::ghost::Ghost;
+use crate::levels::Level;
+use crate::load_map::load_map;
+use crate::mode::Mode;
+use crate::player::SavedPosition;
+use crate::replay::Replay;
+use crate::stock::StoreStock;
+use crate::{App, Transition};
+
+/// Bump this whenever anything below changes, so saves from older builds aren't misread
+pub const VERSION: usize = 3;
+
+/// A run that was quit partway through, to resume from the title screen. Only one is kept at a
+/// time, and it's removed once resumed.
+#[derive(Serialize, Deserialize)]
+pub struct SavedRun {
+    pub version: usize,
+    pub level: String,
+    // The start may have been moved before the run
+    pub start: LonLat,
+    pub vehicle: String,
+    pub upzones: HashSet<BuildingID>,
+    pub mode: Mode,
+    pub seed: u64,
+    pub difficulty: DifficultyPreset,
+
+    pub elapsed: Duration,
+    pub next_event: usize,
+    pub score: usize,
+    pub houses_delivered: usize,
+    pub energy: usize,
+    pub presents: BTreeMap<PresentType, usize>,
+    pub selected_present: PresentType,
+    pub boost: Duration,
+    pub combo: usize,
+    pub last_delivery: Option<Time>,
+    // When each power-up wears off
+    pub double_score_until: Option<Time>,
+    pub speed_until: Option<Time>,
+    pub snack_until: Option<Time>,
+    // Zone progress is worked out again from these
+    pub delivered: BTreeSet<BuildingID>,
+    pub snacked: BTreeSet<BuildingID>,
+    pub stock: StoreStock,
+    pub position: SavedPosition,
+    pub elf: Option<SavedElf>,
+
+    pub ghost: Ghost,
+    pub replay: Replay,
+}
+
+// Just enough to check before trying to read the rest
+#[derive(Deserialize)]
+struct Version {
+    version: usize,
+}
+
+impl SavedRun {
+    pub fn save(&self) {
+        abstio::write_json(path(), self);
+    }
+
+    /// A save that can't be read, probably from an older version of the game, is thrown out
+    pub fn load() -> Option<SavedRun> {
+        let version: Version = abstio::maybe_read_json(path(), &mut Timer::throwaway()).ok()?;
+        if version.version != VERSION {
+            warn!(
+                "Throwing out a saved run from version {}; this is version {}",
+                version.version, VERSION
+            );
+            SavedRun::clear();
+            return None;
+        }
+        match abstio::maybe_read_json(path(), &mut Timer::throwaway()) {
+            Ok(run) => Some(run),
+            Err(err) => {
+                warn!("Throwing out a saved run that couldn't be read: {}", err);
+                SavedRun::clear();
+                None
+            }
+        }
+    }
+
+    pub fn clear() {
+        abstio::delete_file(path());
+    }
+
+    /// Loads the map, then carries on with the run
+    pub fn resume(self, ctx: &mut EventCtx, app: &App) -> Transition {
+        let level = match self.find_level(app) {
+            Some(level) => level,
+            None => {
+                SavedRun::clear();
+                return Transition::Push(PopupMsg::new_state(
+                    ctx,
+                    "Can't resume this run",
+                    vec![format!("The level {} doesn't exist anymore.", self.level)],
+                ));
+            }
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if !abstio::file_exists(level.map.path()) {
+                return map_gui::tools::prompt_to_download_missing_data(
+                    ctx,
+                    level.map.clone(),
+                    Box::new(move |ctx, app| Transition::Replace(load(ctx, app, level, self))),
+                );
+            }
+        }
+
+        Transition::Push(load(ctx, app, level, self))
+    }
+
+    // The level this run was on, starting where it originally did. None if it's gone, like a
+    // custom level that was deleted.
+    fn find_level(&self, app: &App) -> Option<Level> {
+        let boss = Level::boss();
+        // Only until the featured level changes
+        let featured = featured::this_week(app);
+        let mut level = app
+            .session
+            .levels
+            .iter()
+            .chain(std::iter::once(&boss))
+            .chain(&app.session.custom_levels)
+            .chain(&featured)
+            .find(|level| level.title == self.level)?
+            .clone();
+        level.start = self.start;
+        Some(level)
+    }
+}
+
+fn load(ctx: &mut EventCtx, app: &App, level: Level, run: SavedRun) -> Box<dyn State<App>> {
+    load_map(
+        ctx,
+        app,
+        level.map.clone(),
+        Box::new(move |ctx, app| {
+            app.session.music.change_song(&level.music);
+            // The saved start already came from the depot, or from the player moving it, so
+            // don't resolve the depot again
+            Transition::Replace(Game::resume(ctx, app, level, run))
+        }),
+    )
+}
+
+fn path() -> String {
+    abstio::path_player("santa_saved_run.json")
+}
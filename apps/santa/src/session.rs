@@ -3,24 +3,55 @@ use std::collections::{BTreeSet, HashMap};
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_multimap, serialize_multimap, MultiMap, Timer};
+use geom::Duration;
 use map_model::BuildingID;
 use widgetry::{Color, EventCtx};
 
+use crate::achievements::Achievement;
+use crate::controls::{ControlScheme, KeyBindings};
+use crate::difficulty::DifficultyPreset;
+use crate::gamepad::Gamepad;
+use crate::ghost::Ghost;
+use crate::i18n::Locale;
+use crate::leaderboard::LeaderboardSettings;
 use crate::levels::Level;
+use crate::mode::Mode;
 use crate::music::Music;
+use crate::shop::Upgrades;
+use crate::stats_feed::StatsFeed;
 
 /// Persistent state that lasts across levels.
 #[derive(Serialize, Deserialize)]
 pub struct Session {
     pub levels: Vec<Level>,
+    /// Read from the player's data directory every time, so editing the file doesn't need a reset
+    #[serde(skip_serializing, skip_deserializing)]
+    pub custom_levels: Vec<Level>,
     /// Enable this to use the levels, instead of overwriting them with the version in the code.
     pub enable_modding: bool,
+    // Always derived from the palette
+    #[serde(
+        skip_serializing,
+        skip_deserializing,
+        default = "ColorScheme::default_palette"
+    )]
     pub colors: ColorScheme,
+    #[serde(default)]
+    pub palette: Palette,
+    #[serde(default)]
+    pub locale: Locale,
 
-    /// Level title -> the top 3 scores
+    /// Level title -> the top 3 scores. Other difficulties than normal get their own entries,
+    /// from `DifficultyPreset::score_key`.
     pub high_scores: HashMap<String, Vec<usize>>,
     pub levels_unlocked: usize,
     pub current_vehicle: String,
+    /// The mode picked most recently, so the next level starts with it too
+    #[serde(default)]
+    pub last_mode: Mode,
+    /// Also kept from the last level played
+    #[serde(default)]
+    pub difficulty: DifficultyPreset,
     pub vehicles_unlocked: BTreeSet<String>,
     pub upzones_unlocked: usize,
     pub upzones_explained: bool,
@@ -36,18 +67,293 @@ pub struct Session {
     #[serde(skip_serializing, skip_deserializing)]
     pub music: Music,
     pub play_music: bool,
+    /// From 0 to 1
+    #[serde(default = "full_volume")]
+    pub music_volume: f32,
+    #[serde(default = "full_volume")]
+    pub sound_volume: f32,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub gamepad: Gamepad,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub stats_feed: StatsFeed,
+    #[serde(default)]
+    pub disable_gamepad: bool,
+    #[serde(default)]
+    pub keys: KeyBindings,
+    #[serde(default)]
+    pub control_scheme: ControlScheme,
+    #[serde(default)]
+    pub camera: CameraMode,
+    /// From 0 to 1, how far the smooth camera trails behind and looks ahead
+    #[serde(default = "half")]
+    pub camera_lag: f64,
+    #[serde(default = "half")]
+    pub camera_lookahead: f64,
+    #[serde(default = "shown")]
+    pub screen_shake: bool,
+    /// Zoom out when moving fast, and in when the mouse is over a building
+    #[serde(default = "shown")]
+    pub auto_zoom: bool,
+    #[serde(default)]
+    pub snow: SnowDensity,
+    /// Labels over each house with how many presents it wants
+    #[serde(default = "shown")]
+    pub show_score_labels: bool,
+    /// Highlight undelivered houses coming up near the sleigh
+    #[serde(default = "shown")]
+    pub show_nearby_houses: bool,
+    /// Deliver to houses in reach without pressing anything
+    #[serde(default = "shown")]
+    pub auto_drop: bool,
+    /// A fading line behind the sleigh, showing where it's been
+    #[serde(default = "shown")]
+    pub show_trail: bool,
+    /// No screen shake, slow motion, sparks, or snow
+    #[serde(default)]
+    pub reduce_motion: bool,
+    /// How big the text in the game's panels and over houses is
+    #[serde(default)]
+    pub text_size: TextSize,
+    // Players who already have a save file from before the tutorial existed shouldn't be forced
+    // through it.
+    #[serde(default = "tutorial_done_for_old_saves")]
+    pub tutorial_done: bool,
+    /// Level title -> the time to reach each split in the fastest time trial
+    #[serde(default)]
+    pub best_splits: HashMap<String, Vec<Duration>>,
+    /// Level title -> the path of the highest scoring run
+    #[serde(default)]
+    pub ghosts: HashMap<String, Ghost>,
+    #[serde(default)]
+    pub leaderboard: LeaderboardSettings,
+    #[serde(default)]
+    pub achievements: BTreeSet<Achievement>,
+    /// A WebSocket relay for online races, like `ws://localhost:8080`
+    #[serde(default)]
+    pub race_server: String,
+    #[serde(default)]
+    pub elf_helper: bool,
+    /// Bought in the shop, and kept for every level after
+    #[serde(default)]
+    pub upgrades: Upgrades,
+    /// Presents delivered and not yet spent in the shop
+    #[serde(default)]
+    pub shop_credit: usize,
+    /// From the command line, to replay a particular run
+    #[serde(skip_serializing, skip_deserializing)]
+    pub seed_override: Option<u64>,
+    /// From a shared link, the score someone else got with the same setup
+    #[serde(skip_serializing, skip_deserializing)]
+    pub challenge: Option<usize>,
+    /// From the command line; keeps everything in memory
+    #[serde(skip)]
+    pub kiosk: bool,
+    /// From the command line; a bot drives the sleigh
+    #[serde(skip)]
+    pub bot: bool,
+}
+
+fn tutorial_done_for_old_saves() -> bool {
+    true
+}
+
+fn full_volume() -> f32 {
+    1.0
+}
+
+fn shown() -> bool {
+    true
+}
+
+fn half() -> f64 {
+    0.5
 }
 
-#[derive(Serialize, Deserialize)]
 pub struct ColorScheme {
     pub house: Color,
     pub apartment: Color,
     pub store: Color,
     pub visited: Color,
+    /// Highlights delivered houses after the level
+    pub delivered: Color,
 
     pub score: Color,
     pub energy: Color,
     pub boost: Color,
+
+    /// For things going well or badly, like being ahead of or behind a split
+    pub good: Color,
+    pub bad: Color,
+}
+
+impl ColorScheme {
+    pub fn new(palette: Palette) -> ColorScheme {
+        match palette {
+            Palette::Default => ColorScheme {
+                house: Color::hex("#688865"),
+                apartment: Color::hex("#C0F879"),
+                store: Color::hex("#EE702E"),
+                visited: Color::BLACK,
+                delivered: Color::RED,
+
+                score: Color::hex("#83AA51"),
+                energy: Color::hex("#D8B830"),
+                boost: Color::hex("#A32015"),
+
+                good: Color::GREEN,
+                bad: Color::RED,
+            },
+            // From https://jfly.uni-koeln.de/color/, which stays distinct for the common kinds of
+            // color blindness
+            Palette::Colorblind => ColorScheme {
+                house: Color::hex("#0072B2"),
+                apartment: Color::hex("#56B4E9"),
+                store: Color::hex("#E69F00"),
+                visited: Color::BLACK,
+                delivered: Color::hex("#CC79A7"),
+
+                score: Color::hex("#009E73"),
+                energy: Color::hex("#F0E442"),
+                boost: Color::hex("#D55E00"),
+
+                good: Color::hex("#56B4E9"),
+                bad: Color::hex("#D55E00"),
+            },
+        }
+    }
+
+    fn default_palette() -> ColorScheme {
+        ColorScheme::new(Palette::Default)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Palette {
+    Default,
+    Colorblind,
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::Default
+    }
+}
+
+impl Palette {
+    pub const ALL: [Palette; 2] = [Palette::Default, Palette::Colorblind];
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            Palette::Default => "default",
+            Palette::Colorblind => "colorblind friendly",
+        }
+    }
+
+    /// Delivered houses are striped, so they don't only stand out by color
+    pub fn hatch_done(self) -> bool {
+        self == Palette::Colorblind
+    }
+
+    /// One of the gradients from `map_gui::tools::HeatmapOptions`
+    pub fn heatmap_gradient(self) -> &'static str {
+        match self {
+            Palette::Default => "Turbo",
+            Palette::Colorblind => "Inferno",
+        }
+    }
+}
+
+/// How the camera follows the player
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum CameraMode {
+    Centered,
+    /// Only pan once the player gets close to the edge of the screen
+    Loose,
+    /// Trail a little behind the player, looking ahead in the direction they're moving
+    Smooth,
+}
+
+impl Default for CameraMode {
+    fn default() -> CameraMode {
+        CameraMode::Smooth
+    }
+}
+
+impl CameraMode {
+    pub const ALL: [CameraMode; 3] = [CameraMode::Smooth, CameraMode::Centered, CameraMode::Loose];
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            CameraMode::Centered => "always centered",
+            CameraMode::Loose => "only move near the edge of the screen",
+            CameraMode::Smooth => "smoothly follow and look ahead",
+        }
+    }
+}
+
+/// How much snow falls over the map. Less is easier on slower computers.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum SnowDensity {
+    Off,
+    Light,
+    Heavy,
+}
+
+impl Default for SnowDensity {
+    fn default() -> SnowDensity {
+        SnowDensity::Light
+    }
+}
+
+impl SnowDensity {
+    pub const ALL: [SnowDensity; 3] = [SnowDensity::Off, SnowDensity::Light, SnowDensity::Heavy];
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            SnowDensity::Off => "off",
+            SnowDensity::Light => "light",
+            SnowDensity::Heavy => "heavy",
+        }
+    }
+
+    pub fn num_flakes(self) -> usize {
+        match self {
+            SnowDensity::Off => 0,
+            SnowDensity::Light => 20,
+            SnowDensity::Heavy => 80,
+        }
+    }
+}
+
+/// Scales the text in the game's panels and the labels over houses, for anyone who finds the
+/// usual size hard to read
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize, Default)]
+pub enum TextSize {
+    #[default]
+    Normal,
+    Large,
+    Larger,
+}
+
+impl TextSize {
+    pub const ALL: [TextSize; 3] = [TextSize::Normal, TextSize::Large, TextSize::Larger];
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            TextSize::Normal => "normal",
+            TextSize::Large => "large",
+            TextSize::Larger => "larger",
+        }
+    }
+
+    pub fn scale(self) -> f64 {
+        match self {
+            TextSize::Normal => 1.0,
+            TextSize::Large => 1.3,
+            TextSize::Larger => 1.6,
+        }
+    }
 }
 
 impl Session {
@@ -66,6 +372,8 @@ impl Session {
                     session.levels = levels;
                 }
             }
+            session.colors = ColorScheme::new(session.palette);
+            session.custom_levels = Level::load_custom();
             return session;
         }
 
@@ -75,21 +383,17 @@ impl Session {
         }
         Session {
             levels,
+            custom_levels: Level::load_custom(),
             enable_modding: false,
-            colors: ColorScheme {
-                house: Color::hex("#688865"),
-                apartment: Color::hex("#C0F879"),
-                store: Color::hex("#EE702E"),
-                visited: Color::BLACK,
-
-                score: Color::hex("#83AA51"),
-                energy: Color::hex("#D8B830"),
-                boost: Color::hex("#A32015"),
-            },
+            colors: ColorScheme::new(Palette::Default),
+            palette: Palette::Default,
+            locale: Locale::English,
 
             high_scores,
             levels_unlocked: 1,
             current_vehicle: "bike".to_string(),
+            last_mode: Mode::Normal,
+            difficulty: DifficultyPreset::Normal,
             vehicles_unlocked: vec!["bike".to_string()].into_iter().collect(),
             upzones_unlocked: 0,
             upzones_explained: false,
@@ -97,24 +401,100 @@ impl Session {
 
             music: Music::empty(),
             play_music: true,
+            music_volume: 1.0,
+            sound_volume: 1.0,
+
+            gamepad: Gamepad::default(),
+            stats_feed: StatsFeed::default(),
+            disable_gamepad: false,
+            keys: KeyBindings::default(),
+            control_scheme: ControlScheme::Instant,
+            camera: CameraMode::Smooth,
+            camera_lag: 0.5,
+            camera_lookahead: 0.5,
+            screen_shake: true,
+            auto_zoom: true,
+            snow: SnowDensity::Light,
+            show_score_labels: true,
+            show_nearby_houses: true,
+            auto_drop: true,
+            show_trail: true,
+            reduce_motion: false,
+            text_size: TextSize::Normal,
+            tutorial_done: false,
+            best_splits: HashMap::new(),
+            ghosts: HashMap::new(),
+            leaderboard: LeaderboardSettings::default(),
+            achievements: BTreeSet::new(),
+            race_server: String::new(),
+            elf_helper: false,
+            upgrades: Upgrades::default(),
+            shop_credit: 0,
+            seed_override: None,
+            challenge: None,
+            kiosk: false,
+            bot: false,
         }
     }
 
-    /// If a message is returned, a new level and some powers were unlocked.
-    pub fn record_score(&mut self, level: String, score: usize) -> Option<Vec<String>> {
-        let scores = self.high_scores.get_mut(&level).unwrap();
+    /// The boss level opens up once the last regular level has been beaten, on any difficulty
+    pub fn boss_unlocked(&self) -> bool {
+        let last = self.levels.last().unwrap();
+        self.levels_unlocked == self.levels.len()
+            && DifficultyPreset::ALL.into_iter().any(|difficulty| {
+                self.high_scores
+                    .get(&difficulty.score_key(&last.title))
+                    .and_then(|scores| scores.first())
+                    .map(|score| *score >= last.goal)
+                    .unwrap_or(false)
+            })
+    }
+
+    /// How much snow to draw during a level
+    pub fn num_snowflakes(&self) -> usize {
+        if self.reduce_motion {
+            0
+        } else {
+            self.snow.num_flakes()
+        }
+    }
+
+    /// Seeds all of the randomness in a level
+    pub fn seed(&self, mode: Mode) -> u64 {
+        self.seed_override.unwrap_or_else(|| mode.seed())
+    }
+
+    /// If a message is returned, a new level and some powers were unlocked. The level's goal may
+    /// have been scaled to the map, so it's passed in separately. Beating a level on any
+    /// difficulty unlocks the next one.
+    pub fn record_score(
+        &mut self,
+        level: String,
+        goal: usize,
+        score: usize,
+        difficulty: DifficultyPreset,
+    ) -> Option<Vec<String>> {
+        self.shop_credit += score;
+
+        // Levels on maps from the command line also get high scores, but unlock nothing
+        let scores = self
+            .high_scores
+            .entry(difficulty.score_key(&level))
+            .or_insert_with(Vec::new);
         scores.push(score);
         scores.sort_unstable();
         scores.reverse();
         scores.truncate(3);
 
-        let idx = self
-            .levels
-            .iter()
-            .position(|lvl| lvl.title == level)
-            .unwrap();
+        let idx = match self.levels.iter().position(|lvl| lvl.title == level) {
+            Some(idx) => idx,
+            None => {
+                self.save();
+                return None;
+            }
+        };
         let level = &self.levels[idx];
-        let msg = if idx + 1 == self.levels_unlocked && score >= level.goal {
+        let msg = if idx + 1 == self.levels_unlocked && score >= goal {
             if idx + 1 == self.levels.len() {
                 Some(vec![
                     "All levels complete! Nice.".to_string(),
@@ -144,6 +524,44 @@ impl Session {
         msg
     }
 
+    /// Returns true if this finished time trial beat the previous best.
+    pub fn record_splits(&mut self, level: String, splits: Vec<Duration>) -> bool {
+        let better = self
+            .best_splits
+            .get(&level)
+            .and_then(|best| best.last())
+            .map(|best| splits.last().unwrap() < best)
+            .unwrap_or(true);
+        if better {
+            self.best_splits.insert(level, splits);
+            self.save();
+        }
+        better
+    }
+
+    /// Keeps the run to race against next time, if it beat the previous best.
+    pub fn record_ghost(&mut self, level: String, ghost: Ghost) {
+        if self
+            .ghosts
+            .get(&level)
+            .map(|best| ghost.score > best.score)
+            .unwrap_or(true)
+        {
+            self.ghosts.insert(level, ghost);
+            self.save();
+        }
+    }
+
+    /// Returns true if this is the first time the achievement was earned.
+    pub fn unlock_achievement(&mut self, achievement: Achievement) -> bool {
+        if self.achievements.insert(achievement) {
+            self.save();
+            true
+        } else {
+            false
+        }
+    }
+
     pub fn unlock_all(&mut self) {
         self.upzones_unlocked = 0;
         for level in &self.levels {
@@ -152,6 +570,7 @@ impl Session {
         }
         self.levels_unlocked = self.levels.len();
         self.upzones_explained = true;
+        self.tutorial_done = true;
     }
 
     pub fn update_music(&mut self, ctx: &mut EventCtx) {
@@ -163,6 +582,9 @@ impl Session {
     }
 
     pub fn save(&self) {
+        if self.kiosk {
+            return;
+        }
         abstio::write_json(abstio::path_player("santa.json"), self);
     }
 }
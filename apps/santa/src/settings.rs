@@ -0,0 +1,354 @@
+use widgetry::{
+    Choice, DrawBaselayer, EventCtx, GfxCtx, Key, Line, Panel, SimpleState, Slider, State, TextBox,
+    TextExt, Toggle, Widget,
+};
+
+use crate::controls::ControlScheme;
+use crate::i18n::Locale;
+use crate::keybindings::KeyBindingsEditor;
+use crate::session::{CameraMode, ColorScheme, Palette, SnowDensity, TextSize};
+use crate::{App, Transition};
+
+/// Player preferences that can be changed from the pause menu or before starting a level.
+/// Changes take effect immediately and are saved.
+pub struct Settings;
+
+impl Settings {
+    pub fn new_state(ctx: &mut EventCtx, app: &App) -> Box<dyn State<App>> {
+        let locale = app.session.locale;
+        <dyn SimpleState<_>>::new_state(
+            Panel::new_builder(Widget::col(vec![
+                Widget::row(vec![
+                    Line(locale.tr("Settings")).small_heading().into_widget(ctx),
+                    ctx.style().btn_close_widget(ctx),
+                ]),
+                section(
+                    ctx,
+                    locale.tr("Audio"),
+                    vec![
+                        labeled(
+                            ctx,
+                            locale.tr("Music volume"),
+                            Slider::area(
+                                ctx,
+                                0.15 * ctx.canvas.window_width,
+                                app.session.music_volume as f64,
+                                "music volume",
+                            ),
+                        ),
+                        labeled(
+                            ctx,
+                            locale.tr("Sound effects volume"),
+                            Slider::area(
+                                ctx,
+                                0.15 * ctx.canvas.window_width,
+                                app.session.sound_volume as f64,
+                                "sound volume",
+                            ),
+                        ),
+                    ],
+                ),
+                section(
+                    ctx,
+                    locale.tr("Controls"),
+                    vec![
+                        Toggle::checkbox(ctx, "use a gamepad", None, !app.session.disable_gamepad),
+                        Toggle::checkbox(
+                            ctx,
+                            "rotate controls",
+                            None,
+                            app.session.control_scheme == ControlScheme::Rotate,
+                        ),
+                        Toggle::checkbox(
+                            ctx,
+                            "fly towards the mouse",
+                            None,
+                            app.session.control_scheme == ControlScheme::Mouse,
+                        ),
+                        ctx.style().btn_outline.text("Change keys").build_def(ctx),
+                        labeled(
+                            ctx,
+                            locale.tr("Camera"),
+                            Widget::dropdown(
+                                ctx,
+                                "camera",
+                                app.session.camera,
+                                CameraMode::ALL
+                                    .into_iter()
+                                    .map(|c| Choice::new(c.describe(), c))
+                                    .collect(),
+                            ),
+                        ),
+                        labeled(
+                            ctx,
+                            locale.tr("Camera lag"),
+                            Slider::area(
+                                ctx,
+                                0.15 * ctx.canvas.window_width,
+                                app.session.camera_lag,
+                                "camera lag",
+                            ),
+                        ),
+                        labeled(
+                            ctx,
+                            locale.tr("Camera lookahead"),
+                            Slider::area(
+                                ctx,
+                                0.15 * ctx.canvas.window_width,
+                                app.session.camera_lookahead,
+                                "camera lookahead",
+                            ),
+                        ),
+                        Toggle::checkbox(
+                            ctx,
+                            "shake the screen on collisions",
+                            None,
+                            app.session.screen_shake,
+                        ),
+                        Toggle::checkbox(
+                            ctx,
+                            "zoom out when moving fast",
+                            None,
+                            app.session.auto_zoom,
+                        ),
+                    ],
+                ),
+                section(
+                    ctx,
+                    locale.tr("Graphics"),
+                    vec![
+                        labeled(
+                            ctx,
+                            locale.tr("Snow"),
+                            Widget::dropdown(
+                                ctx,
+                                "snow",
+                                app.session.snow,
+                                SnowDensity::ALL
+                                    .into_iter()
+                                    .map(|d| Choice::new(d.describe(), d))
+                                    .collect(),
+                            ),
+                        ),
+                        Toggle::checkbox(
+                            ctx,
+                            "show how many presents each house wants",
+                            None,
+                            app.session.show_score_labels,
+                        ),
+                        Toggle::checkbox(
+                            ctx,
+                            "highlight houses coming up nearby",
+                            None,
+                            app.session.show_nearby_houses,
+                        ),
+                        Toggle::checkbox(
+                            ctx,
+                            "drop presents automatically",
+                            None,
+                            app.session.auto_drop,
+                        ),
+                        Toggle::checkbox(
+                            ctx,
+                            "draw a trail behind the sleigh",
+                            None,
+                            app.session.show_trail,
+                        ),
+                    ],
+                ),
+                section(
+                    ctx,
+                    locale.tr("Accessibility"),
+                    vec![
+                        labeled(
+                            ctx,
+                            locale.tr("Language"),
+                            Widget::dropdown(
+                                ctx,
+                                "language",
+                                app.session.locale,
+                                Locale::ALL
+                                    .into_iter()
+                                    .map(|l| Choice::new(l.name(), l))
+                                    .collect(),
+                            ),
+                        ),
+                        labeled(
+                            ctx,
+                            locale.tr("Colors"),
+                            Widget::dropdown(
+                                ctx,
+                                "palette",
+                                app.session.palette,
+                                Palette::ALL
+                                    .into_iter()
+                                    .map(|p| Choice::new(p.describe(), p))
+                                    .collect(),
+                            ),
+                        ),
+                        labeled(
+                            ctx,
+                            locale.tr("Text size"),
+                            Widget::dropdown(
+                                ctx,
+                                "text size",
+                                app.session.text_size,
+                                TextSize::ALL
+                                    .into_iter()
+                                    .map(|t| Choice::new(t.describe(), t))
+                                    .collect(),
+                            ),
+                        ),
+                        Toggle::checkbox(
+                            ctx,
+                            "one-button controls",
+                            None,
+                            app.session.control_scheme == ControlScheme::OneButton,
+                        ),
+                        Toggle::checkbox(ctx, "reduce motion", None, app.session.reduce_motion),
+                        Toggle::checkbox(
+                            ctx,
+                            "bring an elf to help with small houses",
+                            None,
+                            app.session.elf_helper,
+                        ),
+                    ],
+                ),
+                section(
+                    ctx,
+                    locale.tr("Online"),
+                    vec![
+                        Toggle::checkbox(
+                            ctx,
+                            "share scores with an online leaderboard",
+                            None,
+                            app.session.leaderboard.enabled,
+                        ),
+                        labeled(
+                            ctx,
+                            locale.tr("Leaderboard server"),
+                            TextBox::default_widget(
+                                ctx,
+                                "leaderboard server",
+                                app.session.leaderboard.url.clone(),
+                            ),
+                        ),
+                        labeled(
+                            ctx,
+                            locale.tr("Race server"),
+                            TextBox::default_widget(
+                                ctx,
+                                "race server",
+                                app.session.race_server.clone(),
+                            ),
+                        ),
+                        labeled(
+                            ctx,
+                            locale.tr("Your name"),
+                            TextBox::default_widget(
+                                ctx,
+                                "player name",
+                                app.session.leaderboard.player_name.clone(),
+                            ),
+                        ),
+                    ],
+                ),
+                ctx.style()
+                    .btn_solid_primary
+                    .text("Done")
+                    .hotkey(Key::Enter)
+                    .build_def(ctx),
+            ]))
+            .build(ctx),
+            Box::new(Settings),
+        )
+    }
+}
+
+fn section(ctx: &EventCtx, title: &str, widgets: Vec<Widget>) -> Widget {
+    let mut col = vec![Line(title).bold_body().into_widget(ctx)];
+    col.extend(widgets);
+    Widget::col(col).section(ctx)
+}
+
+fn labeled(ctx: &EventCtx, label: &str, widget: Widget) -> Widget {
+    Widget::row(vec![
+        label.text_widget(ctx).centered_vert(),
+        widget.align_right(),
+    ])
+}
+
+impl SimpleState<App> for Settings {
+    fn on_click(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &mut App,
+        x: &str,
+        _: &mut Panel,
+    ) -> Transition {
+        match x {
+            "close" | "Done" => Transition::Pop,
+            "Change keys" => Transition::Push(KeyBindingsEditor::new_state(ctx, app)),
+            _ => unreachable!(),
+        }
+    }
+
+    fn panel_changed(
+        &mut self,
+        _: &mut EventCtx,
+        app: &mut App,
+        panel: &mut Panel,
+    ) -> Option<Transition> {
+        app.session.music_volume = panel.slider("music volume").get_percent() as f32;
+        app.session.sound_volume = panel.slider("sound volume").get_percent() as f32;
+        app.session
+            .music
+            .set_player_volume(app.session.music_volume, app.session.sound_volume);
+        app.session.disable_gamepad = !panel.is_checked("use a gamepad");
+        app.session.control_scheme = if panel.is_checked("one-button controls") {
+            ControlScheme::OneButton
+        } else if panel.is_checked("fly towards the mouse") {
+            ControlScheme::Mouse
+        } else if panel.is_checked("rotate controls") {
+            ControlScheme::Rotate
+        } else {
+            ControlScheme::Instant
+        };
+        app.session.camera = panel.dropdown_value("camera");
+        app.session.camera_lag = panel.slider("camera lag").get_percent();
+        app.session.camera_lookahead = panel.slider("camera lookahead").get_percent();
+        app.session.screen_shake = panel.is_checked("shake the screen on collisions");
+        app.session.auto_zoom = panel.is_checked("zoom out when moving fast");
+        app.session.snow = panel.dropdown_value("snow");
+        app.session.show_score_labels = panel.is_checked("show how many presents each house wants");
+        app.session.show_nearby_houses = panel.is_checked("highlight houses coming up nearby");
+        app.session.auto_drop = panel.is_checked("drop presents automatically");
+        app.session.show_trail = panel.is_checked("draw a trail behind the sleigh");
+        app.session.locale = panel.dropdown_value("language");
+        app.session.palette = panel.dropdown_value("palette");
+        app.session.colors = ColorScheme::new(app.session.palette);
+        app.session.text_size = panel.dropdown_value("text size");
+        app.session.reduce_motion = panel.is_checked("reduce motion");
+        app.session.elf_helper = panel.is_checked("bring an elf to help with small houses");
+        app.session.leaderboard.enabled =
+            panel.is_checked("share scores with an online leaderboard");
+        app.session.leaderboard.url = panel.text_box("leaderboard server");
+        app.session.leaderboard.player_name = panel.text_box("player name");
+        app.session.race_server = panel.text_box("race server");
+        app.session.save();
+        None
+    }
+
+    fn other_event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        app.session.update_music(ctx);
+        Transition::Keep
+    }
+
+    fn draw_baselayer(&self) -> DrawBaselayer {
+        DrawBaselayer::PreviousState
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        app.session.music.draw(g);
+    }
+}
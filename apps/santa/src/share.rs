@@ -0,0 +1,42 @@
+use widgetry::tools::URLManager;
+
+use crate::levels::Level;
+use crate::mode::Mode;
+
+/// A link that sets up the same level, mode, and seed for someone else, with a score to beat.
+/// On the web, the current page's URL also changes to match, so players can just share it.
+pub fn challenge_url(level: &Level, mode: Mode, seed: u64, score: usize) -> String {
+    // Levels on other maps get worked out from the map again
+    let (level_key, level_value) = if Level::all().iter().any(|lvl| lvl.title == level.title) {
+        ("--level", level.title.clone())
+    } else {
+        (
+            "--map",
+            level
+                .map
+                .path()
+                .strip_prefix(&abstio::path(""))
+                .unwrap()
+                .to_string(),
+        )
+    };
+    let params = vec![
+        (level_key, level_value),
+        ("--mode", mode.name().to_string()),
+        ("--seed", seed.to_string()),
+        ("--challenge", score.to_string()),
+    ];
+    for (key, value) in &params {
+        URLManager::update_url_param(key.to_string(), value.replace(' ', "%20"));
+    }
+    format!(
+        "https://play.abstreet.org/{}/santa.html{}",
+        map_gui::tools::version(),
+        abstutil::args_to_query_string(
+            params
+                .into_iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect()
+        )
+    )
+}
@@ -0,0 +1,202 @@
+use serde::{Deserialize, Serialize};
+
+use abstutil::prettyprint_usize;
+use widgetry::{EventCtx, GfxCtx, Key, Line, Panel, SimpleState, State, Text, Widget};
+
+use crate::tuning::Tuning;
+use crate::{App, Transition};
+
+// Each upgrade can be bought this many times
+const MAX_LEVEL: usize = 3;
+// The first level costs this many presents, the second twice as much, and so on
+const BASE_COST: usize = 500;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Upgrade {
+    Capacity,
+    Recharge,
+    Insulation,
+    Inventory,
+}
+
+impl Upgrade {
+    pub const ALL: [Upgrade; 4] = [
+        Upgrade::Capacity,
+        Upgrade::Recharge,
+        Upgrade::Insulation,
+        Upgrade::Inventory,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Upgrade::Capacity => "Bigger sack",
+            Upgrade::Recharge => "Faster recharge",
+            Upgrade::Insulation => "Better insulation",
+            Upgrade::Inventory => "Store inventory",
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            Upgrade::Capacity => "Carry 2 more of each kind of present",
+            Upgrade::Recharge => "Bus stops and energy pickups give 25% more blood sugar",
+            Upgrade::Insulation => "Boosting, red lights, and crashes burn 20% less blood sugar",
+            Upgrade::Inventory => {
+                "See exactly what's left in each store, and stores stock 25% more"
+            }
+        }
+    }
+}
+
+/// Permanent upgrades, bought between levels with presents delivered
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+pub struct Upgrades {
+    pub capacity: usize,
+    pub recharge: usize,
+    pub insulation: usize,
+    #[serde(default)]
+    pub inventory: usize,
+}
+
+impl Upgrades {
+    pub fn level(&self, upgrade: Upgrade) -> usize {
+        match upgrade {
+            Upgrade::Capacity => self.capacity,
+            Upgrade::Recharge => self.recharge,
+            Upgrade::Insulation => self.insulation,
+            Upgrade::Inventory => self.inventory,
+        }
+    }
+
+    /// None once maxed out
+    pub fn cost(&self, upgrade: Upgrade) -> Option<usize> {
+        let level = self.level(upgrade);
+        if level == MAX_LEVEL {
+            None
+        } else {
+            Some(BASE_COST * (level + 1))
+        }
+    }
+
+    fn buy(&mut self, upgrade: Upgrade) {
+        match upgrade {
+            Upgrade::Capacity => self.capacity += 1,
+            Upgrade::Recharge => self.recharge += 1,
+            Upgrade::Insulation => self.insulation += 1,
+            Upgrade::Inventory => self.inventory += 1,
+        }
+    }
+
+    /// Applied on top of each level's tuning
+    pub fn apply(&self, tuning: &mut Tuning) {
+        tuning.presents_per_type += 2 * self.capacity;
+
+        let recharge = 1.0 + 0.25 * (self.recharge as f64);
+        tuning.bus_stop_recharge_rate *= recharge;
+        tuning.pickup_energy = ((tuning.pickup_energy as f64) * recharge).round() as usize;
+
+        let insulation = 1.0 - 0.2 * (self.insulation as f64);
+        tuning.boost_distance_per_energy = tuning.boost_distance_per_energy / insulation;
+        tuning.boost_energy_per_second *= insulation;
+        tuning.red_light_energy_cost =
+            ((tuning.red_light_energy_cost as f64) * insulation).round() as usize;
+        tuning.collision_energy_cost =
+            ((tuning.collision_energy_cost as f64) * insulation).round() as usize;
+
+        let inventory = 1.0 + 0.25 * (self.inventory as f64);
+        tuning.store_energy_stock *= inventory;
+        tuning.store_present_stock =
+            ((tuning.store_present_stock as f64) * inventory).round() as usize;
+    }
+}
+
+/// Between levels, spend presents delivered so far on upgrades
+pub struct Shop;
+
+impl Shop {
+    pub fn new_state(ctx: &mut EventCtx, app: &App) -> Box<dyn State<App>> {
+        let upgrades = &app.session.upgrades;
+        let credit = app.session.shop_credit;
+        let mut col = vec![
+            Widget::row(vec![
+                Line("Upgrade shop").big_heading_plain().into_widget(ctx),
+                ctx.style().btn_close_widget(ctx),
+            ]),
+            Line(format!(
+                "{} presents to spend, from every level played",
+                prettyprint_usize(credit)
+            ))
+            .into_widget(ctx),
+        ];
+        for upgrade in Upgrade::ALL {
+            let mut txt = Text::new();
+            txt.add_line(
+                Line(format!(
+                    "{} ({} / {})",
+                    upgrade.name(),
+                    upgrades.level(upgrade),
+                    MAX_LEVEL
+                ))
+                .small_heading(),
+            );
+            txt.add_line(upgrade.describe());
+            let btn = match upgrades.cost(upgrade) {
+                Some(cost) => ctx
+                    .style()
+                    .btn_solid_primary
+                    .text(format!("Buy for {}", prettyprint_usize(cost)))
+                    .disabled(cost > credit)
+                    .build_widget(ctx, upgrade.name()),
+                None => Line("Maxed out").secondary().into_widget(ctx),
+            };
+            col.push(Widget::row(vec![
+                txt.into_widget(ctx),
+                btn.centered_vert().align_right(),
+            ]));
+        }
+        col.push(
+            ctx.style()
+                .btn_outline
+                .text("Back")
+                .hotkey(Key::Enter)
+                .build_def(ctx)
+                .centered_horiz(),
+        );
+
+        <dyn SimpleState<_>>::new_state(
+            Panel::new_builder(Widget::col(col)).build(ctx),
+            Box::new(Shop),
+        )
+    }
+}
+
+impl SimpleState<App> for Shop {
+    fn on_click(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &mut App,
+        x: &str,
+        _: &mut Panel,
+    ) -> Transition {
+        match x {
+            "close" | "Back" => Transition::Pop,
+            x => {
+                let upgrade = Upgrade::ALL.into_iter().find(|u| u.name() == x).unwrap();
+                let cost = app.session.upgrades.cost(upgrade).unwrap();
+                app.session.shop_credit -= cost;
+                app.session.upgrades.buy(upgrade);
+                app.session.save();
+                Transition::Replace(Shop::new_state(ctx, app))
+            }
+        }
+    }
+
+    fn other_event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        app.session.update_music(ctx);
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        app.session.music.draw(g);
+    }
+}
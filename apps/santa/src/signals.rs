@@ -0,0 +1,115 @@
+use std::collections::{HashMap, HashSet};
+
+use geom::{Angle, Circle, Distance, Duration, Pt2D, Time};
+use map_model::{IntersectionID, RoadID};
+use widgetry::{Color, Drawable, EventCtx, GeomBatch, GfxCtx};
+
+use crate::App;
+
+const LIGHT_RADIUS: Distance = Distance::const_meters(1.5);
+// How far back from the intersection the lights are drawn
+const LIGHT_SETBACK: Distance = Distance::const_meters(3.0);
+
+/// The real traffic signals from the map, but much simpler. Each one alternates between letting
+/// one pair of crossing directions go and then the other, on a fixed cycle. Neighboring signals
+/// don't coordinate at all.
+pub struct Signals {
+    // For each signal, the roads that get a green light during the first half of the cycle
+    first_half: HashMap<IntersectionID, HashSet<RoadID>>,
+    // Whether the lights were in the first half of their cycle, as currently drawn
+    drawn_phase: Option<bool>,
+    draw: Drawable,
+}
+
+impl Signals {
+    pub fn new(ctx: &EventCtx, app: &App) -> Signals {
+        let mut first_half = HashMap::new();
+        for i in app.map.all_intersections() {
+            if !i.is_traffic_signal() || i.roads.is_empty() {
+                continue;
+            }
+            // Roads roughly parallel to the first one go together
+            let reference = approach_angle(app, i.id, i.roads[0]);
+            let mut group = HashSet::new();
+            for r in &i.roads {
+                let diff = (approach_angle(app, i.id, *r).normalized_degrees()
+                    - reference.normalized_degrees())
+                .rem_euclid(180.0);
+                if !(45.0..135.0).contains(&diff) {
+                    group.insert(*r);
+                }
+            }
+            first_half.insert(i.id, group);
+        }
+
+        Signals {
+            first_half,
+            drawn_phase: None,
+            draw: Drawable::empty(ctx),
+        }
+    }
+
+    /// Is the light red for someone coming from this road? Not a signal means no.
+    pub fn is_red(&self, app: &App, cycle: Duration, i: IntersectionID, from: RoadID) -> bool {
+        if let Some(group) = self.first_half.get(&i) {
+            group.contains(&from) != in_first_half(app.time, cycle, i)
+        } else {
+            false
+        }
+    }
+
+    pub fn update(&mut self, ctx: &mut EventCtx, app: &App, cycle: Duration) {
+        // Half of the signals are offset by half a cycle, so everything only changes twice per
+        // cycle
+        let phase = in_first_half(app.time, cycle, IntersectionID(0));
+        if self.drawn_phase == Some(phase) {
+            return;
+        }
+        self.drawn_phase = Some(phase);
+
+        let mut batch = GeomBatch::new();
+        for (i, group) in &self.first_half {
+            for r in &app.map.get_i(*i).roads {
+                let green = group.contains(r) == in_first_half(app.time, cycle, *i);
+                batch.push(
+                    if green { Color::GREEN } else { Color::RED },
+                    Circle::new(light_position(app, *i, *r), LIGHT_RADIUS).to_polygon(),
+                );
+            }
+        }
+        self.draw = ctx.upload(batch);
+    }
+
+    pub fn draw(&self, g: &mut GfxCtx) {
+        g.redraw(&self.draw);
+    }
+}
+
+fn in_first_half(now: Time, cycle: Duration, i: IntersectionID) -> bool {
+    let mut t = now - Time::START_OF_DAY;
+    if i.0 % 2 == 1 {
+        t += cycle / 2.0;
+    }
+    t % cycle < cycle / 2.0
+}
+
+/// The direction of the road as it meets the intersection
+fn approach_angle(app: &App, i: IntersectionID, r: RoadID) -> Angle {
+    let road = app.map.get_r(r);
+    if road.dst_i == i {
+        road.center_pts.last_line().angle()
+    } else {
+        road.center_pts.first_line().angle()
+    }
+}
+
+fn light_position(app: &App, i: IntersectionID, r: RoadID) -> Pt2D {
+    let road = app.map.get_r(r);
+    let setback = LIGHT_SETBACK.min(road.length() / 2.0);
+    let dist = if road.dst_i == i {
+        road.length() - setback
+    } else {
+        setback
+    };
+    road.center_pts.must_dist_along(dist).0
+}
@@ -0,0 +1,281 @@
+use geom::{Distance, Duration, Pt2D, Time};
+use map_model::BuildingID;
+use widgetry::table::{Col, Filter, Table};
+use widgetry::{
+    Color, DrawBaselayer, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, LinePlot, Outcome,
+    Panel, PlotOptions, Series, SimpleState, State, Text, VerticalAlignment, Widget,
+};
+
+use crate::{App, Transition};
+
+// Recording every tick would make long runs huge, and the charts can't show that much detail
+// anyway
+const SAMPLE_PERIOD: Duration = Duration::const_seconds(1.0);
+// Clicking a delivery zooms close enough to see the house and its neighbors
+const LOG_ZOOM: f64 = 4.0;
+
+/// Tracks how a run went over time, so the charts afterwards can show where it fell apart.
+pub struct RunStats {
+    // Times are since the start of the run
+    score: Vec<(Duration, usize)>,
+    energy: Vec<(Duration, usize)>,
+    deliveries: Vec<Delivery>,
+
+    last_pos: Option<Pt2D>,
+    distance_since_delivery: Distance,
+}
+
+#[derive(Clone)]
+struct Delivery {
+    bldg: BuildingID,
+    // Since the start of the run
+    time: Duration,
+    // How much the score went up
+    score: usize,
+    // How far Santa went since the delivery before
+    distance: Distance,
+}
+
+impl RunStats {
+    pub fn new() -> RunStats {
+        RunStats {
+            score: Vec::new(),
+            energy: Vec::new(),
+            deliveries: Vec::new(),
+
+            last_pos: None,
+            distance_since_delivery: Distance::ZERO,
+        }
+    }
+
+    /// Call every tick
+    pub fn record(&mut self, now: Time, pos: Pt2D, score: usize, energy: usize) {
+        if let Some(last) = self.last_pos {
+            self.distance_since_delivery += last.dist_to(pos);
+        }
+        self.last_pos = Some(pos);
+
+        let t = now - Time::START_OF_DAY;
+        if let Some((last, _)) = self.score.last() {
+            if t - *last < SAMPLE_PERIOD {
+                return;
+            }
+        }
+        self.score.push((t, score));
+        self.energy.push((t, energy));
+    }
+
+    pub fn delivered(&mut self, now: Time, bldg: BuildingID, score: usize) {
+        self.deliveries.push(Delivery {
+            bldg,
+            time: now - Time::START_OF_DAY,
+            score,
+            distance: self.distance_since_delivery,
+        });
+        self.distance_since_delivery = Distance::ZERO;
+    }
+
+    // Counted at the end of each minute, with a partial minute at the end
+    fn deliveries_per_minute(&self) -> Vec<(Duration, usize)> {
+        let end = self.score.last().map(|(t, _)| *t).unwrap_or(Duration::ZERO);
+        let mut pts = vec![(Duration::ZERO, 0)];
+        let mut minute_end = Duration::minutes(1);
+        let mut deliveries = self.deliveries.iter().map(|d| d.time).peekable();
+        loop {
+            let bucket_end = if minute_end < end { minute_end } else { end };
+            let mut count = 0;
+            while deliveries.next_if(|t| *t <= bucket_end).is_some() {
+                count += 1;
+            }
+            pts.push((bucket_end, count));
+            if bucket_end == end {
+                break;
+            }
+            minute_end += Duration::minutes(1);
+        }
+        pts
+    }
+}
+
+/// After a run, charts of the score, blood sugar, and rate of deliveries over time.
+pub struct RunCharts;
+
+impl RunCharts {
+    pub fn new_state(ctx: &mut EventCtx, app: &App, stats: &RunStats) -> Box<dyn State<App>> {
+        let chart = |label: &str, color: Color, pts: Vec<(Duration, usize)>| {
+            LinePlot::new_widget(
+                ctx,
+                label,
+                vec![Series {
+                    label: label.to_string(),
+                    color,
+                    pts,
+                }],
+                PlotOptions::fixed(),
+                app.opts.units,
+            )
+            .section(ctx)
+        };
+
+        <dyn SimpleState<_>>::new_state(
+            Panel::new_builder(Widget::col(vec![
+                Widget::row(vec![
+                    Line("How the run went").small_heading().into_widget(ctx),
+                    ctx.style().btn_close_widget(ctx),
+                ]),
+                Line("Score").into_widget(ctx),
+                chart("score", Color::GREEN, stats.score.clone()),
+                Line("Blood sugar").into_widget(ctx),
+                chart(
+                    "blood sugar",
+                    app.session.colors.boost,
+                    stats.energy.clone(),
+                ),
+                Line("Deliveries per minute").into_widget(ctx),
+                chart(
+                    "deliveries per minute",
+                    Color::ORANGE,
+                    stats.deliveries_per_minute(),
+                ),
+                ctx.style()
+                    .btn_outline
+                    .text("Back")
+                    .hotkey(Key::Escape)
+                    .build_def(ctx),
+            ]))
+            .exact_size_percent(80, 90)
+            .build(ctx),
+            Box::new(RunCharts),
+        )
+    }
+}
+
+impl SimpleState<App> for RunCharts {
+    fn on_click(&mut self, _: &mut EventCtx, _: &mut App, x: &str, _: &mut Panel) -> Transition {
+        match x {
+            "close" | "Back" => Transition::Pop,
+            _ => unreachable!(),
+        }
+    }
+
+    fn other_event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        app.session.update_music(ctx);
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        app.session.music.draw(g);
+    }
+}
+
+/// After a run, every delivery in a table that can be sorted, to study a route. Clicking a row
+/// flies over to that house.
+pub struct DeliveryLog {
+    table: Table<App, (usize, Delivery), ()>,
+    panel: Panel,
+    // Rows are named by their index in here
+    deliveries: Vec<Delivery>,
+    selected: Option<BuildingID>,
+}
+
+impl DeliveryLog {
+    pub fn new_state(ctx: &mut EventCtx, app: &App, stats: &RunStats) -> Box<dyn State<App>> {
+        let entries = stats.deliveries.iter().cloned().enumerate().collect();
+        let mut table: Table<App, (usize, Delivery), ()> = Table::new(
+            "delivery log",
+            entries,
+            Box::new(|(idx, _)| idx.to_string()),
+            "#",
+            Filter::empty(),
+        );
+        table.column(
+            "#",
+            Box::new(|ctx, _, (idx, _)| Text::from((idx + 1).to_string()).render(ctx)),
+            Col::Sortable(Box::new(|rows| rows.sort_by_key(|(idx, _)| *idx))),
+        );
+        table.column(
+            "Address",
+            Box::new(|ctx, app, (_, d)| Text::from(&app.map.get_b(d.bldg).address).render(ctx)),
+            Col::Static,
+        );
+        table.column(
+            "Time",
+            Box::new(|ctx, app, (_, d)| Text::from(d.time.to_string(&app.opts.units)).render(ctx)),
+            Col::Sortable(Box::new(|rows| rows.sort_by_key(|(_, d)| d.time))),
+        );
+        table.column(
+            "Presents",
+            Box::new(|ctx, _, (_, d)| Text::from(d.score.to_string()).render(ctx)),
+            Col::Sortable(Box::new(|rows| rows.sort_by_key(|(_, d)| d.score))),
+        );
+        table.column(
+            "Distance from the last one",
+            Box::new(|ctx, app, (_, d)| {
+                Text::from(d.distance.to_string(&app.opts.units)).render(ctx)
+            }),
+            Col::Sortable(Box::new(|rows| rows.sort_by_key(|(_, d)| d.distance))),
+        );
+
+        let panel = Panel::new_builder(Widget::col(vec![
+            Widget::row(vec![
+                Line("Every delivery").small_heading().into_widget(ctx),
+                ctx.style().btn_close_widget(ctx),
+            ]),
+            if stats.deliveries.is_empty() {
+                Line("Nothing was delivered").into_widget(ctx)
+            } else {
+                table.render(ctx, app)
+            },
+        ]))
+        .aligned(HorizontalAlignment::Center, VerticalAlignment::TopInset)
+        .build(ctx);
+
+        Box::new(DeliveryLog {
+            table,
+            panel,
+            deliveries: stats.deliveries.clone(),
+            selected: None,
+        })
+    }
+}
+
+impl State<App> for DeliveryLog {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        ctx.canvas_movement();
+
+        if let Outcome::Clicked(x) = self.panel.event(ctx) {
+            if self.table.clicked(&x) {
+                self.table.replace_render(ctx, app, &mut self.panel);
+            } else if x == "close" {
+                return Transition::Pop;
+            } else if let Ok(idx) = x.parse::<usize>() {
+                let b = self.deliveries[idx].bldg;
+                self.selected = Some(b);
+                ctx.canvas.cam_zoom = LOG_ZOOM;
+                ctx.canvas.center_on_map_pt(app.map.get_b(b).label_center);
+            } else {
+                unreachable!()
+            }
+        }
+
+        app.session.update_music(ctx);
+        Transition::Keep
+    }
+
+    fn draw_baselayer(&self) -> DrawBaselayer {
+        DrawBaselayer::PreviousState
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        let hovering = self
+            .panel
+            .currently_hovering()
+            .and_then(|x| x.parse::<usize>().ok())
+            .map(|idx| self.deliveries[idx].bldg);
+        for b in hovering.into_iter().chain(self.selected) {
+            g.draw_polygon(Color::CYAN, app.map.get_b(b).polygon.clone());
+        }
+        self.panel.draw(g);
+        app.session.music.draw(g);
+    }
+}
@@ -0,0 +1,191 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use geom::{Duration, LonLat, Time};
+use map_model::BuildingID;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::race::is_would_block;
+
+// How often to send the status; deliveries go out right away
+const SYNC_PERIOD: Duration = Duration::const_seconds(0.25);
+// Clients that never finish connecting get dropped after this long
+#[cfg(not(target_arch = "wasm32"))]
+const HANDSHAKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+#[cfg(not(target_arch = "wasm32"))]
+type Handshake = tungstenite::handshake::MidHandshake<
+    tungstenite::handshake::server::ServerHandshake<
+        std::net::TcpStream,
+        tungstenite::handshake::server::NoCallback,
+    >,
+>;
+
+/// Serves what's happening in the current run over a local WebSocket, so something outside the
+/// game, like a stream overlay in an OBS browser source, can show live stats. It's read-only;
+/// anything clients send is ignored. Every message is one JSON object with a `type`.
+#[derive(Default)]
+pub struct StatsFeed {
+    #[cfg(not(target_arch = "wasm32"))]
+    listener: Option<std::net::TcpListener>,
+    #[cfg(not(target_arch = "wasm32"))]
+    clients: Vec<tungstenite::WebSocket<std::net::TcpStream>>,
+    // Clients partway through connecting, and when they started
+    #[cfg(not(target_arch = "wasm32"))]
+    handshakes: Vec<(Handshake, std::net::SocketAddr, std::time::Instant)>,
+    last_sync: Option<Time>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Update {
+    Status {
+        level: String,
+        score: usize,
+        goal: usize,
+        energy: usize,
+        max_energy: usize,
+        /// None for modes without a time limit
+        seconds_left: Option<f64>,
+        position: LonLat,
+    },
+    Delivery {
+        building: BuildingID,
+        /// How much the score went up
+        presents: usize,
+        score: usize,
+    },
+}
+
+impl StatsFeed {
+    /// Only listens on localhost
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn start(port: u16) -> Result<StatsFeed> {
+        let listener = std::net::TcpListener::bind(("127.0.0.1", port))?;
+        // The game loop can't wait on the network
+        listener.set_nonblocking(true)?;
+        info!("Serving live stats on ws://127.0.0.1:{}", port);
+        Ok(StatsFeed {
+            listener: Some(listener),
+            clients: Vec::new(),
+            handshakes: Vec::new(),
+            last_sync: None,
+        })
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn start(_: u16) -> Result<StatsFeed> {
+        bail!("The web version can't serve live stats")
+    }
+
+    /// Call every tick. Only builds and sends the status periodically, and only when somebody's
+    /// listening.
+    pub fn sync_status<F: FnOnce() -> Update>(&mut self, now: Time, status: F) {
+        if self
+            .last_sync
+            // The clock starts over with each run
+            .map(|t| t <= now && now - t < SYNC_PERIOD)
+            .unwrap_or(false)
+        {
+            return;
+        }
+        self.last_sync = Some(now);
+        self.accept_clients();
+        if self.has_clients() {
+            self.send(status());
+        }
+    }
+
+    pub fn delivery(&mut self, building: BuildingID, presents: usize, score: usize) {
+        if self.has_clients() {
+            self.send(Update::Delivery {
+                building,
+                presents,
+                score,
+            });
+        }
+    }
+
+    fn send(&mut self, update: Update) {
+        match serde_json::to_string(&update) {
+            Ok(txt) => self.send_to_all(txt),
+            Err(err) => warn!("Couldn't encode live stats: {}", err),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl StatsFeed {
+    fn has_clients(&self) -> bool {
+        !self.clients.is_empty()
+    }
+
+    /// Never waits on the network. Each handshake moves along as far as it can every time this is
+    /// called, and picks up where it left off the next time.
+    fn accept_clients(&mut self) {
+        let mut results = Vec::new();
+        if let Some(ref listener) = self.listener {
+            while let Ok((stream, addr)) = listener.accept() {
+                // Accepted sockets don't always inherit non-blocking mode
+                if let Err(err) = stream.set_nonblocking(true) {
+                    warn!(
+                        "Couldn't set up the live stats connection from {}: {}",
+                        addr, err
+                    );
+                    continue;
+                }
+                results.push((tungstenite::accept(stream), addr, std::time::Instant::now()));
+            }
+        }
+        for (handshake, addr, started) in std::mem::take(&mut self.handshakes) {
+            results.push((handshake.handshake(), addr, started));
+        }
+
+        for (result, addr, started) in results {
+            match result {
+                Ok(socket) => {
+                    info!("Live stats client connected from {}", addr);
+                    self.clients.push(socket);
+                }
+                Err(tungstenite::handshake::HandshakeError::Interrupted(handshake)) => {
+                    if started.elapsed() < HANDSHAKE_TIMEOUT {
+                        self.handshakes.push((handshake, addr, started));
+                    } else {
+                        warn!("Live stats client {} never finished connecting", addr);
+                    }
+                }
+                Err(tungstenite::handshake::HandshakeError::Failure(err)) => {
+                    warn!("Live stats handshake with {} failed: {}", addr, err)
+                }
+            }
+        }
+    }
+
+    fn send_to_all(&mut self, txt: String) {
+        self.clients.retain_mut(|socket| {
+            // Reading answers pings and notices clients that closed the connection
+            loop {
+                match socket.read() {
+                    Ok(_) => {}
+                    Err(err) if is_would_block(&err) => break,
+                    Err(_) => return false,
+                }
+            }
+            match socket.send(tungstenite::Message::Text(txt.clone())) {
+                Ok(()) => true,
+                Err(err) => is_would_block(&err),
+            }
+        });
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl StatsFeed {
+    fn has_clients(&self) -> bool {
+        false
+    }
+
+    fn accept_clients(&mut self) {}
+
+    fn send_to_all(&mut self, _: String) {}
+}
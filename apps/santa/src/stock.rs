@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_hashmap, serialize_hashmap};
+use geom::{Duration, Time};
+use map_model::BuildingID;
+
+/// Stores only have so much blood sugar and so many presents on their shelves. Whatever's taken
+/// comes back slowly, so long runs mean moving between stores instead of camping at one. Amounts
+/// are fractions of a full store; the tuning says how much that is.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StoreStock {
+    // How much each store was missing, and when that was. Stores nobody's visited are full.
+    #[serde(
+        serialize_with = "serialize_hashmap",
+        deserialize_with = "deserialize_hashmap"
+    )]
+    missing: HashMap<BuildingID, Missing>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct Missing {
+    energy: f64,
+    presents: f64,
+    at: Time,
+}
+
+impl StoreStock {
+    pub fn new() -> StoreStock {
+        StoreStock {
+            missing: HashMap::new(),
+        }
+    }
+
+    /// The fraction of blood sugar and presents left. An empty store fills back up over
+    /// `restock_time`.
+    pub fn left(&self, b: BuildingID, now: Time, restock_time: Duration) -> (f64, f64) {
+        match self.missing.get(&b) {
+            Some(missing) => {
+                let restocked = (now - missing.at) / restock_time;
+                (
+                    1.0 - (missing.energy - restocked).max(0.0),
+                    1.0 - (missing.presents - restocked).max(0.0),
+                )
+            }
+            None => (1.0, 1.0),
+        }
+    }
+
+    /// Every store that isn't full
+    pub fn visited(&self) -> impl Iterator<Item = &BuildingID> {
+        self.missing.keys()
+    }
+
+    pub fn take(
+        &mut self,
+        b: BuildingID,
+        now: Time,
+        restock_time: Duration,
+        energy: f64,
+        presents: f64,
+    ) {
+        let (energy_left, presents_left) = self.left(b, now, restock_time);
+        self.missing.insert(
+            b,
+            Missing {
+                energy: (1.0 - energy_left + energy).min(1.0),
+                presents: (1.0 - presents_left + presents).min(1.0),
+                at: now,
+            },
+        );
+    }
+}
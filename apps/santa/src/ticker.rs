@@ -0,0 +1,58 @@
+use std::collections::VecDeque;
+
+use geom::{Polygon, Time};
+use widgetry::{Color, Drawable, EventCtx, GfxCtx, Line, ScreenPt, Text};
+
+// In pixels per second
+const SCROLL_SPEED: f64 = 150.0;
+const HEIGHT: f64 = 40.0;
+
+/// A strip along the top of the screen, with headlines scrolling across it one at a time
+pub struct Ticker {
+    queue: VecDeque<String>,
+    // The headline scrolling by now, its size, and when it appeared on the right edge
+    current: Option<(Drawable, f64, f64, Time)>,
+}
+
+impl Ticker {
+    pub fn new() -> Ticker {
+        Ticker {
+            queue: VecDeque::new(),
+            current: None,
+        }
+    }
+
+    /// Waits for anything already scrolling by to finish
+    pub fn announce(&mut self, headline: String) {
+        self.queue.push_back(headline);
+    }
+
+    pub fn event(&mut self, ctx: &mut EventCtx, now: Time) {
+        if let Some((_, width, _, started)) = self.current {
+            let scrolled = SCROLL_SPEED * (now - started).inner_seconds();
+            if scrolled < ctx.canvas.window_width + width {
+                return;
+            }
+            self.current = None;
+        }
+        if let Some(headline) = self.queue.pop_front() {
+            let batch = Text::from(Line(headline).small_heading()).render_autocropped(ctx);
+            let dims = batch.get_dims();
+            self.current = Some((ctx.upload(batch), dims.width, dims.height, now));
+        }
+    }
+
+    pub fn draw(&self, g: &mut GfxCtx, now: Time) {
+        if let Some((ref draw, _, height, started)) = self.current {
+            g.fork_screenspace();
+            g.draw_polygon(
+                Color::BLACK.alpha(0.7),
+                Polygon::rectangle(g.canvas.window_width, HEIGHT),
+            );
+            g.unfork();
+
+            let x = g.canvas.window_width - SCROLL_SPEED * (now - started).inner_seconds();
+            g.redraw_at(ScreenPt::new(x, (HEIGHT - height) / 2.0), draw);
+        }
+    }
+}
@@ -5,19 +5,56 @@ use widgetry::{
     RewriteColor, SimpleState, State, Text, TextExt, Widget,
 };
 
+use crate::achievements::Achievements;
+use crate::attract::{AttractMode, IDLE_BEFORE_DEMO};
+use crate::campaign::{campaign_btn, Campaign};
+use crate::controls::ControlScheme;
+use crate::featured;
+use crate::kiosk::IdleTimer;
 use crate::levels::Level;
+use crate::saved_run::SavedRun;
+use crate::settings::Settings;
+use crate::tutorial::Tutorial;
 use crate::{App, Transition};
 
-pub struct TitleScreen;
+pub struct TitleScreen {
+    // To start the demo
+    idle: IdleTimer,
+}
 
 impl TitleScreen {
     pub fn new_state(ctx: &mut EventCtx, app: &App) -> Box<dyn State<App>> {
         let mut level_buttons = Vec::new();
         for (idx, level) in app.session.levels.iter().enumerate() {
-            if idx < app.session.levels_unlocked {
-                level_buttons.push(unlocked_level(ctx, app, level, idx).margin_below(16));
+            if idx < app.session.levels_unlocked && app.session.tutorial_done {
+                level_buttons.push(
+                    unlocked_level(ctx, app, level, format!("LEVEL {}", idx + 1)).margin_below(16),
+                );
             } else {
-                level_buttons.push(locked_level(ctx, app, level, idx).margin_below(16));
+                level_buttons.push(
+                    locked_level(ctx, app, level, format!("LEVEL {}", idx + 1)).margin_below(16),
+                );
+            }
+        }
+        let boss = Level::boss();
+        if app.session.boss_unlocked() {
+            level_buttons
+                .push(unlocked_level(ctx, app, &boss, "BOSS LEVEL".to_string()).margin_below(16));
+        } else {
+            level_buttons
+                .push(locked_level(ctx, app, &boss, "BOSS LEVEL".to_string()).margin_below(16));
+        }
+        // Whoever wrote these can play them right away
+        for level in &app.session.custom_levels {
+            level_buttons
+                .push(unlocked_level(ctx, app, level, "CUSTOM LEVEL".to_string()).margin_below(16));
+        }
+        if app.session.tutorial_done {
+            if let Some(level) = featured::this_week(app) {
+                level_buttons.push(
+                    unlocked_level(ctx, app, &level, "FEATURED THIS WEEK".to_string())
+                        .margin_below(16),
+                );
             }
         }
 
@@ -46,13 +83,78 @@ impl TitleScreen {
                 .padding(16)
                 .bg(Color::BLACK.alpha(0.8))
                 .centered_horiz(),
+                if app.session.tutorial_done {
+                    Widget::nothing()
+                } else {
+                    ctx.style()
+                        .btn_solid_primary
+                        .text(app.session.locale.tr("Start the tutorial"))
+                        .build_widget(ctx, "Start the tutorial")
+                        .centered_horiz()
+                },
+                // Kiosks never keep a run
+                match SavedRun::load() {
+                    Some(run) if !app.session.kiosk => ctx
+                        .style()
+                        .btn_solid_primary
+                        .text(
+                            app.session
+                                .locale
+                                .tr_args("Resume run on {}", &[run.level.clone()]),
+                        )
+                        .build_widget(ctx, "Resume run")
+                        .centered_horiz(),
+                    _ => Widget::nothing(),
+                },
                 Widget::custom_row(level_buttons).flex_wrap(ctx, Percent::int(80)),
+                Widget::row(
+                    Campaign::all()
+                        .iter()
+                        .map(|campaign| campaign_btn(ctx, app, campaign))
+                        .collect(),
+                )
+                .centered_horiz(),
                 Widget::row(vec![
-                    map_gui::tools::home_btn(ctx),
+                    // Kiosks stay in the game
+                    if app.session.kiosk {
+                        Widget::nothing()
+                    } else {
+                        map_gui::tools::home_btn(ctx)
+                    },
+                    ctx.style()
+                        .btn_outline
+                        .text(app.session.locale.tr("Tutorial"))
+                        .build_widget(ctx, "Tutorial")
+                        .centered_vert(),
+                    ctx.style()
+                        .btn_outline
+                        .text(app.session.locale.tr("How to play"))
+                        .build_widget(ctx, "How to play")
+                        .centered_vert(),
+                    // Importing needs the command line tools
+                    if cfg!(target_arch = "wasm32") || app.session.kiosk {
+                        Widget::nothing()
+                    } else {
+                        ctx.style()
+                            .btn_outline
+                            .text(app.session.locale.tr("Your own map"))
+                            .build_widget(ctx, "Your own map")
+                            .centered_vert()
+                    },
                     ctx.style()
                         .btn_outline
-                        .text("Credits")
-                        .build_def(ctx)
+                        .text(app.session.locale.tr("Achievements"))
+                        .build_widget(ctx, "Achievements")
+                        .centered_vert(),
+                    ctx.style()
+                        .btn_outline
+                        .text(app.session.locale.tr("Settings"))
+                        .build_widget(ctx, "Settings")
+                        .centered_vert(),
+                    ctx.style()
+                        .btn_outline
+                        .text(app.session.locale.tr("Credits"))
+                        .build_widget(ctx, "Credits")
                         .centered_vert(),
                     "Created by Dustin Carlino, Yuwen Li, & Michael Kirk"
                         .text_widget(ctx)
@@ -63,7 +165,9 @@ impl TitleScreen {
                 .bg(Color::BLACK.alpha(0.8)),
             ]))
             .build_custom(ctx),
-            Box::new(TitleScreen),
+            Box::new(TitleScreen {
+                idle: IdleTimer::new(),
+            }),
         )
     }
 }
@@ -83,9 +187,44 @@ impl SimpleState<App> for TitleScreen {
                 map_gui::tools::Executable::Santa,
                 Box::new(|ctx, app, _| Self::new_state(ctx, app)),
             )]),
+            "Start the tutorial" | "Tutorial" => {
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    let map_name = Level::tutorial().map;
+                    if !abstio::file_exists(map_name.path()) {
+                        return map_gui::tools::prompt_to_download_missing_data(
+                            ctx,
+                            map_name,
+                            Box::new(|ctx, app| Transition::Replace(Tutorial::new_state(ctx, app))),
+                        );
+                    }
+                }
+                Transition::Push(Tutorial::new_state(ctx, app))
+            }
+            "Resume run" => match SavedRun::load() {
+                Some(run) => run.resume(ctx, app),
+                None => Transition::Keep,
+            },
+            "How to play" => Transition::Push(HowToPlay::new_state(ctx, app)),
+            #[cfg(not(target_arch = "wasm32"))]
+            "Your own map" => Transition::Push(crate::custom_map::CustomMap::new_state(ctx, app)),
+            "Achievements" => Transition::Push(Achievements::new_state(ctx, app)),
+            "Settings" => Transition::Push(Settings::new_state(ctx, app)),
             "Credits" => Transition::Push(Credits::new_state(ctx)),
             x => {
-                for level in &app.session.levels {
+                if let Some(campaign) = Campaign::all().into_iter().find(|c| c.title == x) {
+                    return campaign.start(ctx, app);
+                }
+                let boss = Level::boss();
+                let featured = featured::this_week(app);
+                for level in app
+                    .session
+                    .levels
+                    .iter()
+                    .chain(std::iter::once(&boss))
+                    .chain(&app.session.custom_levels)
+                    .chain(&featured)
+                {
                     if x == level.title {
                         #[cfg(not(target_arch = "wasm32"))]
                         {
@@ -97,7 +236,10 @@ impl SimpleState<App> for TitleScreen {
                                     map_name,
                                     Box::new(move |ctx, app| {
                                         Transition::Replace(crate::before_level::Picker::new_state(
-                                            ctx, app, level,
+                                            ctx,
+                                            app,
+                                            level,
+                                            app.session.last_mode,
                                         ))
                                     }),
                                 );
@@ -108,6 +250,7 @@ impl SimpleState<App> for TitleScreen {
                             ctx,
                             app,
                             level.clone(),
+                            app.session.last_mode,
                         ));
                     }
                 }
@@ -118,6 +261,12 @@ impl SimpleState<App> for TitleScreen {
 
     fn other_event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
         app.session.update_music(ctx);
+
+        if self.idle.event(ctx, IDLE_BEFORE_DEMO) {
+            if let Some(replay) = AttractMode::find_demo(app) {
+                return Transition::Push(AttractMode::new_state(ctx, app, replay));
+            }
+        }
         Transition::Keep
     }
 
@@ -126,9 +275,9 @@ impl SimpleState<App> for TitleScreen {
     }
 }
 
-fn level_btn(ctx: &mut EventCtx, app: &App, level: &Level, idx: usize) -> GeomBatch {
+fn level_btn(ctx: &mut EventCtx, app: &App, level: &Level, heading: String) -> GeomBatch {
     let mut txt = Text::new();
-    txt.add_line(Line(format!("LEVEL {}", idx + 1)).small_heading());
+    txt.add_line(Line(heading).small_heading());
     txt.add_line(Line(&level.title).small_heading());
     txt.add_line(&level.description);
     let batch = txt.wrap_to_pct(ctx, 15).render_autocropped(ctx);
@@ -149,8 +298,8 @@ fn level_btn(ctx: &mut EventCtx, app: &App, level: &Level, idx: usize) -> GeomBa
 }
 
 // TODO Preview the map, add padding, add the linear gradient...
-fn locked_level(ctx: &mut EventCtx, app: &App, level: &Level, idx: usize) -> Widget {
-    let mut batch = level_btn(ctx, app, level, idx);
+fn locked_level(ctx: &mut EventCtx, app: &App, level: &Level, heading: String) -> Widget {
+    let mut batch = level_btn(ctx, app, level, heading);
     let hitbox = batch.get_bounds().get_rectangle();
     let center = hitbox.center();
     batch.push(app.cs.fade_map_dark, hitbox);
@@ -158,8 +307,8 @@ fn locked_level(ctx: &mut EventCtx, app: &App, level: &Level, idx: usize) -> Wid
     batch.into_widget(ctx)
 }
 
-fn unlocked_level(ctx: &mut EventCtx, app: &App, level: &Level, idx: usize) -> Widget {
-    let normal = level_btn(ctx, app, level, idx);
+fn unlocked_level(ctx: &mut EventCtx, app: &App, level: &Level, heading: String) -> Widget {
+    let normal = level_btn(ctx, app, level, heading);
     let hovered = normal
         .clone()
         .color(RewriteColor::Change(Color::WHITE, Color::WHITE.alpha(0.6)));
@@ -170,6 +319,133 @@ fn unlocked_level(ctx: &mut EventCtx, app: &App, level: &Level, idx: usize) -> W
         .build_widget(ctx, &level.title)
 }
 
+struct HowToPlay;
+
+impl HowToPlay {
+    fn new_state(ctx: &mut EventCtx, app: &App) -> Box<dyn State<App>> {
+        let keys = &app.session.keys;
+        let hotkey = ctx.style().text_hotkey_color;
+        let scheme_heading = |scheme: ControlScheme, name: &str| {
+            if app.session.control_scheme == scheme {
+                Line(format!("{} (current)", name)).small_heading()
+            } else {
+                Line(name).small_heading()
+            }
+        };
+
+        <dyn SimpleState<_>>::new_state(
+            Panel::new_builder(Widget::col(vec![
+                Widget::row(vec![
+                    Line("How to play").big_heading_plain().into_widget(ctx),
+                    ctx.style().btn_close_widget(ctx),
+                ]),
+                Text::from_multiline(vec![
+                    Line(
+                        "Ride past houses and apartments to drop off presents. Bigger buildings \
+                         need more presents.",
+                    ),
+                    Line(
+                        "Each house wants a toy, book, or sweater -- carry the right one! Stores \
+                         restock presents too.",
+                    ),
+                    Line("Every delivery burns some blood sugar. Ride past a store to refill it."),
+                    Line(
+                        "Bike and bus lanes charge up your boost. Deliver quickly in a row for a \
+                         combo! Grab power-ups along the way, and watch out for people and cars.",
+                    ),
+                ])
+                .wrap_to_pct(ctx, 50)
+                .into_widget(ctx),
+                Text::from(scheme_heading(ControlScheme::Instant, "Direct controls"))
+                    .into_widget(ctx),
+                Text::from_all(vec![
+                    Line(format!(
+                        "{}, {}, {}, {}",
+                        keys.describe("up"),
+                        keys.describe("left"),
+                        keys.describe("down"),
+                        keys.describe("right")
+                    ))
+                    .fg(hotkey),
+                    Line(" move in that direction"),
+                ])
+                .into_widget(ctx),
+                Text::from(scheme_heading(ControlScheme::Rotate, "Rotate controls"))
+                    .into_widget(ctx),
+                Text::from_all(vec![
+                    Line(format!(
+                        "{}, {}",
+                        keys.describe("left"),
+                        keys.describe("right")
+                    ))
+                    .fg(hotkey),
+                    Line(" turn (hold to turn faster), "),
+                    Line(format!(
+                        "{}, {}",
+                        keys.describe("up"),
+                        keys.describe("down")
+                    ))
+                    .fg(hotkey),
+                    Line(" go forwards or backwards"),
+                ])
+                .into_widget(ctx),
+                Text::from(scheme_heading(
+                    ControlScheme::OneButton,
+                    "One-button controls",
+                ))
+                .into_widget(ctx),
+                Text::from_all(vec![
+                    Line("The sleigh keeps moving, and "),
+                    Line(keys.describe("right")).fg(hotkey),
+                    Line(" turns to the next direction. Presents drop automatically."),
+                ])
+                .into_widget(ctx),
+                Text::from(scheme_heading(ControlScheme::Mouse, "Mouse controls")).into_widget(ctx),
+                Line(
+                    "The sleigh flies towards the cursor, faster the further away it is. Keep the \
+                     cursor on the sleigh to stop.",
+                )
+                .into_widget(ctx),
+                Text::from_all(vec![
+                    Line(keys.describe("boost")).fg(hotkey),
+                    Line(" to boost, "),
+                    Line(keys.describe("switch present")).fg(hotkey),
+                    Line(" to switch presents, "),
+                    Line(keys.describe("pause")).fg(hotkey),
+                    Line(" to pause. Switch control schemes before starting a level."),
+                ])
+                .into_widget(ctx),
+                ctx.style()
+                    .btn_outline
+                    .text("Back")
+                    .hotkey(Key::Enter)
+                    .build_def(ctx)
+                    .centered_horiz(),
+            ]))
+            .build(ctx),
+            Box::new(HowToPlay),
+        )
+    }
+}
+
+impl SimpleState<App> for HowToPlay {
+    fn on_click(&mut self, _: &mut EventCtx, _: &mut App, x: &str, _: &mut Panel) -> Transition {
+        match x {
+            "close" | "Back" => Transition::Pop,
+            _ => unreachable!(),
+        }
+    }
+
+    fn other_event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        app.session.update_music(ctx);
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        app.session.music.draw(g);
+    }
+}
+
 struct Credits;
 
 impl Credits {
@@ -0,0 +1,136 @@
+use geom::{Angle, Circle, Distance, Pt2D};
+use widgetry::{Color, Drawable, EventCtx, GeomBatch, GfxCtx, Line, Text};
+
+// In pixels
+const JOYSTICK_RADIUS: f64 = 80.0;
+const BUTTON_RADIUS: f64 = 50.0;
+
+/// An on-screen joystick and boost button, so the game is playable on touchscreens without a
+/// keyboard. Touches arrive as left mouse button events, so this also works with a mouse.
+pub struct TouchControls {
+    enabled: bool,
+    boost_color: Color,
+    held: Option<Held>,
+    cursor: Pt2D,
+
+    /// Same meaning as `Gamepad::stick`
+    pub stick: Option<(Angle, f64)>,
+    pub boost: bool,
+
+    draw: Drawable,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Held {
+    Joystick,
+    Boost,
+}
+
+impl TouchControls {
+    /// Only the web version has these controls enabled.
+    pub fn new(ctx: &mut EventCtx, boost_color: Color) -> TouchControls {
+        let mut touch = TouchControls {
+            enabled: cfg!(target_arch = "wasm32"),
+            boost_color,
+            held: None,
+            cursor: Pt2D::new(0.0, 0.0),
+            stick: None,
+            boost: false,
+            draw: Drawable::empty(ctx),
+        };
+        touch.redraw(ctx);
+        touch
+    }
+
+    pub fn event(&mut self, ctx: &mut EventCtx) {
+        if !self.enabled {
+            return;
+        }
+
+        let orig = (self.held, self.stick, self.boost);
+        let (joystick, button) = positions(ctx);
+        if let Some(pt) = ctx.input.get_moved_mouse() {
+            self.cursor = pt.to_pt();
+        }
+        if ctx.input.left_mouse_button_pressed() {
+            self.cursor = ctx.canvas.get_cursor().to_pt();
+            if self.cursor.dist_to(joystick) <= Distance::meters(JOYSTICK_RADIUS) {
+                self.held = Some(Held::Joystick);
+            } else if self.cursor.dist_to(button) <= Distance::meters(BUTTON_RADIUS) {
+                self.held = Some(Held::Boost);
+            }
+        }
+        if ctx.input.left_mouse_button_released() || ctx.input.window_lost_cursor() {
+            self.held = None;
+        }
+
+        self.boost = self.held == Some(Held::Boost);
+        self.stick = None;
+        if self.held == Some(Held::Joystick) {
+            let dist = self.cursor.dist_to(joystick).inner_meters();
+            // A small dead zone, so a tap in the middle doesn't jerk around
+            if dist > 0.1 * JOYSTICK_RADIUS {
+                let dx = self.cursor.x() - joystick.x();
+                let dy = self.cursor.y() - joystick.y();
+                self.stick = Some((
+                    Angle::new_rads(dy.atan2(dx)),
+                    (dist / JOYSTICK_RADIUS).min(1.0),
+                ));
+            }
+        }
+
+        if (self.held, self.stick, self.boost) != orig || ctx.input.is_window_resized() {
+            self.redraw(ctx);
+        }
+    }
+
+    fn redraw(&mut self, ctx: &mut EventCtx) {
+        if !self.enabled {
+            return;
+        }
+        let (joystick, button) = positions(ctx);
+        let mut batch = GeomBatch::new();
+        batch.push(
+            Color::WHITE.alpha(0.3),
+            Circle::new(joystick, Distance::meters(JOYSTICK_RADIUS)).to_polygon(),
+        );
+        let knob = if let Some((angle, pct)) = self.stick {
+            joystick.project_away(Distance::meters(pct * JOYSTICK_RADIUS), angle)
+        } else {
+            joystick
+        };
+        batch.push(
+            Color::WHITE.alpha(0.8),
+            Circle::new(knob, Distance::meters(0.4 * JOYSTICK_RADIUS)).to_polygon(),
+        );
+
+        batch.push(
+            self.boost_color.alpha(if self.boost { 0.9 } else { 0.5 }),
+            Circle::new(button, Distance::meters(BUTTON_RADIUS)).to_polygon(),
+        );
+        batch.append(
+            Text::from(Line("Boost"))
+                .render_autocropped(ctx)
+                .centered_on(button),
+        );
+        self.draw = ctx.upload(batch);
+    }
+
+    pub fn draw(&self, g: &mut GfxCtx) {
+        if !self.enabled {
+            return;
+        }
+        g.fork_screenspace();
+        g.redraw(&self.draw);
+        g.unfork();
+    }
+}
+
+/// Screen-space centers of the joystick and the boost button
+fn positions(ctx: &EventCtx) -> (Pt2D, Pt2D) {
+    let y = ctx.canvas.window_height - 2.0 * JOYSTICK_RADIUS;
+    (
+        Pt2D::new(0.3 * ctx.canvas.window_width, y),
+        Pt2D::new(0.6 * ctx.canvas.window_width, y),
+    )
+}
@@ -0,0 +1,58 @@
+use geom::{Distance, Duration, PolyLine, Pt2D};
+use widgetry::{Color, Drawable, EventCtx, GeomBatch, GfxCtx};
+
+/// How far back the trail goes
+pub const TRAIL_LENGTH: Duration = Duration::const_seconds(20.0);
+/// Only every few recorded positions are kept, so the trail doesn't have hundreds of points
+pub const TRAIL_EVERY: usize = 3;
+// Older parts of the trail fade out in this many steps
+const FADE_STEPS: usize = 8;
+const MAX_ALPHA: f32 = 0.6;
+
+/// A line behind the sleigh of where it's been recently. The points come from the same samples
+/// as the ghost and replay, so in a replay, the trail matches the one shown while playing.
+pub struct Trail {
+    // As currently drawn
+    pts: Vec<Pt2D>,
+    draw: Drawable,
+}
+
+impl Trail {
+    pub fn new(ctx: &mut EventCtx) -> Trail {
+        Trail {
+            pts: Vec::new(),
+            draw: Drawable::empty(ctx),
+        }
+    }
+
+    /// Oldest first; only redraws when they change
+    pub fn update(&mut self, ctx: &mut EventCtx, mut pts: Vec<Pt2D>) {
+        // When the sleigh stops, the same position gets recorded repeatedly
+        pts.dedup();
+        if pts == self.pts {
+            return;
+        }
+        self.pts = pts;
+
+        let mut batch = GeomBatch::new();
+        if self.pts.len() >= 2 {
+            let per_step = ((self.pts.len() - 1) as f64 / FADE_STEPS as f64).ceil() as usize;
+            // Neighboring pieces share a point, so there are no gaps
+            for (step, start) in (0..self.pts.len() - 1).step_by(per_step).enumerate() {
+                let end = (start + per_step + 1).min(self.pts.len());
+                if let Ok(pl) = PolyLine::new(self.pts[start..end].to_vec()) {
+                    let alpha = MAX_ALPHA * ((step + 1) as f32) / (FADE_STEPS as f32);
+                    batch.push(
+                        Color::WHITE.alpha(alpha),
+                        pl.make_polygons(Distance::meters(1.0)),
+                    );
+                }
+            }
+        }
+        self.draw = ctx.upload(batch);
+    }
+
+    pub fn draw(&self, g: &mut GfxCtx) {
+        g.redraw(&self.draw);
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::Timer;
+use geom::{Distance, Duration};
+
+use crate::buildings::StoreType;
+
+/// All of the numbers that affect gameplay balance. The defaults are defined here, but they can be
+/// overridden by `santa_tuning.json` in the player's data directory, so balancing doesn't require
+/// recompiling. In dev mode, F5 reloads the file in the middle of a level.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Tuning {
+    /// Scales the speed of every vehicle
+    pub speed_multiplier: f64,
+    /// When out of energy, move this much slower
+    pub hangry_speed_multiplier: f64,
+    /// How much hills affect speed. A value of 3 means a 10% uphill grade slows down by 30%.
+    pub grade_speed_factor: f64,
+    /// If set, replaces the vehicle's normal max energy
+    pub max_energy: Option<usize>,
+    /// In meters per second, when the wind is blowing hardest
+    pub max_wind_speed: f64,
+    /// Warn the player when energy drops below this
+    pub low_energy_warning: usize,
+    /// Warn the player when this much time is left
+    pub low_time_warning: Duration,
+
+    pub max_boost: Duration,
+    /// How much boost is gained per second spent on bike and bus lanes
+    pub acquire_boost_rate: f64,
+    pub boost_speed_multiplier: f64,
+    /// After using up all boost, it can't be used again for this long
+    pub boost_cooldown: Duration,
+    /// Boosting off of bike and bus lanes burns 1 energy every time the player covers this much
+    /// ground
+    pub boost_distance_per_energy: Distance,
+    /// On top of the distance, boosting off of bike and bus lanes burns this much energy per
+    /// second, so holding boost while stuck or stopped isn't free
+    pub boost_energy_per_second: f64,
+
+    /// Deliveries made within this long of each other build up a combo
+    pub combo_window: Duration,
+    /// Each delivery in a combo increases the multiplier this much
+    pub combo_step: f64,
+    pub max_combo_multiplier: f64,
+    /// Delivering to every house along a street awards this fraction of its presents as a bonus
+    pub zone_bonus: f64,
+
+    /// Waiting near a bus stop trickles in this much blood sugar per second
+    pub bus_stop_recharge_rate: f64,
+    /// How many of each kind of present can be carried at once
+    pub presents_per_type: usize,
+    /// Visiting a store refills blood sugar up to this fraction of the max
+    pub supermarket_refill: f64,
+    pub restaurant_refill: f64,
+    pub convenience_store_refill: f64,
+    /// How much blood sugar each store has on its shelves, as a multiple of the vehicle's max
+    pub store_energy_stock: f64,
+    /// How many presents each store has on its shelves, counting every kind together
+    pub store_present_stock: usize,
+    /// How long an emptied store takes to restock its shelves
+    pub store_restock_time: Duration,
+
+    /// How much blood sugar an energy pickup gives
+    pub pickup_energy: usize,
+    pub speed_pickup_multiplier: f64,
+    /// How long the double score and speed pickups last
+    pub power_up_duration: Duration,
+    /// Snacking at a restaurant multiplies the score from deliveries for a while
+    pub snack_multiplier: f64,
+    pub snack_duration: Duration,
+    /// How long after riding past a restaurant there's still time to stop for a snack
+    pub snack_window: Duration,
+
+    /// How long it takes each traffic signal to go through both phases
+    pub signal_cycle: Duration,
+    /// Flying through a red light burns this much energy
+    pub red_light_energy_cost: usize,
+
+    /// Going back to the start refills blood sugar and presents, but takes this many presents
+    /// off the score
+    pub go_home_penalty: usize,
+    /// And can't be done again for this long
+    pub go_home_cooldown: Duration,
+
+    /// Running into a person or car burns this much energy
+    pub collision_energy_cost: usize,
+    /// And stops the player for this long
+    pub stun_duration: Duration,
+
+    /// Deliveries worth at least this much get celebrated
+    pub big_delivery_score: usize,
+    /// By slowing down time for this long, measured in game time
+    pub slow_motion_duration: Duration,
+    /// To this fraction of the usual speed
+    pub slow_motion_factor: f64,
+
+    /// In endless mode, delivered houses want presents again after this long
+    pub endless_regrow_time: Duration,
+    /// Each time a house wants presents again, it wants this fraction of the previous amount
+    pub endless_decay: f64,
+    /// In endless mode, a cluster of delivered houses wants presents again this often
+    pub surge_period: Duration,
+    /// Delivered houses this close to the middle of the cluster are part of it
+    pub surge_radius: Distance,
+    /// And want this many times their usual amount
+    pub surge_multiplier: f64,
+
+    /// If set, replaces the level's goal
+    pub goal: Option<usize>,
+}
+
+impl Default for Tuning {
+    fn default() -> Tuning {
+        Tuning {
+            speed_multiplier: 1.0,
+            hangry_speed_multiplier: 0.3,
+            grade_speed_factor: 3.0,
+            max_energy: None,
+            max_wind_speed: 10.0,
+            low_energy_warning: 30,
+            low_time_warning: Duration::seconds(20.0),
+
+            max_boost: Duration::seconds(5.0),
+            acquire_boost_rate: 0.5,
+            boost_speed_multiplier: 2.0,
+            boost_cooldown: Duration::seconds(3.0),
+            boost_distance_per_energy: Distance::meters(25.0),
+            boost_energy_per_second: 0.2,
+
+            combo_window: Duration::seconds(10.0),
+            combo_step: 0.1,
+            max_combo_multiplier: 2.0,
+            zone_bonus: 0.5,
+
+            bus_stop_recharge_rate: 2.0,
+            presents_per_type: 40,
+            supermarket_refill: 1.0,
+            restaurant_refill: 0.75,
+            convenience_store_refill: 0.5,
+            store_energy_stock: 3.0,
+            store_present_stock: 150,
+            store_restock_time: Duration::minutes(3),
+
+            pickup_energy: 25,
+            speed_pickup_multiplier: 1.5,
+            power_up_duration: Duration::seconds(15.0),
+            snack_multiplier: 2.0,
+            snack_duration: Duration::seconds(10.0),
+            snack_window: Duration::seconds(2.0),
+
+            signal_cycle: Duration::seconds(20.0),
+            red_light_energy_cost: 5,
+
+            go_home_penalty: 20,
+            go_home_cooldown: Duration::seconds(60.0),
+
+            collision_energy_cost: 5,
+            stun_duration: Duration::seconds(1.0),
+
+            big_delivery_score: 50,
+            slow_motion_duration: Duration::seconds(0.5),
+            slow_motion_factor: 0.25,
+
+            endless_regrow_time: Duration::minutes(3),
+            endless_decay: 0.5,
+            surge_period: Duration::seconds(90.0),
+            surge_radius: Distance::meters(150.0),
+            surge_multiplier: 2.0,
+
+            goal: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct TuningFile {
+    #[serde(default)]
+    default: Tuning,
+    /// Level title -> settings for only that level. These completely replace `default`; any
+    /// missing fields use the built-in values.
+    #[serde(default)]
+    levels: BTreeMap<String, Tuning>,
+}
+
+impl Tuning {
+    pub fn refill(&self, store: StoreType) -> f64 {
+        match store {
+            StoreType::Supermarket | StoreType::Upzoned => self.supermarket_refill,
+            StoreType::Restaurant => self.restaurant_refill,
+            StoreType::ConvenienceStore => self.convenience_store_refill,
+        }
+    }
+
+    /// Falls back to the built-in defaults if the file is missing or broken.
+    pub fn load(level: &str) -> Tuning {
+        match abstio::maybe_read_json::<TuningFile>(
+            abstio::path_player("santa_tuning.json"),
+            &mut Timer::throwaway(),
+        ) {
+            Ok(mut file) => file.levels.remove(level).unwrap_or(file.default),
+            Err(err) => {
+                // A missing file is the normal case, so don't be loud about it
+                debug!("Using default tuning: {}", err);
+                Tuning::default()
+            }
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::HashSet;
+
+use geom::Duration;
+use widgetry::{
+    EventCtx, GfxCtx, HorizontalAlignment, Line, Outcome, Panel, State, Text, VerticalAlignment,
+    Widget,
+};
+
+use crate::game::Game;
+use crate::levels::Level;
+use crate::load_map::load_map;
+use crate::mode::Mode;
+use crate::vehicles::Vehicle;
+use crate::{App, Transition};
+
+// How long the player has to ride around before moving on
+const MOVE_TIME: Duration = Duration::const_seconds(3.0);
+// Drain energy to this much, so running out doesn't take forever
+const LOW_ENERGY: usize = 3;
+
+/// Walks a new player through the basics, one step at a time, on top of a normal game. The real
+/// levels stay locked until this is finished.
+pub struct Tutorial {
+    stage: Stage,
+    moved_for: Duration,
+    panel: Panel,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Stage {
+    Move,
+    Deliver,
+    RunOutOfEnergy,
+    Recharge,
+    Done,
+}
+
+/// What happened in the game this frame
+pub struct Progress {
+    pub dt: Duration,
+    pub moved: bool,
+    pub delivered: bool,
+    pub refilled: bool,
+    pub energy: usize,
+}
+
+impl Tutorial {
+    pub fn new_state(ctx: &mut EventCtx, app: &App) -> Box<dyn State<App>> {
+        let level = Level::tutorial();
+        load_map(
+            ctx,
+            app,
+            level.map.clone(),
+            Box::new(move |ctx, app| {
+                app.session.music.change_song(&level.music);
+                let tutorial = Tutorial {
+                    stage: Stage::Move,
+                    moved_for: Duration::ZERO,
+                    panel: make_panel(ctx, app, Stage::Move),
+                };
+                Transition::Replace(Game::new_state(
+                    ctx,
+                    app,
+                    level,
+                    Vehicle::get("bike"),
+                    HashSet::new(),
+                    Mode::Normal,
+                    Some(tutorial),
+                ))
+            }),
+        )
+    }
+
+    /// Advances through the stages. If energy should be drained to make the next stage happen
+    /// sooner, returns the new amount.
+    pub fn update(
+        &mut self,
+        ctx: &mut EventCtx,
+        app: &mut App,
+        progress: Progress,
+    ) -> Option<usize> {
+        let mut drain_energy = None;
+        let next = match self.stage {
+            Stage::Move => {
+                if progress.moved {
+                    self.moved_for += progress.dt;
+                }
+                (self.moved_for >= MOVE_TIME).then_some(Stage::Deliver)
+            }
+            Stage::Deliver => progress.delivered.then_some(Stage::RunOutOfEnergy),
+            Stage::RunOutOfEnergy => (progress.energy == 0).then_some(Stage::Recharge),
+            Stage::Recharge => progress.refilled.then_some(Stage::Done),
+            Stage::Done => None,
+        };
+
+        if let Some(stage) = next {
+            self.stage = stage;
+            self.panel = make_panel(ctx, app, stage);
+            match stage {
+                Stage::RunOutOfEnergy => {
+                    drain_energy = Some(progress.energy.min(LOW_ENERGY));
+                }
+                Stage::Done => {
+                    app.session.tutorial_done = true;
+                    app.session.save();
+                }
+                _ => {}
+            }
+        }
+        drain_energy
+    }
+
+    pub fn event(&mut self, ctx: &mut EventCtx, app: &App) -> Option<Transition> {
+        if let Outcome::Clicked(x) = self.panel.event(ctx) {
+            match x.as_ref() {
+                "Back to title" => {
+                    return Some(Transition::Multi(vec![
+                        Transition::Pop,
+                        Transition::Replace(crate::title::TitleScreen::new_state(ctx, app)),
+                    ]));
+                }
+                _ => unreachable!(),
+            }
+        }
+        None
+    }
+
+    pub fn draw(&self, g: &mut GfxCtx) {
+        self.panel.draw(g);
+    }
+}
+
+fn make_panel(ctx: &mut EventCtx, app: &App, stage: Stage) -> Panel {
+    let keys = &app.session.keys;
+    let hotkey = ctx.style().text_hotkey_color;
+    let (title, txt) = match stage {
+        Stage::Move => (
+            "Getting around",
+            Text::from_all(vec![
+                Line("Use "),
+                Line(format!(
+                    "{}, {}, {}, {}",
+                    keys.describe("up"),
+                    keys.describe("left"),
+                    keys.describe("down"),
+                    keys.describe("right")
+                ))
+                .fg(hotkey),
+                Line(" to ride around. Try it out!"),
+            ]),
+        ),
+        Stage::Deliver => (
+            "Delivering presents",
+            Text::from_multiline(vec![
+                Line("Colored buildings are houses and apartments waiting for presents."),
+                Line("Ride past one to drop off presents. Apartments need more presents."),
+                Line(format!(
+                    "Each house wants a toy, book, or sweater. Press {} to switch.",
+                    keys.describe("switch present")
+                )),
+            ]),
+        ),
+        Stage::RunOutOfEnergy => (
+            "Blood sugar",
+            Text::from_multiline(vec![
+                Line("Every delivery uses up some blood sugar -- watch the yellow bar."),
+                Line("You're running low! Keep delivering until it's empty."),
+            ]),
+        ),
+        Stage::Recharge => (
+            "Refilling",
+            Text::from_multiline(vec![
+                Line("Out of blood sugar, you move slowly and can't deliver anything."),
+                Line("Follow the red arrow to a store and ride past it to refill."),
+            ]),
+        ),
+        Stage::Done => (
+            "Tutorial complete!",
+            Text::from_multiline(vec![
+                Line("Keep practicing here, or head back and try a real level."),
+                Line("Every level has a time limit, so deliver as much as you can!"),
+            ]),
+        ),
+    };
+
+    let mut col = vec![
+        Line(title).small_heading().into_widget(ctx),
+        txt.into_widget(ctx),
+    ];
+    if stage == Stage::Done {
+        col.push(
+            ctx.style()
+                .btn_solid_primary
+                .text("Back to title")
+                .build_def(ctx),
+        );
+    }
+    Panel::new_builder(Widget::col(col))
+        .aligned(HorizontalAlignment::Center, VerticalAlignment::TopInset)
+        .build(ctx)
+}
@@ -1,11 +1,16 @@
-use geom::{Speed, Time};
-use widgetry::{GeomBatch, Prerender};
+use geom::{Distance, Speed, Time};
+use widgetry::{GeomBatch, GfxCtx, Prerender};
+
+use crate::player::Player;
+use crate::App;
 
 pub struct Vehicle {
     pub name: String,
 
     pub speed: Speed,
     pub max_energy: usize,
+    /// Houses with their driveway this close get presents, without having to ride right past
+    pub delivery_radius: Distance,
 
     // Paths to SVGs to draw in sequence
     draw_frames: Vec<&'static str>,
@@ -14,12 +19,18 @@ pub struct Vehicle {
 
 impl Vehicle {
     pub fn get(name: &str) -> Vehicle {
-        match name {
+        Vehicle::maybe_get(name).unwrap_or_else(|| panic!("Don't know vehicle {}", name))
+    }
+
+    /// None if there's no vehicle called this, like from an old save
+    pub fn maybe_get(name: &str) -> Option<Vehicle> {
+        Some(match name {
             "bike" => Vehicle {
                 name: "bike".to_string(),
 
                 speed: Speed::miles_per_hour(30.0),
                 max_energy: 100,
+                delivery_radius: Distance::meters(5.0),
 
                 draw_frames: vec!["bike1.svg", "bike2.svg", "bike1.svg", "bike3.svg"],
                 scale: 0.05,
@@ -29,6 +40,8 @@ impl Vehicle {
 
                 speed: Speed::miles_per_hour(25.0),
                 max_energy: 300,
+                // The reindeer can lean over
+                delivery_radius: Distance::meters(12.0),
 
                 draw_frames: vec!["sleigh.svg"],
                 scale: 0.08,
@@ -38,6 +51,7 @@ impl Vehicle {
 
                 speed: Speed::miles_per_hour(40.0),
                 max_energy: 150,
+                delivery_radius: Distance::meters(8.0),
 
                 draw_frames: vec![
                     "cargo_bike1.svg",
@@ -47,8 +61,8 @@ impl Vehicle {
                 ],
                 scale: 0.05,
             },
-            x => panic!("Don't know vehicle {}", x),
-        }
+            _ => return None,
+        })
     }
 
     pub fn animate(&self, prerender: &Prerender, time: Time) -> GeomBatch {
@@ -63,6 +77,19 @@ impl Vehicle {
         GeomBatch::load_svg(prerender, &path).scale(self.scale)
     }
 
+    /// Where the player is, facing the way they're going
+    pub fn draw_at(&self, g: &mut GfxCtx, time: Time, player: &Player) {
+        self.animate(g.prerender, time)
+            .centered_on(player.get_pos())
+            .rotate_around_batch_center(player.get_angle())
+            .draw(g);
+    }
+
+    /// How fast this goes on the hills and road surface wherever the player is
+    pub fn speed_at(&self, app: &App, player: &Player, grade_speed_factor: f64) -> Speed {
+        player.terrain_multiplier(app, grade_speed_factor) * self.speed
+    }
+
     /// (max speed, max energy)
     pub fn max_stats() -> (Speed, usize) {
         let mut speed = Speed::ZERO;
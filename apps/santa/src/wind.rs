@@ -0,0 +1,94 @@
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+use geom::{Angle, ArrowCap, Distance, Duration, PolyLine, Pt2D, Time};
+use widgetry::{Color, EventCtx, GeomBatch, Widget};
+
+// How often the wind picks a new direction and strength to drift towards
+const MIN_CHANGE_PERIOD: Duration = Duration::const_seconds(10.0);
+const MAX_CHANGE_PERIOD: Duration = Duration::const_seconds(20.0);
+// How quickly the wind approaches the new target, per second
+const DRIFT_RATE: f64 = 0.2;
+
+/// Wind slowly changes over the course of a level and pushes the player around while they move.
+pub struct Wind {
+    rng: XorShiftRng,
+    angle: Angle,
+    // 0 to 1
+    strength: f64,
+    target_angle: Angle,
+    target_strength: f64,
+    next_change: Time,
+}
+
+impl Wind {
+    pub fn new(now: Time, seed: u64) -> Wind {
+        let mut rng = XorShiftRng::seed_from_u64(seed);
+        let angle = Angle::degrees(rng.gen_range(0.0..360.0));
+        let strength = rng.gen_range(0.0..0.5);
+        Wind {
+            rng,
+            angle,
+            strength,
+            target_angle: angle,
+            target_strength: strength,
+            next_change: now,
+        }
+    }
+
+    pub fn update(&mut self, now: Time, dt: Duration) {
+        if now >= self.next_change {
+            self.target_angle = Angle::degrees(self.rng.gen_range(0.0..360.0));
+            self.target_strength = self.rng.gen_range(0.0..1.0);
+            self.next_change = now
+                + Duration::seconds(self.rng.gen_range(
+                    MIN_CHANGE_PERIOD.inner_seconds()..MAX_CHANGE_PERIOD.inner_seconds(),
+                ));
+        }
+
+        let blend = (DRIFT_RATE * dt.inner_seconds()).min(1.0);
+        // Turn the shortest way around
+        let mut diff = self.target_angle.normalized_radians() - self.angle.normalized_radians();
+        if diff > std::f64::consts::PI {
+            diff -= 2.0 * std::f64::consts::PI;
+        } else if diff < -std::f64::consts::PI {
+            diff += 2.0 * std::f64::consts::PI;
+        }
+        self.angle = Angle::new_rads(self.angle.normalized_radians() + blend * diff);
+        self.strength += blend * (self.target_strength - self.strength);
+    }
+
+    /// Starts turning towards a new direction right away, and keeps it there for a while
+    pub fn shift(&mut self, now: Time, angle: Angle) {
+        self.target_angle = angle;
+        self.next_change = now + MAX_CHANGE_PERIOD;
+    }
+
+    /// How far the wind pushes this frame, in map-space meters
+    pub fn displacement(&self, dt: Duration, max_speed: f64) -> (f64, f64) {
+        let magnitude = self.strength * max_speed * dt.inner_seconds();
+        let (sin, cos) = self.angle.normalized_radians().sin_cos();
+        (magnitude * cos, magnitude * sin)
+    }
+
+    /// Rounded, so the HUD only changes when it's noticeable
+    pub fn describe(&self) -> (isize, isize) {
+        (
+            (self.angle.normalized_degrees() / 10.0).round() as isize,
+            (self.strength * 10.0).round() as isize,
+        )
+    }
+
+    /// An arrow pointing where the wind blows, longer when it's stronger
+    pub fn to_widget(&self, ctx: &EventCtx) -> Widget {
+        let len = 10.0 + 30.0 * self.strength;
+        let mut batch = GeomBatch::new();
+        if let Ok(pl) = PolyLine::new(vec![Pt2D::new(0.0, 0.0), Pt2D::new(len, 0.0)]) {
+            batch.push(
+                Color::WHITE,
+                pl.make_arrow(Distance::meters(4.0), ArrowCap::Triangle),
+            );
+        }
+        batch.rotate(self.angle).autocrop().into_widget(ctx)
+    }
+}
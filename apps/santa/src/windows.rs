@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+use geom::{Angle, Circle, Distance, PolyLine, Pt2D};
+use map_model::BuildingID;
+use widgetry::{Color, Drawable, EventCtx, GeomBatch};
+
+use crate::buildings::{BldgState, Buildings};
+use crate::App;
+
+// About this many houses only take deliveries during part of the level
+const PCT_WITH_WINDOWS: f64 = 0.1;
+
+/// Some houses only open the door during part of the time limit
+#[derive(Clone, Copy, PartialEq)]
+pub enum DeliveryWindow {
+    /// The first third
+    Early,
+    /// The last third
+    Late,
+}
+
+impl DeliveryWindow {
+    /// `pct_elapsed` is how much of the time limit has passed, from 0 to 1
+    pub fn is_open(self, pct_elapsed: f64) -> bool {
+        match self {
+            DeliveryWindow::Early => pct_elapsed < 1.0 / 3.0,
+            DeliveryWindow::Late => pct_elapsed >= 2.0 / 3.0,
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            DeliveryWindow::Early => "Only home early in the night",
+            DeliveryWindow::Late => "Only home late in the night",
+        }
+    }
+}
+
+/// Which houses have a delivery window, and the clock badges marking them
+pub struct DeliveryWindows {
+    windows: HashMap<BuildingID, DeliveryWindow>,
+    pub draw: Drawable,
+    // Whether early and late houses are open, as currently drawn
+    open_shown: Option<(bool, bool)>,
+    // Set after deliveries, since delivered houses lose their badge
+    pub dirty: bool,
+}
+
+/// Picks which houses only take deliveries early or late. The same seed always picks the same
+/// houses.
+pub fn pick(
+    buildings: &HashMap<BuildingID, BldgState>,
+    seed: u64,
+) -> HashMap<BuildingID, DeliveryWindow> {
+    // The buildings come from a HashMap, so sort them to keep the picks the same
+    let mut houses: Vec<BuildingID> = buildings
+        .iter()
+        .filter_map(|(b, state)| {
+            if let BldgState::Undelivered(..) = state {
+                Some(*b)
+            } else {
+                None
+            }
+        })
+        .collect();
+    houses.sort();
+
+    // Use a separate RNG, so the presents each house wants don't change
+    let mut rng = XorShiftRng::seed_from_u64(seed);
+    let mut windows = HashMap::new();
+    for b in houses {
+        if rng.gen_bool(PCT_WITH_WINDOWS) {
+            windows.insert(
+                b,
+                if rng.gen_bool(0.5) {
+                    DeliveryWindow::Early
+                } else {
+                    DeliveryWindow::Late
+                },
+            );
+        }
+    }
+    windows
+}
+
+impl DeliveryWindows {
+    /// Badges for windows from `pick`
+    pub fn new(
+        ctx: &mut EventCtx,
+        app: &App,
+        bldgs: &Buildings,
+        windows: HashMap<BuildingID, DeliveryWindow>,
+    ) -> DeliveryWindows {
+        let mut delivery_windows = DeliveryWindows {
+            windows,
+            draw: Drawable::empty(ctx),
+            open_shown: None,
+            dirty: true,
+        };
+        delivery_windows.update(ctx, app, bldgs, 0.0);
+        delivery_windows
+    }
+
+    pub fn get(&self, b: BuildingID) -> Option<DeliveryWindow> {
+        self.windows.get(&b).cloned()
+    }
+
+    /// Badges are only redrawn when a window opens or closes, or after deliveries
+    pub fn update(&mut self, ctx: &mut EventCtx, app: &App, bldgs: &Buildings, pct_elapsed: f64) {
+        let open = (
+            DeliveryWindow::Early.is_open(pct_elapsed),
+            DeliveryWindow::Late.is_open(pct_elapsed),
+        );
+        if !self.dirty && self.open_shown == Some(open) {
+            return;
+        }
+        self.dirty = false;
+        self.open_shown = Some(open);
+
+        let mut batch = GeomBatch::new();
+        for (b, window) in &self.windows {
+            if !matches!(bldgs.buildings[b], BldgState::Undelivered(..)) {
+                continue;
+            }
+            // Next to the label, not on top of it
+            let center = app.map.get_b(*b).label_center.offset(5.0, -3.0);
+            clock_badge(&mut batch, center, *window, window.is_open(pct_elapsed));
+        }
+        self.draw = ctx.upload(batch);
+    }
+}
+
+// The hour hand points early or late in the night
+fn clock_badge(batch: &mut GeomBatch, center: Pt2D, window: DeliveryWindow, open: bool) {
+    let radius = Distance::meters(2.5);
+    let (face, hands) = if open {
+        (Color::WHITE, Color::BLACK)
+    } else {
+        (Color::grey(0.4), Color::grey(0.7))
+    };
+    batch.push(Color::BLACK, Circle::new(center, radius).to_polygon());
+    batch.push(face, Circle::new(center, radius * 0.8).to_polygon());
+
+    let hour = match window {
+        DeliveryWindow::Early => Angle::degrees(-30.0),
+        DeliveryWindow::Late => Angle::degrees(210.0),
+    };
+    for (angle, length) in [(Angle::degrees(-90.0), 0.7), (hour, 0.5)] {
+        batch.push(
+            hands,
+            PolyLine::must_new(vec![center, center.project_away(radius * length, angle)])
+                .make_polygons(Distance::meters(0.4)),
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::{BTreeMap, HashMap};
+
+use geom::{Distance, Duration, Polygon, Pt2D, Time};
+use map_model::{BuildingID, RoadID};
+use widgetry::{Color, Drawable, EventCtx, GeomBatch, Line, Text};
+
+use crate::buildings::{BldgState, Buildings};
+use crate::App;
+
+// Streets with only a house or two aren't worth a bonus
+const MIN_BUILDINGS_PER_ZONE: usize = 3;
+// Rendering all of the text is slow, so don't do it after every single delivery
+const LAYER_UPDATE_PERIOD: Duration = Duration::const_seconds(1.0);
+
+/// Houses along the same street are grouped together. Delivering to every one of them earns a
+/// bonus.
+pub struct Zones {
+    zones: Vec<Zone>,
+    bldg_to_zone: HashMap<BuildingID, usize>,
+}
+
+pub struct Zone {
+    pub name: String,
+    pub boundary: Polygon,
+    pub center: Pt2D,
+    pub total_units: usize,
+    pub houses: Vec<BuildingID>,
+    num_houses: usize,
+    remaining: usize,
+}
+
+impl Zone {
+    pub fn is_complete(&self) -> bool {
+        self.remaining == 0
+    }
+
+    pub fn pct_done(&self) -> f64 {
+        ((self.num_houses - self.remaining) as f64) / (self.num_houses as f64)
+    }
+}
+
+impl Zones {
+    pub fn new(app: &App, bldgs: &Buildings) -> Zones {
+        // Keep the order deterministic
+        let mut per_road: BTreeMap<RoadID, Vec<(BuildingID, usize)>> = BTreeMap::new();
+        for (b, state) in &bldgs.buildings {
+            if let BldgState::Undelivered(num_housing_units, _) = state {
+                per_road
+                    .entry(app.map.get_b(*b).sidewalk().road)
+                    .or_insert_with(Vec::new)
+                    .push((*b, *num_housing_units));
+            }
+        }
+
+        let mut zones = Vec::new();
+        let mut bldg_to_zone = HashMap::new();
+        for (r, houses) in per_road {
+            if houses.len() < MIN_BUILDINGS_PER_ZONE {
+                continue;
+            }
+            let boundary = match Polygon::convex_hull(
+                houses
+                    .iter()
+                    .map(|(b, _)| app.map.get_b(*b).polygon.clone())
+                    .collect(),
+            ) {
+                Ok(p) => p,
+                Err(_) => continue,
+            };
+            for (b, _) in &houses {
+                bldg_to_zone.insert(*b, zones.len());
+            }
+            zones.push(Zone {
+                name: app.map.get_r(r).get_name(app.opts.language.as_ref()),
+                center: boundary.center(),
+                boundary,
+                total_units: houses.iter().map(|(_, units)| *units).sum(),
+                houses: houses.iter().map(|(b, _)| *b).collect(),
+                num_houses: houses.len(),
+                remaining: houses.len(),
+            });
+        }
+
+        Zones {
+            zones,
+            bldg_to_zone,
+        }
+    }
+
+    /// Records a delivery. If it finished off a zone, returns that zone.
+    pub fn delivered(&mut self, b: BuildingID) -> Option<&Zone> {
+        let zone = &mut self.zones[*self.bldg_to_zone.get(&b)?];
+        zone.remaining -= 1;
+        if zone.is_complete() {
+            Some(zone)
+        } else {
+            None
+        }
+    }
+
+    /// A house went back to wanting presents
+    pub fn undelivered(&mut self, b: BuildingID) {
+        if let Some(idx) = self.bldg_to_zone.get(&b) {
+            self.zones[*idx].remaining += 1;
+        }
+    }
+
+    pub fn get(&self, b: BuildingID) -> Option<&Zone> {
+        self.bldg_to_zone.get(&b).map(|idx| &self.zones[*idx])
+    }
+
+    pub fn num_complete(&self) -> usize {
+        self.zones.iter().filter(|zone| zone.is_complete()).count()
+    }
+
+    /// The zone containing a point, or else the one centered closest to it
+    pub fn closest(&self, pt: Pt2D) -> Option<&Zone> {
+        self.zones
+            .iter()
+            .find(|zone| zone.boundary.contains_pt(pt))
+            .or_else(|| self.zones.iter().min_by_key(|zone| zone.center.dist_to(pt)))
+    }
+}
+
+/// Optionally outlines every zone, with how much of it has been delivered.
+pub struct ZonesLayer {
+    pub draw: Drawable,
+    // Something was delivered since the last update
+    pub dirty: bool,
+    last_update: Option<Time>,
+}
+
+impl ZonesLayer {
+    pub fn new(ctx: &EventCtx) -> ZonesLayer {
+        ZonesLayer {
+            draw: Drawable::empty(ctx),
+            dirty: true,
+            last_update: None,
+        }
+    }
+
+    pub fn update(&mut self, ctx: &mut EventCtx, app: &App, zones: &Zones) {
+        if !self.dirty
+            || self
+                .last_update
+                .map(|t| app.time - t < LAYER_UPDATE_PERIOD)
+                .unwrap_or(false)
+        {
+            return;
+        }
+        self.dirty = false;
+        self.last_update = Some(app.time);
+
+        let mut batch = GeomBatch::new();
+        for zone in &zones.zones {
+            let color = if zone.is_complete() {
+                app.session.colors.score
+            } else {
+                Color::WHITE.alpha(0.6)
+            };
+            batch.push(color, zone.boundary.to_outline(Distance::meters(1.5)));
+            batch.append(
+                Text::from(Line(format!("{}%", (100.0 * zone.pct_done()).round())).fg(color))
+                    .render_autocropped(ctx)
+                    .scale(0.15)
+                    .centered_on(zone.center),
+            );
+        }
+        self.draw = ctx.upload(batch);
+    }
+}
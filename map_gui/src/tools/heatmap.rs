@@ -40,6 +40,12 @@ impl HeatmapOptions {
         }
     }
 
+    /// Use one of the gradients offered by `to_controls`, like "Inferno"
+    pub fn with_color_scheme(mut self, color_scheme: &str) -> HeatmapOptions {
+        self.color_scheme = color_scheme.to_string();
+        self
+    }
+
     pub fn to_controls(&self, ctx: &mut EventCtx, legend: Widget) -> Vec<Widget> {
         vec![
             // TODO Display the value...
@@ -218,10 +218,16 @@ impl<'a> GfxCtx<'a> {
         self.canvas.get_cursor_in_map_space()
     }
 
-    pub(crate) fn get_num_uploads(&self) -> usize {
+    /// How many batches have been uploaded since the last frame was drawn
+    pub fn get_num_uploads(&self) -> usize {
         self.prerender.num_uploads.get()
     }
 
+    /// How many draw calls this frame has made so far
+    pub fn get_num_draw_calls(&self) -> usize {
+        self.num_draw_calls
+    }
+
     pub fn is_screencap(&self) -> bool {
         self.screencap_mode
     }
@@ -3,6 +3,8 @@ use winit::event::{
     ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
 };
 
+use serde::{Deserialize, Serialize};
+
 use geom::Duration;
 
 use crate::{EventCtx, Line, ScreenDims, ScreenPt, TextSpan};
@@ -135,7 +137,7 @@ impl Event {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
 pub enum Key {
     // Case is unspecified.
     // TODO Would be cool to represent A and UpperA, but then release semantics get weird... hold
@@ -190,6 +192,7 @@ pub enum Key {
     Equals,
     SingleQuote,
     Minus,
+    Backtick,
     // Stuff without a straightforward single-character display
     Escape,
     Enter,
@@ -279,6 +282,7 @@ impl Key {
             Key::Equals => Some(if shift_pressed { '+' } else { '=' }),
             Key::SingleQuote => Some(if shift_pressed { '"' } else { '\'' }),
             Key::Minus => Some(if shift_pressed { '_' } else { '-' }),
+            Key::Backtick => Some(if shift_pressed { '~' } else { '`' }),
             Key::Escape
             | Key::Enter
             | Key::Tab
@@ -388,6 +392,7 @@ impl Key {
             VirtualKeyCode::Equals => Key::Equals,
             VirtualKeyCode::Apostrophe => Key::SingleQuote,
             VirtualKeyCode::Minus => Key::Minus,
+            VirtualKeyCode::Grave => Key::Backtick,
             VirtualKeyCode::Escape => Key::Escape,
             VirtualKeyCode::Return => Key::Enter,
             VirtualKeyCode::Tab => Key::Tab,
@@ -21,6 +21,10 @@ pub enum UpdateType {
         zoom: f64,
         dims: ScreenDims,
     },
+    /// Draw once more and save just what's in the window as a PNG.
+    ScreenCaptureCurrent {
+        filename: String,
+    },
 }
 
 pub struct EventCtx<'a> {
@@ -44,7 +44,8 @@ impl UserInput {
         false
     }
 
-    pub(crate) fn any_pressed(&mut self) -> Option<Key> {
+    /// Consumes any key press. Useful for letting the user pick a key to bind.
+    pub fn any_pressed(&mut self) -> Option<Key> {
         if self.event_consumed {
             return None;
         }
@@ -10,7 +10,7 @@ use geom::Duration;
 
 use crate::app_state::App;
 use crate::assets::Assets;
-use crate::tools::screenshot::screenshot_everything;
+use crate::tools::screenshot::{screenshot_current, screenshot_everything};
 use crate::{
     Canvas, CanvasSettings, Event, EventCtx, GfxCtx, Prerender, SharedAppState, Style, Text,
     UpdateType, UserInput,
@@ -519,6 +519,11 @@ pub fn run<
                         error!("Couldn't screenshot everything: {}", err);
                     }
                 }
+                UpdateType::ScreenCaptureCurrent { filename } => {
+                    if let Err(err) = screenshot_current(&mut state, &filename, &prerender) {
+                        error!("Couldn't screenshot {}: {}", filename, err);
+                    }
+                }
             }
         }
     });
@@ -71,3 +71,18 @@ pub(crate) fn screenshot_everything<A: 'static + SharedAppState>(
     state.canvas.cam_y = orig_y;
     Ok(())
 }
+
+/// Take a screenshot of what's currently in the window.
+pub(crate) fn screenshot_current<A: 'static + SharedAppState>(
+    state: &mut State<A>,
+    filename: &str,
+    prerender: &Prerender,
+) -> anyhow::Result<()> {
+    if let Some(dir) = std::path::Path::new(filename).parent() {
+        fs_err::create_dir_all(dir)?;
+    }
+    state.draw(prerender, true);
+    prerender
+        .inner
+        .screencap(state.canvas.get_window_dims(), filename.to_string())
+}